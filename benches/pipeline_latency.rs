@@ -0,0 +1,42 @@
+//! Compares the time taken by a run of inserts executed one at a time with the same inserts sent as a `Pipeline`.
+//!
+//! Needs the test database on port 5433: `cargo bench --bench pipeline_latency`.
+
+use may_postgres::Config;
+use std::time::Instant;
+
+const ROWS: i32 = 1_000;
+
+fn main() {
+    let client = "host=localhost port=5433 user=postgres"
+        .parse::<Config>()
+        .unwrap()
+        .connect()
+        .unwrap();
+    client
+        .batch_execute("CREATE TEMPORARY TABLE bench (id INT)")
+        .unwrap();
+    let insert = client
+        .prepare("INSERT INTO bench (id) VALUES ($1)")
+        .unwrap();
+
+    let start = Instant::now();
+    for i in 0..ROWS {
+        client.execute(&insert, &[&i]).unwrap();
+    }
+    let sequential = start.elapsed();
+
+    let start = Instant::now();
+    let mut pipeline = client.pipeline();
+    for i in 0..ROWS {
+        pipeline.push(&insert, &[&i]).unwrap();
+    }
+    for result in pipeline.execute().unwrap() {
+        result.unwrap();
+    }
+    let pipelined = start.elapsed();
+
+    println!("{:>10} {:>12}", "mode", "time");
+    println!("{:>10} {:>12?}", "sequential", sequential);
+    println!("{:>10} {:>12?}", "pipelined", pipelined);
+}
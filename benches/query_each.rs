@@ -0,0 +1,96 @@
+//! Compares the allocations and the time taken to read a wide result by collecting its rows with `Client::query` and
+//! by visiting them with `Client::query_each`.
+//!
+//! Needs the test database on port 5433: `cargo bench --bench query_each`.
+
+use may_postgres::{Client, Config};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+const ROWS: i32 = 100_000;
+const RUNS: u32 = 5;
+
+const QUERY: &str = "SELECT i, i::INT8 * 2, i::FLOAT8 / 3, 'name ' || i, i % 2 = 0, \
+                     md5(i::TEXT), now(), NULL::TEXT \
+                     FROM generate_series(1, $1) i";
+
+// counts allocations across all threads, including the connection's
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED.fetch_add(new_size, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+fn run<F>(mut read: F) -> (Duration, usize, usize)
+where
+    F: FnMut() -> i64,
+{
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let allocated = ALLOCATED.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        std::hint::black_box(read());
+    }
+    (
+        start.elapsed() / RUNS,
+        (ALLOCATIONS.load(Ordering::Relaxed) - allocations) / RUNS as usize,
+        (ALLOCATED.load(Ordering::Relaxed) - allocated) / RUNS as usize,
+    )
+}
+
+fn main() {
+    let client: Client = "host=localhost port=5433 user=postgres"
+        .parse::<Config>()
+        .unwrap()
+        .connect()
+        .unwrap();
+    let stmt = client.prepare(QUERY).unwrap();
+
+    println!(
+        "{:>12} {:>12} {:>12} {:>12}",
+        "path", "time", "allocations", "bytes"
+    );
+    let (time, allocations, bytes) = run(|| {
+        let rows = client.query(&stmt, &[&ROWS]).unwrap();
+        rows.iter().map(|row| row.get::<_, i64>(1)).sum()
+    });
+    println!(
+        "{:>12} {:>12?} {:>12} {:>12}",
+        "query", time, allocations, bytes
+    );
+    let (time, allocations, bytes) = run(|| {
+        let mut sum = 0;
+        client
+            .query_each(&stmt, &[&ROWS], |row| {
+                sum += row.get::<_, i64>(1);
+                Ok(())
+            })
+            .unwrap();
+        sum
+    });
+    println!(
+        "{:>12} {:>12?} {:>12} {:>12}",
+        "query_each", time, allocations, bytes
+    );
+}
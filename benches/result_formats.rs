@@ -0,0 +1,63 @@
+//! Compares the time taken to fetch and decode numeric and timestamp heavy rows requested in the text and in the
+//! binary format.
+//!
+//! Needs the test database on port 5433: `cargo bench --bench result_formats`.
+
+use chrono_04::NaiveDateTime;
+use may_postgres::types::Format;
+use may_postgres::{Client, Config, Row};
+use std::time::{Duration, Instant, SystemTime};
+
+const ROWS: i32 = 100_000;
+const RUNS: u32 = 5;
+
+const QUERY: &str = "SELECT i::INT8, i * 1.5::FLOAT8, now()::TIMESTAMP + i * INTERVAL '1 second' \
+                     FROM generate_series(1, $1) i";
+
+fn decode_text(row: &Row) -> (i64, f64, NaiveDateTime) {
+    let id = row.get::<_, &str>(0).parse().unwrap();
+    let value = row.get::<_, &str>(1).parse().unwrap();
+    let time = NaiveDateTime::parse_from_str(row.get(2), "%Y-%m-%d %H:%M:%S%.f").unwrap();
+    (id, value, time)
+}
+
+fn decode_binary(row: &Row) -> (i64, f64, SystemTime) {
+    (row.get(0), row.get(1), row.get(2))
+}
+
+fn run<F, T>(client: &Client, format: Format, decode: F) -> (Duration, Duration)
+where
+    F: Fn(&Row) -> T,
+{
+    let stmt = client.prepare(QUERY).unwrap();
+    let mut fetch = Duration::default();
+    let mut decoding = Duration::default();
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        let rows = client
+            .query_with_formats(&stmt, &[&ROWS], &[format])
+            .unwrap();
+        fetch += start.elapsed();
+
+        let start = Instant::now();
+        for row in &rows {
+            std::hint::black_box(decode(row));
+        }
+        decoding += start.elapsed();
+    }
+    (fetch / RUNS, decoding / RUNS)
+}
+
+fn main() {
+    let client = "host=localhost port=5433 user=postgres"
+        .parse::<Config>()
+        .unwrap()
+        .connect()
+        .unwrap();
+
+    println!("{:>8} {:>12} {:>12}", "format", "fetch", "decode");
+    let (fetch, decode) = run(&client, Format::Text, decode_text);
+    println!("{:>8} {:>12?} {:>12?}", "text", fetch, decode);
+    let (fetch, decode) = run(&client, Format::Binary, decode_binary);
+    println!("{:>8} {:>12?} {:>12?}", "binary", fetch, decode);
+}
@@ -0,0 +1,66 @@
+//! Compares the number of socket writes made for a burst of pipelined inserts at different values of
+//! `Config::max_coalesce_bytes`.
+//!
+//! Needs the test database on port 5433: `cargo bench --bench write_coalescing`.
+
+use may_postgres::types::ToSql;
+use may_postgres::Config;
+use std::time::Instant;
+
+const ROWS: i32 = 10_000;
+
+fn run(max_coalesce_bytes: usize) {
+    let client = "host=localhost port=5433 user=postgres"
+        .parse::<Config>()
+        .unwrap()
+        .max_coalesce_bytes(max_coalesce_bytes)
+        .write_buffer_capacity(64 * 1024)
+        .connect()
+        .unwrap();
+    client
+        .batch_execute("CREATE TEMPORARY TABLE bench (id INT, name TEXT)")
+        .unwrap();
+    let stmt = client
+        .prepare("INSERT INTO bench (id, name) VALUES ($1, $2)")
+        .unwrap();
+    let name = "x".repeat(100);
+
+    let before = client.stats();
+    let start = Instant::now();
+    // the streams are only read once every insert has been sent, so the requests queue up behind each other
+    let streams = (0..ROWS)
+        .map(|i| {
+            let params: [&dyn ToSql; 2] = [&i, &name];
+            client.query_raw(&stmt, params.iter().copied()).unwrap()
+        })
+        .collect::<Vec<_>>();
+    for stream in streams {
+        for row in stream {
+            row.unwrap();
+        }
+    }
+    let elapsed = start.elapsed();
+    let after = client.stats();
+
+    let limit = match max_coalesce_bytes {
+        0 => "unlimited".to_string(),
+        n => n.to_string(),
+    };
+    println!(
+        "{:>10} {:>10} {:>10} {:>12?}",
+        limit,
+        after.requests() - before.requests(),
+        after.writes() - before.writes(),
+        elapsed
+    );
+}
+
+fn main() {
+    println!(
+        "{:>10} {:>10} {:>10} {:>12}",
+        "limit", "requests", "writes", "time"
+    );
+    for &limit in &[0, 64 * 1024, 8 * 1024, 1024, 1] {
+        run(limit);
+    }
+}
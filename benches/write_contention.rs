@@ -0,0 +1,63 @@
+//! Measures how requests sent by many coroutines sharing one client contend on the way to the socket, reporting the
+//! throughput and the number of socket writes for an increasing number of senders.
+//!
+//! Each request carries a large parameter so that encoding it, which takes the client's buffer lock, is a noticeable
+//! part of the work.
+//!
+//! Needs the test database on port 5433: `cargo bench --bench write_contention`.
+
+use may_postgres::Config;
+use std::time::Instant;
+
+const REQUESTS: usize = 20_000;
+
+fn run(senders: usize) {
+    let client = "host=localhost port=5433 user=postgres"
+        .parse::<Config>()
+        .unwrap()
+        .connect()
+        .unwrap();
+    let stmt = client.prepare("SELECT length($1::TEXT)").unwrap();
+    let value = "x".repeat(16 * 1024);
+
+    let before = client.stats();
+    let start = Instant::now();
+    let handles = (0..senders)
+        .map(|_| {
+            let client = client.clone();
+            let stmt = stmt.clone();
+            let value = value.clone();
+            may::go!(move || {
+                for _ in 0..REQUESTS / senders {
+                    let row = client.query_one(&stmt, &[&value]).unwrap();
+                    assert_eq!(row.get::<_, i32>(0), value.len() as i32);
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    let elapsed = start.elapsed();
+    let after = client.stats();
+
+    let requests = after.requests() - before.requests();
+    println!(
+        "{:>8} {:>10} {:>10} {:>12?} {:>12.0}",
+        senders,
+        requests,
+        after.writes() - before.writes(),
+        elapsed,
+        requests as f64 / elapsed.as_secs_f64()
+    );
+}
+
+fn main() {
+    println!(
+        "{:>8} {:>10} {:>10} {:>12} {:>12}",
+        "senders", "requests", "writes", "time", "requests/s"
+    );
+    for &senders in &[1, 4, 16, 64, 256] {
+        run(senders);
+    }
+}
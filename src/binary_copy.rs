@@ -102,10 +102,13 @@ struct Header {
 }
 
 /// A stream of rows deserialized from the PostgreSQL binary copy format.
+///
+/// The stream ends at the `-1` field count trailer.
 pub struct BinaryCopyOutStream {
     stream: CopyOutStream,
     types: Arc<Vec<Type>>,
     header: Option<Header>,
+    done: bool,
 }
 
 impl BinaryCopyOutStream {
@@ -115,6 +118,7 @@ impl BinaryCopyOutStream {
             stream,
             types: Arc::new(types.to_vec()),
             header: None,
+            done: false,
         }
     }
 }
@@ -123,6 +127,10 @@ impl Iterator for BinaryCopyOutStream {
     type Item = Result<BinaryCopyOutRow, Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
         let chunk = match self.stream.next() {
             Some(Ok(chunk)) => chunk,
             Some(Err(e)) => return Some(Err(e)),
@@ -157,7 +165,16 @@ impl Iterator for BinaryCopyOutStream {
         o_try!(check_remaining(&chunk, 2));
         let mut len = chunk.get_i16();
         if len == -1 {
-            return None;
+            self.done = true;
+            // consume the rest of the copy so that a late error isn't lost
+            return match self.stream.next() {
+                Some(Ok(_)) => Some(Err(Error::parse(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "unexpected data after trailer",
+                )))),
+                Some(Err(e)) => Some(Err(e)),
+                None => None,
+            };
         }
 
         if has_oids {
@@ -1,13 +1,20 @@
 use crate::client::SocketConfig;
-// use crate::config::Host;
+use crate::config::{Host, SslMode};
+use crate::tls::MakeTlsConnect;
 use crate::{cancel_query_raw, connect_socket, Error};
+use may::net::TcpStream;
 use std::io;
 
-pub(crate) fn cancel_query(
+pub(crate) fn cancel_query<T>(
     config: Option<SocketConfig>,
+    ssl_mode: SslMode,
+    mut tls: T,
     process_id: i32,
     secret_key: i32,
-) -> Result<(), Error> {
+) -> Result<(), Error>
+where
+    T: MakeTlsConnect<TcpStream>,
+{
     let config = match config {
         Some(config) => config,
         None => {
@@ -18,17 +25,22 @@ pub(crate) fn cancel_query(
         }
     };
 
-    // let hostname = match &config.host {
-    //     Host::Tcp(host) => &**host,
-    // };
+    let hostname = match &config.host {
+        Host::Tcp(host) => &**host,
+        // postgres doesn't support TLS over unix sockets, so the choice here doesn't matter
+        #[cfg(unix)]
+        Host::Unix(_) => "",
+    };
+    let tls = tls
+        .make_tls_connect(hostname)
+        .map_err(|e| Error::tls(e.into()))?;
 
     let socket = connect_socket::connect_socket(
         &config.host,
         config.port,
         config.connect_timeout,
-        config.keepalives,
-        config.keepalives_idle,
+        config.keepalive.as_ref(),
     )?;
 
-    cancel_query_raw::cancel_query_raw(socket, process_id, secret_key)
+    cancel_query_raw::cancel_query_raw(socket, ssl_mode, tls, process_id, secret_key)
 }
@@ -1,14 +1,24 @@
-use crate::Error;
+use crate::config::SslMode;
+use crate::socket::Socket;
+use crate::tls::TlsConnect;
+use crate::{connect_tls, Error};
 use bytes::BytesMut;
 use may::net::TcpStream;
 use postgres_protocol::message::frontend;
 use std::io::Write;
 
-pub fn cancel_query_raw(
-    mut stream: TcpStream,
+pub fn cancel_query_raw<T>(
+    stream: Socket,
+    mode: SslMode,
+    tls: T,
     process_id: i32,
     secret_key: i32,
-) -> Result<(), Error> {
+) -> Result<(), Error>
+where
+    T: TlsConnect<TcpStream>,
+{
+    let mut stream = connect_tls::connect_tls(stream, mode, tls)?;
+
     let mut buf = BytesMut::new();
     frontend::cancel_request(process_id, secret_key, &mut buf);
 
@@ -1,4 +1,7 @@
-use crate::{cancel_query, cancel_query_raw, client::SocketConfig, Error};
+use crate::config::SslMode;
+use crate::socket::Socket;
+use crate::tls::{MakeTlsConnect, TlsConnect};
+use crate::{cancel_query, cancel_query_raw, client::SocketConfig, Error, NoTls};
 use may::net::TcpStream;
 
 /// The capability to request cancellation of in-progress queries on a
@@ -6,6 +9,7 @@ use may::net::TcpStream;
 #[derive(Clone)]
 pub struct CancelToken {
     pub(crate) socket_config: Option<SocketConfig>,
+    pub(crate) ssl_mode: SslMode,
     pub(crate) process_id: i32,
     pub(crate) secret_key: i32,
 }
@@ -22,14 +26,59 @@ impl CancelToken {
     /// normally, or that the connection associated with this token is still
     /// active.
     ///
+    /// The cancellation request is sent in plain text. Use `cancel_query_tls` if the server only accepts TLS
+    /// connections.
+    ///
     /// Requires the `runtime` Cargo feature (enabled by default).
     pub fn cancel_query(&self) -> Result<(), Error> {
-        cancel_query::cancel_query(self.socket_config.clone(), self.process_id, self.secret_key)
+        cancel_query::cancel_query(
+            self.socket_config.clone(),
+            SslMode::Disable,
+            NoTls,
+            self.process_id,
+            self.secret_key,
+        )
+    }
+
+    /// Like `cancel_query`, but secures the cancellation connection with TLS according to the `sslmode` the
+    /// original connection was configured with.
+    pub fn cancel_query_tls<T>(&self, tls: T) -> Result<(), Error>
+    where
+        T: MakeTlsConnect<TcpStream>,
+    {
+        cancel_query::cancel_query(
+            self.socket_config.clone(),
+            self.ssl_mode,
+            tls,
+            self.process_id,
+            self.secret_key,
+        )
     }
 
     /// Like `cancel_query`, but uses a stream which is already connected to the server rather than opening a new
     /// connection itself.
     pub fn cancel_query_raw(&self, stream: TcpStream) -> Result<(), Error> {
-        cancel_query_raw::cancel_query_raw(stream, self.process_id, self.secret_key)
+        cancel_query_raw::cancel_query_raw(
+            Socket::Tcp(stream),
+            SslMode::Disable,
+            NoTls,
+            self.process_id,
+            self.secret_key,
+        )
+    }
+
+    /// Like `cancel_query_raw`, but secures the stream with TLS according to the `sslmode` the original connection
+    /// was configured with.
+    pub fn cancel_query_raw_tls<T>(&self, stream: TcpStream, tls: T) -> Result<(), Error>
+    where
+        T: TlsConnect<TcpStream>,
+    {
+        cancel_query_raw::cancel_query_raw(
+            Socket::Tcp(stream),
+            self.ssl_mode,
+            tls,
+            self.process_id,
+            self.secret_key,
+        )
     }
 }
@@ -1,26 +1,38 @@
-use crate::cancel_query;
-use crate::codec::BackendMessages;
-use crate::config::Host;
-use crate::connection::{Connection, Request, RequestMessages};
+use crate::codec::{BackendMessages, FrontendMessage};
+use crate::config::{Config, Host, SslMode};
+use crate::connection::{Connection, ConnectionStats, Request, RequestMessages};
 use crate::copy_out::CopyOutStream;
+use crate::error::{DbError, ScriptError};
+use crate::keepalive::KeepaliveConfig;
+use crate::notifications::Notifications;
 use crate::query::RowStream;
 use crate::simple_query::SimpleQueryStream;
-use crate::types::{Oid, ToSql, Type};
+use crate::statement_cache::StatementCache;
+use crate::types::{Format, Oid, ToSql, Type};
 use crate::{
-    copy_in, copy_out, prepare, query, simple_query, slice_iter, CancelToken, CopyInSink, Error,
-    Row, SimpleQueryMessage, Statement, ToStatement, Transaction, TransactionBuilder,
+    copy_both, copy_in, copy_out, prepare, query, simple_query, slice_iter, CancelToken,
+    CopyBothSink, CopyBothStream, CopyInSink, CopyInWriter, Error, Notification, Pipeline, Row,
+    RowRef, SimpleQueryMessage, SimpleQueryRow, Statement, ToStatement, Transaction,
+    TransactionBuilder,
 };
 use bytes::{Buf, BytesMut};
 use fallible_iterator::FallibleIterator;
+use log::error;
+use may::go;
 use may::sync::{mpsc, Mutex};
 use postgres_protocol::message::backend::Message;
 use std::collections::HashMap;
-use std::sync::Arc;
-use std::time::Duration;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
 
 pub struct Responses {
     receiver: mpsc::Receiver<BackendMessages>,
     cur: BackendMessages,
+    // set when the client has a request timeout
+    deadline: Option<Instant>,
+    timed_out: bool,
 }
 
 impl Responses {
@@ -32,10 +44,52 @@ impl Responses {
                 None => {}
             }
 
-            match self.receiver.recv() {
-                Ok(messages) => self.cur = messages,
-                Err(_) => return Err(Error::closed()),
+            self.recv()?;
+        }
+    }
+
+    /// Reads the `CopyBothResponse` starting a `COPY` in both directions, which `Message` has no variant for.
+    pub fn copy_both_response(&mut self) -> Result<(), Error> {
+        while self.cur.is_empty() {
+            self.recv()?;
+        }
+        if self.cur.copy_both_response() {
+            return Ok(());
+        }
+        self.next()?;
+        Err(Error::unexpected_message())
+    }
+
+    // waits for the next batch of messages once the current one is used up
+    fn recv(&mut self) -> Result<(), Error> {
+        // the rest of a timed out response is discarded by the connection once the receiver is dropped
+        if self.timed_out {
+            return Err(Error::timeout());
+        }
+
+        let messages = match self.deadline {
+            Some(deadline) => loop {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    self.timed_out = true;
+                    return Err(Error::timeout());
+                }
+                // a wait woken early comes back here and waits out what is left of the deadline
+                match self.receiver.recv_timeout(remaining) {
+                    Ok(messages) => break Ok(messages),
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => break Err(()),
+                }
+            },
+            None => self.receiver.recv().map_err(|_| ()),
+        };
+
+        match messages {
+            Ok(messages) => {
+                self.cur = messages;
+                Ok(())
             }
+            Err(()) => Err(Error::closed()),
         }
     }
 }
@@ -51,10 +105,38 @@ struct State {
 pub struct InnerClient {
     pub(crate) sender: Connection,
     state: Mutex<State>,
+    statement_cache: Mutex<StatementCache>,
+    notifications: Mutex<mpsc::Receiver<Notification>>,
+    // the task reading a chunked row stream, which the connection is reserved to until the stream ends, or 0
+    pub(crate) stream_holder: Arc<AtomicUsize>,
+    request_timeout: Option<Duration>,
 }
 
 impl InnerClient {
     pub fn send(&self, messages: RequestMessages) -> Result<Responses, Error> {
+        self.send_inner(messages, self.request_timeout)
+    }
+
+    /// like `send`, but the response is waited for at most `timeout` instead of the configured request timeout
+    pub fn send_timeout(
+        &self,
+        messages: RequestMessages,
+        timeout: Duration,
+    ) -> Result<Responses, Error> {
+        self.send_inner(messages, Some(timeout))
+    }
+
+    /// like `send`, but the response is waited for at most `timeout`, or as long as it takes if that is `None`
+    pub fn send_inner(
+        &self,
+        messages: RequestMessages,
+        timeout: Option<Duration>,
+    ) -> Result<Responses, Error> {
+        // the request would wait for a stream that can only be read by the task waiting for it
+        if self.stream_holder.load(Ordering::Acquire) == task_id() {
+            return Err(Error::stream_held());
+        }
+
         let (sender, receiver) = mpsc::channel();
         let request = Request { messages, sender };
         self.sender.send(request).map_err(|_| Error::closed())?;
@@ -62,6 +144,8 @@ impl InnerClient {
         Ok(Responses {
             receiver,
             cur: BackendMessages::empty(),
+            deadline: timeout.map(|timeout| Instant::now() + timeout),
+            timed_out: false,
         })
     }
 
@@ -97,6 +181,9 @@ impl InnerClient {
         self.state.lock().unwrap().types.insert(oid, type_.clone());
     }
 
+    // Encoding takes the state lock to reuse one buffer, whose capacity comes back once the connection has written
+    // and dropped the frozen request, rather than allocating a buffer per request. The lock is released before the
+    // request is queued, so it is never held across a socket write, and encoding is short next to a round trip.
     pub fn with_buf<F, R>(&self, f: F) -> R
     where
         F: FnOnce(&mut BytesMut) -> R,
@@ -108,29 +195,119 @@ impl InnerClient {
     }
 }
 
+/// identifies the running coroutine, or the thread when not in one, which is never 0
+pub(crate) fn task_id() -> usize {
+    static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+    may::coroutine_local!(static ID: usize = NEXT_ID.fetch_add(1, Ordering::Relaxed));
+    ID.with(|id| *id)
+}
+
 #[derive(Clone)]
 pub(crate) struct SocketConfig {
     pub host: Host,
     pub port: u16,
     pub connect_timeout: Option<Duration>,
-    pub keepalives: bool,
-    pub keepalives_idle: Duration,
+    pub keepalive: Option<KeepaliveConfig>,
+}
+
+// builds `INSERT INTO table (columns) VALUES ($1, $2), ($3, $4), ...` for `rows` rows
+fn insert_query(table: &str, columns: &[&str], rows: usize) -> String {
+    let mut query = format!("INSERT INTO {} ({}) VALUES ", table, columns.join(", "));
+    let mut param = 0;
+    for row in 0..rows {
+        if row > 0 {
+            query.push_str(", ");
+        }
+        query.push('(');
+        for column in 0..columns.len() {
+            if column > 0 {
+                query.push_str(", ");
+            }
+            param += 1;
+            query.push_str(&format!("${}", param));
+        }
+        query.push(')');
+    }
+    query
+}
+
+// Wakes up every `interval` to watch the connection. An idle connection is sent an empty query, which keeps the
+// session from being dropped as idle; its answer is waited for like that of any request. Once the requests in flight at
+// one wake up are still waiting at the next, with nothing received from the server nor written to it in between, the
+// oldest of them has made no progress for a whole interval and the connection is taken for dead and closed, which fails
+// its requests rather than leaving them hanging.
+fn keep_alive(client: Weak<InnerClient>, interval: Duration) {
+    go!(move || {
+        // the progress at the previous wake up, if requests were in flight then
+        let mut waiting = None;
+        loop {
+            may::coroutine::sleep(interval);
+            let client = match client.upgrade() {
+                Some(client) => client,
+                None => return,
+            };
+            if client.sender.is_closed() {
+                return;
+            }
+
+            let progress = client.sender.progress();
+            if progress.stats.in_flight() > 0 {
+                if waiting == Some(progress) {
+                    error!("connection made no progress for {:?}, closing it", interval);
+                    client.sender.abort();
+                    return;
+                }
+                waiting = Some(progress);
+                continue;
+            }
+
+            waiting = None;
+            let buf = match simple_query::encode(&client, ";") {
+                Ok(buf) => buf,
+                Err(_) => continue,
+            };
+            let request = Request {
+                messages: RequestMessages::Single(FrontendMessage::Raw(buf)),
+                // the answer is discarded, the next wake up only checks it arrived
+                sender: mpsc::channel().0,
+            };
+            // a sender that filled the last free slot since the check has put a request in flight anyway
+            if client.sender.try_send(request).is_err() {
+                return;
+            }
+        }
+    });
 }
 
 /// An asynchronous PostgreSQL client.
 ///
 /// The client is one half of what is returned when a connection is established. Users interact with the database
 /// through this client object.
+///
+/// Cloning a client is cheap, and the clones share its connection, along with its prepared statement cache and type
+/// information, so they can be handed to as many coroutines as needed. Requests from clones are queued on the one
+/// connection like those of a single shared client, and the connection is closed once the last clone is dropped, or
+/// as soon as any clone calls `close`.
+/// Session state is shared as well: while a `Transaction` is open, the requests of every clone run inside it, so a
+/// transaction should only be used through the client it was started on, with no other clone active meanwhile.
+#[derive(Clone)]
 pub struct Client {
     inner: Arc<InnerClient>,
     socket_config: Option<SocketConfig>,
+    ssl_mode: SslMode,
     process_id: i32,
     secret_key: i32,
 }
 
 impl Client {
-    pub(crate) fn new(sender: Connection, process_id: i32, secret_key: i32) -> Client {
-        Client {
+    pub(crate) fn new(
+        sender: Connection,
+        notifications: mpsc::Receiver<Notification>,
+        config: &Config,
+        process_id: i32,
+        secret_key: i32,
+    ) -> Client {
+        let client = Client {
             inner: Arc::new(InnerClient {
                 sender,
                 state: Mutex::new(State {
@@ -138,19 +315,34 @@ impl Client {
                     typeinfo_composite: None,
                     typeinfo_enum: None,
                     types: HashMap::new(),
-                    buf: BytesMut::new(),
+                    buf: BytesMut::with_capacity(config.write_buffer_capacity),
                 }),
+                statement_cache: Mutex::new(StatementCache::new(config.statement_cache_size)),
+                notifications: Mutex::new(notifications),
+                stream_holder: Arc::new(AtomicUsize::new(0)),
+                request_timeout: config.request_timeout,
             }),
             socket_config: None,
+            ssl_mode: config.ssl_mode,
             process_id,
             secret_key,
+        };
+        if let Some(interval) = config.keepalive_query {
+            keep_alive(Arc::downgrade(&client.inner), interval);
         }
+        client
     }
 
     pub(crate) fn inner(&self) -> &Arc<InnerClient> {
         &self.inner
     }
 
+    // drops the state one borrower of a pooled connection may have left behind for the next
+    pub(crate) fn reset(&self, trace_protocol: bool) {
+        self.inner.sender.set_notice_callback(None);
+        self.inner.sender.set_trace_protocol(trace_protocol);
+    }
+
     pub(crate) fn set_socket_config(&mut self, socket_config: SocketConfig) {
         self.socket_config = Some(socket_config);
     }
@@ -171,6 +363,37 @@ impl Client {
         prepare::prepare(&self.inner, query, parameter_types)
     }
 
+    /// Like `prepare`, but reuses a statement previously prepared from the same query text.
+    ///
+    /// Statements are cached per client in a least recently used cache whose size is set by
+    /// `Config::statement_cache_size`. A cache hit does not contact the server at all. When a statement is evicted
+    /// the server-side statement is closed as soon as the last handle to it is dropped.
+    pub fn prepare_cached(&self, query: &str) -> Result<Statement, Error> {
+        if let Some(statement) = self.inner.statement_cache.lock().unwrap().get(query) {
+            return Ok(statement);
+        }
+
+        let statement = self.prepare(query)?;
+        let mut cache = self.inner.statement_cache.lock().unwrap();
+        // a clone of the client may have prepared the same query meanwhile, in which case its statement is kept
+        if let Some(cached) = cache.get(query) {
+            drop(cache);
+            return Ok(cached);
+        }
+        let evicted = cache.insert(query, statement.clone());
+        drop(cache);
+        // closing the evicted statements needs the client state lock
+        drop(evicted);
+
+        Ok(statement)
+    }
+
+    /// Removes all statements from the cache used by `prepare_cached`.
+    pub fn clear_statement_cache(&self) {
+        let evicted = self.inner.statement_cache.lock().unwrap().clear();
+        drop(evicted);
+    }
+
     /// Executes a statement, returning a vector of the resulting rows.
     ///
     /// A statement may contain parameters, specified by `$n`, where `n` is the index of the parameter of the list
@@ -187,7 +410,7 @@ impl Client {
     where
         T: ?Sized + ToStatement,
     {
-        self.query_raw(statement, slice_iter(params))?.collect()
+        FallibleIterator::collect(self.query_raw(statement, slice_iter(params))?)
     }
 
     /// Executes a statement which returns a single row, returning it.
@@ -210,13 +433,13 @@ impl Client {
     {
         let mut stream = self.query_raw(statement, slice_iter(params))?;
 
-        let row = match stream.next().transpose()? {
+        let row = match FallibleIterator::next(&mut stream)? {
             Some(row) => row,
-            None => return Err(Error::row_count()),
+            None => return Err(Error::no_rows()),
         };
 
-        if stream.next().transpose()?.is_some() {
-            return Err(Error::row_count());
+        if FallibleIterator::next(&mut stream)?.is_some() {
+            return Err(Error::too_many_rows());
         }
 
         Ok(row)
@@ -246,18 +469,163 @@ impl Client {
     {
         let mut stream = self.query_raw(statement, slice_iter(params))?;
 
-        let row = match stream.next().transpose()? {
+        let row = match FallibleIterator::next(&mut stream)? {
             Some(row) => row,
             None => return Ok(None),
         };
 
-        if stream.next().transpose()?.is_some() {
-            return Err(Error::row_count());
+        if FallibleIterator::next(&mut stream)?.is_some() {
+            return Err(Error::too_many_rows());
         }
 
         Ok(Some(row))
     }
 
+    /// Executes many statements, returning the rows of each of them in the same order.
+    ///
+    /// Every statement is sent as a request of its own before any response is read, so the connection writes them out
+    /// together instead of waiting for each one to finish first. This is cheaper than spawning a coroutine per query,
+    /// and the server executes them in the order given. A raw query string is prepared before it is sent, which costs
+    /// a round trip of its own, so statements should be prepared up front to get the full benefit.
+    ///
+    /// Each statement succeeds or fails on its own: an error, whether from preparing, encoding or executing a
+    /// statement, is returned in its place and the other statements still run. Outside of a transaction each statement
+    /// commits on its own; inside one, an error aborts the transaction and the statements after it fail too. Use
+    /// `Pipeline` to send statements that should succeed or fail together.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of parameters provided for a statement does not match the number it expects.
+    pub fn query_all<T>(
+        &self,
+        statements: &[(&T, &[&(dyn ToSql + Sync)])],
+    ) -> Vec<Result<Vec<Row>, Error>>
+    where
+        T: ?Sized + ToStatement,
+    {
+        let streams = statements
+            .iter()
+            .map(|(statement, params)| self.query_raw(*statement, slice_iter(params)))
+            .collect::<Vec<_>>();
+        streams
+            .into_iter()
+            .map(|rows| rows.and_then(FallibleIterator::collect))
+            .collect()
+    }
+
+    /// Like `query_typed`, but uses the unnamed statement rather than preparing a named one.
+    ///
+    /// The query is parsed into the unnamed statement with the given parameter types, then bound, described and
+    /// executed, all in a single request, so it costs one round trip. Unlike passing a query string to `query`, this
+    /// leaves no statement behind to be closed afterwards, which suits queries that are only run once. The types are
+    /// needed up front since the parameters are sent in the binary format, which depends on them. The unnamed
+    /// statement is replaced by the next query parsed on the connection, which doesn't matter here since it is only
+    /// used within the request.
+    ///
+    /// A value whose `ToSql` implementation doesn't accept its type fails with an "error serializing parameter" error
+    /// naming its index, before anything is sent to the server.
+    pub fn query_unnamed(
+        &self,
+        query: &str,
+        params: &[(&(dyn ToSql + Sync), Type)],
+    ) -> Result<Vec<Row>, Error> {
+        let types = params.iter().map(|(_, ty)| ty.clone()).collect::<Vec<_>>();
+        let rows = query::query_unnamed(
+            &self.inner,
+            query,
+            &types,
+            params.iter().map(|(param, _)| *param as _),
+        )?;
+        FallibleIterator::collect(rows)
+    }
+
+    /// Like `query`, but takes the type of each parameter along with its value.
+    ///
+    /// Each value is checked against its type before anything is sent to the server, and a value whose `ToSql`
+    /// implementation doesn't accept the type fails with an "error serializing parameter" error naming its index.
+    /// The statement is then prepared with the given types, rather than having the server infer them, and executed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the query has more parameters than the number of values provided.
+    pub fn query_typed(
+        &self,
+        query: &str,
+        params: &[(&(dyn ToSql + Sync), Type)],
+    ) -> Result<Vec<Row>, Error> {
+        let mut buf = BytesMut::new();
+        for (idx, (param, ty)) in params.iter().enumerate() {
+            param
+                .to_sql_checked(ty, &mut buf)
+                .map_err(|e| Error::to_sql(e, idx))?;
+            buf.clear();
+        }
+
+        let types = params.iter().map(|(_, ty)| ty.clone()).collect::<Vec<_>>();
+        let statement = self.prepare_typed(query, &types)?;
+        let params = params.iter().map(|(param, _)| *param).collect::<Vec<_>>();
+        self.query(&statement, &params)
+    }
+
+    /// Like `query`, but asks the server to send the results in the given formats.
+    ///
+    /// `formats` holds either one format for each column of the statement, or a single format used for all of them.
+    /// Values in `Format::Text` are sent as Postgres prints them and can be read from the rows as strings, which
+    /// allows reading types that have no binary `FromSql` implementation. Use `Row::format` to tell the formats apart
+    /// when reading raw bytes with `Row::get_bytes`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of parameters provided does not match the number expected, or if the number of formats
+    /// is neither 1 nor the number of columns.
+    pub fn query_with_formats<T>(
+        &self,
+        statement: &T,
+        params: &[&(dyn ToSql + Sync)],
+        formats: &[Format],
+    ) -> Result<Vec<Row>, Error>
+    where
+        T: ?Sized + ToStatement,
+    {
+        let statement = statement.__convert().into_statement(self)?;
+        let rows = {
+            let _g = self.inner.sender.read_lock();
+            query::query_with_formats(&self.inner, statement, slice_iter(params), formats)?
+        };
+        FallibleIterator::collect(rows)
+    }
+
+    /// Like `query`, but returns every value in its text representation, whatever the type of its column.
+    ///
+    /// The results are requested in `Format::Text`, so the values read from the rows are strings as Postgres prints
+    /// them, and `NULL`s are `None`. This suits tools which only display values, since types without a `FromSql`
+    /// implementation, like `NUMERIC` or user-defined types, read the same as any other. Parameters are still sent
+    /// in the binary format.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of parameters provided does not match the number expected.
+    pub fn query_raw_txt<T>(
+        &self,
+        statement: &T,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<Vec<SimpleQueryRow>, Error>
+    where
+        T: ?Sized + ToStatement,
+    {
+        let statement = statement.__convert().into_statement(self)?;
+        let columns = statement
+            .columns()
+            .iter()
+            .map(|c| c.name().to_string())
+            .collect::<Arc<[String]>>();
+        let rows = self.query_with_formats(&statement, params, &[Format::Text])?;
+        Ok(rows
+            .into_iter()
+            .map(|row| row.into_simple(columns.clone()))
+            .collect())
+    }
+
     /// The maximally flexible version of [`query`].
     ///
     /// A statement may contain parameters, specified by `$n`, where `n` is the index of the parameter of the list
@@ -283,6 +651,76 @@ impl Client {
         query::query(&self.inner, statement, params)
     }
 
+    /// Like [`query_raw`], but requests the rows from the server `fetch_size` at a time as the stream is read.
+    ///
+    /// This keeps memory use bounded for large result sets. The rows are read from a portal which only stays open
+    /// until the next `Sync` outside of a transaction, so while the stream is alive the connection is reserved to it:
+    /// requests from other coroutines wait until it has been read to completion or dropped. A request made on the
+    /// same connection by the coroutine reading the stream could never complete, and returns an error instead.
+    ///
+    /// If `fetch_size` is 0 or negative, all rows are requested at once.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of parameters provided does not match the number expected.
+    ///
+    /// [`query_raw`]: #method.query_raw
+    pub fn query_raw_chunked<'a, T, I>(
+        &self,
+        statement: &T,
+        params: I,
+        fetch_size: i32,
+    ) -> Result<RowStream, Error>
+    where
+        T: ?Sized + ToStatement,
+        I: IntoIterator<Item = &'a dyn ToSql>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        if fetch_size <= 0 {
+            return self.query_raw(statement, params);
+        }
+
+        let statement = statement.__convert().into_statement(self)?;
+        query::query_chunked(&self.inner, statement, params, fetch_size)
+    }
+
+    /// Executes a statement, passing each resulting row to a callback, and returns the number of rows.
+    ///
+    /// The rows are `RowRef`s which read their values straight from the buffer they were received in, without the
+    /// allocation a `Row` makes, which adds up for large result sets. A row can't be kept past the callback. An error
+    /// returned by the callback stops the query and is returned, and the rest of the rows are discarded.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), may_postgres::Error> {
+    /// let client = may_postgres::connect("host=localhost user=postgres")?;
+    /// let mut total = 0;
+    /// client.query_each("SELECT amount FROM payment", &[], |row| {
+    ///     total += row.get::<_, i64>(0);
+    ///     Ok(())
+    /// })?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of parameters provided does not match the number expected.
+    pub fn query_each<T, F>(
+        &self,
+        statement: &T,
+        params: &[&(dyn ToSql + Sync)],
+        f: F,
+    ) -> Result<u64, Error>
+    where
+        T: ?Sized + ToStatement,
+        F: FnMut(RowRef<'_>) -> Result<(), Error>,
+    {
+        let statement = statement.__convert().into_statement(self)?;
+        query::query_each(&self.inner, statement, slice_iter(params), f)
+    }
+
     /// Executes a statement, returning the number of rows modified.
     ///
     /// A statement may contain parameters, specified by `$n`, where `n` is the index of the parameter of the list
@@ -328,6 +766,168 @@ impl Client {
         query::execute(self.inner(), statement, params)
     }
 
+    /// Like `execute`, but returns the command tag the server completed the statement with.
+    ///
+    /// The tag is the name of the command, followed by a count for commands that have one, like `INSERT 0 5`,
+    /// `MERGE 3`, `COPY 10`, or just `CREATE TABLE` or `CALL`. An empty query returns an empty string.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of parameters provided does not match the number expected.
+    pub fn execute_with_tag<T>(
+        &self,
+        statement: &T,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<String, Error>
+    where
+        T: ?Sized + ToStatement,
+    {
+        let statement = statement.__convert().into_statement(self)?;
+        query::execute_with_tag(self.inner(), statement, slice_iter(params))
+    }
+
+    /// Inserts rows into a table, returning the number of rows inserted.
+    ///
+    /// The rows are inserted by multi-row `INSERT ... VALUES` statements, each taking as many rows as fit in the
+    /// 65535 parameters a statement can have. The statements are prepared, which costs a round trip for each distinct
+    /// number of rows, and then sent together in a `Pipeline`, so the whole insert runs in a single implicit
+    /// transaction unless the client is already inside an explicit one, and either all rows are inserted or none are.
+    ///
+    /// `table` and `columns` are written into the statement as given, so names which need quoting, or come from an
+    /// untrusted source, must be passed through `escape_identifier` first. With more than 65535 columns not even one
+    /// row fits in a statement, and an error for which `Error::is_too_many_params` returns `true` is returned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `columns` is empty, or if a row doesn't have one value for each column.
+    pub fn insert_many(
+        &self,
+        table: &str,
+        columns: &[&str],
+        rows: &[&[&(dyn ToSql + Sync)]],
+    ) -> Result<u64, Error> {
+        assert!(!columns.is_empty(), "expected at least 1 column");
+        for row in rows {
+            assert!(
+                row.len() == columns.len(),
+                "expected {} values per row but got {}",
+                columns.len(),
+                row.len()
+            );
+        }
+        if columns.len() > query::MAX_PARAMS {
+            return Err(Error::too_many_params(columns.len()));
+        }
+
+        let rows_per_statement = query::MAX_PARAMS / columns.len();
+        let mut statements: Vec<(usize, Statement)> = vec![];
+        let mut pipeline = self.pipeline();
+        for chunk in rows.chunks(rows_per_statement) {
+            let statement = match statements.iter().find(|(len, _)| *len == chunk.len()) {
+                Some((_, statement)) => statement.clone(),
+                None => {
+                    let statement = self.prepare(&insert_query(table, columns, chunk.len()))?;
+                    statements.push((chunk.len(), statement.clone()));
+                    statement
+                }
+            };
+            let params = chunk
+                .iter()
+                .flat_map(|row| row.iter().copied())
+                .collect::<Vec<_>>();
+            pipeline.push(&statement, &params)?;
+        }
+
+        let mut count = 0;
+        for result in pipeline.execute()? {
+            count += result?;
+        }
+        Ok(count)
+    }
+
+    /// Like `query`, but gives up waiting after `timeout` has elapsed.
+    ///
+    /// When the timeout expires a cancellation request is sent to the server, and `Error::timeout` is returned once it
+    /// has been delivered, or right away if it can't be, like for a client connected with `Config::connect_raw`. The
+    /// connection reads and discards the rest of the query's response in the background, so it remains usable
+    /// afterwards.
+    ///
+    /// The timeout covers the execution of the statement only. Preparing a query string first is bounded by
+    /// `Config::request_timeout` instead, if it is set.
+    ///
+    /// Requests from all coroutines sharing this client are pipelined over a single connection, and the server can
+    /// only cancel whatever it is executing at the moment the cancellation arrives. If this query is still queued
+    /// behind requests from other coroutines when the timeout expires, one of those requests may be cancelled
+    /// instead, and if the query completes just as the timeout expires the cancellation may hit the next request on
+    /// the connection.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of parameters provided does not match the number expected.
+    pub fn query_timeout<T>(
+        &self,
+        statement: &T,
+        params: &[&(dyn ToSql + Sync)],
+        timeout: Duration,
+    ) -> Result<Vec<Row>, Error>
+    where
+        T: ?Sized + ToStatement,
+    {
+        let statement = statement.__convert().into_statement(self)?;
+        self.cancel_on_timeout(|| {
+            let rows = {
+                let _g = self.inner.sender.read_lock();
+                query::query_timeout(&self.inner, statement, slice_iter(params), timeout)?
+            };
+            FallibleIterator::collect(rows)
+        })
+    }
+
+    /// Like `execute`, but gives up waiting after `timeout` has elapsed.
+    ///
+    /// See `query_timeout` for details on how the timeout is enforced.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of parameters provided does not match the number expected.
+    pub fn execute_timeout<T>(
+        &self,
+        statement: &T,
+        params: &[&(dyn ToSql + Sync)],
+        timeout: Duration,
+    ) -> Result<u64, Error>
+    where
+        T: ?Sized + ToStatement,
+    {
+        let statement = statement.__convert().into_statement(self)?;
+        self.cancel_on_timeout(|| {
+            query::execute_timeout(&self.inner, statement, slice_iter(params), timeout)
+        })
+    }
+
+    // cancels the query on the server once its response has timed out, which the caller is told about either way
+    fn cancel_on_timeout<F, R>(&self, f: F) -> Result<R, Error>
+    where
+        F: FnOnce() -> Result<R, Error>,
+    {
+        match f() {
+            Err(e) if e.is_timeout() => {
+                if let Err(e) = self.cancel_query() {
+                    error!("failed to cancel timed out query: {}", e);
+                }
+                Err(e)
+            }
+            result => result,
+        }
+    }
+
+    /// Begins a batch of statements which is sent to the server in a single round trip.
+    ///
+    /// See `Pipeline` for how the statements are executed and how errors are reported.
+    pub fn pipeline(&self) -> Pipeline<'_> {
+        Pipeline::new(self)
+    }
+
     /// Executes a `COPY FROM STDIN` statement, returning a sink used to write the copy data.
     ///
     /// PostgreSQL does not support parameters in `COPY` statements, so this method does not take any. The copy *must*
@@ -345,6 +945,22 @@ impl Client {
         copy_in::copy_in(self.inner(), statement)
     }
 
+    /// Executes a `COPY FROM STDIN` statement, returning a writer for the copy data.
+    ///
+    /// Like `copy_in`, but the data is written through `std::io::Write`, for example by a CSV encoder. The copy *must*
+    /// be explicitly completed via the `CopyInWriter::finish` method. If it is not, the copy will be aborted.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the statement contains parameters.
+    pub fn copy_in_writer<T>(&self, statement: &T) -> Result<CopyInWriter, Error>
+    where
+        T: ?Sized + ToStatement,
+    {
+        let statement = statement.__convert().into_statement(self)?;
+        copy_in::copy_in_writer(self.inner(), statement)
+    }
+
     /// Executes a `COPY TO STDOUT` statement, returning a stream of the resulting data.
     ///
     /// PostgreSQL does not support parameters in `COPY` statements, so this method does not take any.
@@ -360,6 +976,17 @@ impl Client {
         copy_out::copy_out(self.inner(), statement)
     }
 
+    /// Runs a command which starts a copy in both directions, returning the halves which send data to the server and
+    /// receive data from it.
+    ///
+    /// This is how the streaming replication started by `START_REPLICATION` on a replication connection, opened with
+    /// `Config::replication_mode`, is carried; the `replication` module handles the messages exchanged over it. The
+    /// command is sent with the simple query protocol, and the copy isn't bounded by `Config::request_timeout`. Other
+    /// requests on the client wait until the copy is over.
+    pub fn copy_both(&self, query: &str) -> Result<(CopyBothSink, CopyBothStream), Error> {
+        copy_both::copy_both(self.inner(), query)
+    }
+
     /// Executes a sequence of SQL statements using the simple query protocol, returning the resulting rows.
     ///
     /// Statements should be separated by semicolons. If an error occurs, execution of the sequence will stop at that
@@ -396,6 +1023,22 @@ impl Client {
         simple_query::batch_execute(self.inner(), query)
     }
 
+    /// Like `batch_execute`, but reports which statement of the script failed.
+    ///
+    /// The script is sent as a single simple query, so the semantics are the same as for `batch_execute`: unless it
+    /// contains its own `BEGIN`, the server runs it in an implicit transaction which is rolled back when a statement
+    /// fails. The failing statement is found by counting the statements the server completed before the error. On
+    /// success, the number of statements run is returned.
+    ///
+    /// # Warning
+    ///
+    /// Prepared statements should be use for any query which contains user-specified data, as they provided the
+    /// functionality to safely embed that data in the request. Do not form statements via string concatenation and pass
+    /// them to this method!
+    pub fn execute_script(&self, script: &str) -> Result<usize, ScriptError> {
+        simple_query::execute_script(self.inner(), script)
+    }
+
     /// Begins a new database transaction.
     ///
     /// The transaction will roll back by default - use the `commit` method to commit it.
@@ -412,12 +1055,36 @@ impl Client {
         TransactionBuilder::new(self)
     }
 
+    /// Returns the notifications received from the server over this connection.
+    ///
+    /// Notifications are delivered for channels the session subscribed to with `LISTEN`. They are buffered by the
+    /// connection as they arrive, so none are lost between calls. Only one `Notifications` handle can be held at a
+    /// time; other callers will wait until it is dropped.
+    pub fn notifications(&self) -> Notifications<'_> {
+        Notifications::new(self.inner.notifications.lock().unwrap())
+    }
+
+    /// Sets the handler invoked for each notice sent by the server.
+    ///
+    /// Notices are non-fatal messages such as `WARNING: there is already a transaction in progress` or the output of
+    /// `RAISE NOTICE`. The handler runs on the connection's background coroutine, so it should return quickly. By
+    /// default notices are logged at the `info` level.
+    pub fn set_notice_callback<F>(&self, callback: F)
+    where
+        F: Fn(DbError) + Send + Sync + 'static,
+    {
+        self.inner
+            .sender
+            .set_notice_callback(Some(Arc::new(callback)));
+    }
+
     /// Constructs a cancellation token that can later be used to request
     /// cancellation of a query running on the connection associated with
     /// this client.
     pub fn cancel_token(&self) -> CancelToken {
         CancelToken {
             socket_config: self.socket_config.clone(),
+            ssl_mode: self.ssl_mode,
             process_id: self.process_id,
             secret_key: self.secret_key,
         }
@@ -429,14 +1096,74 @@ impl Client {
     /// only be returned if the client was unable to connect to the database.
     ///
     pub fn cancel_query(&self) -> Result<(), Error> {
-        cancel_query::cancel_query(self.socket_config.clone(), self.process_id, self.secret_key)
+        self.cancel_token().cancel_query()
+    }
+
+    /// Returns the process id of the server backend serving the connection.
+    ///
+    /// This is the `pid` of the connection's row in `pg_stat_activity`, as returned by `pg_backend_pid()`.
+    pub fn backend_pid(&self) -> i32 {
+        self.process_id
+    }
+
+    /// Returns the value of a runtime parameter reported by the server, like `server_version`, `server_encoding`,
+    /// `TimeZone` or `integer_datetimes`.
+    ///
+    /// The server reports a set of parameters when the connection is established, and reports them again whenever
+    /// they change, e.g. after a `SET TimeZone`. Other parameters are not reported and return `None`.
+    pub fn parameter(&self, name: &str) -> Option<String> {
+        self.inner.sender.parameter(name)
+    }
+
+    /// Switches the logging of the protocol messages exchanged with the server on or off.
+    ///
+    /// See `Config::trace_protocol`. The setting is shared by all clones of the client.
+    pub fn set_trace_protocol(&self, trace_protocol: bool) {
+        self.inner.sender.set_trace_protocol(trace_protocol);
+    }
+
+    /// Returns a snapshot of the activity counters of the connection.
+    ///
+    /// The counters are updated by the connection's background coroutines, so a snapshot taken while other
+    /// coroutines use the client may already be out of date.
+    pub fn stats(&self) -> ConnectionStats {
+        self.inner.sender.stats()
+    }
+
+    /// Closes the connection gracefully.
+    ///
+    /// No new requests are accepted from this point on, but the ones already sent are written out and answered by the
+    /// server before the session is terminated, so row streams and other responses obtained earlier can still be read
+    /// to the end afterwards. This blocks until the connection's coroutines have exited. Dropping a client instead
+    /// stops the connection immediately, discarding requests which have not been answered yet.
+    ///
+    /// The connection is shared by every clone of the client, so closing one closes it for all of them: what they sent
+    /// earlier is still answered, and their later requests fail with an error for which `Error::is_closed` returns
+    /// `true`. To give up a single handle while other clones keep using the connection, drop it instead; the
+    /// connection is only stopped once the last clone is dropped.
+    ///
+    /// Returns an error for which `Error::is_closed` returns `true` if the connection had already closed.
+    pub fn close(self) -> Result<(), Error> {
+        if self.inner.sender.shutdown() {
+            Ok(())
+        } else {
+            Err(Error::closed())
+        }
     }
 
     /// Determines if the connection to the server has already closed.
     ///
-    /// In that case, all future queries will fail.
-    pub fn _is_closed(&self) -> bool {
-        // self.inner.sender.is_closed()
-        unimplemented!()
+    /// In that case, all future queries will fail with an error for which `Error::is_closed` returns `true`.
+    pub fn is_closed(&self) -> bool {
+        self.inner.sender.is_closed()
+    }
+
+    /// Checks that the connection is still usable by sending an empty query and waiting at most `timeout` for the
+    /// server to answer it.
+    ///
+    /// Returns `false` right away, without a round trip, if the connection is known to be closed. If the answer doesn't
+    /// arrive in time, it is discarded when it does.
+    pub fn is_valid(&self, timeout: Duration) -> bool {
+        !self.is_closed() && simple_query::ping(&self.inner, timeout).is_ok()
     }
 }
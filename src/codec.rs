@@ -1,12 +1,14 @@
 use bytes::{Buf, BufMut, Bytes, BytesMut};
 use fallible_iterator::FallibleIterator;
-use may::net::TcpStream;
+use log::error;
 use postgres_protocol::message::backend;
 use postgres_protocol::message::frontend::CopyData;
 use std::io::{self, Read};
 
 pub use frame_codec::Framed;
 
+const COPY_BOTH_RESPONSE_TAG: u8 = b'W';
+
 pub enum FrontendMessage {
     Raw(Bytes),
     CopyData(CopyData<Box<dyn Buf + Send>>),
@@ -20,11 +22,70 @@ pub enum BackendMessage {
     Async(backend::Message),
 }
 
-pub struct BackendMessages(BytesMut);
+pub struct BackendMessages {
+    buf: BytesMut,
+    // whether there is an `ErrorResponse` among the messages, so that most batches aren't scanned for a fatal one
+    has_error: bool,
+}
 
 impl BackendMessages {
     pub fn empty() -> BackendMessages {
-        BackendMessages(BytesMut::new())
+        BackendMessages {
+            buf: BytesMut::new(),
+            has_error: false,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    /// Consumes a `CopyBothResponse` at the front of the messages, returning whether there was one.
+    ///
+    /// postgres-protocol doesn't know the message, so it is only expected in reply to a `COPY` in both directions and
+    /// fails to parse anywhere else.
+    pub fn copy_both_response(&mut self) -> bool {
+        if self.buf.first() != Some(&COPY_BOTH_RESPONSE_TAG) || self.buf.len() < 5 {
+            return false;
+        }
+        let len = u32::from_be_bytes([self.buf[1], self.buf[2], self.buf[3], self.buf[4]]) as usize;
+        self.buf.advance((len + 1).min(self.buf.len()));
+        true
+    }
+
+    /// Lists the tags of the messages, for logging.
+    pub fn tags(&self) -> String {
+        tags(&self.buf)
+    }
+
+    /// Returns the first `FATAL` or `PANIC` error among the messages, after which the server closes the connection.
+    pub fn fatal_error(&self) -> io::Result<Option<backend::ErrorResponseBody>> {
+        if !self.has_error {
+            return Ok(None);
+        }
+
+        let mut idx = 0;
+        while let Some(header) = backend::Header::parse(&self.buf[idx..])? {
+            let len = header.len() as usize + 1;
+            if header.tag() == backend::ERROR_RESPONSE_TAG {
+                let mut buf = BytesMut::from(&self.buf[idx..idx + len]);
+                // a malformed error isn't taken as fatal, its waiter gets the parse error when reading it
+                if let Ok(Some(backend::Message::ErrorResponse(body))) =
+                    backend::Message::parse(&mut buf)
+                {
+                    let fatal = body.fields().any(|f| {
+                        // `S` may be localized, `V` is only sent by 9.6+
+                        let severity = f.type_() == b'V' || f.type_() == b'S';
+                        Ok(severity && (f.value() == "FATAL" || f.value() == "PANIC"))
+                    });
+                    if let Ok(true) = fatal {
+                        return Ok(Some(body));
+                    }
+                }
+            }
+            idx += len;
+        }
+        Ok(None)
     }
 }
 
@@ -33,10 +94,24 @@ impl FallibleIterator for BackendMessages {
     type Error = io::Error;
 
     fn next(&mut self) -> io::Result<Option<backend::Message>> {
-        backend::Message::parse(&mut self.0)
+        backend::Message::parse(&mut self.buf)
     }
 }
 
+/// Lists the tags of the messages in `buf`, like `P B E S`, for logging.
+pub fn tags(mut buf: &[u8]) -> String {
+    let mut tags = String::new();
+    while buf.len() >= 5 {
+        if !tags.is_empty() {
+            tags.push(' ');
+        }
+        tags.push(buf[0] as char);
+        let len = u32::from_be_bytes([buf[1], buf[2], buf[3], buf[4]]) as usize;
+        buf = &buf[(len + 1).min(buf.len())..];
+    }
+    tags
+}
+
 pub struct PostgresCodec;
 
 // impl Encoder
@@ -56,6 +131,7 @@ impl PostgresCodec {
     pub fn decode(&mut self, src: &mut BytesMut) -> Result<Option<BackendMessage>, io::Error> {
         let mut idx = 0;
         let mut request_complete = false;
+        let mut has_error = false;
 
         while let Some(header) = backend::Header::parse(&src[idx..])? {
             let len = header.len() as usize + 1;
@@ -68,8 +144,17 @@ impl PostgresCodec {
                 | backend::NOTIFICATION_RESPONSE_TAG
                 | backend::PARAMETER_STATUS_TAG => {
                     if idx == 0 {
-                        let message = backend::Message::parse(src)?.unwrap();
-                        return Ok(Some(BackendMessage::Async(message)));
+                        // the message is split off before its body is parsed, so a malformed one is dropped without
+                        // losing track of where the next one starts
+                        match backend::Message::parse(src) {
+                            Ok(message) => {
+                                return Ok(Some(BackendMessage::Async(message.unwrap())))
+                            }
+                            Err(e) => {
+                                error!("dropping malformed asynchronous message: {}", e);
+                                continue;
+                            }
+                        }
                     } else {
                         break;
                     }
@@ -78,7 +163,10 @@ impl PostgresCodec {
             }
 
             idx += len;
+            has_error |= header.tag() == backend::ERROR_RESPONSE_TAG;
 
+            // every request ends with a sync, so it is complete once the server is ready again, whether it finished
+            // with `CommandComplete`, `EmptyQueryResponse` or an error
             if header.tag() == backend::READY_FOR_QUERY_TAG {
                 request_complete = true;
                 break;
@@ -89,7 +177,10 @@ impl PostgresCodec {
             Ok(None)
         } else {
             Ok(Some(BackendMessage::Normal {
-                messages: BackendMessages(src.split_to(idx)),
+                messages: BackendMessages {
+                    buf: src.split_to(idx),
+                    has_error,
+                },
                 request_complete,
             }))
         }
@@ -102,18 +193,22 @@ mod frame_codec {
     use may::sync::{RwLock, RwLockReadGuard};
     use std::sync::Arc;
 
-    pub struct Framed {
-        r_stream: TcpStream,
+    pub struct Framed<S> {
+        r_stream: S,
         read_buf: BytesMut,
         codec: PostgresCodec,
         rw_lock: Arc<RwLock<()>>,
         lock: Option<RwLockReadGuard<'static, ()>>,
     }
 
-    impl Framed {
-        pub fn new(s: TcpStream) -> Self {
+    impl<S: Read> Framed<S> {
+        pub fn new(s: S) -> Self {
             let rw_lock = Arc::new(RwLock::new(()));
-            let lock = unsafe { std::mem::transmute(rw_lock.read().unwrap()) };
+            let lock = unsafe {
+                std::mem::transmute::<RwLockReadGuard<'_, ()>, RwLockReadGuard<'static, ()>>(
+                    rw_lock.read().unwrap(),
+                )
+            };
             Framed {
                 r_stream: s,
                 read_buf: BytesMut::with_capacity(4096 * 8),
@@ -123,14 +218,23 @@ mod frame_codec {
             }
         }
 
-        pub fn inner_mut(&mut self) -> &mut TcpStream {
+        pub fn inner_mut(&mut self) -> &mut S {
             &mut self.r_stream
         }
 
+        pub fn inner(&self) -> &S {
+            &self.r_stream
+        }
+
         pub fn get_rw_lock(&self) -> Arc<RwLock<()>> {
             self.rw_lock.clone()
         }
 
+        /// decode a message that is already in the read buffer without touching the socket
+        pub fn buffered_msg(&mut self) -> io::Result<Option<BackendMessage>> {
+            self.codec.decode(&mut self.read_buf)
+        }
+
         pub fn next_msg(&mut self) -> io::Result<BackendMessage> {
             loop {
                 if let Some(msg) = self.codec.decode(&mut self.read_buf)? {
@@ -147,7 +251,11 @@ mod frame_codec {
                         unsafe { &mut *(self.read_buf.bytes_mut() as *mut _ as *mut [u8]) };
                     drop(self.lock.take());
                     let ret = self.r_stream.read(read_buf)?;
-                    let lock = unsafe { std::mem::transmute(self.rw_lock.read().unwrap()) };
+                    let lock = unsafe {
+                        std::mem::transmute::<RwLockReadGuard<'_, ()>, RwLockReadGuard<'static, ()>>(
+                            self.rw_lock.read().unwrap(),
+                        )
+                    };
                     self.lock = Some(lock);
                     ret
                 };
@@ -164,6 +272,7 @@ mod frame_codec {
 #[cfg(unix_asd)]
 mod frame_codec {
     use super::*;
+    use may::net::TcpStream;
 
     pub struct Framed {
         r_stream: TcpStream,
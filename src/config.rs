@@ -2,6 +2,9 @@
 
 use crate::connect::connect;
 use crate::connect_raw::connect_raw;
+use crate::keepalive::KeepaliveConfig;
+use crate::socket::Socket;
+use crate::tls::{MakeTlsConnect, NoTls, TlsConnect};
 use crate::{Client, Error};
 use std::borrow::Cow;
 use std::str;
@@ -14,7 +17,7 @@ use std::ffi::OsStr;
 #[cfg(unix)]
 use std::os::unix::ffi::OsStrExt;
 #[cfg(unix)]
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use may::net::TcpStream;
 
@@ -27,14 +30,73 @@ pub enum TargetSessionAttrs {
     ReadWrite,
 }
 
+/// TLS configuration.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum SslMode {
+    /// Do not use TLS.
+    Disable,
+    /// Attempt to connect with TLS but allow sessions without.
+    Prefer,
+    /// Require the use of TLS.
+    Require,
+}
+
+/// Channel binding configuration.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum ChannelBinding {
+    /// Do not use channel binding.
+    Disable,
+    /// Attempt to use channel binding but allow sessions without.
+    Prefer,
+    /// Require the use of channel binding.
+    Require,
+}
+
+/// Replication mode configuration.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum ReplicationMode {
+    /// Physical replication, which streams the WAL of the whole cluster.
+    Physical,
+    /// Logical replication, which streams the changes of the database connected to through a replication slot.
+    Logical,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub(crate) enum Host {
     Tcp(String),
+    #[cfg(unix)]
+    Unix(PathBuf),
 }
 
 /// Connection configuration.
 ///
-/// Configuration can be parsed from libpq-style connection strings. These strings come in two formats:
+/// Configuration can be built up with the setters of a `Config` created by `Config::new`, each of which returns the
+/// configuration so that calls can be chained:
+///
+/// ```no_run
+/// use may_postgres::config::SslMode;
+/// use may_postgres::Config;
+/// use std::time::Duration;
+///
+/// # fn main() -> Result<(), may_postgres::Error> {
+/// let client = Config::new()
+///     .host("localhost")
+///     .port(5432)
+///     .user("postgres")
+///     .password("secret")
+///     .dbname("app")
+///     .application_name("worker")
+///     .connect_timeout(Duration::from_secs(10))
+///     .ssl_mode(SslMode::Disable)
+///     .connect()?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Configuration can also be parsed from libpq-style connection strings. These strings come in two formats:
 ///
 /// # Key-Value
 ///
@@ -44,31 +106,43 @@ pub(crate) enum Host {
 /// ## Keys
 ///
 /// * `user` - The username to authenticate with. Required.
-/// * `password` - The password to authenticate with.
+/// * `password` - The password to authenticate with. It is sent with whichever method the server asks for, which
+///   follows the `pg_hba.conf` entry and, for `md5` entries, how the password is stored (`password_encryption`):
+///   `password` sends it in cleartext, `md5` sends `md5(md5(password + user) + salt)`, and `scram-sha-256` (or `md5`
+///   with a password stored as SCRAM) runs a SCRAM-SHA-256 exchange. GSSAPI authentication, for `gss` entries, needs
+///   the `with-gssapi` feature and uses the Kerberos credentials of the process instead. SSPI is not supported.
 /// * `dbname` - The name of the database to connect to. Defaults to the username.
-/// * `options` - Command line options used to configure the server.
+/// * `options` - Command line options used to configure the server, like `-c statement_timeout=5000`.
 /// * `application_name` - Sets the `application_name` parameter on the server.
+/// * `krbsrvname` - The Kerberos service name of the server, for GSSAPI authentication. Defaults to `postgres`.
 /// * `sslmode` - Controls usage of TLS. If set to `disable`, TLS will not be used. If set to `prefer`, TLS will be used
-///     if available, but not used otherwise. If set to `require`, TLS will be forced to be used. Defaults to `prefer`.
+///   if available, but not used otherwise. If set to `require`, TLS will be forced to be used. Defaults to `prefer`.
 /// * `host` - The host to connect to. On Unix platforms, if the host starts with a `/` character it is treated as the
-///     path to the directory containing Unix domain sockets. Otherwise, it is treated as a hostname. Multiple hosts
-///     can be specified, separated by commas. Each host will be tried in turn when connecting. Required if connecting
-///     with the `connect` method.
+///   path to the directory containing Unix domain sockets. Otherwise, it is treated as a hostname. Multiple hosts
+///   can be specified, separated by commas. Each host will be tried in turn when connecting. Required if connecting
+///   with the `connect` method.
 /// * `port` - The port to connect to. Multiple ports can be specified, separated by commas. The number of ports must be
-///     either 1, in which case it will be used for all hosts, or the same as the number of hosts. Defaults to 5432 if
-///     omitted or the empty string.
+///   either 1, in which case it will be used for all hosts, or the same as the number of hosts. Defaults to 5432 if
+///   omitted or the empty string.
 /// * `connect_timeout` - The time limit in seconds applied to each socket-level connection attempt. Note that hostnames
-///     can resolve to multiple IP addresses, and this limit is applied to each address. Defaults to no timeout.
+///   can resolve to multiple IP addresses, and this limit is applied to each address. The same limit also bounds the
+///   startup and authentication with the host once its socket is connected. Defaults to no timeout.
 /// * `keepalives` - Controls the use of TCP keepalive. A value of 0 disables keepalive and nonzero integers enable it.
-///     This option is ignored when connecting with Unix sockets. Defaults to on.
+///   This option is ignored when connecting with Unix sockets. Defaults to on.
 /// * `keepalives_idle` - The number of seconds of inactivity after which a keepalive message is sent to the server.
-///     This option is ignored when connecting with Unix sockets. Defaults to 2 hours.
+///   This option is ignored when connecting with Unix sockets. Defaults to 2 hours.
+/// * `keepalives_interval` - The time interval in seconds between keepalive probes that the server does not
+///   acknowledge. This option is ignored when connecting with Unix sockets. Defaults to the operating system default.
+/// * `keepalives_retries` - The number of unacknowledged keepalive probes after which the connection is considered
+///   dead. This option is ignored when connecting with Unix sockets. Defaults to the operating system default.
 /// * `target_session_attrs` - Specifies requirements of the session. If set to `read-write`, the client will check that
-///     the `transaction_read_write` session parameter is set to `on`. This can be used to connect to the primary server
-///     in a database cluster as opposed to the secondary read-only mirrors. Defaults to `all`.
+///   the `transaction_read_write` session parameter is set to `on`. This can be used to connect to the primary server
+///   in a database cluster as opposed to the secondary read-only mirrors. Defaults to `all`.
 /// * `channel_binding` - Controls usage of channel binding in the authentication process. If set to `disable`, channel
-///     binding will not be used. If set to `prefer`, channel binding will be used if available, but not used otherwise.
-///     If set to `require`, the authentication process will fail if channel binding is not used. Defaults to `prefer`.
+///   binding will not be used. If set to `prefer`, channel binding will be used if available, but not used otherwise.
+///   If set to `require`, the authentication process will fail if channel binding is not used. Defaults to `prefer`.
+/// * `replication` - Opens a replication connection. If set to `true`, it is a physical replication connection, and if
+///   set to `database`, a logical one. Defaults to `false`.
 ///
 /// ## Examples
 ///
@@ -115,12 +189,25 @@ pub struct Config {
     pub(crate) dbname: Option<String>,
     pub(crate) options: Option<String>,
     pub(crate) application_name: Option<String>,
+    pub(crate) krbsrvname: String,
+    pub(crate) ssl_mode: SslMode,
     pub(crate) host: Vec<Host>,
     pub(crate) port: Vec<u16>,
     pub(crate) connect_timeout: Option<Duration>,
     pub(crate) keepalives: bool,
     pub(crate) keepalives_idle: Duration,
+    pub(crate) keepalives_interval: Option<Duration>,
+    pub(crate) keepalives_retries: Option<u32>,
     pub(crate) target_session_attrs: TargetSessionAttrs,
+    pub(crate) channel_binding: ChannelBinding,
+    pub(crate) replication_mode: Option<ReplicationMode>,
+    pub(crate) statement_cache_size: usize,
+    pub(crate) max_in_flight: usize,
+    pub(crate) write_buffer_capacity: usize,
+    pub(crate) max_coalesce_bytes: usize,
+    pub(crate) request_timeout: Option<Duration>,
+    pub(crate) keepalive_query: Option<Duration>,
+    pub(crate) trace_protocol: bool,
 }
 
 impl Default for Config {
@@ -138,12 +225,25 @@ impl Config {
             dbname: None,
             options: None,
             application_name: None,
+            krbsrvname: "postgres".to_string(),
+            ssl_mode: SslMode::Prefer,
             host: vec![],
             port: vec![],
             connect_timeout: None,
             keepalives: true,
             keepalives_idle: Duration::from_secs(2 * 60 * 60),
+            keepalives_interval: None,
+            keepalives_retries: None,
             target_session_attrs: TargetSessionAttrs::Any,
+            channel_binding: ChannelBinding::Prefer,
+            replication_mode: None,
+            statement_cache_size: 512,
+            max_in_flight: 0,
+            write_buffer_capacity: 1024,
+            max_coalesce_bytes: 0,
+            request_timeout: None,
+            keepalive_query: None,
+            trace_protocol: false,
         }
     }
 
@@ -173,6 +273,10 @@ impl Config {
     }
 
     /// Sets command line options used to configure the server.
+    ///
+    /// The options are sent when connecting, so runtime parameters set with `-c name=value`, like
+    /// `-c statement_timeout=5000`, apply to the whole session. Options are separated by spaces, and a space within a
+    /// value is escaped with a backslash.
     pub fn options(&mut self, options: &str) -> &mut Config {
         self.options = Some(options.to_string());
         self
@@ -184,6 +288,22 @@ impl Config {
         self
     }
 
+    /// Sets the Kerberos service name used for GSSAPI authentication.
+    ///
+    /// The server's principal is `<krbsrvname>@<host>`, with the host name being connected to. Defaults to `postgres`.
+    pub fn krbsrvname(&mut self, krbsrvname: &str) -> &mut Config {
+        self.krbsrvname = krbsrvname.to_string();
+        self
+    }
+
+    /// Sets the SSL configuration.
+    ///
+    /// Defaults to `prefer`.
+    pub fn ssl_mode(&mut self, ssl_mode: SslMode) -> &mut Config {
+        self.ssl_mode = ssl_mode;
+        self
+    }
+
     /// Adds a host to the configuration.
     ///
     /// Multiple hosts can be specified by calling this method multiple times, and each will be tried in order. On Unix
@@ -204,13 +324,12 @@ impl Config {
     ///
     /// Unlike `host`, this method allows non-UTF8 paths.
     #[cfg(unix)]
-    pub fn host_path<T>(&mut self, _host: T) -> &mut Config
+    pub fn host_path<T>(&mut self, host: T) -> &mut Config
     where
         T: AsRef<Path>,
     {
-        // self.host.push(Host::Unix(host.as_ref().to_path_buf()));
-        // self
-        unimplemented!()
+        self.host.push(Host::Unix(host.as_ref().to_path_buf()));
+        self
     }
 
     /// Adds a port to the configuration.
@@ -226,7 +345,9 @@ impl Config {
     /// Sets the timeout applied to socket-level connection attempts.
     ///
     /// Note that hostnames can resolve to multiple IP addresses, and this timeout will apply to each address of each
-    /// host separately. Defaults to no limit.
+    /// host separately. The time left once the socket is connected bounds the startup and authentication; if it runs
+    /// out the socket is shut down and the attempt fails with an error for which `Error::is_timeout` returns true.
+    /// Defaults to no limit.
     pub fn connect_timeout(&mut self, connect_timeout: Duration) -> &mut Config {
         self.connect_timeout = Some(connect_timeout);
         self
@@ -248,6 +369,24 @@ impl Config {
         self
     }
 
+    /// Sets the time between keepalive probes that the server does not acknowledge.
+    ///
+    /// This is ignored for Unix domain sockets, if the `keepalives` option is disabled, and on platforms without
+    /// `TCP_KEEPINTVL` (OpenBSD, Solaris and Redox). Defaults to the operating system default.
+    pub fn keepalives_interval(&mut self, keepalives_interval: Duration) -> &mut Config {
+        self.keepalives_interval = Some(keepalives_interval);
+        self
+    }
+
+    /// Sets the number of unacknowledged keepalive probes after which the connection is considered dead.
+    ///
+    /// This is ignored for Unix domain sockets, if the `keepalives` option is disabled, and on platforms without
+    /// `TCP_KEEPCNT` (Windows, OpenBSD, Solaris and Redox). Defaults to the operating system default.
+    pub fn keepalives_retries(&mut self, keepalives_retries: u32) -> &mut Config {
+        self.keepalives_retries = Some(keepalives_retries);
+        self
+    }
+
     /// Sets the requirements of the session.
     ///
     /// This can be used to connect to the primary server in a clustered database rather than one of the read-only
@@ -260,6 +399,120 @@ impl Config {
         self
     }
 
+    /// Sets the channel binding behavior.
+    ///
+    /// Channel binding ties SCRAM-SHA-256 authentication to the TLS session, using the `SCRAM-SHA-256-PLUS` mechanism
+    /// with the `tls-server-end-point` hash of the server's certificate, so that the authentication can't be relayed by
+    /// a man in the middle. It is only possible over TLS, and only if the `TlsStream` implementation of the TLS backend
+    /// exposes that hash from `TlsStream::channel_binding`. With `Require`, authentication fails if channel binding
+    /// wasn't used, including when the server doesn't ask for a password at all.
+    ///
+    /// Defaults to `prefer`.
+    pub fn channel_binding(&mut self, channel_binding: ChannelBinding) -> &mut Config {
+        self.channel_binding = channel_binding;
+        self
+    }
+
+    /// Opens a replication connection in the given mode.
+    ///
+    /// A replication connection runs the replication commands, like `START_REPLICATION`, which is followed with
+    /// `Client::copy_both`. Only the simple query protocol is available on it, and a logical replication connection
+    /// can run plain SQL that way too.
+    pub fn replication_mode(&mut self, replication_mode: ReplicationMode) -> &mut Config {
+        self.replication_mode = Some(replication_mode);
+        self
+    }
+
+    /// Sets the number of statements kept by `Client::prepare_cached`.
+    ///
+    /// A size of 0 disables caching. Defaults to 512.
+    pub fn statement_cache_size(&mut self, statement_cache_size: usize) -> &mut Config {
+        self.statement_cache_size = statement_cache_size;
+        self
+    }
+
+    /// Sets the maximum number of requests which may wait for their response at a time.
+    ///
+    /// Once the limit is reached, sending another request blocks the calling coroutine until the server completes an
+    /// earlier one. A stream returned by `Client::query_raw` with a fetch size set occupies a slot until it is
+    /// consumed or dropped, so the limit must be larger than the number of such streams a coroutine holds open while
+    /// it sends other requests.
+    ///
+    /// If 0, the number is not limited. Defaults to 0.
+    pub fn max_in_flight(&mut self, max_in_flight: usize) -> &mut Config {
+        self.max_in_flight = max_in_flight;
+        self
+    }
+
+    /// Sets the initial capacity in bytes of the buffer requests are encoded into.
+    ///
+    /// The buffer grows as needed, so a larger capacity only saves reallocations when large requests, such as bulk
+    /// inserts, are sent right after connecting. Defaults to 1024.
+    pub fn write_buffer_capacity(&mut self, write_buffer_capacity: usize) -> &mut Config {
+        self.write_buffer_capacity = write_buffer_capacity;
+        self
+    }
+
+    /// Sets the number of bytes of pending requests after which they are written to the socket.
+    ///
+    /// Requests sent while earlier ones are still being written are coalesced into a single write, which is made as
+    /// soon as no further request is waiting, so a lone request is never delayed. This bounds how much is coalesced
+    /// when requests keep coming. `ConnectionStats::writes` shows the effect.
+    ///
+    /// If 0, the amount is only bounded by the number of buffered messages. Defaults to 0.
+    pub fn max_coalesce_bytes(&mut self, max_coalesce_bytes: usize) -> &mut Config {
+        self.max_coalesce_bytes = max_coalesce_bytes;
+        self
+    }
+
+    /// Sets how long each request waits for its response before failing with an error for which `Error::is_timeout`
+    /// returns `true`.
+    ///
+    /// The timeout applies to every call on the client and covers the whole response, so a query whose rows are
+    /// still streaming in when it elapses fails as well. Nothing is sent to the server when it expires: the request
+    /// keeps running, and the connection reads and discards the rest of its response before handing later responses
+    /// to their callers, so the connection stays usable. Requests are answered in order, so a request queued behind a
+    /// slow one can time out without having started. Use `Client::query_timeout` to cancel the query on the server
+    /// instead.
+    ///
+    /// A timed out statement may still take effect. In particular, after a `COMMIT` times out the transaction may or
+    /// may not have been committed.
+    ///
+    /// By default, requests wait indefinitely.
+    pub fn request_timeout(&mut self, request_timeout: Duration) -> &mut Config {
+        self.request_timeout = Some(request_timeout);
+        self
+    }
+
+    /// Sets the interval at which the connection is checked for signs of life.
+    ///
+    /// A background coroutine wakes up once per interval. If the connection has no request in flight, it sends an empty
+    /// query, which keeps the session from being dropped as idle by the server or the network in between; the query
+    /// is an ordinary request, queued like the client's own, and counts towards `ConnectionStats::requests`. If the
+    /// requests in flight at one wake up, the query included, are still waiting at the next and nothing was received
+    /// from the server nor written to it in between, the connection is closed, so that a connection which died
+    /// silently fails its requests instead of leaving them hanging.
+    ///
+    /// A request the server takes longer than the interval to send anything for, like a query running that long
+    /// before returning its first row, or a copy whose data isn't written for that long, looks the same, so the
+    /// interval should be longer than those.
+    ///
+    /// If `None`, no query is sent. Defaults to `None`.
+    pub fn keepalive_query(&mut self, interval: Option<Duration>) -> &mut Config {
+        self.keepalive_query = interval;
+        self
+    }
+
+    /// Controls the logging of the protocol messages exchanged with the server.
+    ///
+    /// If enabled, the connection logs the tags of the messages it writes and reads at the `trace` level of the `log`
+    /// crate, like `sent P D S` or `received 1 t T Z`, without their content. It can also be switched at runtime with
+    /// `Client::set_trace_protocol`. Disabled, it costs a flag check per batch of messages. Defaults to `false`.
+    pub fn trace_protocol(&mut self, trace_protocol: bool) -> &mut Config {
+        self.trace_protocol = trace_protocol;
+        self
+    }
+
     fn param(&mut self, key: &str, value: &str) -> Result<(), Error> {
         match key {
             "user" => {
@@ -277,6 +530,18 @@ impl Config {
             "application_name" => {
                 self.application_name(value);
             }
+            "krbsrvname" => {
+                self.krbsrvname(value);
+            }
+            "sslmode" => {
+                let mode = match value {
+                    "disable" => SslMode::Disable,
+                    "prefer" => SslMode::Prefer,
+                    "require" => SslMode::Require,
+                    _ => return Err(Error::config_parse(Box::new(InvalidValue("sslmode")))),
+                };
+                self.ssl_mode(mode);
+            }
             "host" => {
                 for host in value.split(',') {
                     self.host(host);
@@ -315,8 +580,22 @@ impl Config {
                     self.keepalives_idle(Duration::from_secs(keepalives_idle as u64));
                 }
             }
+            "keepalives_interval" => {
+                let keepalives_interval = value.parse::<i64>().map_err(|_| {
+                    Error::config_parse(Box::new(InvalidValue("keepalives_interval")))
+                })?;
+                if keepalives_interval > 0 {
+                    self.keepalives_interval(Duration::from_secs(keepalives_interval as u64));
+                }
+            }
+            "keepalives_retries" => {
+                let keepalives_retries = value.parse::<u32>().map_err(|_| {
+                    Error::config_parse(Box::new(InvalidValue("keepalives_retries")))
+                })?;
+                self.keepalives_retries(keepalives_retries);
+            }
             "target_session_attrs" => {
-                let target_session_attrs = match &*value {
+                let target_session_attrs = match value {
                     "any" => TargetSessionAttrs::Any,
                     "read-write" => TargetSessionAttrs::ReadWrite,
                     _ => {
@@ -327,6 +606,29 @@ impl Config {
                 };
                 self.target_session_attrs(target_session_attrs);
             }
+            "channel_binding" => {
+                let channel_binding = match value {
+                    "disable" => ChannelBinding::Disable,
+                    "prefer" => ChannelBinding::Prefer,
+                    "require" => ChannelBinding::Require,
+                    _ => {
+                        return Err(Error::config_parse(Box::new(InvalidValue(
+                            "channel_binding",
+                        ))))
+                    }
+                };
+                self.channel_binding(channel_binding);
+            }
+            "replication" => match value {
+                "true" | "on" | "yes" | "1" => {
+                    self.replication_mode(ReplicationMode::Physical);
+                }
+                "database" => {
+                    self.replication_mode(ReplicationMode::Logical);
+                }
+                "false" | "off" | "no" | "0" => self.replication_mode = None,
+                _ => return Err(Error::config_parse(Box::new(InvalidValue("replication")))),
+            },
             key => {
                 return Err(Error::config_parse(Box::new(UnknownOption(
                     key.to_string(),
@@ -337,18 +639,62 @@ impl Config {
         Ok(())
     }
 
+    pub(crate) fn keepalive_config(&self) -> Option<KeepaliveConfig> {
+        if self.keepalives {
+            Some(KeepaliveConfig {
+                idle: self.keepalives_idle,
+                interval: self.keepalives_interval,
+                retries: self.keepalives_retries,
+            })
+        } else {
+            None
+        }
+    }
+
     /// Opens a connection to a PostgreSQL database.
     ///
+    /// Each configured host is tried in order until a connection succeeds. If all of them fail, the returned error
+    /// describes the failure of every host.
+    ///
     /// Requires the `runtime` Cargo feature (enabled by default).
     pub fn connect(&self) -> Result<Client, Error> {
-        connect(self)
+        connect(NoTls, self)
+    }
+
+    /// Like `connect`, but uses the provided TLS connector to secure the connection according to `sslmode`.
+    pub fn connect_tls<T>(&self, tls: T) -> Result<Client, Error>
+    where
+        T: MakeTlsConnect<TcpStream>,
+    {
+        connect(tls, self)
+    }
+
+    fn first_tcp_host(&self) -> Option<&str> {
+        self.host.iter().find_map(|host| match host {
+            Host::Tcp(host) => Some(&**host),
+            #[cfg(unix)]
+            Host::Unix(_) => None,
+        })
     }
 
     /// Connects to a PostgreSQL database over an arbitrary stream.
     ///
-    /// All of the settings other than `user`, `password`, `dbname`, `options`, and `application` name are ignored.
+    /// All of the settings other than `user`, `password`, `dbname`, `options`, and `application` name are ignored,
+    /// except that GSSAPI authentication takes the server's host name from the first TCP host, if there is one.
+    /// Like the sockets opened by `connect`, the stream gets `TCP_NODELAY` set, so that small requests are not held
+    /// back waiting for the acknowledgement of earlier ones.
     pub fn connect_raw(&self, stream: TcpStream) -> Result<Client, Error> {
-        connect_raw(stream, self)
+        stream.set_nodelay(true).map_err(Error::connect)?;
+        connect_raw(Socket::Tcp(stream), NoTls, self, self.first_tcp_host())
+    }
+
+    /// Like `connect_raw`, but uses the provided TLS connector to secure the stream according to `sslmode`.
+    pub fn connect_raw_tls<T>(&self, stream: TcpStream, tls: T) -> Result<Client, Error>
+    where
+        T: TlsConnect<TcpStream>,
+    {
+        stream.set_nodelay(true).map_err(Error::connect)?;
+        connect_raw(Socket::Tcp(stream), tls, self, self.first_tcp_host())
     }
 }
 
@@ -379,13 +725,25 @@ impl fmt::Debug for Config {
             .field("dbname", &self.dbname)
             .field("options", &self.options)
             .field("application_name", &self.application_name)
-            // .field("ssl_mode", &self.ssl_mode)
+            .field("krbsrvname", &self.krbsrvname)
+            .field("ssl_mode", &self.ssl_mode)
             .field("host", &self.host)
             .field("port", &self.port)
             .field("connect_timeout", &self.connect_timeout)
             .field("keepalives", &self.keepalives)
             .field("keepalives_idle", &self.keepalives_idle)
+            .field("keepalives_interval", &self.keepalives_interval)
+            .field("keepalives_retries", &self.keepalives_retries)
             .field("target_session_attrs", &self.target_session_attrs)
+            .field("channel_binding", &self.channel_binding)
+            .field("replication_mode", &self.replication_mode)
+            .field("statement_cache_size", &self.statement_cache_size)
+            .field("max_in_flight", &self.max_in_flight)
+            .field("write_buffer_capacity", &self.write_buffer_capacity)
+            .field("max_coalesce_bytes", &self.max_coalesce_bytes)
+            .field("request_timeout", &self.request_timeout)
+            .field("keepalive_query", &self.keepalive_query)
+            .field("trace_protocol", &self.trace_protocol)
             .finish()
     }
 }
@@ -738,7 +1096,7 @@ impl<'a> UrlParser<'a> {
     #[cfg(unix)]
     fn host_param(&mut self, s: &str) -> Result<(), Error> {
         let decoded = Cow::from(percent_encoding::percent_decode(s.as_bytes()));
-        if decoded.get(0) == Some(&b'/') {
+        if decoded.first() == Some(&b'/') {
             self.config.host_path(OsStr::from_bytes(&decoded));
         } else {
             let decoded = str::from_utf8(&decoded).map_err(|e| Error::config_parse(Box::new(e)))?;
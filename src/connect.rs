@@ -2,10 +2,23 @@ use crate::client::SocketConfig;
 use crate::config::{Host, TargetSessionAttrs};
 use crate::connect_raw::connect_raw;
 use crate::connect_socket::connect_socket;
+use crate::error::ConnectErrors;
+use crate::socket::Socket;
+use crate::tls::{MakeTlsConnect, TlsConnect};
 use crate::{Client, Config, Error, SimpleQueryMessage};
+use may::go;
+use may::net::TcpStream;
+use may::sync::SyncFlag;
 use std::io;
+use std::net::Shutdown;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-pub fn connect(config: &Config) -> Result<Client, Error> {
+pub fn connect<T>(mut tls: T, config: &Config) -> Result<Client, Error>
+where
+    T: MakeTlsConnect<TcpStream>,
+{
     if config.host.is_empty() {
         return Err(Error::config("host missing".into()));
     }
@@ -14,37 +27,88 @@ pub fn connect(config: &Config) -> Result<Client, Error> {
         return Err(Error::config("invalid number of ports".into()));
     }
 
-    let mut error = None;
+    let mut errors = vec![];
     for (i, host) in config.host.iter().enumerate() {
         let port = *config
             .port
             .get(i)
-            .or_else(|| config.port.get(0))
+            .or_else(|| config.port.first())
             .unwrap_or(&5432);
 
-        // let hostname = match host {
-        //     Host::Tcp(host) => &**host,
-        // };
+        let hostname = match host {
+            Host::Tcp(host) => &**host,
+            // postgres doesn't support TLS over unix sockets, so the choice here doesn't matter
+            #[cfg(unix)]
+            Host::Unix(_) => "",
+        };
 
-        match connect_once(host, port, config) {
+        let result = tls
+            .make_tls_connect(hostname)
+            .map_err(|e| Error::tls(e.into()))
+            .and_then(|tls| connect_once(host, port, tls, config));
+        match result {
             Ok(client) => return Ok(client),
-            Err(e) => error = Some(e),
+            Err(e) => errors.push((host, port, e)),
         }
     }
 
-    Err(error.unwrap())
+    if errors.len() == 1 {
+        return Err(errors.pop().unwrap().2);
+    }
+
+    let errors = errors
+        .into_iter()
+        .map(|(host, port, e)| (display_host(host, port), e))
+        .collect();
+    Err(Error::connect_errors(ConnectErrors::new(errors)))
 }
 
-fn connect_once(host: &Host, port: u16, config: &Config) -> Result<Client, Error> {
-    let socket = connect_socket(
-        host,
+fn display_host(host: &Host, port: u16) -> String {
+    match host {
+        Host::Tcp(host) => format!("{}:{}", host, port),
+        #[cfg(unix)]
+        Host::Unix(path) => format!("{}", path.join(format!(".s.PGSQL.{}", port)).display()),
+    }
+}
+
+fn connect_once<T>(host: &Host, port: u16, tls: T, config: &Config) -> Result<Client, Error>
+where
+    T: TlsConnect<TcpStream>,
+{
+    let start = Instant::now();
+    let keepalive = config.keepalive_config();
+    let socket = connect_socket(host, port, config.connect_timeout, keepalive.as_ref())?;
+
+    let mut client = match config.connect_timeout {
+        Some(timeout) => {
+            let remaining = timeout.checked_sub(start.elapsed()).unwrap_or_default();
+            handshake_timeout(socket, remaining, |socket| {
+                handshake(socket, tls, config, host)
+            })?
+        }
+        None => handshake(socket, tls, config, host)?,
+    };
+
+    client.set_socket_config(SocketConfig {
+        host: host.clone(),
         port,
-        config.connect_timeout,
-        config.keepalives,
-        config.keepalives_idle,
-    )?;
+        connect_timeout: config.connect_timeout,
+        keepalive,
+    });
+
+    Ok(client)
+}
 
-    let mut client = connect_raw(socket, config)?;
+fn handshake<T>(socket: Socket, tls: T, config: &Config, host: &Host) -> Result<Client, Error>
+where
+    T: TlsConnect<TcpStream>,
+{
+    let host_name = match host {
+        Host::Tcp(host) => Some(&**host),
+        #[cfg(unix)]
+        Host::Unix(_) => None,
+    };
+    let client = connect_raw(socket, tls, config, host_name)?;
 
     if let TargetSessionAttrs::ReadWrite = config.target_session_attrs {
         let mut rows = client.simple_query_raw("SHOW transaction_read_only")?;
@@ -67,13 +131,36 @@ fn connect_once(host: &Host, port: u16, config: &Config) -> Result<Client, Error
         }
     }
 
-    client.set_socket_config(SocketConfig {
-        host: host.clone(),
-        port,
-        connect_timeout: config.connect_timeout,
-        keepalives: config.keepalives,
-        keepalives_idle: config.keepalives_idle,
-    });
-
     Ok(client)
 }
+
+// bounds the startup and authentication by shutting the socket down once the timeout elapses
+fn handshake_timeout<F>(socket: Socket, timeout: Duration, f: F) -> Result<Client, Error>
+where
+    F: FnOnce(Socket) -> Result<Client, Error>,
+{
+    let watched = socket.try_clone().map_err(Error::connect)?;
+    let done = Arc::new(SyncFlag::new());
+    let timed_out = Arc::new(AtomicBool::new(false));
+
+    let watchdog = {
+        let done = done.clone();
+        let timed_out = timed_out.clone();
+        go!(move || {
+            if !done.wait_timeout(timeout) {
+                timed_out.store(true, Ordering::Relaxed);
+                watched.shutdown(Shutdown::Both).ok();
+            }
+        })
+    };
+
+    let result = f(socket);
+    done.fire();
+    watchdog.join().ok();
+
+    // a client whose socket was shut down is of no use even if the handshake got through
+    if timed_out.load(Ordering::Relaxed) {
+        return Err(Error::timeout());
+    }
+    result
+}
@@ -1,10 +1,16 @@
 use crate::codec::{BackendMessage, BackendMessages, Framed, FrontendMessage, PostgresCodec};
-use crate::config::Config;
+use crate::config::{self, Config, ReplicationMode};
+use crate::connect_tls::connect_tls;
 use crate::connection::Connection;
+#[cfg(feature = "with-gssapi")]
+use crate::gssapi::ClientContext;
+use crate::socket::Socket;
+use crate::tls::{MaybeTlsStream, TlsConnect, TlsStream};
 use crate::{Client, Error};
 use bytes::BytesMut;
 use fallible_iterator::FallibleIterator;
 use may::net::TcpStream;
+use may::sync::mpsc;
 use postgres_protocol::authentication;
 use postgres_protocol::authentication::sasl;
 use postgres_protocol::authentication::sasl::ScramSha256;
@@ -13,12 +19,15 @@ use postgres_protocol::message::frontend;
 use std::collections::HashMap;
 use std::io::{self, Write};
 
-pub struct StartupStream {
-    inner: Framed,
+pub struct StartupStream<T> {
+    inner: Framed<MaybeTlsStream<T>>,
     buf: BackendMessages,
 }
 
-impl StartupStream {
+impl<T> StartupStream<T>
+where
+    T: TlsStream,
+{
     fn send(&mut self, msg: FrontendMessage) -> io::Result<()> {
         let mut encoder = PostgresCodec;
         let mut data = BytesMut::with_capacity(512);
@@ -40,23 +49,53 @@ impl StartupStream {
     }
 }
 
-pub fn connect_raw(stream: TcpStream, config: &Config) -> Result<Client, Error> {
+// `host` is the name of the server, which GSSAPI authentication needs
+pub fn connect_raw<T>(
+    stream: Socket,
+    tls: T,
+    config: &Config,
+    host: Option<&str>,
+) -> Result<Client, Error>
+where
+    T: TlsConnect<TcpStream>,
+{
+    let stream = connect_tls(stream, config.ssl_mode, tls)?;
+
     let mut stream = StartupStream {
         inner: Framed::new(stream),
         buf: BackendMessages::empty(),
     };
 
     startup(&mut stream, config)?;
-    authenticate(&mut stream, config)?;
+    authenticate(&mut stream, config, host)?;
     let (process_id, secret_key, parameters) = read_info(&mut stream)?;
 
-    let connection = Connection::new(stream.inner, parameters);
-    let client = Client::new(connection, process_id, secret_key);
+    // the binary date and time formats are 8 byte floats instead of microsecond counts on such servers
+    if parameters.get("integer_datetimes").map(|s| &**s) == Some("off") {
+        return Err(Error::connect(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "server uses floating point timestamps (integer_datetimes is off), which are not supported",
+        )));
+    }
+
+    let (notification_tx, notification_rx) = mpsc::channel();
+    let connection = Connection::new(
+        stream.inner,
+        parameters,
+        notification_tx,
+        config.max_in_flight,
+        config.max_coalesce_bytes,
+        config.trace_protocol,
+    );
+    let client = Client::new(connection, notification_rx, config, process_id, secret_key);
 
     Ok(client)
 }
 
-fn startup(stream: &mut StartupStream, config: &Config) -> Result<(), Error> {
+fn startup<T>(stream: &mut StartupStream<T>, config: &Config) -> Result<(), Error>
+where
+    T: TlsStream,
+{
     let mut params = vec![("client_encoding", "UTF8"), ("timezone", "GMT")];
     if let Some(user) = &config.user {
         params.push(("user", &**user));
@@ -70,6 +109,11 @@ fn startup(stream: &mut StartupStream, config: &Config) -> Result<(), Error> {
     if let Some(application_name) = &config.application_name {
         params.push(("application_name", &**application_name));
     }
+    match config.replication_mode {
+        Some(ReplicationMode::Physical) => params.push(("replication", "true")),
+        Some(ReplicationMode::Logical) => params.push(("replication", "database")),
+        None => {}
+    }
 
     let mut buf = BytesMut::new();
     frontend::startup_message(params, &mut buf).map_err(Error::encode)?;
@@ -79,10 +123,22 @@ fn startup(stream: &mut StartupStream, config: &Config) -> Result<(), Error> {
         .map_err(Error::io)
 }
 
-fn authenticate(stream: &mut StartupStream, config: &Config) -> Result<(), Error> {
+fn authenticate<T>(
+    stream: &mut StartupStream<T>,
+    config: &Config,
+    host: Option<&str>,
+) -> Result<(), Error>
+where
+    T: TlsStream,
+{
     match stream.next_msg().map_err(Error::io)? {
-        Message::AuthenticationOk => return Ok(()),
+        Message::AuthenticationOk => {
+            can_skip_channel_binding(config)?;
+            return Ok(());
+        }
         Message::AuthenticationCleartextPassword => {
+            can_skip_channel_binding(config)?;
+
             let pass = config
                 .password
                 .as_ref()
@@ -91,6 +147,8 @@ fn authenticate(stream: &mut StartupStream, config: &Config) -> Result<(), Error
             authenticate_password(stream, pass)?;
         }
         Message::AuthenticationMd5Password(body) => {
+            can_skip_channel_binding(config)?;
+
             let user = config
                 .user
                 .as_ref()
@@ -109,12 +167,19 @@ fn authenticate(stream: &mut StartupStream, config: &Config) -> Result<(), Error
                 .as_ref()
                 .ok_or_else(|| Error::config("password missing".into()))?;
 
-            authenticate_sasl(stream, body, pass)?;
+            authenticate_sasl(stream, body, config, pass)?;
+        }
+        Message::AuthenticationGss => {
+            can_skip_channel_binding(config)?;
+
+            authenticate_gss(stream, config, host)?;
         }
-        Message::AuthenticationKerberosV5
-        | Message::AuthenticationScmCredential
-        | Message::AuthenticationGss
-        | Message::AuthenticationSspi => {
+        Message::AuthenticationSspi => {
+            return Err(Error::authentication(
+                "SSPI authentication is not supported".into(),
+            ))
+        }
+        Message::AuthenticationKerberosV5 | Message::AuthenticationScmCredential => {
             return Err(Error::authentication(
                 "unsupported authentication method".into(),
             ))
@@ -130,7 +195,68 @@ fn authenticate(stream: &mut StartupStream, config: &Config) -> Result<(), Error
     }
 }
 
-fn authenticate_password(stream: &mut StartupStream, password: &[u8]) -> Result<(), Error> {
+fn can_skip_channel_binding(config: &Config) -> Result<(), Error> {
+    match config.channel_binding {
+        config::ChannelBinding::Disable | config::ChannelBinding::Prefer => Ok(()),
+        config::ChannelBinding::Require => Err(Error::authentication(
+            "server did not use channel binding".into(),
+        )),
+    }
+}
+
+#[cfg(feature = "with-gssapi")]
+fn authenticate_gss<T>(
+    stream: &mut StartupStream<T>,
+    config: &Config,
+    host: Option<&str>,
+) -> Result<(), Error>
+where
+    T: TlsStream,
+{
+    let host = host.ok_or_else(|| {
+        Error::authentication("GSSAPI authentication needs the host name of the server".into())
+    })?;
+    let mut context =
+        ClientContext::new(&config.krbsrvname, host).map_err(Error::authentication)?;
+
+    let mut input = None;
+    loop {
+        let (token, complete) = context
+            .step(input.as_deref())
+            .map_err(Error::authentication)?;
+        if !token.is_empty() {
+            let mut buf = BytesMut::new();
+            frontend::sasl_response(&token, &mut buf).map_err(Error::encode)?;
+            stream
+                .send(FrontendMessage::Raw(buf.freeze()))
+                .map_err(Error::io)?;
+        }
+        if complete {
+            return Ok(());
+        }
+
+        match stream.next_msg().map_err(Error::io)? {
+            Message::AuthenticationGssContinue(body) => input = Some(body.data().to_vec()),
+            Message::ErrorResponse(body) => return Err(Error::db(body)),
+            _ => return Err(Error::unexpected_message()),
+        }
+    }
+}
+
+#[cfg(not(feature = "with-gssapi"))]
+fn authenticate_gss<T>(_: &mut StartupStream<T>, _: &Config, _: Option<&str>) -> Result<(), Error>
+where
+    T: TlsStream,
+{
+    Err(Error::authentication(
+        "GSSAPI authentication requires the `with-gssapi` feature".into(),
+    ))
+}
+
+fn authenticate_password<T>(stream: &mut StartupStream<T>, password: &[u8]) -> Result<(), Error>
+where
+    T: TlsStream,
+{
     let mut buf = BytesMut::new();
     frontend::password_message(password, &mut buf).map_err(Error::encode)?;
 
@@ -139,11 +265,15 @@ fn authenticate_password(stream: &mut StartupStream, password: &[u8]) -> Result<
         .map_err(Error::io)
 }
 
-fn authenticate_sasl(
-    stream: &mut StartupStream,
+fn authenticate_sasl<T>(
+    stream: &mut StartupStream<T>,
     body: AuthenticationSaslBody,
+    config: &Config,
     password: &[u8],
-) -> Result<(), Error> {
+) -> Result<(), Error>
+where
+    T: TlsStream,
+{
     let mut has_scram = false;
     let mut has_scram_plus = false;
     let mut mechanisms = body.mechanisms();
@@ -155,12 +285,32 @@ fn authenticate_sasl(
         }
     }
 
-    let (channel_binding, mechanism) = if has_scram_plus || has_scram {
-        (sasl::ChannelBinding::unsupported(), sasl::SCRAM_SHA_256)
+    let channel_binding = stream
+        .inner
+        .inner()
+        .channel_binding()
+        .tls_server_end_point
+        .filter(|_| config.channel_binding != config::ChannelBinding::Disable)
+        .map(sasl::ChannelBinding::tls_server_end_point);
+
+    let (channel_binding, mechanism) = if has_scram_plus {
+        match channel_binding {
+            Some(channel_binding) => (channel_binding, sasl::SCRAM_SHA_256_PLUS),
+            None => (sasl::ChannelBinding::unsupported(), sasl::SCRAM_SHA_256),
+        }
+    } else if has_scram {
+        match channel_binding {
+            Some(_) => (sasl::ChannelBinding::unrequested(), sasl::SCRAM_SHA_256),
+            None => (sasl::ChannelBinding::unsupported(), sasl::SCRAM_SHA_256),
+        }
     } else {
         return Err(Error::authentication("unsupported SASL mechanism".into()));
     };
 
+    if mechanism != sasl::SCRAM_SHA_256_PLUS {
+        can_skip_channel_binding(config)?;
+    }
+
     let mut scram = ScramSha256::new(password, channel_binding);
 
     let mut buf = BytesMut::new();
@@ -198,7 +348,10 @@ fn authenticate_sasl(
     Ok(())
 }
 
-fn read_info(stream: &mut StartupStream) -> Result<(i32, i32, HashMap<String, String>), Error> {
+fn read_info<T>(stream: &mut StartupStream<T>) -> Result<(i32, i32, HashMap<String, String>), Error>
+where
+    T: TlsStream,
+{
     let mut process_id = 0;
     let mut secret_key = 0;
     let mut parameters = HashMap::new();
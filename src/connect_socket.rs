@@ -4,17 +4,21 @@ use std::time::Duration;
 use std::vec;
 
 use may::net::TcpStream;
+#[cfg(unix)]
+use may::os::unix::net::UnixStream;
+use socket2::{SockRef, TcpKeepalive};
 
 use crate::config::Host;
+use crate::keepalive::KeepaliveConfig;
+use crate::socket::Socket;
 use crate::Error;
 
 pub(crate) fn connect_socket(
     host: &Host,
     port: u16,
     connect_timeout: Option<Duration>,
-    _keepalives: bool,
-    _keepalives_idle: Duration,
-) -> Result<TcpStream, Error> {
+    keepalive_config: Option<&KeepaliveConfig>,
+) -> Result<Socket, Error> {
     match host {
         Host::Tcp(host) => {
             let addrs = match host.parse::<IpAddr>() {
@@ -29,14 +33,16 @@ pub(crate) fn connect_socket(
             for addr in addrs {
                 let new_error = match connect_with_timeout(&addr, connect_timeout) {
                     Ok(socket) => {
+                        // requests are coalesced by the connection's writer already, so Nagle's algorithm would only
+                        // hold a lone request back until the previous one is acknowledged
                         socket.set_nodelay(true).map_err(Error::connect)?;
-                        // if keepalives {
-                        //     socket
-                        //         .set_keepalive(Some(keepalives_idle))
-                        //         .map_err(Error::connect)?;
-                        // }
+                        if let Some(keepalive_config) = keepalive_config {
+                            SockRef::from(socket.inner())
+                                .set_tcp_keepalive(&TcpKeepalive::from(keepalive_config))
+                                .map_err(Error::connect)?;
+                        }
 
-                        return Ok(socket);
+                        return Ok(Socket::Tcp(socket));
                     }
                     Err(e) => e,
                 };
@@ -51,6 +57,12 @@ pub(crate) fn connect_socket(
             });
             Err(error)
         }
+        #[cfg(unix)]
+        Host::Unix(path) => {
+            let path = path.join(format!(".s.PGSQL.{}", port));
+            let socket = UnixStream::connect(path).map_err(Error::connect)?;
+            Ok(Socket::Unix(socket))
+        }
     }
 }
 
@@ -61,7 +73,13 @@ fn dns(host: &str, port: u16) -> io::Result<vec::IntoIter<SocketAddr>> {
 
 fn connect_with_timeout(addr: &SocketAddr, timeout: Option<Duration>) -> Result<TcpStream, Error> {
     match timeout {
-        Some(timeout) => TcpStream::connect_timeout(addr, timeout).map_err(Error::connect),
+        Some(timeout) => TcpStream::connect_timeout(addr, timeout).map_err(|e| {
+            if e.kind() == io::ErrorKind::TimedOut {
+                Error::timeout()
+            } else {
+                Error::connect(e)
+            }
+        }),
         None => TcpStream::connect(addr).map_err(Error::connect),
     }
 }
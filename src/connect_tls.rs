@@ -0,0 +1,57 @@
+use crate::config::SslMode;
+use crate::socket::Socket;
+use crate::tls::private::ForcePrivateApi;
+use crate::tls::{MaybeTlsStream, TlsConnect};
+use crate::Error;
+use bytes::BytesMut;
+use may::net::TcpStream;
+use postgres_protocol::message::frontend;
+use std::io::{Read, Write};
+
+pub fn connect_tls<T>(
+    stream: Socket,
+    mode: SslMode,
+    tls: T,
+) -> Result<MaybeTlsStream<T::Stream>, Error>
+where
+    T: TlsConnect<TcpStream>,
+{
+    let mut stream = match stream {
+        Socket::Tcp(stream) => stream,
+        // the server never accepts TLS over Unix sockets
+        #[cfg(unix)]
+        stream @ Socket::Unix(_) => {
+            if SslMode::Require == mode {
+                return Err(Error::tls("TLS is not supported over Unix sockets".into()));
+            }
+            return Ok(MaybeTlsStream::Raw(stream));
+        }
+    };
+
+    match mode {
+        SslMode::Disable => return Ok(MaybeTlsStream::Raw(Socket::Tcp(stream))),
+        SslMode::Prefer if !tls.can_connect(ForcePrivateApi) => {
+            return Ok(MaybeTlsStream::Raw(Socket::Tcp(stream)))
+        }
+        SslMode::Prefer | SslMode::Require => {}
+    }
+
+    let mut buf = BytesMut::new();
+    frontend::ssl_request(&mut buf);
+    stream.write_all(&buf).map_err(Error::io)?;
+
+    let mut buf = [0];
+    stream.read_exact(&mut buf).map_err(Error::io)?;
+
+    if buf[0] != b'S' {
+        if SslMode::Require == mode {
+            return Err(Error::tls("server does not support TLS".into()));
+        } else {
+            return Ok(MaybeTlsStream::Raw(Socket::Tcp(stream)));
+        }
+    }
+
+    let stream = tls.connect(stream).map_err(|e| Error::tls(e.into()))?;
+
+    Ok(MaybeTlsStream::Tls(stream))
+}
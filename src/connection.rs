@@ -1,23 +1,37 @@
-use crate::codec::{BackendMessage, BackendMessages, Framed, FrontendMessage};
+use crate::codec::{self, BackendMessage, BackendMessages, Framed, FrontendMessage};
+use crate::copy_both::CopyBothReceiver;
 use crate::copy_in::CopyInReceiver;
+use crate::error::DbError;
+use crate::query::PortalReceiver;
+use crate::tls::{MaybeTlsStream, TlsStream};
 use crate::vec_buf::VecBufs;
-use crate::Error;
+use crate::{Error, Notification};
 use bytes::BytesMut;
 use fallible_iterator::FallibleIterator;
-use log::error;
+use log::{error, info, trace};
 use may::coroutine::JoinHandle;
 use may::go;
-use may::sync::{mpsc, RwLock, RwLockReadGuard};
+use may::sync::{mpsc, Mutex, RwLock, RwLockReadGuard, Semphore, SyncFlag};
 use may_queue::spsc;
 use postgres_protocol::message::backend::Message;
 use postgres_protocol::message::frontend;
 use std::collections::HashMap;
-use std::io;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
 pub enum RequestMessages {
     Single(FrontendMessage),
     CopyIn(CopyInReceiver),
+    CopyBoth(CopyBothReceiver),
+    Portal(PortalReceiver),
+}
+
+/// messages which are produced over time by the caller, written without interleaving other requests
+pub trait MessageStream {
+    fn try_recv(&mut self) -> Result<Option<FrontendMessage>, ()>;
+    fn recv(&mut self) -> Result<Option<FrontendMessage>, ()>;
 }
 
 pub struct Request {
@@ -29,12 +43,107 @@ pub struct Response {
     tx: mpsc::Sender<BackendMessages>,
 }
 
+pub type NoticeCallback = Arc<dyn Fn(DbError) + Send + Sync>;
+
+/// counters updated by the connection coroutines
+#[derive(Default)]
+struct Counters {
+    requests: AtomicU64,
+    bytes_written: AtomicU64,
+    writes: AtomicU64,
+    responses: AtomicU64,
+    // the messages read from the socket
+    received: AtomicU64,
+}
+
+/// counts the write calls made on the socket
+struct CountingWriter<W> {
+    inner: W,
+    counters: Arc<Counters>,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.counters.writes.fetch_add(1, Ordering::Relaxed);
+        self.inner.write(buf)
+    }
+
+    fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
+        self.counters.writes.fetch_add(1, Ordering::Relaxed);
+        self.inner.write_vectored(bufs)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A snapshot of the activity of a connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectionStats {
+    requests: u64,
+    bytes_written: u64,
+    writes: u64,
+    responses: u64,
+}
+
+impl ConnectionStats {
+    /// Returns the number of requests written to the socket.
+    pub fn requests(&self) -> u64 {
+        self.requests
+    }
+
+    /// Returns the number of bytes written to the socket.
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
+    /// Returns the number of write calls made on the socket.
+    ///
+    /// Requests sent close together are coalesced into a single write, so this is usually smaller than `requests`.
+    pub fn writes(&self) -> u64 {
+        self.writes
+    }
+
+    /// Returns the number of requests the server has fully responded to.
+    pub fn responses(&self) -> u64 {
+        self.responses
+    }
+
+    /// Returns the number of requests written to the socket which are still waiting for their response.
+    ///
+    /// A growing value means requests are pipelined faster than the server answers them.
+    pub fn in_flight(&self) -> u64 {
+        self.requests.saturating_sub(self.responses)
+    }
+}
+
+/// what a connection has done so far, which stays the same while it makes no progress
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Progress {
+    pub stats: ConnectionStats,
+    pub received: u64,
+}
+
 /// A connection to a PostgreSQL database.
 pub(crate) struct Connection {
     rx_handle: JoinHandle<()>,
     tx_handle: JoinHandle<()>,
     req_tx: mpsc::Sender<Request>,
     rw_lock: Arc<RwLock<()>>,
+    notice_callback: Arc<Mutex<Option<NoticeCallback>>>,
+    is_closed: Arc<AtomicBool>,
+    counters: Arc<Counters>,
+    // set while the messages exchanged are logged
+    trace: Arc<AtomicBool>,
+    // one permit for each request that may still be sent when the in-flight number is limited
+    permits: Option<Arc<Semphore>>,
+    // the latest values reported by `ParameterStatus` messages
+    parameters: Arc<Mutex<HashMap<String, String>>>,
+    // set once `shutdown` starts, after which no new requests are accepted
+    closing: AtomicBool,
+    // shuts the socket down, which ends the reader as if the server had closed the connection
+    abort: Box<dyn Fn() + Send + Sync>,
 }
 
 impl Drop for Connection {
@@ -48,31 +157,192 @@ impl Drop for Connection {
     }
 }
 
+fn write_message<W: Write>(
+    writer: &mut VecBufs<W>,
+    msg: FrontendMessage,
+    counters: &Counters,
+    trace: &AtomicBool,
+) -> io::Result<()> {
+    let buf = match msg {
+        FrontendMessage::Raw(buf) => buf,
+        FrontendMessage::CopyData(data) => {
+            let mut buf = BytesMut::new();
+            data.write(&mut buf);
+            buf.freeze()
+        }
+    };
+    if trace.load(Ordering::Relaxed) {
+        trace!("sent {}", codec::tags(&buf));
+    }
+    counters
+        .bytes_written
+        .fetch_add(buf.len() as u64, Ordering::Relaxed);
+    writer.write_bytes(buf)
+}
+
+fn write_stream<W: Write, S: MessageStream>(
+    writer: &mut VecBufs<W>,
+    rcv: &mut S,
+    counters: &Counters,
+    trace: &AtomicBool,
+) -> io::Result<()> {
+    let mut msg = rcv.try_recv();
+    loop {
+        match msg {
+            Ok(Some(m)) => {
+                write_message(writer, m, counters, trace)?;
+                msg = rcv.try_recv();
+            }
+            Ok(None) => {
+                writer.flush()?;
+
+                // no data found we just write all the data and wait
+                msg = rcv.recv();
+            }
+            Err(_) => return Ok(()),
+        }
+    }
+}
+
 impl Connection {
-    pub(crate) fn new(mut stream: Framed, mut parameters: HashMap<String, String>) -> Connection {
+    pub(crate) fn new<T>(
+        mut stream: Framed<MaybeTlsStream<T>>,
+        parameters: HashMap<String, String>,
+        notifications: mpsc::Sender<Notification>,
+        max_in_flight: usize,
+        max_coalesce_bytes: usize,
+        trace_protocol: bool,
+    ) -> Connection
+    where
+        T: TlsStream + Send + 'static,
+    {
         let writer = stream
             .inner_mut()
             .try_clone()
             .expect("failed to clone stream for wirter");
+        let abort = {
+            let socket = std::sync::Mutex::new(
+                stream
+                    .inner_mut()
+                    .try_clone()
+                    .expect("failed to clone stream for abort"),
+            );
+            Box::new(move || {
+                socket
+                    .lock()
+                    .unwrap()
+                    .shutdown(std::net::Shutdown::Both)
+                    .ok();
+            })
+        };
         let rw_lock = stream.get_rw_lock();
         let rsp_queue = Arc::new(spsc::Queue::new());
         let (req_tx, req_rx) = mpsc::channel();
+        let notice_callback = Arc::new(Mutex::new(None::<NoticeCallback>));
+        let is_closed = Arc::new(AtomicBool::new(false));
+        let writer_done = Arc::new(SyncFlag::new());
+        let counters = Arc::new(Counters::default());
+        let trace = Arc::new(AtomicBool::new(trace_protocol));
+        let parameters = Arc::new(Mutex::new(parameters));
+        let permits = match max_in_flight {
+            0 => None,
+            n => Some(Arc::new(Semphore::new(n))),
+        };
         let rx_handle = {
             let rsp_queue: Arc<spsc::Queue<Response>> = rsp_queue.clone();
             let req_tx = req_tx.clone();
+            let notice_callback = notice_callback.clone();
+            let is_closed = is_closed.clone();
+            let writer_done = writer_done.clone();
+            let counters = counters.clone();
+            let permits = permits.clone();
+            let parameters = parameters.clone();
+            let trace = trace.clone();
             go!(move || {
+                // A malformed message only fails the connection when its end can't be told, which loses the framing
+                // of everything after it, or when it answers no request, which means the responses are out of sync
+                // with the requests. Otherwise the messages are framed by their length alone: those of a response
+                // are parsed by its waiter, which gets the parse error and discards the rest of the response while
+                // the connection carries on, and asynchronous ones are logged and dropped.
                 let mut main = || -> Result<(), Error> {
                     const MAX_CACHE_SIZE: usize = 128;
                     let mut message_cache = Vec::with_capacity(MAX_CACHE_SIZE);
                     loop {
-                        match stream.next_msg().map_err(Error::io)? {
-                            BackendMessage::Async(Message::NoticeResponse(_body)) => {}
-                            BackendMessage::Async(Message::NotificationResponse(_body)) => {}
+                        let msg = match stream.buffered_msg().map_err(Error::io)? {
+                            Some(msg) => msg,
+                            None => {
+                                // nothing more is buffered, hand over what we have before blocking on the socket
+                                if let Some(response) = unsafe { rsp_queue.peek() } {
+                                    for msg in message_cache.drain(..) {
+                                        response.tx.send(msg).ok();
+                                    }
+                                }
+                                stream.next_msg().map_err(Error::io)?
+                            }
+                        };
+
+                        counters.received.fetch_add(1, Ordering::Relaxed);
+
+                        if trace.load(Ordering::Relaxed) {
+                            match &msg {
+                                BackendMessage::Normal { messages, .. } => {
+                                    trace!("received {}", messages.tags())
+                                }
+                                BackendMessage::Async(Message::NoticeResponse(_)) => {
+                                    trace!("received N")
+                                }
+                                BackendMessage::Async(Message::NotificationResponse(_)) => {
+                                    trace!("received A")
+                                }
+                                BackendMessage::Async(_) => trace!("received S"),
+                            }
+                        }
+
+                        match msg {
+                            // asynchronous messages belong to no request, so a malformed one is only logged
+                            BackendMessage::Async(Message::NoticeResponse(body)) => {
+                                let notice = match DbError::parse(&mut body.fields()) {
+                                    Ok(notice) => notice,
+                                    Err(e) => {
+                                        error!("dropping malformed notice: {}", e);
+                                        continue;
+                                    }
+                                };
+                                // release the lock before running user code
+                                let callback = notice_callback.lock().unwrap().clone();
+                                match callback {
+                                    Some(callback) => callback(notice),
+                                    None => info!("{}: {}", notice.severity(), notice.message()),
+                                }
+                            }
+                            BackendMessage::Async(Message::NotificationResponse(body)) => {
+                                let (channel, payload) = match (body.channel(), body.message()) {
+                                    (Ok(channel), Ok(payload)) => (channel, payload),
+                                    (Err(e), _) | (_, Err(e)) => {
+                                        error!("dropping malformed notification: {}", e);
+                                        continue;
+                                    }
+                                };
+                                let notification = Notification {
+                                    process_id: body.process_id(),
+                                    channel: channel.to_string(),
+                                    payload: payload.to_string(),
+                                };
+                                // the receiver is never blocked on, so there is no issue if nobody listens
+                                notifications.send(notification).ok();
+                            }
                             BackendMessage::Async(Message::ParameterStatus(body)) => {
-                                parameters.insert(
-                                    body.name().map_err(Error::parse)?.to_string(),
-                                    body.value().map_err(Error::parse)?.to_string(),
-                                );
+                                match (body.name(), body.value()) {
+                                    (Ok(name), Ok(value)) => {
+                                        parameters
+                                            .lock()
+                                            .unwrap()
+                                            .insert(name.to_string(), value.to_string());
+                                    }
+                                    (Err(e), _) | (_, Err(e)) => {
+                                        error!("dropping malformed parameter status: {}", e)
+                                    }
+                                }
                             }
                             BackendMessage::Async(_) => unreachable!(),
                             BackendMessage::Normal {
@@ -89,6 +359,18 @@ impl Connection {
                                     },
                                 };
 
+                                // the server closes the connection after a fatal error, so refuse new requests right
+                                // away and hand the error to its waiter before failing the rest
+                                if let Some(error) = messages.fatal_error().map_err(Error::parse)? {
+                                    is_closed.store(true, Ordering::Release);
+                                    message_cache.push(messages);
+                                    for msg in message_cache.drain(..) {
+                                        response.tx.send(msg).ok();
+                                    }
+                                    rsp_queue.pop();
+                                    return Err(Error::db(error));
+                                }
+
                                 message_cache.push(messages);
 
                                 if message_cache.len() >= MAX_CACHE_SIZE {
@@ -98,10 +380,15 @@ impl Connection {
                                 }
 
                                 if request_complete {
+                                    // counted before the caller can see the end of its response
+                                    counters.responses.fetch_add(1, Ordering::Relaxed);
                                     for msg in message_cache.drain(..) {
                                         response.tx.send(msg).ok();
                                     }
                                     rsp_queue.pop();
+                                    if let Some(permits) = &permits {
+                                        permits.post();
+                                    }
                                 }
                             }
                         }
@@ -110,23 +397,52 @@ impl Connection {
 
                 if let Err(e) = main() {
                     error!("receiver closed. err={}", e);
-                    let mut request = BytesMut::new();
-                    frontend::terminate(&mut request);
-                    let (tx, _rx) = mpsc::channel();
-                    let req = Request {
-                        messages: RequestMessages::Single(FrontendMessage::Raw(request.freeze())),
-                        sender: tx,
-                    };
-                    req_tx.send(req).ok();
+                }
+
+                // refuse new requests, then wake up the writer with a terminate so that it can exit
+                is_closed.store(true, Ordering::Release);
+                if let Some(permits) = &permits {
+                    // senders waiting for a slot notice the closed flag and wake each other in turn
+                    permits.post();
+                }
+                let mut request = BytesMut::new();
+                frontend::terminate(&mut request);
+                let (tx, _rx) = mpsc::channel();
+                let req = Request {
+                    messages: RequestMessages::Single(FrontendMessage::Raw(request.freeze())),
+                    sender: tx,
+                };
+                req_tx.send(req).ok();
+
+                // fail every pending request, the writer may still push a few until it notices the closed flag
+                loop {
+                    while rsp_queue.pop().is_some() {}
+                    if writer_done.wait_timeout(Duration::from_millis(10)) {
+                        while rsp_queue.pop().is_some() {}
+                        break;
+                    }
                 }
                 stream.inner_mut().shutdown(std::net::Shutdown::Both).ok();
             })
         };
 
         let rw_lock_1 = rw_lock.clone();
+        let is_closed_1 = is_closed.clone();
+        let counters_1 = counters.clone();
+        let trace_1 = trace.clone();
+        let is_limited = permits.is_some();
 
+        // The writer coroutine is the only one touching the socket, so no lock is held while writing to it. Senders
+        // encode their requests into `Bytes` of their own before queueing them, and the writer hands the queued
+        // buffers to a single vectored write without copying them; a request encoded during a flush is simply
+        // picked up by the next one, which already gives the overlap double-buffering would. The one lock the writer
+        // takes is the write side of `rw_lock`, for a yield before flushing and never across the write itself.
         let tx_handle = go!(move || {
-            let mut writer = VecBufs::new(writer);
+            let writer = CountingWriter {
+                inner: writer,
+                counters: counters_1.clone(),
+            };
+            let mut writer = VecBufs::new(writer, max_coalesce_bytes);
             let mut main = || -> Result<(), io::Error> {
                 use std::sync::mpsc::TryRecvError;
                 let mut request = req_rx.try_recv();
@@ -134,47 +450,38 @@ impl Connection {
                     match request {
                         Ok(req) => {
                             rsp_queue.push(Response { tx: req.sender });
+                            counters_1.requests.fetch_add(1, Ordering::Relaxed);
                             match req.messages {
-                                RequestMessages::Single(msg) => match msg {
-                                    FrontendMessage::Raw(buf) => writer.write_bytes(buf)?,
-                                    FrontendMessage::CopyData(data) => {
-                                        let mut buf = BytesMut::new();
-                                        data.write(&mut buf);
-                                        writer.write_bytes(buf.freeze())?;
-                                    }
-                                },
+                                RequestMessages::Single(msg) => {
+                                    write_message(&mut writer, msg, &counters_1, &trace_1)?
+                                }
                                 RequestMessages::CopyIn(mut rcv) => {
-                                    let mut copy_in_msg = rcv.try_recv();
-                                    loop {
-                                        match copy_in_msg {
-                                            Ok(Some(msg)) => {
-                                                match msg {
-                                                    FrontendMessage::Raw(buf) => {
-                                                        writer.write_bytes(buf)?
-                                                    }
-                                                    FrontendMessage::CopyData(data) => {
-                                                        let mut buf = BytesMut::new();
-                                                        data.write(&mut buf);
-                                                        writer.write_bytes(buf.freeze())?;
-                                                    }
-                                                }
-                                                copy_in_msg = rcv.try_recv();
-                                            }
-                                            Ok(None) => {
-                                                writer.flush()?;
-
-                                                // no data found we just write all the data and wait
-                                                copy_in_msg = rcv.recv();
-                                            }
-                                            Err(_) => break,
-                                        }
-                                    }
+                                    write_stream(&mut writer, &mut rcv, &counters_1, &trace_1)?
+                                }
+                                RequestMessages::CopyBoth(mut rcv) => {
+                                    write_stream(&mut writer, &mut rcv, &counters_1, &trace_1)?
+                                }
+                                RequestMessages::Portal(mut rcv) => {
+                                    write_stream(&mut writer, &mut rcv, &counters_1, &trace_1)?
                                 }
                             }
+                            if is_closed_1.load(Ordering::Acquire) {
+                                writer.flush()?;
+                                return Err(io::Error::new(
+                                    io::ErrorKind::BrokenPipe,
+                                    "connection closed",
+                                ));
+                            }
                             request = req_rx.try_recv();
                         }
                         Err(TryRecvError::Empty) => {
-                            let _g = rw_lock_1.write().unwrap();
+                            // waiting for concurrent senders batches their requests into one flush, but a sender
+                            // blocked on an in-flight slot holds its lock until earlier requests are answered
+                            let _g = if is_limited {
+                                None
+                            } else {
+                                Some(rw_lock_1.write().unwrap())
+                            };
                             may::coroutine::yield_now();
                             request = req_rx.try_recv();
                             match &request {
@@ -198,10 +505,19 @@ impl Connection {
                 }
             };
 
+            // the requests of a failed batch are already in the response queue, which the reader empties once the
+            // shutdown below ends its read, and the ones still queued are dropped along with `req_rx`, so every
+            // waiter sees its response channel close
             if let Err(e) = main() {
                 error!("writer closed. err={}", e);
             }
-            writer.inner_mut().shutdown(std::net::Shutdown::Both).ok();
+            is_closed_1.store(true, Ordering::Release);
+            writer
+                .inner_mut()
+                .inner
+                .shutdown(std::net::Shutdown::Both)
+                .ok();
+            writer_done.fire();
         });
 
         Connection {
@@ -209,14 +525,122 @@ impl Connection {
             tx_handle,
             req_tx,
             rw_lock,
+            notice_callback,
+            is_closed,
+            counters,
+            trace,
+            permits,
+            parameters,
+            closing: AtomicBool::new(false),
+            abort,
         }
     }
 
-    /// send a request to the connection
+    /// determine if the background coroutines have exited
+    pub fn is_closed(&self) -> bool {
+        self.is_closed.load(Ordering::Acquire)
+    }
+
+    /// send a request to the connection, requests are written in the order they are sent, from any coroutine or thread
     pub fn send(&self, req: Request) -> io::Result<()> {
-        self.req_tx
-            .send(req)
-            .map_err(|_| io::Error::new(io::ErrorKind::Other, "send req failed"))
+        self.send_inner(req, true).map(|_| ())
+    }
+
+    /// like `send`, but returns false instead of waiting when the limit of requests in flight is reached
+    pub fn try_send(&self, req: Request) -> io::Result<bool> {
+        self.send_inner(req, false)
+    }
+
+    fn send_inner(&self, req: Request, wait: bool) -> io::Result<bool> {
+        let closed = || io::Error::new(io::ErrorKind::BrokenPipe, "connection closed");
+        if self.is_closed() || self.closing.load(Ordering::Acquire) {
+            return Err(closed());
+        }
+
+        if let Some(permits) = &self.permits {
+            if wait {
+                permits.wait();
+            } else if !permits.try_wait() {
+                return Ok(false);
+            }
+            if self.is_closed() {
+                // pass the wake up on to the next waiting sender
+                permits.post();
+                return Err(closed());
+            }
+        }
+
+        self.req_tx.send(req).map(|_| true).map_err(|_| {
+            if let Some(permits) = &self.permits {
+                permits.post();
+            }
+            io::Error::other("send req failed")
+        })
+    }
+
+    /// terminate the session once the requests sent so far have been answered, then wait for the coroutines to exit
+    ///
+    /// returns false if the connection was already closed
+    pub fn shutdown(&self) -> bool {
+        if self.closing.swap(true, Ordering::AcqRel) || self.is_closed() {
+            return false;
+        }
+
+        // requests are written in order, so the server answers all earlier ones before it sees the terminate and
+        // closes the socket, which ends the reader once every response has been handed over
+        let mut request = BytesMut::new();
+        frontend::terminate(&mut request);
+        let (tx, _rx) = mpsc::channel();
+        let req = Request {
+            messages: RequestMessages::Single(FrontendMessage::Raw(request.freeze())),
+            sender: tx,
+        };
+        if self.req_tx.send(req).is_err() {
+            return false;
+        }
+
+        self.rx_handle.wait();
+        self.tx_handle.wait();
+        true
+    }
+
+    /// close the connection right away without terminating the session, failing every pending request
+    pub fn abort(&self) {
+        self.is_closed.store(true, Ordering::Release);
+        (self.abort)();
+    }
+
+    /// switch the logging of the messages exchanged with the server on or off
+    pub fn set_trace_protocol(&self, trace_protocol: bool) {
+        self.trace.store(trace_protocol, Ordering::Relaxed);
+    }
+
+    /// look up the latest value the server reported for a runtime parameter
+    pub fn parameter(&self, name: &str) -> Option<String> {
+        self.parameters.lock().unwrap().get(name).cloned()
+    }
+
+    /// take a snapshot of the connection counters, along with the number of messages read from the socket
+    pub fn progress(&self) -> Progress {
+        Progress {
+            stats: self.stats(),
+            received: self.counters.received.load(Ordering::Relaxed),
+        }
+    }
+
+    /// take a snapshot of the connection counters
+    pub fn stats(&self) -> ConnectionStats {
+        ConnectionStats {
+            requests: self.counters.requests.load(Ordering::Relaxed),
+            bytes_written: self.counters.bytes_written.load(Ordering::Relaxed),
+            writes: self.counters.writes.load(Ordering::Relaxed),
+            responses: self.counters.responses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// set the handler invoked for each notice the server sends, or go back to logging them if it is `None`
+    pub fn set_notice_callback(&self, callback: Option<NoticeCallback>) {
+        *self.notice_callback.lock().unwrap() = callback;
     }
 
     pub fn read_lock(&self) -> RwLockReadGuard<'_, ()> {
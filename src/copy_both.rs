@@ -0,0 +1,194 @@
+use crate::client::{InnerClient, Responses};
+use crate::codec::FrontendMessage;
+use crate::connection::{MessageStream, RequestMessages};
+use crate::{simple_query, Error};
+use bytes::{Buf, Bytes, BytesMut};
+use may::sync::mpsc;
+use postgres_protocol::message::backend::Message;
+use postgres_protocol::message::frontend;
+use postgres_protocol::message::frontend::CopyData;
+
+enum CopyBothMessage {
+    Message(FrontendMessage),
+    Done,
+    // the server never entered copy-both mode, so there is nothing to end
+    Abort,
+}
+
+pub struct CopyBothReceiver {
+    receiver: mpsc::Receiver<CopyBothMessage>,
+    done: bool,
+}
+
+impl CopyBothReceiver {
+    fn frontend_message(
+        &mut self,
+        message: Result<CopyBothMessage, ()>,
+    ) -> Option<FrontendMessage> {
+        let mut buf = BytesMut::new();
+        match message {
+            Ok(CopyBothMessage::Message(message)) => return Some(message),
+            Ok(CopyBothMessage::Abort) => {
+                self.done = true;
+                return None;
+            }
+            // a dropped sink ends the copy too, the server then finishes its half
+            Ok(CopyBothMessage::Done) | Err(()) => frontend::copy_done(&mut buf),
+        }
+        self.done = true;
+        Some(FrontendMessage::Raw(buf.freeze()))
+    }
+}
+
+// the copy is started with a simple query, so unlike `COPY ... FROM STDIN` it isn't followed by a sync
+impl MessageStream for CopyBothReceiver {
+    fn try_recv(&mut self) -> Result<Option<FrontendMessage>, ()> {
+        use std::sync::mpsc::TryRecvError;
+        if self.done {
+            return Err(());
+        }
+
+        let message = match self.receiver.try_recv() {
+            Ok(message) => self.frontend_message(Ok(message)),
+            Err(TryRecvError::Empty) => return Ok(None),
+            Err(_) => self.frontend_message(Err(())),
+        };
+        message.map(Some).ok_or(())
+    }
+
+    fn recv(&mut self) -> Result<Option<FrontendMessage>, ()> {
+        if self.done {
+            return Err(());
+        }
+
+        let message = self.receiver.recv().map_err(|_| ());
+        self.frontend_message(message).map(Some).ok_or(())
+    }
+}
+
+/// The sending half of a copy started by `Client::copy_both`.
+///
+/// Each `send` is written to the server as one `CopyData` message. The copy ends once `finish` is called or the sink is
+/// dropped, after which the server ends its half and the `CopyBothStream` finishes.
+pub struct CopyBothSink {
+    sender: mpsc::Sender<CopyBothMessage>,
+    finished: bool,
+}
+
+impl CopyBothSink {
+    /// Sends `data` to the server in a `CopyData` message.
+    pub fn send<T>(&mut self, data: T) -> Result<(), Error>
+    where
+        T: Buf + Send + 'static,
+    {
+        self.check_finished()?;
+        let data: Box<dyn Buf + Send> = Box::new(data);
+        let data = CopyData::new(data).map_err(Error::encode)?;
+        self.sender
+            .send(CopyBothMessage::Message(FrontendMessage::CopyData(data)))
+            .map_err(|_| Error::closed())
+    }
+
+    /// Ends the client's half of the copy with a `CopyDone`.
+    ///
+    /// The server keeps sending until it has processed it, so the data still arriving should be read from the
+    /// `CopyBothStream` until it finishes.
+    pub fn finish(&mut self) -> Result<(), Error> {
+        self.check_finished()?;
+        self.finished = true;
+        self.sender
+            .send(CopyBothMessage::Done)
+            .map_err(|_| Error::closed())
+    }
+
+    pub(crate) fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    fn check_finished(&self) -> Result<(), Error> {
+        if self.finished {
+            return Err(Error::copy_finished());
+        }
+        Ok(())
+    }
+}
+
+/// The receiving half of a copy started by `Client::copy_both`.
+///
+/// Each item is the payload of one `CopyData` message from the server. The stream ends at the `ReadyForQuery` which
+/// follows the end of the copy, skipping whatever results the command sends after its `CopyDone`.
+pub struct CopyBothStream {
+    responses: Responses,
+    done: bool,
+}
+
+impl Iterator for CopyBothStream {
+    type Item = Result<Bytes, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        loop {
+            let message = match self.responses.next() {
+                Ok(message) => message,
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            };
+
+            match message {
+                Message::CopyData(body) => return Some(Ok(body.into_bytes())),
+                Message::CopyDone
+                | Message::RowDescription(_)
+                | Message::DataRow(_)
+                | Message::CommandComplete(_) => {}
+                Message::ReadyForQuery(_) => {
+                    self.done = true;
+                    return None;
+                }
+                _ => {
+                    self.done = true;
+                    return Some(Err(Error::unexpected_message()));
+                }
+            }
+        }
+    }
+}
+
+pub fn copy_both(
+    client: &InnerClient,
+    query: &str,
+) -> Result<(CopyBothSink, CopyBothStream), Error> {
+    let buf = simple_query::encode(client, query)?;
+
+    let (sender, receiver) = mpsc::channel();
+    let receiver = CopyBothReceiver {
+        receiver,
+        done: false,
+    };
+    // the copy can last as long as the caller wants, so it isn't bounded by the request timeout
+    let mut responses = client.send_inner(RequestMessages::CopyBoth(receiver), None)?;
+
+    sender
+        .send(CopyBothMessage::Message(FrontendMessage::Raw(buf)))
+        .map_err(|_| Error::closed())?;
+
+    if let Err(e) = responses.copy_both_response() {
+        sender.send(CopyBothMessage::Abort).ok();
+        return Err(e);
+    }
+
+    Ok((
+        CopyBothSink {
+            sender,
+            finished: false,
+        },
+        CopyBothStream {
+            responses,
+            done: false,
+        },
+    ))
+}
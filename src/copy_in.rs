@@ -1,17 +1,27 @@
 use crate::client::{InnerClient, Responses};
 use crate::codec::FrontendMessage;
-use crate::connection::RequestMessages;
-use crate::{query, slice_iter, Error, Statement};
+use crate::connection::{MessageStream, RequestMessages};
+use crate::copy_progress::ProgressTracker;
+use crate::error::SqlState;
+use crate::types::Format;
+use crate::{query, slice_iter, CopyProgress, Error, Statement};
 use bytes::{buf::ext::BufExt, Buf, BufMut, BytesMut};
 use may::sync::mpsc;
 use postgres_protocol::message::backend::Message;
 use postgres_protocol::message::frontend;
 use postgres_protocol::message::frontend::CopyData;
+use std::io::{self, Write};
 use std::marker::PhantomData;
 
+const BINARY_SIGNATURE: &[u8] = b"PGCOPY\n\xff\r\n\0";
+const BAD_SIGNATURE: &str = "binary COPY data must start with the PGCOPY signature";
+const ABORTED: &str = "copy aborted by the client before it finished";
+
 enum CopyInMessage {
     Message(FrontendMessage),
     Done,
+    // an encoded `CopyFail` message
+    Fail(BytesMut),
 }
 
 pub struct CopyInReceiver {
@@ -26,55 +36,48 @@ impl CopyInReceiver {
             done: false,
         }
     }
+}
+
+impl CopyInReceiver {
+    fn frontend_message(&mut self, message: Result<CopyInMessage, ()>) -> Option<FrontendMessage> {
+        let mut buf = match message {
+            Ok(CopyInMessage::Message(message)) => return Some(message),
+            Ok(CopyInMessage::Done) => {
+                let mut buf = BytesMut::new();
+                frontend::copy_done(&mut buf);
+                buf
+            }
+            Ok(CopyInMessage::Fail(fail)) => fail,
+            // the sink or writer was dropped before the copy was done, the message has no NUL byte so it always encodes
+            Err(()) => copy_fail(ABORTED).unwrap_or_default(),
+        };
+        frontend::sync(&mut buf);
+        self.done = true;
+        Some(FrontendMessage::Raw(buf.freeze()))
+    }
+}
 
-    pub fn try_recv(&mut self) -> Result<Option<FrontendMessage>, ()> {
+impl MessageStream for CopyInReceiver {
+    fn try_recv(&mut self) -> Result<Option<FrontendMessage>, ()> {
         use std::sync::mpsc::TryRecvError;
         if self.done {
             return Err(());
         }
 
         match self.receiver.try_recv() {
-            Ok(CopyInMessage::Message(message)) => Ok(Some(message)),
-            Ok(CopyInMessage::Done) => {
-                self.done = true;
-                let mut buf = BytesMut::new();
-                frontend::copy_done(&mut buf);
-                frontend::sync(&mut buf);
-                Ok(Some(FrontendMessage::Raw(buf.freeze())))
-            }
+            Ok(message) => Ok(self.frontend_message(Ok(message))),
             Err(TryRecvError::Empty) => Ok(None),
-            Err(_) => {
-                self.done = true;
-                let mut buf = BytesMut::new();
-                frontend::copy_fail("", &mut buf).unwrap();
-                frontend::sync(&mut buf);
-                Ok(Some(FrontendMessage::Raw(buf.freeze())))
-            }
+            Err(_) => Ok(self.frontend_message(Err(()))),
         }
     }
 
-    pub fn recv(&mut self) -> Result<Option<FrontendMessage>, ()> {
+    fn recv(&mut self) -> Result<Option<FrontendMessage>, ()> {
         if self.done {
             return Err(());
         }
 
-        match self.receiver.recv() {
-            Ok(CopyInMessage::Message(message)) => Ok(Some(message)),
-            Ok(CopyInMessage::Done) => {
-                self.done = true;
-                let mut buf = BytesMut::new();
-                frontend::copy_done(&mut buf);
-                frontend::sync(&mut buf);
-                Ok(Some(FrontendMessage::Raw(buf.freeze())))
-            }
-            Err(_) => {
-                self.done = true;
-                let mut buf = BytesMut::new();
-                frontend::copy_fail("", &mut buf).unwrap();
-                frontend::sync(&mut buf);
-                Ok(Some(FrontendMessage::Raw(buf.freeze())))
-            }
-        }
+        let message = self.receiver.recv().map_err(|_| ());
+        Ok(self.frontend_message(message))
     }
 }
 
@@ -82,10 +85,11 @@ impl CopyInReceiver {
 ///
 /// The copy *must* be explicitly completed via the `Sink::close` or `finish` methods. If it is
 /// not, the copy will be aborted.
+///
+/// For a binary copy the data must start with the `PGCOPY` signature. It is checked before anything is sent, and data
+/// without it aborts the copy with an error rather than reaching the server.
 pub struct CopyInSink<T> {
-    sender: mpsc::Sender<CopyInMessage>,
-    responses: Responses,
-    buf: BytesMut,
+    state: CopyInState,
     _p: PhantomData<T>,
 }
 
@@ -93,27 +97,85 @@ impl<T> CopyInSink<T>
 where
     T: Buf + 'static + Send,
 {
+    /// Returns the format the server expects the data in, as declared by the `COPY` statement.
+    pub fn format(&self) -> Format {
+        self.state.format
+    }
+
+    /// Sets a callback invoked with the progress of the copy each time at least `interval` more bytes have been sent,
+    /// and once more when the copy is finished.
+    ///
+    /// The callback runs on the coroutine sending the data, after the data is queued, so it never holds up the
+    /// connection writing to the socket. Counting the rows of a text copy needs each item as contiguous bytes, which
+    /// copies items that aren't `Bytes` already.
+    pub fn on_progress<F>(&mut self, interval: u64, callback: F)
+    where
+        F: FnMut(CopyProgress) + Send + 'static,
+    {
+        self.state.on_progress(interval, Box::new(callback));
+    }
+
     /// send a buf
-    pub fn send(&mut self, item: T) -> Result<(), Error> {
+    pub fn send(&mut self, mut item: T) -> Result<(), Error> {
+        self.state.check_finished()?;
+        let mut progress = match self.state.progress.take() {
+            Some(progress) => progress,
+            None => return self.send_item(item),
+        };
+
+        let result = if progress.counts_rows() {
+            let data = item.to_bytes();
+            let result = self.send_item(data.clone());
+            if result.is_ok() {
+                progress.update(&data);
+            }
+            result
+        } else {
+            let len = item.remaining();
+            let result = self.send_item(item);
+            if result.is_ok() {
+                progress.advance(len);
+            }
+            result
+        };
+        self.state.progress = Some(progress);
+        result
+    }
+
+    fn send_item<B>(&mut self, item: B) -> Result<(), Error>
+    where
+        B: Buf + 'static + Send,
+    {
+        let state = &mut self.state;
+        if !state.checked {
+            state.buf.put(item);
+            if state.buf.len() < BINARY_SIGNATURE.len() {
+                return Ok(());
+            }
+            state.check_signature()?;
+            if state.buf.len() <= 4096 {
+                return Ok(());
+            }
+            let data = Box::new(state.buf.split().freeze());
+            return state.send_data(data);
+        }
+
         let data: Box<dyn Buf + Send> = if item.remaining() > 4096 {
-            if self.buf.is_empty() {
+            if state.buf.is_empty() {
                 Box::new(item)
             } else {
-                Box::new(self.buf.split().freeze().chain(item))
+                Box::new(state.buf.split().freeze().chain(item))
             }
         } else {
-            self.buf.put(item);
-            if self.buf.len() > 4096 {
-                Box::new(self.buf.split().freeze())
+            state.buf.put(item);
+            if state.buf.len() > 4096 {
+                Box::new(state.buf.split().freeze())
             } else {
                 return Ok(());
             }
         };
 
-        let data = CopyData::new(data).map_err(Error::encode)?;
-        self.sender
-            .send(CopyInMessage::Message(FrontendMessage::CopyData(data)))
-            .map_err(|_| Error::closed())
+        state.send_data(data)
     }
 
     /// send iterator of bufs
@@ -132,66 +194,255 @@ where
     /// The `Sink::close` method is equivalent to `finish`, except that it does not return the
     /// number of rows.
     pub fn finish(&mut self) -> Result<u64, Error> {
-        // flush the remaining data
-        let data: Box<dyn Buf + Send> = Box::new(self.buf.split().freeze());
-        if data.remaining() > 0 {
-            let data = CopyData::new(data).map_err(Error::encode)?;
-            self.sender
-                .send(CopyInMessage::Message(FrontendMessage::CopyData(data)))
-                .map_err(|_| Error::closed())?;
+        self.state.finish()
+    }
+}
+
+pub fn copy_in<T>(client: &InnerClient, statement: Statement) -> Result<CopyInSink<T>, Error>
+where
+    T: Buf + 'static + Send,
+{
+    Ok(CopyInSink {
+        state: CopyInState::start(client, statement)?,
+        _p: PhantomData,
+    })
+}
+
+pub fn copy_in_writer(client: &InnerClient, statement: Statement) -> Result<CopyInWriter, Error> {
+    Ok(CopyInWriter {
+        state: CopyInState::start(client, statement)?,
+    })
+}
+
+// the state of a copy shared by `CopyInSink` and `CopyInWriter`, which only differ in how the data is handed over
+struct CopyInState {
+    sender: mpsc::Sender<CopyInMessage>,
+    responses: Responses,
+    // data not sent yet, gathered into messages of about 4KiB
+    buf: BytesMut,
+    format: Format,
+    // whether the data of a binary copy was found to start with the signature, always set for a text copy
+    checked: bool,
+    finished: bool,
+    progress: Option<ProgressTracker>,
+}
+
+impl CopyInState {
+    fn start(client: &InnerClient, statement: Statement) -> Result<CopyInState, Error> {
+        // debug!("executing copy in statement {}", statement.name());
+
+        let buf = query::encode(client, &statement, slice_iter(&[]))?;
+
+        let (sender, receiver) = mpsc::channel();
+        let receiver = CopyInReceiver::new(receiver);
+        let mut responses = client.send(RequestMessages::CopyIn(receiver))?;
+
+        sender
+            .send(CopyInMessage::Message(FrontendMessage::Raw(buf)))
+            .map_err(|_| Error::closed())?;
+
+        match responses.next()? {
+            Message::BindComplete => {}
+            _ => return Err(Error::unexpected_message()),
+        }
+
+        let format = match responses.next()? {
+            Message::CopyInResponse(body) if body.format() == 0 => Format::Text,
+            Message::CopyInResponse(_) => Format::Binary,
+            _ => return Err(Error::unexpected_message()),
+        };
+
+        Ok(CopyInState {
+            sender,
+            responses,
+            buf: BytesMut::new(),
+            format,
+            checked: format == Format::Text,
+            finished: false,
+            progress: None,
+        })
+    }
+
+    fn on_progress(&mut self, interval: u64, callback: Box<dyn FnMut(CopyProgress) + Send>) {
+        self.progress = Some(ProgressTracker::new(
+            interval,
+            self.format == Format::Text,
+            callback,
+        ));
+    }
+
+    fn check_finished(&self) -> Result<(), Error> {
+        if self.finished {
+            return Err(Error::copy_finished());
+        }
+        Ok(())
+    }
+
+    fn check_signature(&mut self) -> Result<(), Error> {
+        self.checked = true;
+        if self.buf.starts_with(BINARY_SIGNATURE) {
+            return Ok(());
         }
+        self.buf.clear();
+        self.finished = true;
+        Err(reject(&self.sender, &mut self.responses))
+    }
 
+    fn send_data(&self, data: Box<dyn Buf + Send>) -> Result<(), Error> {
+        let data = CopyData::new(data).map_err(Error::encode)?;
         self.sender
-            .send(CopyInMessage::Done)
-            .map_err(|_| Error::closed())?;
+            .send(CopyInMessage::Message(FrontendMessage::CopyData(data)))
+            .map_err(|_| Error::closed())
+    }
+
+    // sends what is buffered, unless the signature of a binary copy can't be checked yet
+    fn send_buf(&mut self) -> Result<(), Error> {
+        if !self.checked {
+            if self.buf.len() < BINARY_SIGNATURE.len() {
+                return Ok(());
+            }
+            self.check_signature()?;
+        }
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        let data = Box::new(self.buf.split().freeze());
+        self.send_data(data)
+    }
+
+    fn end(&mut self, message: CopyInMessage) -> Result<(), Error> {
+        self.check_finished()?;
+        self.finished = true;
+        self.sender.send(message).map_err(|_| Error::closed())
+    }
+
+    fn finish(&mut self) -> Result<u64, Error> {
+        self.check_finished()?;
+        if !self.checked {
+            self.check_signature()?;
+        }
+        self.send_buf()?;
+        self.end(CopyInMessage::Done)?;
 
         match self.responses.next()? {
             Message::CommandComplete(body) => {
-                let rows = body
-                    .tag()
-                    .map_err(Error::parse)?
-                    .rsplit(' ')
-                    .next()
-                    .unwrap()
-                    .parse()
-                    .unwrap_or(0);
+                let rows = query::extract_row_affected(&body)?;
+                if let Some(progress) = &mut self.progress {
+                    progress.finish();
+                }
                 Ok(rows)
             }
             _ => Err(Error::unexpected_message()),
         }
     }
+
+    fn abort(&mut self, message: &str) -> Result<(), Error> {
+        self.check_finished()?;
+        let fail = copy_fail(message)?;
+        self.buf.clear();
+        self.end(CopyInMessage::Fail(fail))?;
+
+        match self.responses.next() {
+            Err(e) if e.code() == Some(&SqlState::QUERY_CANCELED) => Ok(()),
+            Err(e) => Err(e),
+            Ok(_) => Err(Error::unexpected_message()),
+        }
+    }
 }
 
-pub fn copy_in<T>(client: &InnerClient, statement: Statement) -> Result<CopyInSink<T>, Error>
-where
-    T: Buf + 'static + Send,
-{
-    // debug!("executing copy in statement {}", statement.name());
+// encodes a `CopyFail` message, which fails if `message` contains a NUL byte
+fn copy_fail(message: &str) -> Result<BytesMut, Error> {
+    let mut buf = BytesMut::new();
+    frontend::copy_fail(message, &mut buf).map_err(Error::encode)?;
+    Ok(buf)
+}
+
+// aborts a binary copy whose data doesn't start with the signature, returning the error to report for it
+fn reject(sender: &mpsc::Sender<CopyInMessage>, responses: &mut Responses) -> Error {
+    let fail = match copy_fail(BAD_SIGNATURE) {
+        Ok(fail) => fail,
+        Err(e) => return e,
+    };
+    if sender.send(CopyInMessage::Fail(fail)).is_err() {
+        return Error::closed();
+    }
+
+    match responses.next() {
+        Err(e) if e.code() == Some(&SqlState::QUERY_CANCELED) => {
+            Error::encode(io::Error::new(io::ErrorKind::InvalidData, BAD_SIGNATURE))
+        }
+        Err(e) => e,
+        Ok(_) => Error::unexpected_message(),
+    }
+}
+
+/// A writer for `COPY ... FROM STDIN` query data.
+///
+/// The written data is buffered and sent in `CopyData` messages of about 4KiB, and `flush` sends what is buffered so
+/// far. It doesn't wait for the server, which reports problems with the data once the copy is finished. The copy *must*
+/// be explicitly completed via the `finish` method. If it is not, the copy will be aborted.
+///
+/// For a binary copy the data must start with the `PGCOPY` signature. It is checked before anything is sent, and data
+/// without it aborts the copy with an error rather than reaching the server.
+pub struct CopyInWriter {
+    state: CopyInState,
+}
 
-    let buf = query::encode(client, &statement, slice_iter(&[]))?;
+impl CopyInWriter {
+    /// Returns the format the server expects the data in, as declared by the `COPY` statement.
+    pub fn format(&self) -> Format {
+        self.state.format
+    }
 
-    let (sender, receiver) = mpsc::channel();
-    let receiver = CopyInReceiver::new(receiver);
-    let mut responses = client.send(RequestMessages::CopyIn(receiver))?;
+    /// Sets a callback invoked with the progress of the copy each time at least `interval` more bytes have been
+    /// written, and once more when the copy is finished.
+    ///
+    /// The callback runs on the coroutine writing the data, so it never holds up the connection writing to the
+    /// socket.
+    pub fn on_progress<F>(&mut self, interval: u64, callback: F)
+    where
+        F: FnMut(CopyProgress) + Send + 'static,
+    {
+        self.state.on_progress(interval, Box::new(callback));
+    }
 
-    sender
-        .send(CopyInMessage::Message(FrontendMessage::Raw(buf)))
-        .map_err(|_| Error::closed())?;
+    /// Completes the copy, returning the number of rows inserted.
+    ///
+    /// Returns an error if the copy has already been finished or aborted.
+    pub fn finish(&mut self) -> Result<u64, Error> {
+        self.state.finish()
+    }
 
-    match responses.next()? {
-        Message::BindComplete => {}
-        _ => return Err(Error::unexpected_message()),
+    /// Aborts the copy, discarding the data written so far.
+    ///
+    /// The server fails the `COPY` statement with an error containing `message`, which is expected and not returned.
+    /// Returns an error if the copy has already been finished or aborted, or if `message` contains a NUL byte, in which
+    /// case the copy is left running.
+    pub fn abort(&mut self, message: &str) -> Result<(), Error> {
+        self.state.abort(message)
     }
+}
 
-    match responses.next()? {
-        Message::CopyInResponse(_) => {}
-        _ => return Err(Error::unexpected_message()),
+impl Write for CopyInWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let state = &mut self.state;
+        state.check_finished().map_err(io::Error::other)?;
+        state.buf.extend_from_slice(buf);
+        if state.buf.len() > 4096 {
+            state
+                .send_buf()
+                .map_err(|e| io::Error::new(io::ErrorKind::BrokenPipe, e))?;
+        }
+        if let Some(progress) = &mut state.progress {
+            progress.update(buf);
+        }
+        Ok(buf.len())
     }
 
-    Ok(CopyInSink {
-        sender,
-        responses,
-        buf: BytesMut::new(),
-        _p: PhantomData,
-    })
+    fn flush(&mut self) -> io::Result<()> {
+        self.state.check_finished().map_err(io::Error::other)?;
+        self.state
+            .send_buf()
+            .map_err(|e| io::Error::new(io::ErrorKind::BrokenPipe, e))
+    }
 }
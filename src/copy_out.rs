@@ -1,17 +1,24 @@
 use crate::client::{InnerClient, Responses};
 use crate::codec::FrontendMessage;
 use crate::connection::RequestMessages;
-use crate::{query, slice_iter, Error, Statement};
+use crate::copy_progress::ProgressTracker;
+use crate::{query, slice_iter, CopyProgress, Error, Statement};
 use bytes::Bytes;
 use postgres_protocol::message::backend::Message;
 
 pub fn copy_out(client: &InnerClient, statement: Statement) -> Result<CopyOutStream, Error> {
     let buf = query::encode(client, &statement, slice_iter(&[]))?;
-    let responses = start(client, buf)?;
-    Ok(CopyOutStream { responses })
+    let (responses, text) = start(client, buf)?;
+    Ok(CopyOutStream {
+        responses,
+        done: false,
+        text,
+        progress: None,
+    })
 }
 
-fn start(client: &InnerClient, buf: Bytes) -> Result<Responses, Error> {
+// returns whether the data is in the text format
+fn start(client: &InnerClient, buf: Bytes) -> Result<(Responses, bool), Error> {
     let mut responses = client.send(RequestMessages::Single(FrontendMessage::Raw(buf)))?;
 
     match responses.next()? {
@@ -19,27 +26,81 @@ fn start(client: &InnerClient, buf: Bytes) -> Result<Responses, Error> {
         _ => return Err(Error::unexpected_message()),
     }
 
-    match responses.next()? {
-        Message::CopyOutResponse(_) => {}
+    let text = match responses.next()? {
+        Message::CopyOutResponse(body) => body.format() == 0,
         _ => return Err(Error::unexpected_message()),
-    }
+    };
 
-    Ok(responses)
+    Ok((responses, text))
 }
 
 /// A stream of `COPY ... TO STDOUT` query data.
+///
+/// Each item is the payload of one `CopyData` message. The stream ends at the `CommandComplete` that follows
+/// `CopyDone`.
 pub struct CopyOutStream {
     responses: Responses,
+    done: bool,
+    text: bool,
+    progress: Option<ProgressTracker>,
+}
+
+impl CopyOutStream {
+    /// Sets a callback invoked with the progress of the copy each time at least `interval` more bytes have been read
+    /// from the stream, and once more when the stream ends.
+    ///
+    /// The callback runs on the coroutine iterating the stream, between two items, while the connection keeps
+    /// receiving the data that follows.
+    pub fn on_progress<F>(&mut self, interval: u64, callback: F)
+    where
+        F: FnMut(CopyProgress) + Send + 'static,
+    {
+        self.progress = Some(ProgressTracker::new(
+            interval,
+            self.text,
+            Box::new(callback),
+        ));
+    }
 }
 
 impl Iterator for CopyOutStream {
     type Item = Result<Bytes, Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        match o_try!(self.responses.next()) {
-            Message::CopyData(body) => Some(Ok(body.into_bytes())),
-            Message::CopyDone => None,
-            _ => Some(Err(Error::unexpected_message())),
+        if self.done {
+            return None;
+        }
+
+        loop {
+            let message = match self.responses.next() {
+                Ok(message) => message,
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            };
+
+            match message {
+                Message::CopyData(body) => {
+                    let data = body.into_bytes();
+                    if let Some(progress) = &mut self.progress {
+                        progress.update(&data);
+                    }
+                    return Some(Ok(data));
+                }
+                Message::CopyDone => {}
+                Message::CommandComplete(_) => {
+                    self.done = true;
+                    if let Some(progress) = &mut self.progress {
+                        progress.finish();
+                    }
+                    return None;
+                }
+                _ => {
+                    self.done = true;
+                    return Some(Err(Error::unexpected_message()));
+                }
+            }
         }
     }
 }
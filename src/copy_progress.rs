@@ -0,0 +1,77 @@
+/// The progress of a `COPY`, as reported to the callback set with `on_progress` on a copy's sink, writer or stream.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct CopyProgress {
+    bytes: u64,
+    rows: Option<u64>,
+}
+
+impl CopyProgress {
+    /// Returns the number of bytes of data transferred so far.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Returns the number of rows transferred so far, or `None` for a binary copy.
+    ///
+    /// Rows are counted as lines, so a CSV value containing a line break counts as more than one.
+    pub fn rows(&self) -> Option<u64> {
+        self.rows
+    }
+}
+
+// counts the data passed through a copy, and calls back every `interval` bytes
+pub(crate) struct ProgressTracker {
+    callback: Box<dyn FnMut(CopyProgress) + Send>,
+    interval: u64,
+    reported: u64,
+    progress: CopyProgress,
+}
+
+impl ProgressTracker {
+    pub fn new(
+        interval: u64,
+        text: bool,
+        callback: Box<dyn FnMut(CopyProgress) + Send>,
+    ) -> ProgressTracker {
+        ProgressTracker {
+            callback,
+            interval: interval.max(1),
+            reported: 0,
+            progress: CopyProgress {
+                bytes: 0,
+                rows: if text { Some(0) } else { None },
+            },
+        }
+    }
+
+    pub fn counts_rows(&self) -> bool {
+        self.progress.rows.is_some()
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        if let Some(rows) = &mut self.progress.rows {
+            *rows += data.iter().filter(|b| **b == b'\n').count() as u64;
+        }
+        self.advance(data.len());
+    }
+
+    // for binary data, whose rows aren't counted
+    pub fn advance(&mut self, len: usize) {
+        self.progress.bytes += len as u64;
+        if self.progress.bytes - self.reported >= self.interval {
+            self.report();
+        }
+    }
+
+    // reports what is left since the last call, once the copy is done
+    pub fn finish(&mut self) {
+        if self.progress.bytes > self.reported {
+            self.report();
+        }
+    }
+
+    fn report(&mut self) {
+        self.reported = self.progress.bytes;
+        (self.callback)(self.progress);
+    }
+}
@@ -11,6 +11,17 @@ pub use self::sqlstate::*;
 #[allow(clippy::unreadable_literal)]
 mod sqlstate;
 
+impl SqlState {
+    /// Returns the class of the code, which is its first two characters.
+    ///
+    /// Codes in a class share a category, like `23` for integrity constraint violations or `40` for transaction
+    /// rollbacks, so an error can be matched by class without listing every code in it.
+    pub fn class(&self) -> &str {
+        let code = self.code();
+        code.get(..2).unwrap_or(code)
+    }
+}
+
 /// The severity of a Postgres error or notice.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Severity {
@@ -308,8 +319,44 @@ impl DbError {
 }
 
 impl fmt::Display for DbError {
+    // along the lines of psql's verbose output, with a line for each field after the message
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(fmt, "{}: {}", self.severity, self.message)
+        write!(
+            fmt,
+            "{} {}: {}",
+            self.severity,
+            self.code.code(),
+            self.message
+        )?;
+        if let Some(detail) = &self.detail {
+            write!(fmt, "\nDETAIL: {}", detail)?;
+        }
+        if let Some(hint) = &self.hint {
+            write!(fmt, "\nHINT: {}", hint)?;
+        }
+        match &self.position {
+            Some(ErrorPosition::Original(position)) => write!(fmt, "\nPOSITION: {}", position)?,
+            Some(ErrorPosition::Internal { position, query }) => {
+                write!(fmt, "\nPOSITION: {} in internal query: {}", position, query)?
+            }
+            None => {}
+        }
+        if let Some(where_) = &self.where_ {
+            write!(fmt, "\nCONTEXT: {}", where_)?;
+        }
+        let names = [
+            ("SCHEMA", &self.schema),
+            ("TABLE", &self.table),
+            ("COLUMN", &self.column),
+            ("DATATYPE", &self.datatype),
+            ("CONSTRAINT", &self.constraint),
+        ];
+        for (field, name) in names {
+            if let Some(name) = name {
+                write!(fmt, "\n{} NAME: {}", field, name)?;
+            }
+        }
+        Ok(())
     }
 }
 
@@ -333,7 +380,7 @@ pub enum ErrorPosition {
 enum Kind {
     Io,
     UnexpectedMessage,
-    // Tls,
+    Tls,
     ToSql(usize),
     FromSql(usize),
     Column(String),
@@ -344,8 +391,19 @@ enum Kind {
     Authentication,
     ConfigParse,
     Config,
-    RowCount,
+    RowCount(RowCount),
     Connect,
+    Timeout,
+    Skipped,
+    TooManyParams(usize),
+    CopyFinished,
+    StreamHeld,
+}
+
+#[derive(Debug, PartialEq)]
+enum RowCount {
+    None,
+    TooMany,
 }
 
 struct ErrorInner {
@@ -370,7 +428,7 @@ impl fmt::Display for Error {
         match &self.0.kind {
             Kind::Io => fmt.write_str("error communicating with the server")?,
             Kind::UnexpectedMessage => fmt.write_str("unexpected message from server")?,
-            // Kind::Tls => fmt.write_str("error performing TLS handshake")?,
+            Kind::Tls => fmt.write_str("error performing TLS handshake")?,
             Kind::ToSql(idx) => write!(fmt, "error serializing parameter {}", idx)?,
             Kind::FromSql(idx) => write!(fmt, "error deserializing column {}", idx)?,
             Kind::Column(column) => write!(fmt, "invalid column `{}`", column)?,
@@ -381,8 +439,22 @@ impl fmt::Display for Error {
             Kind::Authentication => fmt.write_str("authentication error")?,
             Kind::ConfigParse => fmt.write_str("invalid connection string")?,
             Kind::Config => fmt.write_str("invalid configuration")?,
-            Kind::RowCount => fmt.write_str("query returned an unexpected number of rows")?,
+            Kind::RowCount(_) => fmt.write_str("query returned an unexpected number of rows")?,
             Kind::Connect => fmt.write_str("error connecting to server")?,
+            Kind::Timeout => fmt.write_str("timeout waiting for server")?,
+            Kind::Skipped => {
+                fmt.write_str("statement skipped after an earlier error in the pipeline")?
+            }
+            Kind::TooManyParams(count) => write!(
+                fmt,
+                "too many parameters: {} given but a statement takes at most {}",
+                count,
+                crate::query::MAX_PARAMS
+            )?,
+            Kind::CopyFinished => fmt.write_str("copy already finished or aborted")?,
+            Kind::StreamHeld => fmt.write_str(
+                "the connection is reserved to a chunked row stream held by the same coroutine",
+            )?,
         };
         if let Some(ref cause) = self.0.cause {
             write!(fmt, ": {}", cause)?;
@@ -397,6 +469,81 @@ impl error::Error for Error {
     }
 }
 
+/// An error running a script with `Client::execute_script`, along with the statement it occurred at.
+#[derive(Debug)]
+pub struct ScriptError {
+    statement: Option<usize>,
+    error: Error,
+}
+
+impl ScriptError {
+    pub(crate) fn new(statement: Option<usize>, error: Error) -> ScriptError {
+        ScriptError { statement, error }
+    }
+
+    /// Returns the 0-based index of the statement which failed, if the server reported the error.
+    ///
+    /// Empty statements, as in `SELECT 1;; SELECT 2`, are not counted since the server skips them. Errors which are
+    /// not tied to a statement, such as an I/O error or a closed connection, have no index.
+    pub fn statement(&self) -> Option<usize> {
+        self.statement
+    }
+
+    /// Returns the error itself.
+    pub fn error(&self) -> &Error {
+        &self.error
+    }
+
+    /// Consumes the `ScriptError`, returning the error itself.
+    pub fn into_error(self) -> Error {
+        self.error
+    }
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.statement {
+            Some(statement) => write!(fmt, "error in statement {}: {}", statement, self.error),
+            None => write!(fmt, "error running script: {}", self.error),
+        }
+    }
+}
+
+impl error::Error for ScriptError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// The errors of every host tried by a connection attempt with several hosts, in order.
+///
+/// It is the source of the error returned once all of them have failed, as given by `Error::as_connect_errors`.
+#[derive(Debug)]
+pub struct ConnectErrors(Vec<(String, Error)>);
+
+impl ConnectErrors {
+    pub(crate) fn new(errors: Vec<(String, Error)>) -> ConnectErrors {
+        ConnectErrors(errors)
+    }
+
+    /// Returns each host, as `host:port` or the path of its socket, along with the error connecting to it.
+    pub fn errors(&self) -> &[(String, Error)] {
+        &self.0
+    }
+}
+
+impl fmt::Display for ConnectErrors {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "all {} hosts failed", self.0.len())?;
+        for (host, error) in &self.0 {
+            write!(fmt, "; {}: {}", host, error)?;
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for ConnectErrors {}
+
 impl Error {
     /// Consumes the error, returning its cause.
     pub fn into_source(self) -> Option<Box<dyn error::Error + Sync + Send>> {
@@ -408,9 +555,77 @@ impl Error {
     /// This is a convenience method that downcasts the cause to a `DbError`
     /// and returns its code.
     pub fn code(&self) -> Option<&SqlState> {
+        self.as_db_error().map(DbError::code)
+    }
+
+    /// Returns the error reported by the server, if this is one.
+    ///
+    /// The `DbError` holds every field of the server's error response, like the detail, hint, and the names of the
+    /// table and constraint involved.
+    pub fn as_db_error(&self) -> Option<&DbError> {
+        self.source().and_then(|e| e.downcast_ref::<DbError>())
+    }
+
+    /// Returns the error of each host, if this is the error of a connection attempt for which every one of several
+    /// hosts failed.
+    pub fn as_connect_errors(&self) -> Option<&ConnectErrors> {
         self.source()
-            .and_then(|e| e.downcast_ref::<DbError>())
-            .map(DbError::code)
+            .and_then(|e| e.downcast_ref::<ConnectErrors>())
+    }
+
+    /// Determines if the error was associated with closed connection.
+    pub fn is_closed(&self) -> bool {
+        self.0.kind == Kind::Closed
+    }
+
+    /// Determines if the error was caused by a query returning no rows when one was required, as reported by
+    /// `Client::query_one`.
+    pub fn is_no_rows(&self) -> bool {
+        self.0.kind == Kind::RowCount(RowCount::None)
+    }
+
+    /// Determines if the error was caused by a query returning more than one row, as reported by
+    /// `Client::query_one` and `Client::query_opt`.
+    pub fn is_too_many_rows(&self) -> bool {
+        self.0.kind == Kind::RowCount(RowCount::TooMany)
+    }
+
+    /// Determines if the error was caused by looking up a column which doesn't exist, by name or by index.
+    pub fn is_missing_column(&self) -> bool {
+        matches!(self.0.kind, Kind::Column(_))
+    }
+
+    /// Determines if the error was caused by reading a column into a Rust type which doesn't accept its Postgres type.
+    pub fn is_wrong_type(&self) -> bool {
+        matches!(self.0.kind, Kind::FromSql(_))
+            && self
+                .source()
+                .is_some_and(|e| e.is::<crate::types::WrongType>())
+    }
+
+    /// Determines if the error was caused by reading a SQL `NULL` into a Rust type other than `Option`.
+    pub fn is_unexpected_null(&self) -> bool {
+        matches!(self.0.kind, Kind::FromSql(_))
+            && self
+                .source()
+                .is_some_and(|e| e.is::<crate::types::WasNull>())
+    }
+
+    /// Determines if the error was caused by a client-side timeout.
+    pub fn is_timeout(&self) -> bool {
+        self.0.kind == Kind::Timeout
+    }
+
+    /// Determines if the error was caused by an earlier statement of a `Pipeline` failing, which made the server
+    /// skip this one.
+    pub fn is_skipped(&self) -> bool {
+        self.0.kind == Kind::Skipped
+    }
+
+    /// Determines if the error was caused by passing more parameters than a statement can take, which is checked
+    /// before anything is sent to the server.
+    pub fn is_too_many_params(&self) -> bool {
+        matches!(self.0.kind, Kind::TooManyParams(_))
     }
 
     fn new(kind: Kind, cause: Option<Box<dyn error::Error + Sync + Send>>) -> Error {
@@ -454,9 +669,9 @@ impl Error {
         Error::new(Kind::Column(column), None)
     }
 
-    // pub(crate) fn tls(e: Box<dyn error::Error + Sync + Send>) -> Error {
-    //     Error::new(Kind::Tls, Some(e))
-    // }
+    pub(crate) fn tls(e: Box<dyn error::Error + Sync + Send>) -> Error {
+        Error::new(Kind::Tls, Some(e))
+    }
 
     pub(crate) fn io(e: io::Error) -> Error {
         Error::new(Kind::Io, Some(Box::new(e)))
@@ -474,11 +689,39 @@ impl Error {
         Error::new(Kind::Config, Some(e))
     }
 
-    pub(crate) fn row_count() -> Error {
-        Error::new(Kind::RowCount, None)
+    pub(crate) fn no_rows() -> Error {
+        Error::new(Kind::RowCount(RowCount::None), None)
+    }
+
+    pub(crate) fn too_many_rows() -> Error {
+        Error::new(Kind::RowCount(RowCount::TooMany), None)
     }
 
     pub(crate) fn connect(e: io::Error) -> Error {
         Error::new(Kind::Connect, Some(Box::new(e)))
     }
+
+    pub(crate) fn connect_errors(errors: ConnectErrors) -> Error {
+        Error::new(Kind::Connect, Some(Box::new(errors)))
+    }
+
+    pub(crate) fn timeout() -> Error {
+        Error::new(Kind::Timeout, None)
+    }
+
+    pub(crate) fn skipped() -> Error {
+        Error::new(Kind::Skipped, None)
+    }
+
+    pub(crate) fn too_many_params(count: usize) -> Error {
+        Error::new(Kind::TooManyParams(count), None)
+    }
+
+    pub(crate) fn copy_finished() -> Error {
+        Error::new(Kind::CopyFinished, None)
+    }
+
+    pub(crate) fn stream_held() -> Error {
+        Error::new(Kind::StreamHeld, None)
+    }
 }
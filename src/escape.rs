@@ -0,0 +1,52 @@
+/// Escapes a string so it can be used as an identifier, such as a table or column name, in a query.
+///
+/// Like libpq's `PQescapeIdentifier`, the name is wrapped in double quotes and any double quote in it is doubled, so
+/// its case and any special characters are kept. Postgres names can't contain a null character, so the name ends at
+/// the first one, as the C string would.
+///
+/// # Example
+///
+/// ```
+/// assert_eq!(may_postgres::escape_identifier(r#"my "table""#), r#""my ""table""""#);
+/// ```
+pub fn escape_identifier(input: &str) -> String {
+    escape(input, '"')
+}
+
+/// Escapes a string so it can be used as a string literal in a query.
+///
+/// Like libpq's `PQescapeLiteral`, the string is wrapped in single quotes and any single quote in it is doubled. If it
+/// contains a backslash, backslashes are doubled too and the literal is written as an escape string, ` E'...'`, with
+/// a leading space, so it means the same whatever the server's `standard_conforming_strings` setting. The string ends
+/// at the first null character, as the C string would.
+///
+/// Passing values as query parameters is preferable wherever the server accepts one.
+///
+/// # Example
+///
+/// ```
+/// assert_eq!(may_postgres::escape_literal("it's"), "'it''s'");
+/// assert_eq!(may_postgres::escape_literal(r"C:\dir"), r" E'C:\\dir'");
+/// ```
+pub fn escape_literal(input: &str) -> String {
+    escape(input, '\'')
+}
+
+fn escape(input: &str, quote: char) -> String {
+    let input = input.split('\0').next().unwrap_or("");
+    let backslashes = quote == '\'' && input.contains('\\');
+
+    let mut output = String::with_capacity(input.len() + 4);
+    if backslashes {
+        output.push_str(" E");
+    }
+    output.push(quote);
+    for c in input.chars() {
+        if c == quote || (backslashes && c == '\\') {
+            output.push(c);
+        }
+        output.push(c);
+    }
+    output.push(quote);
+    output
+}
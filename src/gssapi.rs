@@ -0,0 +1,232 @@
+//! A minimal binding to the GSSAPI library of MIT Kerberos, covering what the client side of a Postgres GSSAPI
+//! authentication exchange needs.
+
+use std::error::Error;
+use std::os::raw::{c_int, c_void};
+use std::ptr;
+
+#[allow(non_camel_case_types)]
+type OM_uint32 = u32;
+
+#[repr(C)]
+struct gss_buffer_desc {
+    length: usize,
+    value: *mut c_void,
+}
+
+#[repr(C)]
+struct gss_OID_desc {
+    length: OM_uint32,
+    elements: *mut c_void,
+}
+
+#[allow(non_camel_case_types)]
+type gss_OID = *mut gss_OID_desc;
+#[allow(non_camel_case_types)]
+type gss_name_t = *mut c_void;
+#[allow(non_camel_case_types)]
+type gss_ctx_id_t = *mut c_void;
+
+const GSS_S_COMPLETE: OM_uint32 = 0;
+const GSS_S_CONTINUE_NEEDED: OM_uint32 = 1;
+// the calling and routine error fields of a major status
+const GSS_ERROR_MASK: OM_uint32 = 0xffff_0000;
+const GSS_C_MUTUAL_FLAG: OM_uint32 = 2;
+const GSS_C_GSS_CODE: c_int = 1;
+const GSS_C_MECH_CODE: c_int = 2;
+
+#[link(name = "gssapi_krb5")]
+extern "C" {
+    static GSS_C_NT_HOSTBASED_SERVICE: gss_OID;
+
+    fn gss_import_name(
+        minor_status: *mut OM_uint32,
+        input_name_buffer: *mut gss_buffer_desc,
+        input_name_type: gss_OID,
+        output_name: *mut gss_name_t,
+    ) -> OM_uint32;
+
+    fn gss_release_name(minor_status: *mut OM_uint32, name: *mut gss_name_t) -> OM_uint32;
+
+    #[allow(clippy::too_many_arguments)]
+    fn gss_init_sec_context(
+        minor_status: *mut OM_uint32,
+        initiator_cred_handle: *mut c_void,
+        context_handle: *mut gss_ctx_id_t,
+        target_name: gss_name_t,
+        mech_type: gss_OID,
+        req_flags: OM_uint32,
+        time_req: OM_uint32,
+        input_chan_bindings: *mut c_void,
+        input_token: *mut gss_buffer_desc,
+        actual_mech_type: *mut gss_OID,
+        output_token: *mut gss_buffer_desc,
+        ret_flags: *mut OM_uint32,
+        time_rec: *mut OM_uint32,
+    ) -> OM_uint32;
+
+    fn gss_delete_sec_context(
+        minor_status: *mut OM_uint32,
+        context_handle: *mut gss_ctx_id_t,
+        output_token: *mut gss_buffer_desc,
+    ) -> OM_uint32;
+
+    fn gss_release_buffer(minor_status: *mut OM_uint32, buffer: *mut gss_buffer_desc) -> OM_uint32;
+
+    fn gss_display_status(
+        minor_status: *mut OM_uint32,
+        status_value: OM_uint32,
+        status_type: c_int,
+        mech_type: gss_OID,
+        message_context: *mut OM_uint32,
+        status_string: *mut gss_buffer_desc,
+    ) -> OM_uint32;
+}
+
+fn empty_buffer() -> gss_buffer_desc {
+    gss_buffer_desc {
+        length: 0,
+        value: ptr::null_mut(),
+    }
+}
+
+// the messages the library has for a status code, like libpq's pg_GSS_error
+fn display_status(status: OM_uint32, status_type: c_int) -> String {
+    let mut messages = vec![];
+    let mut message_context = 0;
+    loop {
+        let mut minor = 0;
+        let mut buf = empty_buffer();
+        let major = unsafe {
+            gss_display_status(
+                &mut minor,
+                status,
+                status_type,
+                ptr::null_mut(),
+                &mut message_context,
+                &mut buf,
+            )
+        };
+        if major & GSS_ERROR_MASK != 0 {
+            break;
+        }
+        if !buf.value.is_null() {
+            let message = unsafe { std::slice::from_raw_parts(buf.value as *const u8, buf.length) };
+            messages.push(String::from_utf8_lossy(message).into_owned());
+            unsafe { gss_release_buffer(&mut minor, &mut buf) };
+        }
+        if message_context == 0 {
+            break;
+        }
+    }
+    messages.join(", ")
+}
+
+fn gss_error(action: &str, major: OM_uint32, minor: OM_uint32) -> Box<dyn Error + Sync + Send> {
+    format!(
+        "GSSAPI error {}: {}: {}",
+        action,
+        display_status(major, GSS_C_GSS_CODE),
+        display_status(minor, GSS_C_MECH_CODE),
+    )
+    .into()
+}
+
+/// The client side of a security context with the server's Kerberos principal.
+pub struct ClientContext {
+    target: gss_name_t,
+    context: gss_ctx_id_t,
+}
+
+impl ClientContext {
+    /// Creates a context for the `service@host` principal, as libpq does with `krbsrvname` and the host name.
+    pub fn new(service: &str, host: &str) -> Result<ClientContext, Box<dyn Error + Sync + Send>> {
+        let mut name = format!("{}@{}", service, host).into_bytes();
+        let mut buf = gss_buffer_desc {
+            length: name.len(),
+            value: name.as_mut_ptr() as *mut c_void,
+        };
+        let mut target = ptr::null_mut();
+        let mut minor = 0;
+        let major = unsafe {
+            gss_import_name(
+                &mut minor,
+                &mut buf,
+                GSS_C_NT_HOSTBASED_SERVICE,
+                &mut target,
+            )
+        };
+        if major & GSS_ERROR_MASK != 0 {
+            return Err(gss_error("importing the server name", major, minor));
+        }
+
+        Ok(ClientContext {
+            target,
+            context: ptr::null_mut(),
+        })
+    }
+
+    /// Runs one step of the exchange with the token sent by the server, if any.
+    ///
+    /// Returns the token to send to the server, which may be empty, and whether the context is established.
+    pub fn step(
+        &mut self,
+        input: Option<&[u8]>,
+    ) -> Result<(Vec<u8>, bool), Box<dyn Error + Sync + Send>> {
+        let mut input = input.map(|input| gss_buffer_desc {
+            length: input.len(),
+            value: input.as_ptr() as *mut c_void,
+        });
+        let input = input
+            .as_mut()
+            .map_or(ptr::null_mut(), |input| input as *mut _);
+        let mut output = empty_buffer();
+        let mut minor = 0;
+        let major = unsafe {
+            gss_init_sec_context(
+                &mut minor,
+                ptr::null_mut(),
+                &mut self.context,
+                self.target,
+                ptr::null_mut(),
+                GSS_C_MUTUAL_FLAG,
+                0,
+                ptr::null_mut(),
+                input,
+                ptr::null_mut(),
+                &mut output,
+                ptr::null_mut(),
+                ptr::null_mut(),
+            )
+        };
+
+        let token = if output.value.is_null() {
+            vec![]
+        } else {
+            let token =
+                unsafe { std::slice::from_raw_parts(output.value as *const u8, output.length) }
+                    .to_vec();
+            let mut minor = 0;
+            unsafe { gss_release_buffer(&mut minor, &mut output) };
+            token
+        };
+
+        match major {
+            GSS_S_COMPLETE => Ok((token, true)),
+            GSS_S_CONTINUE_NEEDED => Ok((token, false)),
+            _ => Err(gss_error("initiating the security context", major, minor)),
+        }
+    }
+}
+
+impl Drop for ClientContext {
+    fn drop(&mut self) {
+        let mut minor = 0;
+        unsafe {
+            if !self.context.is_null() {
+                gss_delete_sec_context(&mut minor, &mut self.context, ptr::null_mut());
+            }
+            gss_release_name(&mut minor, &mut self.target);
+        }
+    }
+}
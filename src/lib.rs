@@ -24,9 +24,15 @@
 //!
 //! # Behavior
 //!
-//! Calling a method like `Client::query` on its own does nothing. The associated request is not sent to the database
-//! until the future returned by the method is first polled. Requests are executed in the order that they are first
-//! polled, not in the order that their futures are created.
+//! A method like `Client::query` sends its request and then waits for the response. Requests from every coroutine and
+//! native thread sharing a client go through a single queue, and the connection writes them to the server, which
+//! executes them, in the order they entered it. A request is therefore executed after every request whose method
+//! returned before it was sent, whichever coroutine or thread made the calls, so statements that depend on each other
+//! only need to be issued one after the other. Requests sent concurrently, with nothing ordering the calls, may be
+//! executed in any order.
+//!
+//! A method given a query string prepares it first, which is a separate request, so requests from other coroutines
+//! may be executed between the preparation and the execution of the statement.
 //!
 //! # Pipelining
 //!
@@ -53,9 +59,17 @@
 //! In both cases, the PostgreSQL server is executing the queries sequentially - pipelining just allows both sides of
 //! the connection to work concurrently when possible.
 //!
-//! Pipelining happens automatically when futures are polled concurrently (for example, by using the futures `join`
-//! combinator):
+//! Pipelining happens automatically when requests are sent concurrently from several coroutines (for example, by using
+//! the `may::join!` macro).
+//!
+//! # GSSAPI authentication
 //!
+//! The `with-gssapi` feature adds GSSAPI (Kerberos) authentication, which a server asks for when the `pg_hba.conf`
+//! entry of the connection uses the `gss` method. It links to the GSSAPI library of MIT Kerberos, `libgssapi_krb5`,
+//! which is available on Linux and the BSDs through their Kerberos development packages (`libkrb5-dev` on Debian,
+//! `krb5-devel` on Fedora). Heimdal, the macOS GSS framework and Windows SSPI are not supported. The credentials are
+//! those of the process, as obtained with `kinit` or from a keytab, and the server's principal is
+//! `<krbsrvname>@<host>`; see `Config::krbsrvname`.
 #![doc(html_root_url = "https://docs.rs/may-postgres/0.1.0")]
 #![warn(rust_2018_idioms, clippy::all, missing_docs)]
 
@@ -68,23 +82,70 @@ macro_rules! o_try {
     };
 }
 
+/// Builds a parameter slice for methods like `Client::query` out of values of different types.
+///
+/// Each value is borrowed, so the macro can be used directly in the argument position or bound to a variable.
+///
+/// # Example
+///
+/// ```
+/// use may_postgres::params;
+///
+/// let name = String::from("steven");
+/// let params = params![1i32, "text", name, Some(2.5f64), None::<i64>];
+/// assert_eq!(params.len(), 5);
+/// ```
+///
+/// ```no_run
+/// # fn main() -> Result<(), may_postgres::Error> {
+/// use may_postgres::params;
+///
+/// let client = may_postgres::connect("host=localhost user=postgres")?;
+/// client.execute(
+///     "INSERT INTO person (name, age) VALUES ($1, $2)",
+///     params!["steven", 31i32],
+/// )?;
+/// let rows = client.query("SELECT name FROM person", params![])?;
+/// # Ok(())
+/// # }
+/// ```
+#[macro_export]
+macro_rules! params {
+    () => {
+        &[] as &[&(dyn $crate::types::ToSql + ::std::marker::Sync); 0]
+    };
+    ($($param:expr),+ $(,)?) => {
+        &[$(&$param as &(dyn $crate::types::ToSql + ::std::marker::Sync)),+]
+    };
+}
+
 pub use crate::cancel_token::CancelToken;
 pub use crate::client::Client;
 pub use crate::config::Config;
-pub use crate::copy_in::CopyInSink;
+pub use crate::connection::ConnectionStats;
+pub use crate::copy_both::{CopyBothSink, CopyBothStream};
+pub use crate::copy_in::{CopyInSink, CopyInWriter};
 pub use crate::copy_out::CopyOutStream;
+pub use crate::copy_progress::CopyProgress;
 use crate::error::DbError;
 pub use crate::error::Error;
+pub use crate::escape::{escape_identifier, escape_literal};
 pub use crate::generic_client::GenericClient;
+pub use crate::pipeline::Pipeline;
+pub use crate::pool::{Pool, PoolBuilder, PooledConnection};
 pub use crate::portal::Portal;
 pub use crate::query::RowStream;
-pub use crate::row::{Row, SimpleQueryRow};
+pub use crate::reconnect::{ReconnectingClient, RetryPolicy};
+pub use crate::row::{Row, RowRef, SimpleQueryRow};
 pub use crate::simple_query::SimpleQueryStream;
 pub use crate::statement::{Column, Statement};
+use crate::tls::MakeTlsConnect;
+pub use crate::tls::NoTls;
 pub use crate::to_statement::ToStatement;
 pub use crate::transaction::Transaction;
 pub use crate::transaction_builder::{IsolationLevel, TransactionBuilder};
 use crate::types::ToSql;
+pub use fallible_iterator;
 
 pub mod binary_copy;
 mod bind;
@@ -97,21 +158,36 @@ pub mod config;
 mod connect;
 mod connect_raw;
 mod connect_socket;
+mod connect_tls;
 // #[cfg(not(unix))]
 mod connection;
 // #[cfg(unix)]
 // #[path = "connection_unix.rs"]
 // mod connection;
+mod copy_both;
 mod copy_in;
 mod copy_out;
+mod copy_progress;
 pub mod error;
+mod escape;
 mod generic_client;
+#[cfg(feature = "with-gssapi")]
+mod gssapi;
+mod keepalive;
+pub mod notifications;
+mod pipeline;
+mod pool;
 mod portal;
 mod prepare;
 mod query;
+mod reconnect;
+pub mod replication;
 pub mod row;
 mod simple_query;
+mod socket;
 mod statement;
+mod statement_cache;
+pub mod tls;
 mod to_statement;
 mod transaction;
 mod transaction_builder;
@@ -130,6 +206,17 @@ pub fn connect(config: &str) -> Result<Client, Error> {
     config.connect()
 }
 
+/// Like `connect`, but uses the provided TLS connector to secure the connection.
+///
+/// Whether TLS is actually used is controlled by the `sslmode` option of the connection string.
+pub fn connect_tls<T>(config: &str, tls: T) -> Result<Client, Error>
+where
+    T: MakeTlsConnect<may::net::TcpStream>,
+{
+    let config = config.parse::<Config>()?;
+    config.connect_tls(tls)
+}
+
 /// An asynchronous notification.
 #[derive(Clone, Debug)]
 pub struct Notification {
@@ -0,0 +1,89 @@
+//! Asynchronous notifications.
+
+use crate::Notification;
+use may::sync::{mpsc, MutexGuard};
+use std::time::Duration;
+
+/// Notifications from a PostgreSQL backend.
+///
+/// Notifications are buffered by the connection as they arrive, whether or not anyone is currently reading them.
+pub struct Notifications<'a> {
+    receiver: MutexGuard<'a, mpsc::Receiver<Notification>>,
+}
+
+impl<'a> Notifications<'a> {
+    pub(crate) fn new(receiver: MutexGuard<'a, mpsc::Receiver<Notification>>) -> Notifications<'a> {
+        Notifications { receiver }
+    }
+
+    /// Returns a nonblocking iterator over notifications.
+    ///
+    /// If there are no already buffered pending notifications, this iterator will terminate immediately.
+    pub fn iter(&mut self) -> Iter<'_> {
+        Iter {
+            receiver: &self.receiver,
+        }
+    }
+
+    /// Returns a blocking iterator over notifications.
+    ///
+    /// If there are no already buffered pending notifications, this iterator will block indefinitely waiting on the
+    /// PostgreSQL backend server to send one. It will only terminate when the underlying connection is closed.
+    pub fn blocking_iter(&mut self) -> BlockingIter<'_> {
+        BlockingIter {
+            receiver: &self.receiver,
+        }
+    }
+
+    /// Returns an iterator over notifications which blocks a limited amount of time.
+    ///
+    /// If there are no already buffered pending notifications, this iterator will block waiting on the PostgreSQL
+    /// backend server to send one up to the provided timeout. It will terminate when the timeout expires or the
+    /// underlying connection is closed.
+    pub fn timeout_iter(&mut self, timeout: Duration) -> TimeoutIter<'_> {
+        TimeoutIter {
+            receiver: &self.receiver,
+            timeout,
+        }
+    }
+}
+
+/// A nonblocking iterator over pending notifications.
+pub struct Iter<'a> {
+    receiver: &'a mpsc::Receiver<Notification>,
+}
+
+impl Iterator for Iter<'_> {
+    type Item = Notification;
+
+    fn next(&mut self) -> Option<Notification> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+/// A blocking iterator over pending notifications.
+pub struct BlockingIter<'a> {
+    receiver: &'a mpsc::Receiver<Notification>,
+}
+
+impl Iterator for BlockingIter<'_> {
+    type Item = Notification;
+
+    fn next(&mut self) -> Option<Notification> {
+        self.receiver.recv().ok()
+    }
+}
+
+/// A time-limited blocking iterator over pending notifications.
+pub struct TimeoutIter<'a> {
+    receiver: &'a mpsc::Receiver<Notification>,
+    timeout: Duration,
+}
+
+impl Iterator for TimeoutIter<'_> {
+    type Item = Notification;
+
+    fn next(&mut self) -> Option<Notification> {
+        self.receiver.recv_timeout(self.timeout).ok()
+    }
+}
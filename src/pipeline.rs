@@ -0,0 +1,154 @@
+use crate::client::Responses;
+use crate::codec::FrontendMessage;
+use crate::connection::RequestMessages;
+use crate::types::ToSql;
+use crate::{query, slice_iter, Client, Error, Row, Statement, ToStatement};
+use bytes::BytesMut;
+use postgres_protocol::message::backend::Message;
+use postgres_protocol::message::frontend;
+
+/// A batch of statements sent to the server in a single request.
+///
+/// Every pushed statement is encoded as a `Bind`/`Execute` pair and the whole batch is written at once, followed by a
+/// single `Sync`, so the batch costs one network round trip no matter how many statements it holds. The results come
+/// back in the order the statements were pushed.
+///
+/// Since there is only one `Sync`, the server runs the statements in a single implicit transaction unless the batch
+/// is already inside an explicit one. When a statement fails, the server skips the rest of the batch and the effects
+/// of the statements before it are rolled back along with it. The failing statement reports its error and the
+/// statements after it report an error for which `Error::is_skipped` returns true.
+pub struct Pipeline<'a> {
+    client: &'a Client,
+    statements: Vec<Statement>,
+    buf: BytesMut,
+}
+
+impl<'a> Pipeline<'a> {
+    pub(crate) fn new(client: &'a Client) -> Pipeline<'a> {
+        Pipeline {
+            client,
+            statements: vec![],
+            buf: BytesMut::new(),
+        }
+    }
+
+    /// Adds a statement to the batch.
+    ///
+    /// The `statement` argument can either be a `Statement`, or a raw query string. A raw query string is prepared
+    /// right away, which costs a round trip of its own, so statements should be prepared up front to get the full
+    /// benefit of the batch.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of parameters provided does not match the number expected.
+    pub fn push<T>(&mut self, statement: &T, params: &[&(dyn ToSql + Sync)]) -> Result<(), Error>
+    where
+        T: ?Sized + ToStatement,
+    {
+        let statement = statement.__convert().into_statement(self.client)?;
+
+        let len = self.buf.len();
+        let r = query::encode_bind(&statement, slice_iter(params), "", &mut self.buf)
+            .and_then(|()| frontend::execute("", 0, &mut self.buf).map_err(Error::encode));
+        if let Err(e) = r {
+            // leave out the partially encoded messages
+            self.buf.truncate(len);
+            return Err(e);
+        }
+
+        self.statements.push(statement);
+        Ok(())
+    }
+
+    /// Returns the number of statements in the batch.
+    pub fn len(&self) -> usize {
+        self.statements.len()
+    }
+
+    /// Determines if the batch holds no statements.
+    pub fn is_empty(&self) -> bool {
+        self.statements.is_empty()
+    }
+
+    /// Sends the batch, returning the number of rows modified by each statement.
+    ///
+    /// If a statement does not modify any rows (e.g. `SELECT`), 0 is returned for it. The outer error is only returned
+    /// if the request as a whole failed, for instance because the connection was closed.
+    pub fn execute(self) -> Result<Vec<Result<u64, Error>>, Error> {
+        let results = self.run(false)?;
+        Ok(results.into_iter().map(|r| r.map(|c| c.count)).collect())
+    }
+
+    /// Sends the batch, returning the rows produced by each statement.
+    ///
+    /// The outer error is only returned if the request as a whole failed, for instance because the connection was
+    /// closed.
+    pub fn query(self) -> Result<Vec<Result<Vec<Row>, Error>>, Error> {
+        let results = self.run(true)?;
+        Ok(results.into_iter().map(|r| r.map(|c| c.rows)).collect())
+    }
+
+    fn run(mut self, keep_rows: bool) -> Result<Vec<Result<Completion, Error>>, Error> {
+        if self.statements.is_empty() {
+            return Ok(vec![]);
+        }
+
+        frontend::sync(&mut self.buf);
+        let buf = self.buf.freeze();
+        let mut responses = self
+            .client
+            .inner()
+            .send(RequestMessages::Single(FrontendMessage::Raw(buf)))?;
+
+        let mut results = Vec::with_capacity(self.statements.len());
+        let mut statements = self.statements.into_iter();
+        for statement in &mut statements {
+            match read_result(&mut responses, &statement, keep_rows) {
+                Ok(result) => results.push(Ok(result)),
+                // the server skips everything up to the sync after an error
+                Err(e) if e.code().is_some() => {
+                    results.push(Err(e));
+                    break;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        results.extend(statements.map(|_| Err(Error::skipped())));
+
+        Ok(results)
+    }
+}
+
+/// the outcome of a single statement of the batch
+struct Completion {
+    rows: Vec<Row>,
+    count: u64,
+}
+
+fn read_result(
+    responses: &mut Responses,
+    statement: &Statement,
+    keep_rows: bool,
+) -> Result<Completion, Error> {
+    let mut rows = vec![];
+    let mut read = 0;
+    loop {
+        match responses.next()? {
+            Message::BindComplete => {}
+            Message::DataRow(body) => {
+                read += 1;
+                if keep_rows {
+                    rows.push(Row::new(statement.clone(), body, None)?);
+                }
+            }
+            Message::CommandComplete(body) => {
+                let count = query::extract_row_affected(&body)?;
+                return Ok(Completion { rows, count });
+            }
+            Message::EmptyQueryResponse => return Ok(Completion { rows, count: 0 }),
+            // a portal suspended at a row limit has no command tag, so the rows read are counted instead
+            Message::PortalSuspended => return Ok(Completion { rows, count: read }),
+            _ => return Err(Error::unexpected_message()),
+        }
+    }
+}
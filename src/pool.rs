@@ -0,0 +1,283 @@
+use crate::tls::MakeTlsConnect;
+use crate::{Client, Config, Error, NoTls};
+use log::info;
+use may::net::TcpStream;
+use may::sync::{Condvar, Mutex};
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// opens a connection, taking at most the given time if there is a limit
+type Connector = Box<dyn Fn(Option<Duration>) -> Result<Client, Error> + Send + Sync>;
+
+/// A builder for connection pools.
+pub struct PoolBuilder {
+    max_size: usize,
+    connection_timeout: Option<Duration>,
+    validation_timeout: Duration,
+    eager: bool,
+}
+
+impl PoolBuilder {
+    /// Sets the maximum number of connections the pool holds, including the ones in use.
+    ///
+    /// Defaults to 10.
+    pub fn max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size.max(1);
+        self
+    }
+
+    /// Sets how long `Pool::get` may take to hand out a connection.
+    ///
+    /// Once it elapses while waiting for a connection to be returned, `Pool::get` returns an error for which
+    /// `Error::is_timeout` returns `true`. Checking an idle connection and opening a new one are limited to the time
+    /// left as well, on top of the validation timeout and `Config::connect_timeout`, and opening one which runs out of
+    /// it returns the error of the attempt. By default it waits indefinitely.
+    pub fn connection_timeout(mut self, connection_timeout: Duration) -> Self {
+        self.connection_timeout = Some(connection_timeout);
+        self
+    }
+
+    /// Sets how long `Pool::get` waits for the server to answer the check of an idle connection.
+    ///
+    /// A connection which doesn't answer in time is discarded like a closed one. Defaults to 5 seconds.
+    pub fn validation_timeout(mut self, validation_timeout: Duration) -> Self {
+        self.validation_timeout = validation_timeout;
+        self
+    }
+
+    /// Controls whether all connections are opened when the pool is built.
+    ///
+    /// Otherwise they are opened as `Pool::get` needs them. Defaults to `false`.
+    pub fn eager(mut self, eager: bool) -> Self {
+        self.eager = eager;
+        self
+    }
+
+    /// Builds the pool.
+    ///
+    /// If the pool is eager, an error is returned if any of the connections can't be opened.
+    pub fn build(self, config: Config) -> Result<Pool, Error> {
+        self.build_tls(config, NoTls)
+    }
+
+    /// Like `build`, but uses the provided TLS connector to secure each connection according to `sslmode`.
+    pub fn build_tls<T>(self, config: Config, tls: T) -> Result<Pool, Error>
+    where
+        T: MakeTlsConnect<TcpStream> + Clone + Send + Sync + 'static,
+    {
+        let trace_protocol = config.trace_protocol;
+        let connector: Connector = Box::new(move |limit| match limit {
+            Some(limit) => {
+                let mut config = config.clone();
+                let timeout = config.connect_timeout.map_or(limit, |t| t.min(limit));
+                config.connect_timeout(timeout).connect_tls(tls.clone())
+            }
+            None => config.connect_tls(tls.clone()),
+        });
+
+        let mut idle = vec![];
+        if self.eager {
+            for _ in 0..self.max_size {
+                idle.push(connector(None)?);
+            }
+        }
+
+        Ok(Pool {
+            inner: Arc::new(PoolInner {
+                connector,
+                max_size: self.max_size,
+                connection_timeout: self.connection_timeout,
+                validation_timeout: self.validation_timeout,
+                trace_protocol,
+                state: Mutex::new(State {
+                    size: idle.len(),
+                    idle,
+                }),
+                available: Condvar::new(),
+            }),
+        })
+    }
+}
+
+struct State {
+    // idle connections, most recently returned last
+    idle: Vec<Client>,
+    // number of connections, both idle and in use, plus the ones being opened
+    size: usize,
+}
+
+struct PoolInner {
+    connector: Connector,
+    max_size: usize,
+    connection_timeout: Option<Duration>,
+    validation_timeout: Duration,
+    // the configured setting, restored when a connection is returned
+    trace_protocol: bool,
+    state: Mutex<State>,
+    available: Condvar,
+}
+
+impl PoolInner {
+    // gives up the slot of a connection which was closed or failed to open
+    fn release(&self) {
+        self.state.lock().unwrap().size -= 1;
+        self.available.notify_one();
+    }
+}
+
+/// A pool of connections to the database.
+///
+/// Connections are handed out as `PooledConnection` guards, which return them to the pool when dropped. An idle
+/// connection is checked with an empty query before it is handed out again, and discarded if its connection has
+/// closed in the meantime or the server doesn't answer within the validation timeout. Cloning a pool creates a new
+/// handle to the same connections.
+#[derive(Clone)]
+pub struct Pool {
+    inner: Arc<PoolInner>,
+}
+
+impl Pool {
+    /// Returns a builder for a pool.
+    pub fn builder() -> PoolBuilder {
+        PoolBuilder {
+            max_size: 10,
+            connection_timeout: None,
+            validation_timeout: Duration::from_secs(5),
+            eager: false,
+        }
+    }
+
+    /// Creates a lazily connecting pool with the default settings.
+    pub fn new(config: Config) -> Result<Pool, Error> {
+        Pool::builder().build(config)
+    }
+
+    /// Retrieves a connection from the pool.
+    ///
+    /// An idle connection is reused if there is one, otherwise a new connection is opened as long as the pool is
+    /// below its maximum size. If neither is possible, this waits for another connection to be returned.
+    pub fn get(&self) -> Result<PooledConnection, Error> {
+        let deadline = self
+            .inner
+            .connection_timeout
+            .map(|timeout| Instant::now() + timeout);
+
+        // the time left before the deadline, which is zero once it has passed
+        let remaining =
+            || deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+
+        let mut state = self.inner.state.lock().unwrap();
+        loop {
+            if let Some(client) = state.idle.pop() {
+                drop(state);
+                let timeout = remaining().map_or(self.inner.validation_timeout, |remaining| {
+                    remaining.min(self.inner.validation_timeout)
+                });
+                if client.is_valid(timeout) {
+                    return Ok(self.guard(client));
+                }
+                info!("discarding a broken pooled connection");
+                drop(client);
+                self.inner.release();
+                state = self.inner.state.lock().unwrap();
+                continue;
+            }
+
+            if state.size < self.inner.max_size {
+                state.size += 1;
+                drop(state);
+                return match (self.inner.connector)(remaining()) {
+                    Ok(client) => Ok(self.guard(client)),
+                    Err(e) => {
+                        self.inner.release();
+                        Err(e)
+                    }
+                };
+            }
+
+            state = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(Error::timeout());
+                    }
+                    self.inner
+                        .available
+                        .wait_timeout(state, deadline - now)
+                        .unwrap()
+                        .0
+                }
+                None => self.inner.available.wait(state).unwrap(),
+            };
+        }
+    }
+
+    /// Returns the number of connections, both idle and in use.
+    pub fn size(&self) -> usize {
+        self.inner.state.lock().unwrap().size
+    }
+
+    /// Returns the number of idle connections.
+    pub fn idle(&self) -> usize {
+        self.inner.state.lock().unwrap().idle.len()
+    }
+
+    fn guard(&self, client: Client) -> PooledConnection {
+        PooledConnection {
+            pool: self.inner.clone(),
+            client: Some(client),
+        }
+    }
+}
+
+/// A connection borrowed from a `Pool`.
+///
+/// It dereferences to a `Client`, and is returned to the pool when dropped unless its connection has closed. On its
+/// return, the notice callback and protocol tracing set through it are reset, so the next borrower doesn't inherit
+/// them. The session itself is kept, including the statements cached by `Client::prepare_cached` and state set with
+/// SQL, such as `SET`.
+///
+/// A clone of the `Client`, made through the dereference, shares the connection. If one is still alive when the
+/// guard is dropped, the connection is not returned: the pool gives up its slot and leaves the connection to the
+/// clones, which close it once the last of them is dropped.
+pub struct PooledConnection {
+    pool: Arc<PoolInner>,
+    client: Option<Client>,
+}
+
+impl Deref for PooledConnection {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        self.client.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledConnection {
+    fn deref_mut(&mut self) -> &mut Client {
+        self.client.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledConnection {
+    fn drop(&mut self) {
+        let client = self.client.take().unwrap();
+        if client.is_closed() {
+            drop(client);
+            self.pool.release();
+            return;
+        }
+        // handing out a connection that a clone can still use would share it between two borrowers
+        if Arc::strong_count(client.inner()) > 1 {
+            info!("not returning a pooled connection which is still shared by a clone");
+            drop(client);
+            self.pool.release();
+            return;
+        }
+
+        client.reset(self.pool.trace_protocol);
+        self.pool.state.lock().unwrap().idle.push(client);
+        self.pool.available.notify_one();
+    }
+}
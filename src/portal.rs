@@ -1,18 +1,25 @@
 use crate::client::InnerClient;
 use crate::codec::FrontendMessage;
 use crate::connection::RequestMessages;
-use crate::Statement;
+use crate::{Error, Statement};
+use postgres_protocol::message::backend::Message;
 use postgres_protocol::message::frontend;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Weak};
 
 struct Inner {
     client: Weak<InnerClient>,
     name: String,
     statement: Statement,
+    exhausted: AtomicBool,
+    closed: AtomicBool,
 }
 
 impl Drop for Inner {
     fn drop(&mut self) {
+        if self.closed.load(Ordering::Relaxed) {
+            return;
+        }
         if let Some(client) = self.client.upgrade() {
             let buf = client.with_buf(|buf| {
                 frontend::close(b'P', &self.name, buf).unwrap();
@@ -37,6 +44,8 @@ impl Portal {
             client: Arc::downgrade(client),
             name,
             statement,
+            exhausted: AtomicBool::new(false),
+            closed: AtomicBool::new(false),
         }))
     }
 
@@ -47,4 +56,37 @@ impl Portal {
     pub(crate) fn statement(&self) -> &Statement {
         &self.0.statement
     }
+
+    pub(crate) fn set_exhausted(&self) {
+        self.0.exhausted.store(true, Ordering::Relaxed);
+    }
+
+    /// Determines if all rows of the portal have been returned.
+    ///
+    /// A call to `Transaction::query_portal` which stops at its row limit leaves the portal suspended, even if no rows
+    /// remain after it; only a call which reads the end of the rows marks the portal exhausted.
+    pub fn is_exhausted(&self) -> bool {
+        self.0.exhausted.load(Ordering::Relaxed)
+    }
+
+    /// Closes the portal, releasing its resources on the server.
+    ///
+    /// Portals are otherwise closed once the last clone is dropped, without waiting for the server to confirm it.
+    /// Clones of a closed portal can no longer be executed.
+    pub fn close(self) -> Result<(), Error> {
+        let client = self.0.client.upgrade().ok_or_else(Error::closed)?;
+        self.0.closed.store(true, Ordering::Relaxed);
+
+        let buf = client.with_buf(|buf| {
+            frontend::close(b'P', &self.0.name, buf).map_err(Error::encode)?;
+            frontend::sync(buf);
+            Ok(buf.split().freeze())
+        })?;
+        let mut responses = client.send(RequestMessages::Single(FrontendMessage::Raw(buf)))?;
+
+        match responses.next()? {
+            Message::CloseComplete => Ok(()),
+            _ => Err(Error::unexpected_message()),
+        }
+    }
 }
@@ -7,7 +7,7 @@ use crate::{query, slice_iter};
 use crate::{Column, Error, Statement};
 use bytes::Bytes;
 use fallible_iterator::FallibleIterator;
-use postgres_protocol::message::backend::Message;
+use postgres_protocol::message::backend::{Message, RowDescriptionBody};
 use postgres_protocol::message::frontend;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
@@ -55,9 +55,19 @@ ORDER BY attnum
 static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
 
 pub fn prepare(client: &Arc<InnerClient>, query: &str, types: &[Type]) -> Result<Statement, Error> {
-    let _g = client.sender.read_lock();
     let name = format!("s{}", NEXT_ID.fetch_add(1, Ordering::SeqCst));
-    let buf = encode(client, &name, query, types)?;
+    let (parameters, columns) = describe(client, &name, query, types)?;
+    Ok(Statement::new(client, name, parameters, columns))
+}
+
+fn describe(
+    client: &Arc<InnerClient>,
+    name: &str,
+    query: &str,
+    types: &[Type],
+) -> Result<(Vec<Type>, Vec<Column>), Error> {
+    let _g = client.sender.read_lock();
+    let buf = encode(client, name, query, types)?;
     let mut responses = client.send(RequestMessages::Single(FrontendMessage::Raw(buf)))?;
     drop(_g);
 
@@ -84,22 +94,38 @@ pub fn prepare(client: &Arc<InnerClient>, query: &str, types: &[Type]) -> Result
         parameters.push(type_);
     }
 
+    let columns = match row_description {
+        Some(row_description) => columns(client, &row_description)?,
+        None => vec![],
+    };
+
+    Ok((parameters, columns))
+}
+
+/// Looks up the columns of a row description, along with their types.
+pub fn columns(
+    client: &Arc<InnerClient>,
+    row_description: &RowDescriptionBody,
+) -> Result<Vec<Column>, Error> {
     let mut columns = vec![];
-    if let Some(row_description) = row_description {
-        let mut it = row_description.fields();
-        while let Some(field) = it.next().map_err(Error::parse)? {
-            let type_ = get_type(client, field.type_oid())?;
-            let column = Column::new(field.name().to_string(), type_);
-            columns.push(column);
-        }
+    let mut it = row_description.fields();
+    while let Some(field) = it.next().map_err(Error::parse)? {
+        let type_ = get_type(client, field.type_oid())?;
+        let column = Column::new(
+            field.name().to_string(),
+            type_,
+            Some(field.table_oid()).filter(|&oid| oid != 0),
+            Some(field.column_id()).filter(|&id| id != 0),
+            field.type_modifier(),
+        );
+        columns.push(column);
     }
-
-    Ok(Statement::new(client, name, parameters, columns))
+    Ok(columns)
 }
 
 fn encode(client: &InnerClient, name: &str, query: &str, types: &[Type]) -> Result<Bytes, Error> {
     client.with_buf(|buf| {
-        frontend::parse(name, query, types.iter().map(Type::oid), buf).map_err(Error::encode)?;
+        query::encode_parse(name, query, types.iter().map(Type::oid), buf)?;
         frontend::describe(b'S', name, buf).map_err(Error::encode)?;
         frontend::sync(buf);
         Ok(buf.split().freeze())
@@ -119,7 +145,7 @@ fn get_type(client: &Arc<InnerClient>, oid: Oid) -> Result<Type, Error> {
 
     let mut rows = query::query(client, stmt, slice_iter(&[&oid]))?;
 
-    let row = match rows.next().transpose()? {
+    let row = match FallibleIterator::next(&mut rows)? {
         Some(row) => row,
         None => return Err(Error::unexpected_message()),
     };
@@ -179,9 +205,8 @@ fn typeinfo_statement(client: &Arc<InnerClient>) -> Result<Statement, Error> {
 fn get_enum_variants(client: &Arc<InnerClient>, oid: Oid) -> Result<Vec<String>, Error> {
     let stmt = typeinfo_enum_statement(client)?;
 
-    query::query(client, stmt, slice_iter(&[&oid]))?
-        .map(|row| row.and_then(|r| r.try_get(0)))
-        .collect()
+    let rows = query::query(client, stmt, slice_iter(&[&oid]))?;
+    FallibleIterator::map(rows, |row| row.try_get(0)).collect()
 }
 
 fn typeinfo_enum_statement(client: &Arc<InnerClient>) -> Result<Statement, Error> {
@@ -204,7 +229,8 @@ fn typeinfo_enum_statement(client: &Arc<InnerClient>) -> Result<Statement, Error
 fn get_composite_fields(client: &Arc<InnerClient>, oid: Oid) -> Result<Vec<Field>, Error> {
     let stmt = typeinfo_composite_statement(client)?;
 
-    let rows = query::query(client, stmt, slice_iter(&[&oid]))?.collect::<Result<Vec<_>, _>>()?;
+    let rows = query::query(client, stmt, slice_iter(&[&oid]))?;
+    let rows = FallibleIterator::collect::<Vec<_>>(rows)?;
 
     let mut fields = vec![];
     for row in rows {
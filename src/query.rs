@@ -1,11 +1,18 @@
-use crate::client::{InnerClient, Responses};
+use crate::client::{task_id, InnerClient, Responses};
 use crate::codec::FrontendMessage;
-use crate::connection::RequestMessages;
-use crate::types::{IsNull, ToSql};
-use crate::{Error, Portal, Row, Statement};
-use bytes::{Bytes, BytesMut};
-use postgres_protocol::message::backend::Message;
+use crate::connection::{MessageStream, RequestMessages};
+use crate::types::{Format, IsNull, Kind, Oid, ToSql, Type, WrongType};
+use crate::{prepare, Error, Portal, Row, RowRef, Statement};
+use bytes::{BufMut, Bytes, BytesMut};
+use fallible_iterator::FallibleIterator;
+use may::sync::mpsc;
+use postgres_protocol::message::backend::{CommandCompleteBody, Message};
 use postgres_protocol::message::frontend;
+use std::convert::TryFrom;
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 
 pub fn query<'a, I>(
     client: &InnerClient,
@@ -21,6 +28,238 @@ where
     Ok(RowStream {
         statement,
         responses,
+        fetch: None,
+        portal: None,
+        formats: None,
+    })
+}
+
+/// Like `query`, but the response is waited for at most `timeout`.
+pub fn query_timeout<'a, I>(
+    client: &InnerClient,
+    statement: Statement,
+    params: I,
+    timeout: Duration,
+) -> Result<RowStream, Error>
+where
+    I: IntoIterator<Item = &'a dyn ToSql>,
+    I::IntoIter: ExactSizeIterator,
+{
+    let buf = encode(client, &statement, params)?;
+    let responses =
+        client.send_timeout(RequestMessages::Single(FrontendMessage::Raw(buf)), timeout)?;
+    Ok(RowStream {
+        statement,
+        responses,
+        fetch: None,
+        portal: None,
+        formats: None,
+    })
+}
+
+/// Like `query`, but asks for the results in the given formats.
+///
+/// `formats` holds either one format for every column, or a single format used for all of them.
+pub fn query_with_formats<'a, I>(
+    client: &InnerClient,
+    statement: Statement,
+    params: I,
+    formats: &[Format],
+) -> Result<RowStream, Error>
+where
+    I: IntoIterator<Item = &'a dyn ToSql>,
+    I::IntoIter: ExactSizeIterator,
+{
+    let columns = statement.columns().len();
+    assert!(
+        formats.len() == 1 || formats.len() == columns,
+        "expected 1 or {} result formats but got {}",
+        columns,
+        formats.len()
+    );
+    let formats: Arc<[Format]> = if formats.len() == columns {
+        formats.into()
+    } else {
+        vec![formats[0]; columns].into()
+    };
+
+    let buf = client.with_buf(|buf| {
+        encode_bind_with_formats(&statement, params, "", &formats, buf)?;
+        frontend::execute("", 0, buf).map_err(Error::encode)?;
+        frontend::sync(buf);
+        Ok(buf.split().freeze())
+    })?;
+
+    let responses = start(client, buf)?;
+    Ok(RowStream {
+        statement,
+        responses,
+        fetch: None,
+        portal: None,
+        formats: Some(formats),
+    })
+}
+
+/// Like `query`, but parses `query` into the unnamed statement with the parameter types `types`, and binds, describes
+/// and executes it in the same request, so there is a single round trip and no named statement to close.
+pub fn query_unnamed<'a, I>(
+    client: &Arc<InnerClient>,
+    query: &str,
+    types: &[Type],
+    params: I,
+) -> Result<RowStream, Error>
+where
+    I: IntoIterator<Item = &'a dyn ToSql>,
+    I::IntoIter: ExactSizeIterator,
+{
+    let buf = client.with_buf(|buf| {
+        encode_parse("", query, types.iter().map(Type::oid), buf)?;
+        encode_bind(&Statement::unnamed(types.to_vec(), vec![]), params, "", buf)?;
+        frontend::describe(b'P', "", buf).map_err(Error::encode)?;
+        frontend::execute("", 0, buf).map_err(Error::encode)?;
+        frontend::sync(buf);
+        Ok(buf.split().freeze())
+    })?;
+
+    let mut responses = {
+        let _g = client.sender.read_lock();
+        start(client, buf)?
+    };
+    match responses.next()? {
+        Message::ParseComplete => {}
+        _ => return Err(Error::unexpected_message()),
+    }
+    match responses.next()? {
+        Message::BindComplete => {}
+        _ => return Err(Error::unexpected_message()),
+    }
+    let columns = match responses.next()? {
+        Message::RowDescription(body) => prepare::columns(client, &body)?,
+        Message::NoData => vec![],
+        _ => return Err(Error::unexpected_message()),
+    };
+
+    Ok(RowStream {
+        statement: Statement::unnamed(types.to_vec(), columns),
+        responses,
+        fetch: None,
+        portal: None,
+        formats: None,
+    })
+}
+
+/// the follow-up messages of a query that is fetched in chunks through the unnamed portal
+pub struct PortalReceiver {
+    receiver: mpsc::Receiver<FrontendMessage>,
+}
+
+impl MessageStream for PortalReceiver {
+    fn try_recv(&mut self) -> Result<Option<FrontendMessage>, ()> {
+        use std::sync::mpsc::TryRecvError;
+        match self.receiver.try_recv() {
+            Ok(message) => Ok(Some(message)),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(_) => Err(()),
+        }
+    }
+
+    fn recv(&mut self) -> Result<Option<FrontendMessage>, ()> {
+        self.receiver.recv().map(Some).map_err(|_| ())
+    }
+}
+
+struct Fetch {
+    sender: mpsc::Sender<FrontendMessage>,
+    fetch_size: i32,
+    // the client's record of the task reading the stream, cleared once the portal is released
+    holder: Arc<AtomicUsize>,
+}
+
+impl Drop for Fetch {
+    fn drop(&mut self) {
+        self.holder.store(0, Ordering::Release);
+    }
+}
+
+impl Fetch {
+    // the stream may have been handed to another coroutine since it was last read
+    fn hold(&self) {
+        self.holder.store(task_id(), Ordering::Release);
+    }
+
+    fn send(&self, f: impl FnOnce(&mut BytesMut)) {
+        let mut buf = BytesMut::new();
+        f(&mut buf);
+        // a closed connection is reported by the response stream
+        self.sender.send(FrontendMessage::Raw(buf.freeze())).ok();
+    }
+
+    fn execute(&self) {
+        let fetch_size = self.fetch_size;
+        self.send(|buf| {
+            frontend::execute("", fetch_size, buf).unwrap();
+            flush(buf);
+        });
+    }
+
+    fn sync(self) {
+        self.send(frontend::sync);
+    }
+
+    fn close(self) {
+        self.send(|buf| {
+            frontend::close(b'P', "", buf).unwrap();
+            frontend::sync(buf);
+        });
+    }
+}
+
+// postgres-protocol has no encoder for the `Flush` message
+fn flush(buf: &mut BytesMut) {
+    buf.put_u8(b'H');
+    buf.put_i32(4);
+}
+
+/// Like `query`, but only asks the server for `fetch_size` rows at a time.
+///
+/// The rows are read through the unnamed portal with `Execute` messages that are followed by `Flush` rather than
+/// `Sync`, so the portal stays open between chunks. The connection writes nothing else until the stream completes or
+/// is dropped, and a request from the task reading the stream fails rather than waiting for it.
+pub fn query_chunked<'a, I>(
+    client: &InnerClient,
+    statement: Statement,
+    params: I,
+    fetch_size: i32,
+) -> Result<RowStream, Error>
+where
+    I: IntoIterator<Item = &'a dyn ToSql>,
+    I::IntoIter: ExactSizeIterator,
+{
+    let buf = client.with_buf(|buf| {
+        encode_bind(&statement, params, "", buf)?;
+        frontend::execute("", fetch_size, buf).map_err(Error::encode)?;
+        flush(buf);
+        Ok(buf.split().freeze())
+    })?;
+
+    let (sender, receiver) = mpsc::channel();
+    sender
+        .send(FrontendMessage::Raw(buf))
+        .map_err(|_| Error::closed())?;
+    let responses = client.send(RequestMessages::Portal(PortalReceiver { receiver }))?;
+    let fetch = Fetch {
+        sender,
+        fetch_size,
+        holder: client.stream_holder.clone(),
+    };
+    fetch.hold();
+
+    Ok(RowStream {
+        statement,
+        responses,
+        fetch: Some(fetch),
+        portal: None,
+        formats: None,
     })
 }
 
@@ -40,6 +279,9 @@ pub fn query_portal(
     Ok(RowStream {
         statement: portal.statement().clone(),
         responses,
+        fetch: None,
+        portal: Some(portal.clone()),
+        formats: None,
     })
 }
 
@@ -49,29 +291,117 @@ where
     I::IntoIter: ExactSizeIterator,
 {
     let buf = encode(client, &statement, params)?;
-    let mut responses = start(client, buf)?;
+    match complete(start(client, buf)?)? {
+        Some(body) => extract_row_affected(&body),
+        None => Ok(0),
+    }
+}
+
+/// Like `execute`, but the response is waited for at most `timeout`.
+pub fn execute_timeout<'a, I>(
+    client: &InnerClient,
+    statement: Statement,
+    params: I,
+    timeout: Duration,
+) -> Result<u64, Error>
+where
+    I: IntoIterator<Item = &'a dyn ToSql>,
+    I::IntoIter: ExactSizeIterator,
+{
+    let buf = encode(client, &statement, params)?;
+    let responses =
+        client.send_timeout(RequestMessages::Single(FrontendMessage::Raw(buf)), timeout)?;
+    match complete(responses)? {
+        Some(body) => extract_row_affected(&body),
+        None => Ok(0),
+    }
+}
+
+/// Like `execute`, but returns the whole `CommandComplete` tag, or an empty string for an empty query.
+pub fn execute_with_tag<'a, I>(
+    client: &InnerClient,
+    statement: Statement,
+    params: I,
+) -> Result<String, Error>
+where
+    I: IntoIterator<Item = &'a dyn ToSql>,
+    I::IntoIter: ExactSizeIterator,
+{
+    let buf = encode(client, &statement, params)?;
+    match complete(start(client, buf)?)? {
+        Some(body) => Ok(body.tag().map_err(Error::parse)?.to_string()),
+        None => Ok(String::new()),
+    }
+}
 
+// reads the response of an execution, skipping its rows, and returns how it completed or `None` for an empty query
+fn complete(mut responses: Responses) -> Result<Option<CommandCompleteBody>, Error> {
     loop {
         match responses.next()? {
             Message::BindComplete => continue,
             Message::DataRow(_) => {}
-            Message::CommandComplete(body) => {
-                let rows = body
-                    .tag()
-                    .map_err(Error::parse)?
-                    .rsplit(' ')
-                    .next()
-                    .unwrap()
-                    .parse()
-                    .unwrap_or(0);
-                return Ok(rows);
+            Message::CommandComplete(body) => return Ok(Some(body)),
+            // a portal suspended at a row limit ends without a command tag, like an empty query
+            Message::EmptyQueryResponse | Message::PortalSuspended => return Ok(None),
+            _ => return Err(Error::unexpected_message()),
+        }
+    }
+}
+
+/// Executes the statement, passing each row to `f` as a view into the message it was received in, and returns the
+/// number of rows.
+pub fn query_each<'a, I, F>(
+    client: &InnerClient,
+    statement: Statement,
+    params: I,
+    mut f: F,
+) -> Result<u64, Error>
+where
+    I: IntoIterator<Item = &'a dyn ToSql>,
+    I::IntoIter: ExactSizeIterator,
+    F: FnMut(RowRef<'_>) -> Result<(), Error>,
+{
+    let buf = encode(client, &statement, params)?;
+    let mut responses = {
+        // released before running the callback, which may use the client itself
+        let _g = client.sender.read_lock();
+        start(client, buf)?
+    };
+
+    // reused for every row, where a `Row` allocates its own
+    let mut ranges = vec![];
+    loop {
+        match responses.next()? {
+            Message::BindComplete => continue,
+            Message::DataRow(body) => {
+                ranges.clear();
+                let mut it = body.ranges();
+                while let Some(range) = it.next().map_err(Error::parse)? {
+                    ranges.push(range);
+                }
+                f(RowRef::new(&statement, &body, &ranges))?;
             }
-            Message::EmptyQueryResponse => return Ok(0),
+            Message::CommandComplete(body) => return extract_row_affected(&body),
+            Message::EmptyQueryResponse | Message::PortalSuspended => return Ok(0),
             _ => return Err(Error::unexpected_message()),
         }
     }
 }
 
+/// Extracts the number of rows affected from a `CommandComplete` tag.
+///
+/// The count is the last word of the tag, e.g. `UPDATE 3`, `SELECT 10`, or `INSERT 0 5` where the first number is
+/// the legacy OID field. Tags without a count, like `CREATE TABLE`, report 0.
+pub fn extract_row_affected(body: &CommandCompleteBody) -> Result<u64, Error> {
+    let tag = body.tag().map_err(Error::parse)?;
+    let rows = tag
+        .rsplit(' ')
+        .next()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(0);
+    Ok(rows)
+}
+
 fn start(client: &InnerClient, buf: Bytes) -> Result<Responses, Error> {
     client.send(RequestMessages::Single(FrontendMessage::Raw(buf)))
 }
@@ -95,12 +425,31 @@ pub fn encode_bind<'a, I>(
     portal: &str,
     buf: &mut BytesMut,
 ) -> Result<(), Error>
+where
+    I: IntoIterator<Item = &'a dyn ToSql>,
+    I::IntoIter: ExactSizeIterator,
+{
+    encode_bind_with_formats(statement, params, portal, &[Format::Binary], buf)
+}
+
+/// Like `encode_bind`, but requests the results in `formats`, which follows the protocol's rules: a single format
+/// applies to every column, otherwise there is one per column.
+pub fn encode_bind_with_formats<'a, I>(
+    statement: &Statement,
+    params: I,
+    portal: &str,
+    formats: &[Format],
+    buf: &mut BytesMut,
+) -> Result<(), Error>
 where
     I: IntoIterator<Item = &'a dyn ToSql>,
     I::IntoIter: ExactSizeIterator,
 {
     let params = params.into_iter();
 
+    if params.len() > MAX_PARAMS {
+        return Err(Error::too_many_params(params.len()));
+    }
     assert!(
         statement.params().len() == params.len(),
         "expected {} parameters but got {}",
@@ -108,52 +457,210 @@ where
         params.len()
     );
 
-    let mut error_idx = 0;
-    let r = frontend::bind(
-        portal,
-        statement.name(),
-        Some(1),
-        params.zip(statement.params()).enumerate(),
-        |(idx, (param, ty)), buf| match param.to_sql_checked(ty, buf) {
-            Ok(IsNull::No) => Ok(postgres_protocol::IsNull::No),
-            Ok(IsNull::Yes) => Ok(postgres_protocol::IsNull::Yes),
-            Err(e) => {
-                error_idx = idx;
-                Err(e)
+    buf.put_u8(b'B');
+    write_body(buf, |buf| {
+        write_cstr(portal, buf)?;
+        write_cstr(statement.name(), buf)?;
+        // every parameter is sent in the binary format
+        buf.put_u16(1);
+        buf.put_i16(Format::Binary.code());
+        buf.put_u16(params.len() as u16);
+        for (idx, (param, ty)) in params.zip(statement.params()).enumerate() {
+            let base = buf.len();
+            buf.put_i32(0);
+            let len = match to_sql_checked(param, ty, buf) {
+                Ok(IsNull::No) => to_i32(buf.len() - base - 4)?,
+                Ok(IsNull::Yes) => -1,
+                Err(e) => return Err(Error::to_sql(e, idx)),
+            };
+            buf[base..base + 4].copy_from_slice(&len.to_be_bytes());
+        }
+        buf.put_u16(formats.len() as u16);
+        for format in formats {
+            buf.put_i16(format.code());
+        }
+        Ok(())
+    })
+}
+
+// a domain is transferred like its base type, so a value which doesn't accept the domain is written as its base type,
+// keeping the error about the domain if that fails too
+fn to_sql_checked(
+    param: &dyn ToSql,
+    ty: &Type,
+    buf: &mut BytesMut,
+) -> Result<IsNull, Box<dyn std::error::Error + Sync + Send>> {
+    let base = buf.len();
+    match param.to_sql_checked(ty, buf) {
+        Err(e) if e.is::<WrongType>() => match ty.kind() {
+            Kind::Domain(inner) => {
+                buf.truncate(base);
+                to_sql_checked(param, inner, buf).map_err(|_| e)
             }
+            _ => Err(e),
         },
-        Some(1),
-        buf,
-    );
-    match r {
-        Ok(()) => Ok(()),
-        Err(frontend::BindError::Conversion(e)) => Err(Error::to_sql(e, error_idx)),
-        Err(frontend::BindError::Serialization(e)) => Err(Error::encode(e)),
+        result => result,
+    }
+}
+
+/// The most parameters a statement can take, since the protocol sends their count as a 16-bit unsigned integer.
+pub const MAX_PARAMS: usize = u16::MAX as usize;
+
+// postgres-protocol writes the parameter counts of `Parse` and `Bind` as signed 16-bit integers, which only allows
+// for half of the parameters the server accepts
+pub fn encode_parse<I>(name: &str, query: &str, types: I, buf: &mut BytesMut) -> Result<(), Error>
+where
+    I: IntoIterator<Item = Oid>,
+    I::IntoIter: ExactSizeIterator,
+{
+    let types = types.into_iter();
+    if types.len() > MAX_PARAMS {
+        return Err(Error::too_many_params(types.len()));
+    }
+
+    buf.put_u8(b'P');
+    write_body(buf, |buf| {
+        write_cstr(name, buf)?;
+        write_cstr(query, buf)?;
+        buf.put_u16(types.len() as u16);
+        for oid in types {
+            buf.put_u32(oid);
+        }
+        Ok(())
+    })
+}
+
+fn write_body<F>(buf: &mut BytesMut, f: F) -> Result<(), Error>
+where
+    F: FnOnce(&mut BytesMut) -> Result<(), Error>,
+{
+    let base = buf.len();
+    buf.put_i32(0);
+    f(buf)?;
+    let len = to_i32(buf.len() - base)?;
+    buf[base..base + 4].copy_from_slice(&len.to_be_bytes());
+    Ok(())
+}
+
+fn write_cstr(s: &str, buf: &mut BytesMut) -> Result<(), Error> {
+    if s.as_bytes().contains(&0) {
+        return Err(Error::encode(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "string contains embedded null",
+        )));
     }
+    buf.put_slice(s.as_bytes());
+    buf.put_u8(0);
+    Ok(())
+}
+
+fn to_i32(len: usize) -> Result<i32, Error> {
+    i32::try_from(len).map_err(|_| {
+        Error::encode(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "value too large to transmit",
+        ))
+    })
 }
 
-/// stream of rows returned by query
+/// A stream of rows returned by a query.
+///
+/// The rows are decoded as they are read. The connection's reader hands the response over in batches of backend
+/// messages, each holding whatever was read from the socket at once, and reading a row takes the next message from
+/// the current batch, waiting for the next batch only once it is used up. The stream ends with the response, and
+/// should not be read any further after it yields an error.
+///
+/// `RowStream` is an `Iterator` of `Result<Row, Error>`, and also a `FallibleIterator` of rows, whose adapters pass
+/// the first error through instead of yielding it as an item:
+///
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use may_postgres::fallible_iterator::FallibleIterator;
+/// use may_postgres::types::ToSql;
+///
+/// let client = may_postgres::connect("host=localhost user=postgres")?;
+/// let names = client
+///     .query_raw("SELECT name FROM person", std::iter::empty::<&dyn ToSql>())?
+///     .map_err(|e| format!("listing people failed: {}", e))
+///     .map(|row| row.try_get::<_, String>(0).map_err(|e| e.to_string()))
+///     .collect::<Vec<String>>()?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Methods both traits have, like `map` or `collect`, are ambiguous on the stream itself while `FallibleIterator` is
+/// in scope; call them as `FallibleIterator::map(stream, ...)` or `Iterator::map(stream, ...)` to pick one.
 pub struct RowStream {
     statement: Statement,
     responses: Responses,
+    fetch: Option<Fetch>,
+    // set when executing a portal, which is marked exhausted once its rows end
+    portal: Option<Portal>,
+    // the format of each column, if not all binary
+    formats: Option<Arc<[Format]>>,
+}
+
+impl Drop for RowStream {
+    fn drop(&mut self) {
+        // release the portal and the connection if the rows were not read to the end
+        if let Some(fetch) = self.fetch.take() {
+            fetch.close();
+        }
+    }
 }
 
 impl Iterator for RowStream {
     type Item = Result<Row, Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if let Some(fetch) = &self.fetch {
+            fetch.hold();
+        }
         loop {
-            match o_try!(self.responses.next()) {
+            let message = match self.responses.next() {
+                Ok(message) => message,
+                Err(e) => {
+                    // the server skips everything up to the next sync after an error
+                    if let Some(fetch) = self.fetch.take() {
+                        fetch.sync();
+                    }
+                    return Some(Err(e));
+                }
+            };
+
+            match message {
                 Message::DataRow(body) => {
-                    return Some(Ok(o_try!(Row::new(self.statement.clone(), body))))
+                    return Some(Ok(o_try!(Row::new(
+                        self.statement.clone(),
+                        body,
+                        self.formats.clone()
+                    ))))
+                }
+                Message::PortalSuspended => match &self.fetch {
+                    Some(fetch) => fetch.execute(),
+                    None => return None,
+                },
+                Message::EmptyQueryResponse | Message::CommandComplete(_) => {
+                    if let Some(fetch) = self.fetch.take() {
+                        fetch.sync();
+                    }
+                    if let Some(portal) = &self.portal {
+                        portal.set_exhausted();
+                    }
+                    return None;
                 }
-                Message::EmptyQueryResponse
-                | Message::CommandComplete(_)
-                | Message::PortalSuspended => return None,
-                Message::ErrorResponse(body) => return Some(Err(Error::db(body))),
-                Message::BindComplete => {}
+                Message::ParseComplete | Message::BindComplete => {}
                 _ => return Some(Err(Error::unexpected_message())),
             };
         }
     }
 }
+
+impl FallibleIterator for RowStream {
+    type Item = Row;
+    type Error = Error;
+
+    fn next(&mut self) -> Result<Option<Row>, Error> {
+        Iterator::next(self).transpose()
+    }
+}
@@ -0,0 +1,169 @@
+use crate::tls::MakeTlsConnect;
+use crate::types::ToSql;
+use crate::{Client, Config, Error, NoTls, Row, ToStatement};
+use log::info;
+use may::net::TcpStream;
+use may::sync::Mutex;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Controls how often and how quickly a `ReconnectingClient` dials the server again.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    base_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Creates a new policy.
+    ///
+    /// Up to `max_attempts` connection attempts are made before giving up, waiting `base_delay` after the first failed
+    /// attempt and doubling the wait after each further one.
+    pub fn new(max_attempts: u32, base_delay: Duration) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: max_attempts.max(1),
+            base_delay,
+        }
+    }
+
+    /// Returns the maximum number of connection attempts.
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// Returns the wait after the first failed connection attempt.
+    pub fn base_delay(&self) -> Duration {
+        self.base_delay
+    }
+
+    fn delay(&self, attempt: u32) -> Duration {
+        self.base_delay
+            .checked_mul(1 << attempt.min(16))
+            .unwrap_or(Duration::MAX)
+    }
+}
+
+impl Default for RetryPolicy {
+    /// 5 attempts, starting with a 100 millisecond wait.
+    fn default() -> RetryPolicy {
+        RetryPolicy::new(5, Duration::from_millis(100))
+    }
+}
+
+type Connector = Box<dyn Fn() -> Result<Client, Error> + Send + Sync>;
+
+/// A client which connects to the server again once its connection is closed.
+///
+/// The connection is checked before each request. If it has closed, for example because the server restarted, a new
+/// one is dialed with the stored `Config` according to the `RetryPolicy` before the request is sent. A request which
+/// is in progress when the connection breaks still fails with an error for which `Error::is_closed` returns `true`;
+/// it is not sent again, since the server may already have run it.
+///
+/// Prepared statements belong to the connection they were prepared on, so a `Statement` obtained before a reconnect
+/// can't be used afterwards; pass query strings to the methods of this type instead. The statement cache of a new
+/// connection starts out empty, so statements passed to `Client::prepare_cached` are simply prepared again on first
+/// use. Session state such as `SET` parameters and temporary tables is lost as well.
+pub struct ReconnectingClient {
+    connector: Connector,
+    policy: RetryPolicy,
+    client: Mutex<Arc<Client>>,
+}
+
+impl ReconnectingClient {
+    /// Connects to the database, retrying according to `policy`.
+    pub fn new(config: Config, policy: RetryPolicy) -> Result<ReconnectingClient, Error> {
+        ReconnectingClient::new_tls(config, NoTls, policy)
+    }
+
+    /// Like `new`, but uses the provided TLS connector to secure each connection according to `sslmode`.
+    pub fn new_tls<T>(
+        config: Config,
+        tls: T,
+        policy: RetryPolicy,
+    ) -> Result<ReconnectingClient, Error>
+    where
+        T: MakeTlsConnect<TcpStream> + Clone + Send + Sync + 'static,
+    {
+        let connector: Connector = Box::new(move || config.connect_tls(tls.clone()));
+        let client = dial(&connector, &policy)?;
+        Ok(ReconnectingClient {
+            connector,
+            policy,
+            client: Mutex::new(Arc::new(client)),
+        })
+    }
+
+    /// Returns the current client, connecting again first if its connection has closed.
+    ///
+    /// The returned client is not replaced if its connection closes later on, so it should not be held on to.
+    pub fn client(&self) -> Result<Arc<Client>, Error> {
+        let mut client = self.client.lock().unwrap();
+        if client.is_closed() {
+            info!("connection closed, reconnecting");
+            *client = Arc::new(dial(&self.connector, &self.policy)?);
+        }
+        Ok(client.clone())
+    }
+
+    /// Like `Client::query`.
+    pub fn query<T>(&self, statement: &T, params: &[&(dyn ToSql + Sync)]) -> Result<Vec<Row>, Error>
+    where
+        T: ?Sized + ToStatement,
+    {
+        self.client()?.query(statement, params)
+    }
+
+    /// Like `Client::query_one`.
+    pub fn query_one<T>(&self, statement: &T, params: &[&(dyn ToSql + Sync)]) -> Result<Row, Error>
+    where
+        T: ?Sized + ToStatement,
+    {
+        self.client()?.query_one(statement, params)
+    }
+
+    /// Like `Client::query_opt`.
+    pub fn query_opt<T>(
+        &self,
+        statement: &T,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<Option<Row>, Error>
+    where
+        T: ?Sized + ToStatement,
+    {
+        self.client()?.query_opt(statement, params)
+    }
+
+    /// Like `Client::execute`.
+    pub fn execute<T>(&self, statement: &T, params: &[&(dyn ToSql + Sync)]) -> Result<u64, Error>
+    where
+        T: ?Sized + ToStatement,
+    {
+        self.client()?.execute(statement, params)
+    }
+
+    /// Like `Client::batch_execute`.
+    pub fn batch_execute(&self, query: &str) -> Result<(), Error> {
+        self.client()?.batch_execute(query)
+    }
+}
+
+fn dial(connector: &Connector, policy: &RetryPolicy) -> Result<Client, Error> {
+    let mut attempt = 0;
+    loop {
+        match connector() {
+            Ok(client) => return Ok(client),
+            Err(e) if attempt + 1 < policy.max_attempts => {
+                let delay = policy.delay(attempt);
+                info!(
+                    "connection attempt {} failed, retrying in {:?}: {}",
+                    attempt + 1,
+                    delay,
+                    e
+                );
+                may::coroutine::sleep(delay);
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
@@ -0,0 +1,270 @@
+//! Streaming replication.
+//!
+//! A replication connection, opened with `Config::replication_mode`, starts streaming with a `START_REPLICATION`
+//! command run through `Client::copy_both`. The server then sends its WAL, or the output of the logical decoding
+//! plugin, in `XLogData` messages and checks on the client with keepalive messages, while the client reports its
+//! progress in standby status updates. `ReplicationStream` handles that exchange.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use may_postgres::config::ReplicationMode;
+//! use may_postgres::replication::ReplicationStream;
+//! use may_postgres::Config;
+//!
+//! # fn main() -> Result<(), may_postgres::Error> {
+//! let client = Config::new()
+//!     .host("localhost")
+//!     .user("postgres")
+//!     .replication_mode(ReplicationMode::Logical)
+//!     .connect()?;
+//!
+//! let (sink, stream) = client.copy_both("START_REPLICATION SLOT my_slot LOGICAL 0/0")?;
+//! let mut stream = ReplicationStream::new(sink, stream);
+//! while let Some(data) = stream.next().transpose()? {
+//!     println!("{:?}", data.data());
+//!     stream.set_flushed_lsn(data.wal_start());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{CopyBothSink, CopyBothStream, Error};
+use byteorder::{BigEndian, ByteOrder};
+use bytes::{BufMut, Bytes, BytesMut};
+use std::io;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const XLOG_DATA_TAG: u8 = b'w';
+const PRIMARY_KEEPALIVE_TAG: u8 = b'k';
+const STANDBY_STATUS_UPDATE_TAG: u8 = b'r';
+
+// the replication protocol counts microseconds from 2000-01-01
+const PG_EPOCH_OFFSET: Duration = Duration::from_secs(946_684_800);
+
+/// A message sent by the server in the `CopyData` of a replication stream.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum ReplicationMessage {
+    /// A piece of WAL, or of the output of a logical decoding plugin.
+    XLogData(XLogData),
+    /// A keepalive, which may ask for a standby status update.
+    PrimaryKeepAlive(PrimaryKeepAlive),
+}
+
+impl ReplicationMessage {
+    /// Parses the payload of a `CopyData` message.
+    pub fn parse(buf: &Bytes) -> Result<ReplicationMessage, Error> {
+        let tag = match buf.first() {
+            Some(tag) => *tag,
+            None => return Err(invalid("empty replication message")),
+        };
+
+        match tag {
+            XLOG_DATA_TAG => {
+                if buf.len() < 25 {
+                    return Err(invalid("truncated XLogData message"));
+                }
+                Ok(ReplicationMessage::XLogData(XLogData {
+                    wal_start: BigEndian::read_u64(&buf[1..]),
+                    wal_end: BigEndian::read_u64(&buf[9..]),
+                    timestamp: from_pg_time(BigEndian::read_i64(&buf[17..])),
+                    data: buf.slice(25..),
+                }))
+            }
+            PRIMARY_KEEPALIVE_TAG => {
+                if buf.len() < 18 {
+                    return Err(invalid("truncated primary keepalive message"));
+                }
+                Ok(ReplicationMessage::PrimaryKeepAlive(PrimaryKeepAlive {
+                    wal_end: BigEndian::read_u64(&buf[1..]),
+                    timestamp: from_pg_time(BigEndian::read_i64(&buf[9..])),
+                    reply: buf[17] == 1,
+                }))
+            }
+            tag => Err(invalid(&format!(
+                "unknown replication message tag `{}`",
+                tag
+            ))),
+        }
+    }
+}
+
+/// A piece of WAL, or of the output of a logical decoding plugin.
+#[derive(Debug, Clone, PartialEq)]
+pub struct XLogData {
+    wal_start: u64,
+    wal_end: u64,
+    timestamp: SystemTime,
+    data: Bytes,
+}
+
+impl XLogData {
+    /// Returns the WAL position the data starts at.
+    pub fn wal_start(&self) -> u64 {
+        self.wal_start
+    }
+
+    /// Returns the current end of WAL on the server.
+    pub fn wal_end(&self) -> u64 {
+        self.wal_end
+    }
+
+    /// Returns the time the message was sent.
+    pub fn timestamp(&self) -> SystemTime {
+        self.timestamp
+    }
+
+    /// Returns the data.
+    pub fn data(&self) -> &Bytes {
+        &self.data
+    }
+
+    /// Consumes the message, returning its data.
+    pub fn into_data(self) -> Bytes {
+        self.data
+    }
+}
+
+/// A keepalive from the server.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrimaryKeepAlive {
+    wal_end: u64,
+    timestamp: SystemTime,
+    reply: bool,
+}
+
+impl PrimaryKeepAlive {
+    /// Returns the current end of WAL on the server.
+    pub fn wal_end(&self) -> u64 {
+        self.wal_end
+    }
+
+    /// Returns the time the message was sent.
+    pub fn timestamp(&self) -> SystemTime {
+        self.timestamp
+    }
+
+    /// Determines if the server asks for a standby status update right away, without which it eventually ends the
+    /// connection as timed out.
+    pub fn reply(&self) -> bool {
+        self.reply
+    }
+}
+
+/// Encodes a standby status update, which reports the WAL positions the client has written, flushed and applied.
+///
+/// Positions the client doesn't track can be 0. If `reply` is set, the server answers with a keepalive right away.
+pub fn standby_status_update(
+    write_lsn: u64,
+    flush_lsn: u64,
+    apply_lsn: u64,
+    timestamp: SystemTime,
+    reply: bool,
+) -> Bytes {
+    let mut buf = BytesMut::with_capacity(34);
+    buf.put_u8(STANDBY_STATUS_UPDATE_TAG);
+    buf.put_u64(write_lsn);
+    buf.put_u64(flush_lsn);
+    buf.put_u64(apply_lsn);
+    buf.put_i64(to_pg_time(timestamp));
+    buf.put_u8(reply as u8);
+    buf.freeze()
+}
+
+/// A replication stream over the halves of a copy started by `Client::copy_both`.
+///
+/// Iterating yields the `XLogData` messages. Keepalives which ask for a reply are answered with a standby status update
+/// reporting the position set by `set_flushed_lsn` as written, flushed and applied, so the server keeps the WAL from
+/// there on. The iteration ends once the server has processed `finish`, or ends the stream itself.
+pub struct ReplicationStream {
+    sink: CopyBothSink,
+    stream: CopyBothStream,
+    flushed_lsn: u64,
+}
+
+impl ReplicationStream {
+    /// Creates a stream over the halves of a copy started with `START_REPLICATION`.
+    pub fn new(sink: CopyBothSink, stream: CopyBothStream) -> ReplicationStream {
+        ReplicationStream {
+            sink,
+            stream,
+            flushed_lsn: 0,
+        }
+    }
+
+    /// Sets the position up to which the received data has been durably processed, which the standby status updates
+    /// report to the server.
+    pub fn set_flushed_lsn(&mut self, lsn: u64) {
+        self.flushed_lsn = lsn;
+    }
+
+    /// Returns the position set by `set_flushed_lsn`.
+    pub fn flushed_lsn(&self) -> u64 {
+        self.flushed_lsn
+    }
+
+    /// Sends a standby status update now, asking the server to answer with a keepalive if `reply` is set.
+    pub fn send_status_update(&mut self, reply: bool) -> Result<(), Error> {
+        let lsn = self.flushed_lsn;
+        self.sink.send(standby_status_update(
+            lsn,
+            lsn,
+            lsn,
+            SystemTime::now(),
+            reply,
+        ))
+    }
+
+    /// Ends the replication. The remaining messages should be read until the iteration ends.
+    pub fn finish(&mut self) -> Result<(), Error> {
+        self.sink.finish()
+    }
+}
+
+impl Iterator for ReplicationStream {
+    type Item = Result<XLogData, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let message = match self.stream.next()? {
+                Ok(message) => message,
+                Err(e) => return Some(Err(e)),
+            };
+
+            match ReplicationMessage::parse(&message) {
+                Ok(ReplicationMessage::XLogData(data)) => return Some(Ok(data)),
+                Ok(ReplicationMessage::PrimaryKeepAlive(keepalive)) => {
+                    // once the client has finished its half there is no need, nor a way, to answer
+                    if keepalive.reply() && !self.sink.is_finished() {
+                        if let Err(e) = self.send_status_update(false) {
+                            return Some(Err(e));
+                        }
+                    }
+                }
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+fn invalid(message: &str) -> Error {
+    Error::parse(io::Error::new(io::ErrorKind::InvalidData, message))
+}
+
+fn from_pg_time(micros: i64) -> SystemTime {
+    let epoch = UNIX_EPOCH + PG_EPOCH_OFFSET;
+    if micros >= 0 {
+        epoch + Duration::from_micros(micros as u64)
+    } else {
+        epoch - Duration::from_micros(micros.unsigned_abs())
+    }
+}
+
+fn to_pg_time(time: SystemTime) -> i64 {
+    let epoch = UNIX_EPOCH + PG_EPOCH_OFFSET;
+    match time.duration_since(epoch) {
+        Ok(duration) => duration.as_micros() as i64,
+        Err(e) => -(e.duration().as_micros() as i64),
+    }
+}
@@ -2,13 +2,14 @@
 
 use crate::row::sealed::{AsName, Sealed};
 use crate::statement::Column;
-use crate::types::{FromSql, Type, WrongType};
+use crate::types::{Format, FromSql, Type, WrongType};
 use crate::{Error, Statement};
 use fallible_iterator::FallibleIterator;
 use postgres_protocol::message::backend::DataRowBody;
+use std::error;
 use std::fmt;
 use std::ops::Range;
-use std::str;
+use std::str::{self, FromStr};
 use std::sync::Arc;
 
 mod sealed {
@@ -78,9 +79,9 @@ impl RowIndex for str {
     }
 }
 
-impl<'a, T> Sealed for &'a T where T: ?Sized + Sealed {}
+impl<T> Sealed for &T where T: ?Sized + Sealed {}
 
-impl<'a, T> RowIndex for &'a T
+impl<T> RowIndex for &T
 where
     T: ?Sized + RowIndex,
 {
@@ -98,15 +99,21 @@ pub struct Row {
     statement: Statement,
     body: DataRowBody,
     ranges: Vec<Option<Range<usize>>>,
+    formats: Option<Arc<[Format]>>,
 }
 
 impl Row {
-    pub(crate) fn new(statement: Statement, body: DataRowBody) -> Result<Row, Error> {
+    pub(crate) fn new(
+        statement: Statement,
+        body: DataRowBody,
+        formats: Option<Arc<[Format]>>,
+    ) -> Result<Row, Error> {
         let ranges = body.ranges().collect().map_err(Error::parse)?;
         Ok(Row {
             statement,
             body,
             ranges,
+            formats,
         })
     }
 
@@ -125,14 +132,158 @@ impl Row {
         self.columns().len()
     }
 
+    /// Returns the format the value of a column was sent in.
+    ///
+    /// This is `Format::Binary` unless the query asked for text results with `Client::query_with_formats`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of bounds.
+    pub fn format(&self, idx: usize) -> Format {
+        self.as_row_ref().format(idx)
+    }
+
     /// Deserializes a value from the row.
     ///
-    /// The value can be specified either by its numeric index in the row, or by its column name.
+    /// The value can be specified either by its numeric index in the row, or by its column name. A value in the text
+    /// format can only be read as a string, like `&str` or `String`, whatever the type of its column, or parsed with
+    /// `Row::get_parsed`.
     ///
     /// # Panics
     ///
     /// Panics if the index is out of bounds or if the value cannot be converted to the specified type.
     pub fn get<'a, I, T>(&'a self, idx: I) -> T
+    where
+        I: RowIndex + fmt::Display,
+        T: FromSql<'a>,
+    {
+        self.as_row_ref().get(idx)
+    }
+
+    /// Like `Row::get`, but returns a `Result` rather than panicking.
+    pub fn try_get<'a, I, T>(&'a self, idx: I) -> Result<T, Error>
+    where
+        I: RowIndex + fmt::Display,
+        T: FromSql<'a>,
+    {
+        self.as_row_ref().try_get(idx)
+    }
+
+    /// Parses a value from its text with `FromStr`, such as an `INT` column sent in the text format into an `i32`.
+    ///
+    /// The value has to be in the text format, or of a string type like `TEXT`. A `NULL` value is an error, it can be
+    /// checked for with `get::<_, Option<&str>>` first.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of bounds or if the value cannot be parsed into the specified type.
+    pub fn get_parsed<I, T>(&self, idx: I) -> T
+    where
+        I: RowIndex + fmt::Display,
+        T: FromStr,
+        T::Err: Into<Box<dyn error::Error + Sync + Send>>,
+    {
+        self.as_row_ref().get_parsed(idx)
+    }
+
+    /// Like `Row::get_parsed`, but returns a `Result` rather than panicking.
+    pub fn try_get_parsed<I, T>(&self, idx: I) -> Result<T, Error>
+    where
+        I: RowIndex + fmt::Display,
+        T: FromStr,
+        T::Err: Into<Box<dyn error::Error + Sync + Send>>,
+    {
+        self.as_row_ref().try_get_parsed(idx)
+    }
+
+    /// Returns the raw bytes of a value in the row, or `None` if it is `NULL`.
+    ///
+    /// The bytes borrow from the buffer the row was received into, so even a large `BYTEA` value is not copied. Values
+    /// are in Postgres' binary format, which is just the bytes for `BYTEA`, unless `Row::format` reports the text
+    /// format. This is the same as `get::<_, &[u8]>` for a `BYTEA` column, but works with columns of any type.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of bounds.
+    pub fn get_bytes<I>(&self, idx: I) -> Option<&[u8]>
+    where
+        I: RowIndex + fmt::Display,
+    {
+        self.as_row_ref().get_bytes(idx)
+    }
+
+    // the values are expected in the text format
+    pub(crate) fn into_simple(self, columns: Arc<[String]>) -> SimpleQueryRow {
+        SimpleQueryRow {
+            columns,
+            body: self.body,
+            ranges: self.ranges,
+        }
+    }
+
+    fn as_row_ref(&self) -> RowRef<'_> {
+        RowRef {
+            statement: &self.statement,
+            body: &self.body,
+            ranges: &self.ranges,
+            formats: self.formats.as_deref(),
+        }
+    }
+}
+
+/// A row borrowed from the buffer it was received in, as passed to the callback of `Client::query_each`.
+///
+/// Unlike a `Row`, it doesn't keep its own list of where its values are, so reading a result set this way makes no
+/// allocation per row. It can't outlive the callback it is passed to; values which borrow from it, like a `&str`,
+/// have to be copied to be kept.
+#[derive(Copy, Clone)]
+pub struct RowRef<'a> {
+    statement: &'a Statement,
+    body: &'a DataRowBody,
+    ranges: &'a [Option<Range<usize>>],
+    formats: Option<&'a [Format]>,
+}
+
+impl<'a> RowRef<'a> {
+    pub(crate) fn new(
+        statement: &'a Statement,
+        body: &'a DataRowBody,
+        ranges: &'a [Option<Range<usize>>],
+    ) -> RowRef<'a> {
+        RowRef {
+            statement,
+            body,
+            ranges,
+            formats: None,
+        }
+    }
+
+    /// Returns information about the columns of data in the row.
+    pub fn columns(&self) -> &'a [Column] {
+        self.statement.columns()
+    }
+
+    /// Determines if the row contains no values.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of values in the row.
+    pub fn len(&self) -> usize {
+        self.columns().len()
+    }
+
+    /// Like `Row::format`.
+    pub fn format(&self, idx: usize) -> Format {
+        assert!(idx < self.len(), "column index {} out of bounds", idx);
+        match self.formats {
+            Some(formats) => formats[idx],
+            None => Format::Binary,
+        }
+    }
+
+    /// Like `Row::get`.
+    pub fn get<I, T>(&self, idx: I) -> T
     where
         I: RowIndex + fmt::Display,
         T: FromSql<'a>,
@@ -143,8 +294,8 @@ impl Row {
         }
     }
 
-    /// Like `Row::get`, but returns a `Result` rather than panicking.
-    pub fn try_get<'a, I, T>(&'a self, idx: I) -> Result<T, Error>
+    /// Like `Row::try_get`.
+    pub fn try_get<I, T>(&self, idx: I) -> Result<T, Error>
     where
         I: RowIndex + fmt::Display,
         T: FromSql<'a>,
@@ -152,7 +303,51 @@ impl Row {
         self.get_inner(&idx)
     }
 
-    fn get_inner<'a, I, T>(&'a self, idx: &I) -> Result<T, Error>
+    /// Like `Row::get_parsed`.
+    pub fn get_parsed<I, T>(&self, idx: I) -> T
+    where
+        I: RowIndex + fmt::Display,
+        T: FromStr,
+        T::Err: Into<Box<dyn error::Error + Sync + Send>>,
+    {
+        match self.try_get_parsed(&idx) {
+            Ok(ok) => ok,
+            Err(err) => panic!("error retrieving column {}: {}", idx, err),
+        }
+    }
+
+    /// Like `Row::try_get_parsed`.
+    pub fn try_get_parsed<I, T>(&self, idx: I) -> Result<T, Error>
+    where
+        I: RowIndex + fmt::Display,
+        T: FromStr,
+        T::Err: Into<Box<dyn error::Error + Sync + Send>>,
+    {
+        let idx = match idx.__idx(self.columns()) {
+            Some(idx) => idx,
+            None => return Err(Error::column(idx.to_string())),
+        };
+        let text = self.get_inner::<_, &str>(&idx)?;
+        text.parse()
+            .map_err(|e: T::Err| Error::from_sql(e.into(), idx))
+    }
+
+    /// Like `Row::get_bytes`.
+    pub fn get_bytes<I>(&self, idx: I) -> Option<&'a [u8]>
+    where
+        I: RowIndex + fmt::Display,
+    {
+        match idx.__idx(self.columns()) {
+            Some(idx) => self.ranges[idx].clone().map(|r| &self.body.buffer()[r]),
+            None => panic!(
+                "error retrieving column {}: {}",
+                idx,
+                Error::column(idx.to_string())
+            ),
+        }
+    }
+
+    fn get_inner<I, T>(&self, idx: &I) -> Result<T, Error>
     where
         I: RowIndex + fmt::Display,
         T: FromSql<'a>,
@@ -162,6 +357,19 @@ impl Row {
             None => return Err(Error::column(idx.to_string())),
         };
 
+        let buf = self.ranges[idx].clone().map(|r| &self.body.buffer()[r]);
+        if self.format(idx) == Format::Text {
+            // a text value is read like a `TEXT` one, whatever the type of its column
+            if !T::accepts(&Type::TEXT) {
+                return Err(Error::from_sql(
+                    Box::new(WrongType::new::<T>(Type::TEXT)),
+                    idx,
+                ));
+            }
+            return FromSql::from_sql_nullable(&Type::TEXT, buf)
+                .map_err(|e| Error::from_sql(e, idx));
+        }
+
         let ty = self.columns()[idx].type_();
         if !T::accepts(ty) {
             return Err(Error::from_sql(
@@ -170,7 +378,6 @@ impl Row {
             ));
         }
 
-        let buf = self.ranges[idx].clone().map(|r| &self.body.buffer()[r]);
         FromSql::from_sql_nullable(ty, buf).map_err(|e| Error::from_sql(e, idx))
     }
 }
@@ -193,6 +400,11 @@ impl SimpleQueryRow {
         })
     }
 
+    /// Returns the names of the columns of the row.
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
     /// Determines if the row contains no values.
     pub fn is_empty(&self) -> bool {
         self.len() == 0
@@ -1,12 +1,14 @@
 use crate::client::{InnerClient, Responses};
 use crate::codec::FrontendMessage;
 use crate::connection::RequestMessages;
-use crate::{Error, SimpleQueryMessage, SimpleQueryRow};
+use crate::error::ScriptError;
+use crate::{query, Error, SimpleQueryMessage, SimpleQueryRow};
 use bytes::Bytes;
 use fallible_iterator::FallibleIterator;
 use postgres_protocol::message::backend::Message;
 use postgres_protocol::message::frontend;
 use std::sync::Arc;
+use std::time::Duration;
 
 pub fn simple_query(client: &InnerClient, query: &str) -> Result<SimpleQueryStream, Error> {
     let buf = encode(client, query)?;
@@ -34,7 +36,46 @@ pub fn batch_execute(client: &InnerClient, query: &str) -> Result<(), Error> {
     }
 }
 
-fn encode(client: &InnerClient, query: &str) -> Result<Bytes, Error> {
+/// Sends an empty query and waits at most `timeout` for the server to answer it.
+pub fn ping(client: &InnerClient, timeout: Duration) -> Result<(), Error> {
+    let buf = encode(client, ";")?;
+    let mut responses =
+        client.send_timeout(RequestMessages::Single(FrontendMessage::Raw(buf)), timeout)?;
+
+    loop {
+        match responses.next()? {
+            Message::ReadyForQuery(_) => return Ok(()),
+            Message::EmptyQueryResponse => {}
+            _ => return Err(Error::unexpected_message()),
+        }
+    }
+}
+
+pub fn execute_script(client: &InnerClient, script: &str) -> Result<usize, ScriptError> {
+    let buf = encode(client, script).map_err(|e| ScriptError::new(None, e))?;
+    let mut responses = client
+        .send(RequestMessages::Single(FrontendMessage::Raw(buf)))
+        .map_err(|e| ScriptError::new(None, e))?;
+
+    // every statement ends with exactly one of these, so they count the statements before an error. A script with no
+    // statements at all is answered with an EmptyQueryResponse instead, which isn't a statement.
+    let mut completed = 0;
+    loop {
+        match responses.next() {
+            Ok(Message::ReadyForQuery(_)) => return Ok(completed),
+            Ok(Message::CommandComplete(_)) => completed += 1,
+            Ok(Message::EmptyQueryResponse)
+            | Ok(Message::RowDescription(_))
+            | Ok(Message::DataRow(_)) => {}
+            Ok(_) => return Err(ScriptError::new(None, Error::unexpected_message())),
+            // only an error sent by the server belongs to the statement it was running
+            Err(e) if e.code().is_some() => return Err(ScriptError::new(Some(completed), e)),
+            Err(e) => return Err(ScriptError::new(None, e)),
+        }
+    }
+}
+
+pub fn encode(client: &InnerClient, query: &str) -> Result<Bytes, Error> {
     client.with_buf(|buf| {
         frontend::query(query, buf).map_err(Error::encode)?;
         Ok(buf.split().freeze())
@@ -54,13 +95,7 @@ impl Iterator for SimpleQueryStream {
         loop {
             match o_try!(self.responses.next()) {
                 Message::CommandComplete(body) => {
-                    let rows = body.tag().map_err(Error::parse);
-                    let rows = o_try!(rows)
-                        .rsplit(' ')
-                        .next()
-                        .unwrap()
-                        .parse()
-                        .unwrap_or(0);
+                    let rows = o_try!(query::extract_row_affected(&body));
                     return Some(Ok(SimpleQueryMessage::CommandComplete(rows)));
                 }
                 Message::EmptyQueryResponse => {
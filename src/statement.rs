@@ -46,11 +46,24 @@ impl Statement {
         }))
     }
 
+    // describes the unnamed statement, which is never closed explicitly
+    pub(crate) fn unnamed(params: Vec<Type>, columns: Vec<Column>) -> Statement {
+        Statement(Arc::new(StatementInner {
+            client: Weak::new(),
+            name: String::new(),
+            params,
+            columns,
+        }))
+    }
+
     pub(crate) fn name(&self) -> &str {
         &self.0.name
     }
 
     /// Returns the expected types of the statement's parameters.
+    ///
+    /// The types are the ones the server reported when the statement was prepared, so values can be checked against
+    /// them before the statement is executed.
     pub fn params(&self) -> &[Type] {
         &self.0.params
     }
@@ -66,11 +79,26 @@ impl Statement {
 pub struct Column {
     name: String,
     type_: Type,
+    table_oid: Option<u32>,
+    column_id: Option<i16>,
+    type_modifier: i32,
 }
 
 impl Column {
-    pub(crate) fn new(name: String, type_: Type) -> Column {
-        Column { name, type_ }
+    pub(crate) fn new(
+        name: String,
+        type_: Type,
+        table_oid: Option<u32>,
+        column_id: Option<i16>,
+        type_modifier: i32,
+    ) -> Column {
+        Column {
+            name,
+            type_,
+            table_oid,
+            column_id,
+            type_modifier,
+        }
     }
 
     /// Returns the name of the column.
@@ -82,4 +110,25 @@ impl Column {
     pub fn type_(&self) -> &Type {
         &self.type_
     }
+
+    /// Returns the OID of the table the column was taken from.
+    ///
+    /// Returns `None` if the column is not a plain reference to a table column, for example for an expression.
+    pub fn table_oid(&self) -> Option<u32> {
+        self.table_oid
+    }
+
+    /// Returns the attribute number of the column in the table it was taken from.
+    ///
+    /// Returns `None` if the column is not a plain reference to a table column, for example for an expression.
+    pub fn column_id(&self) -> Option<i16> {
+        self.column_id
+    }
+
+    /// Returns the type modifier of the column, such as the length of a `VARCHAR(n)`.
+    ///
+    /// The meaning of the value depends on the type, and it is -1 if the type has no modifier.
+    pub fn type_modifier(&self) -> i32 {
+        self.type_modifier
+    }
 }
@@ -0,0 +1,69 @@
+use crate::Statement;
+use std::collections::{BTreeMap, HashMap};
+
+/// A least recently used cache of prepared statements keyed by their SQL text.
+pub struct StatementCache {
+    capacity: usize,
+    tick: u64,
+    // each statement along with the tick it was last used at
+    entries: HashMap<String, (Statement, u64)>,
+    // the queries by the tick they were last used at, least recently used first
+    order: BTreeMap<u64, String>,
+}
+
+impl StatementCache {
+    pub fn new(capacity: usize) -> StatementCache {
+        StatementCache {
+            capacity,
+            tick: 0,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+        }
+    }
+
+    pub fn get(&mut self, query: &str) -> Option<Statement> {
+        let (statement, used) = self.entries.get_mut(query)?;
+        self.tick += 1;
+        let query = self.order.remove(used).unwrap();
+        *used = self.tick;
+        self.order.insert(self.tick, query);
+        Some(statement.clone())
+    }
+
+    /// insert a statement, returning the entries pushed out of the cache
+    ///
+    /// the returned statements must be dropped without holding any client lock, since dropping the last handle to a
+    /// statement sends a `Close` to the server
+    pub fn insert(&mut self, query: &str, statement: Statement) -> Vec<Statement> {
+        let mut evicted = vec![];
+        if self.capacity == 0 {
+            evicted.push(statement);
+            return evicted;
+        }
+
+        self.tick += 1;
+        if let Some((old, used)) = self
+            .entries
+            .insert(query.to_string(), (statement, self.tick))
+        {
+            self.order.remove(&used);
+            evicted.push(old);
+        }
+        self.order.insert(self.tick, query.to_string());
+
+        while self.entries.len() > self.capacity {
+            let (_, oldest) = self.order.pop_first().unwrap();
+            evicted.extend(self.entries.remove(&oldest).map(|(statement, _)| statement));
+        }
+
+        evicted
+    }
+
+    pub fn clear(&mut self) -> Vec<Statement> {
+        self.order.clear();
+        self.entries
+            .drain()
+            .map(|(_, (statement, _))| statement)
+            .collect()
+    }
+}
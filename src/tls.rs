@@ -0,0 +1,229 @@
+//! TLS support.
+
+use crate::socket::Socket;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::Shutdown;
+
+pub(crate) mod private {
+    pub struct ForcePrivateApi;
+}
+
+/// Channel binding information returned from a TLS handshake.
+pub struct ChannelBinding {
+    pub(crate) tls_server_end_point: Option<Vec<u8>>,
+}
+
+impl ChannelBinding {
+    /// Creates a `ChannelBinding` containing no information.
+    pub fn none() -> ChannelBinding {
+        ChannelBinding {
+            tls_server_end_point: None,
+        }
+    }
+
+    /// Creates a `ChannelBinding` containing `tls-server-end-point` channel binding information.
+    pub fn tls_server_end_point(tls_server_end_point: Vec<u8>) -> ChannelBinding {
+        ChannelBinding {
+            tls_server_end_point: Some(tls_server_end_point),
+        }
+    }
+}
+
+/// A constructor of `TlsConnect`ors.
+///
+/// Requires the `runtime` Cargo feature (enabled by default).
+pub trait MakeTlsConnect<S> {
+    /// The stream type created by the `TlsConnect` implementation.
+    type Stream: TlsStream + Send + 'static;
+    /// The `TlsConnect` implementation created by this type.
+    type TlsConnect: TlsConnect<S, Stream = Self::Stream>;
+    /// The error type returned by the `TlsConnect` implementation.
+    type Error: Into<Box<dyn Error + Sync + Send>>;
+
+    /// Creates a new `TlsConnect`or.
+    ///
+    /// The domain name is provided for certificate verification and SNI.
+    fn make_tls_connect(&mut self, domain: &str) -> Result<Self::TlsConnect, Self::Error>;
+}
+
+/// A function wrapping a stream in a TLS session.
+pub trait TlsConnect<S> {
+    /// The stream returned by the handshake.
+    type Stream: TlsStream + Send + 'static;
+    /// The error returned by the handshake.
+    type Error: Into<Box<dyn Error + Sync + Send>>;
+
+    /// Performs a TLS handshake over the stream.
+    fn connect(self, stream: S) -> Result<Self::Stream, Self::Error>;
+
+    #[doc(hidden)]
+    fn can_connect(&self, _: private::ForcePrivateApi) -> bool {
+        true
+    }
+}
+
+/// A TLS-wrapped connection to a PostgreSQL database.
+///
+/// The connection reads from and writes to the stream from two different coroutines, so an implementation must be
+/// able to hand out additional handles to the same TLS session through `try_clone`. A read blocked on one handle must
+/// not prevent writes through another.
+pub trait TlsStream: Read + Write {
+    /// Returns channel binding information for the session.
+    fn channel_binding(&self) -> ChannelBinding;
+
+    /// Creates a new handle to the same TLS session.
+    fn try_clone(&self) -> io::Result<Self>
+    where
+        Self: Sized;
+
+    /// Shuts down the read, write, or both halves of the underlying connection.
+    fn shutdown(&self, how: Shutdown) -> io::Result<()>;
+}
+
+/// A `MakeTlsConnect` and `TlsConnect` implementation which simply returns an error.
+///
+/// This can be used when `sslmode` is `disable` or `prefer`.
+#[derive(Debug, Copy, Clone)]
+pub struct NoTls;
+
+impl<S> MakeTlsConnect<S> for NoTls {
+    type Stream = NoTlsStream;
+    type TlsConnect = NoTls;
+    type Error = NoTlsError;
+
+    fn make_tls_connect(&mut self, _: &str) -> Result<NoTls, NoTlsError> {
+        Ok(NoTls)
+    }
+}
+
+impl<S> TlsConnect<S> for NoTls {
+    type Stream = NoTlsStream;
+    type Error = NoTlsError;
+
+    fn connect(self, _: S) -> Result<NoTlsStream, NoTlsError> {
+        Err(NoTlsError(()))
+    }
+
+    fn can_connect(&self, _: private::ForcePrivateApi) -> bool {
+        false
+    }
+}
+
+/// The TLS "stream" type produced by the `NoTls` connector.
+///
+/// Since `NoTls` doesn't support TLS, this type is uninhabited.
+pub enum NoTlsStream {}
+
+impl Read for NoTlsStream {
+    fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+        match *self {}
+    }
+}
+
+impl Write for NoTlsStream {
+    fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+        match *self {}
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match *self {}
+    }
+}
+
+impl TlsStream for NoTlsStream {
+    fn channel_binding(&self) -> ChannelBinding {
+        match *self {}
+    }
+
+    fn try_clone(&self) -> io::Result<NoTlsStream> {
+        match *self {}
+    }
+
+    fn shutdown(&self, _: Shutdown) -> io::Result<()> {
+        match *self {}
+    }
+}
+
+/// The error returned by `NoTls`.
+#[derive(Debug)]
+pub struct NoTlsError(());
+
+impl fmt::Display for NoTlsError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.write_str("no TLS implementation configured")
+    }
+}
+
+impl Error for NoTlsError {}
+
+/// A stream which may or may not be wrapped in TLS.
+pub(crate) enum MaybeTlsStream<T> {
+    Raw(Socket),
+    Tls(T),
+}
+
+impl<T> MaybeTlsStream<T>
+where
+    T: TlsStream,
+{
+    pub fn try_clone(&self) -> io::Result<Self> {
+        match self {
+            MaybeTlsStream::Raw(s) => s.try_clone().map(MaybeTlsStream::Raw),
+            MaybeTlsStream::Tls(s) => s.try_clone().map(MaybeTlsStream::Tls),
+        }
+    }
+
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        match self {
+            MaybeTlsStream::Raw(s) => s.shutdown(how),
+            MaybeTlsStream::Tls(s) => s.shutdown(how),
+        }
+    }
+
+    pub fn channel_binding(&self) -> ChannelBinding {
+        match self {
+            MaybeTlsStream::Raw(_) => ChannelBinding::none(),
+            MaybeTlsStream::Tls(s) => s.channel_binding(),
+        }
+    }
+}
+
+impl<T> Read for MaybeTlsStream<T>
+where
+    T: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            MaybeTlsStream::Raw(s) => s.read(buf),
+            MaybeTlsStream::Tls(s) => s.read(buf),
+        }
+    }
+}
+
+impl<T> Write for MaybeTlsStream<T>
+where
+    T: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            MaybeTlsStream::Raw(s) => s.write(buf),
+            MaybeTlsStream::Tls(s) => s.write(buf),
+        }
+    }
+
+    fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
+        match self {
+            MaybeTlsStream::Raw(s) => s.write_vectored(bufs),
+            MaybeTlsStream::Tls(s) => s.write_vectored(bufs),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            MaybeTlsStream::Raw(s) => s.flush(),
+            MaybeTlsStream::Tls(s) => s.flush(),
+        }
+    }
+}
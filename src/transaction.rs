@@ -2,34 +2,48 @@ use crate::codec::FrontendMessage;
 use crate::connection::RequestMessages;
 use crate::copy_out::CopyOutStream;
 use crate::query::RowStream;
-use crate::types::{ToSql, Type};
+use crate::types::{Format, ToSql, Type};
 use crate::{
-    bind, query, slice_iter, CancelToken, Client, CopyInSink, Error, Portal, Row,
-    SimpleQueryMessage, Statement, ToStatement,
+    bind, query, slice_iter, CancelToken, Client, CopyInSink, CopyInWriter, Error, Pipeline,
+    Portal, Row, RowRef, SimpleQueryMessage, SimpleQueryRow, Statement, ToStatement,
 };
 use bytes::Buf;
 use postgres_protocol::message::frontend;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
 
 /// A representation of a PostgreSQL database transaction.
 ///
 /// Transactions will implicitly roll back when dropped. Use the `commit` method to commit the changes made in the
 /// transaction. Transactions can be nested, with inner transactions implemented via safepoints.
+///
+/// `Drop` can't wait for the server, so the implicit rollback is fire-and-forget: the `ROLLBACK` is queued on the
+/// connection and its response is discarded. Requests are executed in the order they are queued, so anything sent
+/// on the client afterwards runs after the rollback. Use the `rollback` method to observe errors.
 pub struct Transaction<'a> {
     client: &'a mut Client,
-    depth: u32,
+    savepoint: Option<Savepoint>,
     done: bool,
 }
 
+/// A savepoint the transaction is nested in.
+struct Savepoint {
+    // already quoted with `escape_identifier`
+    name: String,
+    depth: u32,
+}
+
 impl<'a> Drop for Transaction<'a> {
     fn drop(&mut self) {
         if self.done {
             return;
         }
 
-        let query = if self.depth == 0 {
-            "ROLLBACK".to_string()
-        } else {
-            format!("ROLLBACK TO sp{}", self.depth)
+        // queue the rollback without waiting for the result, a later request on the client is ordered after it
+        let query = match &self.savepoint {
+            Some(sp) => format!("ROLLBACK TO {}", sp.name),
+            None => "ROLLBACK".to_string(),
         };
         let buf = self.client.inner().with_buf(|buf| {
             frontend::query(&query, buf).unwrap();
@@ -46,7 +60,7 @@ impl<'a> Transaction<'a> {
     pub(crate) fn new(client: &'a mut Client) -> Transaction<'a> {
         Transaction {
             client,
-            depth: 0,
+            savepoint: None,
             done: false,
         }
     }
@@ -54,10 +68,9 @@ impl<'a> Transaction<'a> {
     /// Consumes the transaction, committing all changes made within it.
     pub fn commit(mut self) -> Result<(), Error> {
         self.done = true;
-        let query = if self.depth == 0 {
-            "COMMIT".to_string()
-        } else {
-            format!("RELEASE sp{}", self.depth)
+        let query = match &self.savepoint {
+            Some(sp) => format!("RELEASE {}", sp.name),
+            None => "COMMIT".to_string(),
         };
         self.client.batch_execute(&query)
     }
@@ -67,10 +80,9 @@ impl<'a> Transaction<'a> {
     /// This is equivalent to `Transaction`'s `Drop` implementation, but provides any error encountered to the caller.
     pub fn rollback(mut self) -> Result<(), Error> {
         self.done = true;
-        let query = if self.depth == 0 {
-            "ROLLBACK".to_string()
-        } else {
-            format!("ROLLBACK TO sp{}", self.depth)
+        let query = match &self.savepoint {
+            Some(sp) => format!("ROLLBACK TO {}", sp.name),
+            None => "ROLLBACK".to_string(),
         };
         self.client.batch_execute(&query)
     }
@@ -80,6 +92,11 @@ impl<'a> Transaction<'a> {
         self.client.prepare(query)
     }
 
+    /// Like `Client::prepare_cached`.
+    pub fn prepare_cached(&self, query: &str) -> Result<Statement, Error> {
+        self.client.prepare_cached(query)
+    }
+
     /// Like `Client::prepare_typed`.
     pub fn prepare_typed(&self, query: &str, parameter_types: &[Type]) -> Result<Statement, Error> {
         self.client.prepare_typed(query, parameter_types)
@@ -113,6 +130,30 @@ impl<'a> Transaction<'a> {
         self.client.query_opt(statement, params)
     }
 
+    /// Like `Client::query_with_formats`.
+    pub fn query_with_formats<T>(
+        &self,
+        statement: &T,
+        params: &[&(dyn ToSql + Sync)],
+        formats: &[Format],
+    ) -> Result<Vec<Row>, Error>
+    where
+        T: ?Sized + ToStatement,
+    {
+        self.client.query_with_formats(statement, params, formats)
+    }
+
+    /// Like `Client::query_all`.
+    pub fn query_all<T>(
+        &self,
+        statements: &[(&T, &[&(dyn ToSql + Sync)])],
+    ) -> Vec<Result<Vec<Row>, Error>>
+    where
+        T: ?Sized + ToStatement,
+    {
+        self.client.query_all(statements)
+    }
+
     /// Like `Client::query_raw`.
     pub fn query_raw<'b, T, I>(&self, statement: &T, params: I) -> Result<RowStream, Error>
     where
@@ -123,6 +164,32 @@ impl<'a> Transaction<'a> {
         self.client.query_raw(statement, params)
     }
 
+    /// Like `Client::query_raw_txt`.
+    pub fn query_raw_txt<T>(
+        &self,
+        statement: &T,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<Vec<SimpleQueryRow>, Error>
+    where
+        T: ?Sized + ToStatement,
+    {
+        self.client.query_raw_txt(statement, params)
+    }
+
+    /// Like `Client::query_each`.
+    pub fn query_each<T, F>(
+        &self,
+        statement: &T,
+        params: &[&(dyn ToSql + Sync)],
+        f: F,
+    ) -> Result<u64, Error>
+    where
+        T: ?Sized + ToStatement,
+        F: FnMut(RowRef<'_>) -> Result<(), Error>,
+    {
+        self.client.query_each(statement, params, f)
+    }
+
     /// Like `Client::execute`.
     pub fn execute<T>(&self, statement: &T, params: &[&(dyn ToSql + Sync)]) -> Result<u64, Error>
     where
@@ -141,6 +208,28 @@ impl<'a> Transaction<'a> {
         self.client.execute_raw(statement, params)
     }
 
+    /// Like `Client::execute_with_tag`.
+    pub fn execute_with_tag<T>(
+        &self,
+        statement: &T,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<String, Error>
+    where
+        T: ?Sized + ToStatement,
+    {
+        self.client.execute_with_tag(statement, params)
+    }
+
+    /// Like `Client::insert_many`.
+    pub fn insert_many(
+        &self,
+        table: &str,
+        columns: &[&str],
+        rows: &[&[&(dyn ToSql + Sync)]],
+    ) -> Result<u64, Error> {
+        self.client.insert_many(table, columns, rows)
+    }
+
     /// Binds a statement to a set of parameters, creating a `Portal` which can be incrementally queried.
     ///
     /// Portals only last for the duration of the transaction in which they are created, and can only be used on the
@@ -184,6 +273,11 @@ impl<'a> Transaction<'a> {
         query::query_portal(self.client.inner(), portal, max_rows)
     }
 
+    /// Like `Client::pipeline`.
+    pub fn pipeline(&self) -> Pipeline<'_> {
+        self.client.pipeline()
+    }
+
     /// Like `Client::copy_in`.
     pub fn copy_in<T, U>(&self, statement: &T) -> Result<CopyInSink<U>, Error>
     where
@@ -193,6 +287,14 @@ impl<'a> Transaction<'a> {
         self.client.copy_in(statement)
     }
 
+    /// Like `Client::copy_in_writer`.
+    pub fn copy_in_writer<T>(&self, statement: &T) -> Result<CopyInWriter, Error>
+    where
+        T: ?Sized + ToStatement,
+    {
+        self.client.copy_in_writer(statement)
+    }
+
     /// Like `Client::copy_out`.
     pub fn copy_out<T>(&self, statement: &T) -> Result<CopyOutStream, Error>
     where
@@ -221,15 +323,38 @@ impl<'a> Transaction<'a> {
         self.client.cancel_query()
     }
 
-    /// Like `Client::transaction`.
+    /// Like `Client::transaction`, but creates a nested transaction via a savepoint.
+    ///
+    /// The savepoint is given a name unique to the process, so it can't shadow a savepoint of an enclosing transaction.
     pub fn transaction(&mut self) -> Result<Transaction<'_>, Error> {
-        let depth = self.depth + 1;
-        let query = format!("SAVEPOINT sp{}", depth);
+        self._savepoint(None)
+    }
+
+    /// Like `Client::transaction`, but creates a nested transaction via a savepoint with the specified name.
+    ///
+    /// Committing the returned transaction releases the savepoint, and rolling it back (explicitly or by dropping it)
+    /// rolls back to the savepoint, leaving the changes made by the enclosing transaction in place.
+    ///
+    /// The name is quoted with `escape_identifier`, so it is case sensitive and may contain any character.
+    pub fn savepoint<I>(&mut self, name: I) -> Result<Transaction<'_>, Error>
+    where
+        I: Into<String>,
+    {
+        self._savepoint(Some(name.into()))
+    }
+
+    fn _savepoint(&mut self, name: Option<String>) -> Result<Transaction<'_>, Error> {
+        let depth = self.savepoint.as_ref().map_or(0, |sp| sp.depth) + 1;
+        let name = match name {
+            Some(name) => crate::escape_identifier(&name),
+            None => format!("sp{}", NEXT_ID.fetch_add(1, Ordering::SeqCst)),
+        };
+        let query = format!("SAVEPOINT {}", name);
         self.batch_execute(&query)?;
 
         Ok(Transaction {
             client: self.client,
-            depth,
+            savepoint: Some(Savepoint { name, depth }),
             done: false,
         })
     }
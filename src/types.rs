@@ -1,6 +1,453 @@
 //! Types.
 //!
-//! This module is a reexport of the `postgres_types` crate.
+//! This module is a reexport of the `postgres_types` crate, along with the `pg_enum!` macro.
+//!
+//! # The type catalog
+//!
+//! `Type` has a constant for every built-in type, like `Type::INT4`, `Type::TEXT` or `Type::TIMESTAMPTZ`, and
+//! `Type::from_oid` looks one up by its OID. `Type::kind` tells base types from arrays, ranges, enums, composites,
+//! domains and pseudo-types. User-defined types have OIDs which differ between databases; the client looks them up
+//! in the catalog when a statement uses one, and `Type::new` builds one from its name, OID, kind and schema.
+//!
+//! A domain is transferred like its base type. A parameter of a domain type takes any value its base type does, and
+//! the server describes result columns of a domain as the base type.
+//!
+//! # Date and time types
+//!
+//! With the `with-chrono-0_4` feature, the `chrono` types map to Postgres types as follows:
+//!
+//! | Rust type                       | Postgres type              |
+//! |---------------------------------|----------------------------|
+//! | `chrono::NaiveDateTime`         | `TIMESTAMP`                |
+//! | `chrono::DateTime<Utc>`         | `TIMESTAMP WITH TIME ZONE` |
+//! | `chrono::DateTime<Local>`       | `TIMESTAMP WITH TIME ZONE` |
+//! | `chrono::DateTime<FixedOffset>` | `TIMESTAMP WITH TIME ZONE` |
+//! | `chrono::NaiveDate`             | `DATE`                     |
+//! | `chrono::NaiveTime`             | `TIME`                     |
+//!
+//! Values are sent in the binary format, which counts microseconds (or days for `DATE`) from 2000-01-01. A
+//! `DateTime<FixedOffset>` is read back with a zero offset since the server doesn't store the original one. Servers
+//! built with floating point timestamps, which report `integer_datetimes` as `off`, are refused when connecting.
+//!
+//! Postgres' `infinity` and `-infinity` have no `chrono` equivalent. Reading one into a plain `chrono` type fails with
+//! a conversion error rather than producing a clamped value. Wrap the type in `Timestamp` or `Date` to read and write
+//! them, as `Timestamp::PosInfinity` and `Timestamp::NegInfinity` (or the `Date` variants).
+//!
+//! `INTERVAL` maps to `Interval`, which has separate months, days and microseconds like the server's representation.
+//!
+//! # Numeric types
+//!
+//! `NUMERIC` maps to `Numeric`, which keeps the exact digits and scale of the value, along with `NaN` and the
+//! infinities. It converts to and from decimal text with `Display` and `FromStr`, so it can be handed to a decimal
+//! crate through a string. Reading a `NUMERIC` column into a float is rejected rather than rounded.
+//!
+//! `MONEY` maps to `Money`, which keeps the value as the integer number of units the server stores. It isn't read into
+//! a float, or into an `i64`, to avoid mixing it up with other numbers.
+//!
+//! # Network address types
+//!
+//! `IpAddr` maps to `INET`, but only carries the address. Use `IpCidr` to keep the prefix length, or to read and write
+//! `CIDR` values. `MacAddress` and `MacAddress8` map to `MACADDR` and `MACADDR8`, respectively.
+//!
+//! # Geometric types
+//!
+//! `Point`, `Rect`, `Circle`, `Line`, `LineSegment`, `Path` and `Polygon` map to `POINT`, `BOX`, `CIRCLE`, `LINE`,
+//! `LSEG`, `PATH` and `POLYGON`, respectively, with the coordinates as `f64`s like the server stores them. The
+//! `with-geo-types-0_4` feature maps the `geo_types` types to `POINT`, `BOX` and `PATH` as well.
+//!
+//! # Bit string types
+//!
+//! With the `with-bit-vec-0_6` feature, `bit_vec::BitVec` maps to `BIT(n)` and `VARBIT`, including lengths which
+//! aren't a multiple of 8. The server refuses a `BIT(n)` value whose length differs from `n`.
+//!
+//! # Text search types
+//!
+//! `TsVector` maps to `TSVECTOR`, with the lexemes of the document and their positions and weights, and `TsQuery`
+//! maps to `TSQUERY`. A `TsQuery` can be parsed from the syntax of a `TSQUERY` literal; pass a string to
+//! `to_tsquery($1)` instead to have its words normalized.
+//!
+//! # `void` and `"char"`
+//!
+//! The result of a function returning `void` is read as `Void`. The internal single-byte `"char"` type, which the
+//! catalog uses for flags like `pg_class.relkind`, maps to `i8`; it is distinct from `CHAR(n)`, which maps to `&str`
+//! and `String` like the other character types.
+//!
+//! # Object identifiers
+//!
+//! `u32` maps to `OID`, the type of the `oid` columns of the system catalogs. `RegOid` maps to `OID` as well as to the
+//! `reg*` types aliasing it, like `REGCLASS` or `REGTYPE`, which are transferred as the bare OID.
+//!
+//! # Array types
+//!
+//! `Vec<T>` and `&[T]` map to the array type of the type `T` maps to, like `INT4[]` for `Vec<i32>`. An array can be
+//! passed to `= ANY($1)` to match any of its elements. The server infers the array type when the statement is
+//! prepared; with `Client::query_typed`, give the array type, like `Type::INT4_ARRAY`, rather than the element type.
+//!
+//! # Range types
+//!
+//! `Range<T>` maps to the range types, like `INT4RANGE`, `INT8RANGE`, `NUMRANGE` or `TSRANGE`, whose element type
+//! maps to `T`.
+//!
+//! # Enum types
+//!
+//! The `pg_enum!` macro maps a Rust enum to a Postgres enum type. The OID of an enum type differs between databases,
+//! so the type is matched by name and labels when a statement is prepared instead.
+//!
+//! # Composite types
+//!
+//! The `pg_composite!` macro likewise maps a Rust struct to a Postgres composite type, matched by name and fields.
+//!
+//! # `hstore`
+//!
+//! `HashMap<String, Option<String>>` maps to the `hstore` type of the extension of the same name, with `None` for
+//! keys whose value is `NULL`. It needs no feature. Since the extension's OID differs between databases, the type is
+//! matched by its name, which the client looks up in the catalog when a statement uses it.
 
 #[doc(inline)]
 pub use postgres_types::*;
+
+pub use self::geometry::{Circle, Line, LineSegment, Path, Point, Polygon, Rect};
+pub use self::inet::IpCidr;
+pub use self::interval::Interval;
+pub use self::mac::{MacAddress, MacAddress8};
+pub use self::money::Money;
+pub use self::numeric::Numeric;
+pub use self::range::Range;
+pub use self::reg_oid::RegOid;
+pub use self::text_search::{Lexeme, LexemePosition, QueryLexeme, TsQuery, TsVector, Weight};
+pub use self::void::Void;
+
+mod geometry;
+mod inet;
+mod interval;
+mod mac;
+mod money;
+mod numeric;
+mod range;
+mod reg_oid;
+mod text_search;
+mod void;
+
+/// The format a value is transferred in.
+///
+/// Parameters and results use the binary format unless a query asks for text results with
+/// `Client::query_with_formats`. Text values can be read as strings from a `Row` whatever their Postgres type, which
+/// suits types with no binary representation on the Rust side.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Format {
+    /// The text format, as Postgres prints values.
+    Text,
+    /// The binary format `FromSql` and `ToSql` implementations work with.
+    Binary,
+}
+
+impl Format {
+    pub(crate) fn code(self) -> i16 {
+        match self {
+            Format::Text => 0,
+            Format::Binary => 1,
+        }
+    }
+}
+
+/// Defines a Rust enum which maps to a Postgres enum type.
+///
+/// The Postgres type is named after the `=`, and each variant is followed by its label. The generated `FromSql` and
+/// `ToSql` implementations only accept a type with that name whose labels are exactly the ones listed, in any order.
+/// Values are transferred as their label text, and reading a label which isn't listed is an error.
+///
+/// # Example
+///
+/// ```
+/// may_postgres::pg_enum! {
+///     #[derive(Debug, PartialEq)]
+///     pub enum Mood = "mood" {
+///         Sad = "sad",
+///         Ok = "ok",
+///         Happy = "happy",
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! pg_enum {
+    (
+        $(#[$meta:meta])*
+        $vis:vis enum $name:ident = $pg_name:literal {
+            $($variant:ident = $label:literal),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis enum $name {
+            $($variant),*
+        }
+
+        impl<'a> $crate::types::FromSql<'a> for $name {
+            fn from_sql(
+                _: &$crate::types::Type,
+                raw: &'a [u8],
+            ) -> ::std::result::Result<
+                $name,
+                ::std::boxed::Box<dyn ::std::error::Error + ::std::marker::Sync + ::std::marker::Send>,
+            > {
+                $(
+                    if raw == $label.as_bytes() {
+                        return ::std::result::Result::Ok($name::$variant);
+                    }
+                )*
+                ::std::result::Result::Err($crate::types::__unknown_enum_label($pg_name, raw))
+            }
+
+            fn accepts(ty: &$crate::types::Type) -> bool {
+                $crate::types::__enum_accepts(ty, $pg_name, &[$($label),*])
+            }
+        }
+
+        impl $crate::types::ToSql for $name {
+            fn to_sql(
+                &self,
+                _: &$crate::types::Type,
+                out: &mut $crate::types::private::BytesMut,
+            ) -> ::std::result::Result<
+                $crate::types::IsNull,
+                ::std::boxed::Box<dyn ::std::error::Error + ::std::marker::Sync + ::std::marker::Send>,
+            > {
+                let label: &str = match self {
+                    $($name::$variant => $label),*
+                };
+                out.extend_from_slice(label.as_bytes());
+                ::std::result::Result::Ok($crate::types::IsNull::No)
+            }
+
+            fn accepts(ty: &$crate::types::Type) -> bool {
+                $crate::types::__enum_accepts(ty, $pg_name, &[$($label),*])
+            }
+
+            $crate::types::to_sql_checked!();
+        }
+    };
+}
+
+// WARNING: this function is not considered part of this crate's public API.
+#[doc(hidden)]
+pub fn __enum_accepts(ty: &Type, name: &str, labels: &[&str]) -> bool {
+    if ty.name() != name {
+        return false;
+    }
+
+    match ty.kind() {
+        Kind::Enum(variants) => {
+            variants.len() == labels.len() && variants.iter().all(|v| labels.contains(&&**v))
+        }
+        _ => false,
+    }
+}
+
+// WARNING: this function is not considered part of this crate's public API.
+#[doc(hidden)]
+pub fn __unknown_enum_label(name: &str, raw: &[u8]) -> Box<dyn std::error::Error + Sync + Send> {
+    format!(
+        "unknown label `{}` for enum `{}`",
+        String::from_utf8_lossy(raw),
+        name
+    )
+    .into()
+}
+
+/// Defines a Rust struct which maps to a Postgres composite type.
+///
+/// The Postgres type is named after the `=`. The generated `FromSql` and `ToSql` implementations only accept a type
+/// with that name whose fields have the same names, in the same order, as the struct's fields, and whose field types
+/// map to the types of the struct's fields. Each field is transferred in the binary format along with the OID of its
+/// type, which is checked when reading.
+///
+/// # Example
+///
+/// ```
+/// may_postgres::pg_composite! {
+///     #[derive(Debug, PartialEq)]
+///     pub struct InventoryItem = "inventory_item" {
+///         pub name: String,
+///         pub supplier_id: i32,
+///         pub price: Option<f64>,
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! pg_composite {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident = $pg_name:literal {
+            $($(#[$field_meta:meta])* $field_vis:vis $field:ident: $ty:ty),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $($(#[$field_meta])* $field_vis $field: $ty),*
+        }
+
+        impl<'a> $crate::types::FromSql<'a> for $name {
+            fn from_sql(
+                ty: &$crate::types::Type,
+                mut raw: &'a [u8],
+            ) -> ::std::result::Result<
+                $name,
+                ::std::boxed::Box<dyn ::std::error::Error + ::std::marker::Sync + ::std::marker::Send>,
+            > {
+                let mut fields = $crate::types::__composite_fields(ty, &mut raw)?;
+                $(
+                    let $field: $ty = $crate::types::__read_composite_field(&mut fields, &mut raw)?;
+                )*
+                if !raw.is_empty() {
+                    return ::std::result::Result::Err("invalid buffer size".into());
+                }
+                ::std::result::Result::Ok($name {
+                    $($field),*
+                })
+            }
+
+            fn accepts(ty: &$crate::types::Type) -> bool {
+                $crate::types::__composite_accepts(
+                    ty,
+                    $pg_name,
+                    &[$(stringify!($field)),*],
+                    &[$(<$ty as $crate::types::FromSql<'a>>::accepts),*],
+                )
+            }
+        }
+
+        impl $crate::types::ToSql for $name {
+            fn to_sql(
+                &self,
+                ty: &$crate::types::Type,
+                out: &mut $crate::types::private::BytesMut,
+            ) -> ::std::result::Result<
+                $crate::types::IsNull,
+                ::std::boxed::Box<dyn ::std::error::Error + ::std::marker::Sync + ::std::marker::Send>,
+            > {
+                let mut fields = $crate::types::__write_composite_header(ty, out);
+                $(
+                    $crate::types::__write_composite_field(&mut fields, &self.$field, out)?;
+                )*
+                ::std::result::Result::Ok($crate::types::IsNull::No)
+            }
+
+            fn accepts(ty: &$crate::types::Type) -> bool {
+                $crate::types::__composite_accepts(
+                    ty,
+                    $pg_name,
+                    &[$(stringify!($field)),*],
+                    &[$(<$ty as $crate::types::ToSql>::accepts),*],
+                )
+            }
+
+            $crate::types::to_sql_checked!();
+        }
+    };
+}
+
+// WARNING: this function is not considered part of this crate's public API.
+#[doc(hidden)]
+pub fn __composite_accepts(
+    ty: &Type,
+    name: &str,
+    names: &[&str],
+    accepts: &[fn(&Type) -> bool],
+) -> bool {
+    if ty.name() != name {
+        return false;
+    }
+
+    match ty.kind() {
+        Kind::Composite(pg_fields) => {
+            pg_fields.len() == names.len()
+                && pg_fields
+                    .iter()
+                    .zip(names.iter().zip(accepts))
+                    .all(|(f, (name, accepts))| f.name() == *name && accepts(f.type_()))
+        }
+        _ => false,
+    }
+}
+
+fn composite_fields(ty: &Type) -> &[Field] {
+    match ty.kind() {
+        Kind::Composite(fields) => fields,
+        _ => unreachable!(),
+    }
+}
+
+// WARNING: this function is not considered part of this crate's public API.
+#[doc(hidden)]
+pub fn __composite_fields<'b>(
+    ty: &'b Type,
+    raw: &mut &[u8],
+) -> Result<std::slice::Iter<'b, Field>, Box<dyn std::error::Error + Sync + Send>> {
+    let fields = composite_fields(ty);
+    let count = private::read_be_i32(raw)?;
+    if count < 0 || count as usize != fields.len() {
+        return Err(format!("expected {} fields but got {}", fields.len(), count).into());
+    }
+    Ok(fields.iter())
+}
+
+// WARNING: this function is not considered part of this crate's public API.
+#[doc(hidden)]
+pub fn __read_composite_field<'a, T>(
+    fields: &mut std::slice::Iter<'_, Field>,
+    raw: &mut &'a [u8],
+) -> Result<T, Box<dyn std::error::Error + Sync + Send>>
+where
+    T: FromSql<'a>,
+{
+    let field = fields.next().unwrap();
+    let oid = private::read_be_i32(raw)? as Oid;
+    if oid != field.type_().oid() {
+        return Err(format!(
+            "unexpected type OID {} for field `{}`, expected {}",
+            oid,
+            field.name(),
+            field.type_().oid()
+        )
+        .into());
+    }
+    private::read_value(field.type_(), raw)
+}
+
+// WARNING: this function is not considered part of this crate's public API.
+#[doc(hidden)]
+pub fn __write_composite_header<'b>(
+    ty: &'b Type,
+    out: &mut private::BytesMut,
+) -> std::slice::Iter<'b, Field> {
+    let fields = composite_fields(ty);
+    out.extend_from_slice(&(fields.len() as i32).to_be_bytes());
+    fields.iter()
+}
+
+// WARNING: this function is not considered part of this crate's public API.
+#[doc(hidden)]
+pub fn __write_composite_field<T>(
+    fields: &mut std::slice::Iter<'_, Field>,
+    value: &T,
+    out: &mut private::BytesMut,
+) -> Result<(), Box<dyn std::error::Error + Sync + Send>>
+where
+    T: ToSql,
+{
+    let field = fields.next().unwrap();
+    out.extend_from_slice(&field.type_().oid().to_be_bytes());
+    let base = out.len();
+    out.extend_from_slice(&[0; 4]);
+    let len = match value.to_sql(field.type_(), out)? {
+        IsNull::Yes => -1,
+        IsNull::No => {
+            let len = out.len() - base - 4;
+            if len > i32::MAX as usize {
+                return Err("value too large to transmit".into());
+            }
+            len as i32
+        }
+    };
+    out[base..base + 4].copy_from_slice(&len.to_be_bytes());
+    Ok(())
+}
@@ -0,0 +1,348 @@
+use crate::types::{accepts, to_sql_checked, FromSql, IsNull, ToSql, Type};
+use bytes::{BufMut, BytesMut};
+use std::convert::TryFrom;
+use std::error::Error;
+
+/// A `POINT` value.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct Point {
+    /// The x coordinate.
+    pub x: f64,
+    /// The y coordinate.
+    pub y: f64,
+}
+
+impl Point {
+    /// Creates a new point from its coordinates.
+    pub fn new(x: f64, y: f64) -> Point {
+        Point { x, y }
+    }
+
+    fn read(raw: &mut &[u8]) -> Result<Point, Box<dyn Error + Sync + Send>> {
+        Ok(Point {
+            x: read_f64(raw)?,
+            y: read_f64(raw)?,
+        })
+    }
+
+    fn write(&self, w: &mut BytesMut) {
+        w.put_f64(self.x);
+        w.put_f64(self.y);
+    }
+}
+
+impl<'a> FromSql<'a> for Point {
+    fn from_sql(_: &Type, mut raw: &'a [u8]) -> Result<Point, Box<dyn Error + Sync + Send>> {
+        let point = Point::read(&mut raw)?;
+        check_end(raw)?;
+        Ok(point)
+    }
+
+    accepts!(POINT);
+}
+
+impl ToSql for Point {
+    fn to_sql(&self, _: &Type, w: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        self.write(w);
+        Ok(IsNull::No)
+    }
+
+    accepts!(POINT);
+
+    to_sql_checked!();
+}
+
+/// A `BOX` value.
+///
+/// The server stores a box by its upper right and lower left corners, and swaps the coordinates of the corners it
+/// receives as needed, so a box reads back with `high` holding the largest coordinates whatever the corners it was
+/// written with.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct Rect {
+    /// The upper right corner.
+    pub high: Point,
+    /// The lower left corner.
+    pub low: Point,
+}
+
+impl Rect {
+    /// Creates a new box from two opposite corners.
+    pub fn new(high: Point, low: Point) -> Rect {
+        Rect { high, low }
+    }
+}
+
+impl<'a> FromSql<'a> for Rect {
+    fn from_sql(_: &Type, mut raw: &'a [u8]) -> Result<Rect, Box<dyn Error + Sync + Send>> {
+        let high = Point::read(&mut raw)?;
+        let low = Point::read(&mut raw)?;
+        check_end(raw)?;
+        Ok(Rect { high, low })
+    }
+
+    accepts!(BOX);
+}
+
+impl ToSql for Rect {
+    fn to_sql(&self, _: &Type, w: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        self.high.write(w);
+        self.low.write(w);
+        Ok(IsNull::No)
+    }
+
+    accepts!(BOX);
+
+    to_sql_checked!();
+}
+
+/// A `CIRCLE` value.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct Circle {
+    /// The center.
+    pub center: Point,
+    /// The radius, which the server refuses if negative.
+    pub radius: f64,
+}
+
+impl Circle {
+    /// Creates a new circle from its center and radius.
+    pub fn new(center: Point, radius: f64) -> Circle {
+        Circle { center, radius }
+    }
+}
+
+impl<'a> FromSql<'a> for Circle {
+    fn from_sql(_: &Type, mut raw: &'a [u8]) -> Result<Circle, Box<dyn Error + Sync + Send>> {
+        let center = Point::read(&mut raw)?;
+        let radius = read_f64(&mut raw)?;
+        check_end(raw)?;
+        Ok(Circle { center, radius })
+    }
+
+    accepts!(CIRCLE);
+}
+
+impl ToSql for Circle {
+    fn to_sql(&self, _: &Type, w: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        self.center.write(w);
+        w.put_f64(self.radius);
+        Ok(IsNull::No)
+    }
+
+    accepts!(CIRCLE);
+
+    to_sql_checked!();
+}
+
+/// A `LINE` value, the infinite line of the points for which `a * x + b * y + c = 0`.
+///
+/// The server refuses a line with both `a` and `b` zero.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct Line {
+    /// The coefficient of x.
+    pub a: f64,
+    /// The coefficient of y.
+    pub b: f64,
+    /// The constant.
+    pub c: f64,
+}
+
+impl Line {
+    /// Creates a new line from the coefficients of its equation.
+    pub fn new(a: f64, b: f64, c: f64) -> Line {
+        Line { a, b, c }
+    }
+}
+
+impl<'a> FromSql<'a> for Line {
+    fn from_sql(_: &Type, mut raw: &'a [u8]) -> Result<Line, Box<dyn Error + Sync + Send>> {
+        let line = Line {
+            a: read_f64(&mut raw)?,
+            b: read_f64(&mut raw)?,
+            c: read_f64(&mut raw)?,
+        };
+        check_end(raw)?;
+        Ok(line)
+    }
+
+    accepts!(LINE);
+}
+
+impl ToSql for Line {
+    fn to_sql(&self, _: &Type, w: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        w.put_f64(self.a);
+        w.put_f64(self.b);
+        w.put_f64(self.c);
+        Ok(IsNull::No)
+    }
+
+    accepts!(LINE);
+
+    to_sql_checked!();
+}
+
+/// An `LSEG` value, the line segment between two points.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct LineSegment {
+    /// The first end point.
+    pub start: Point,
+    /// The second end point.
+    pub end: Point,
+}
+
+impl LineSegment {
+    /// Creates a new segment from its end points.
+    pub fn new(start: Point, end: Point) -> LineSegment {
+        LineSegment { start, end }
+    }
+}
+
+impl<'a> FromSql<'a> for LineSegment {
+    fn from_sql(_: &Type, mut raw: &'a [u8]) -> Result<LineSegment, Box<dyn Error + Sync + Send>> {
+        let start = Point::read(&mut raw)?;
+        let end = Point::read(&mut raw)?;
+        check_end(raw)?;
+        Ok(LineSegment { start, end })
+    }
+
+    accepts!(LSEG);
+}
+
+impl ToSql for LineSegment {
+    fn to_sql(&self, _: &Type, w: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        self.start.write(w);
+        self.end.write(w);
+        Ok(IsNull::No)
+    }
+
+    accepts!(LSEG);
+
+    to_sql_checked!();
+}
+
+/// A `PATH` value, a series of connected points which is either open or closed back to the first point.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Path {
+    /// Determines if the last point connects back to the first.
+    pub closed: bool,
+    /// The points.
+    pub points: Vec<Point>,
+}
+
+impl Path {
+    /// Creates a new path from its points.
+    pub fn new(closed: bool, points: Vec<Point>) -> Path {
+        Path { closed, points }
+    }
+}
+
+impl<'a> FromSql<'a> for Path {
+    fn from_sql(_: &Type, mut raw: &'a [u8]) -> Result<Path, Box<dyn Error + Sync + Send>> {
+        let closed = match raw.split_first() {
+            Some((&closed, rest)) => {
+                raw = rest;
+                closed != 0
+            }
+            None => return Err("invalid message length: path length mismatch".into()),
+        };
+        let points = read_points(&mut raw)?;
+        check_end(raw)?;
+        Ok(Path { closed, points })
+    }
+
+    accepts!(PATH);
+}
+
+impl ToSql for Path {
+    fn to_sql(&self, _: &Type, w: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        w.put_u8(self.closed as u8);
+        write_points(&self.points, w)?;
+        Ok(IsNull::No)
+    }
+
+    accepts!(PATH);
+
+    to_sql_checked!();
+}
+
+/// A `POLYGON` value, given by its vertices.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Polygon {
+    /// The vertices.
+    pub points: Vec<Point>,
+}
+
+impl Polygon {
+    /// Creates a new polygon from its vertices.
+    pub fn new(points: Vec<Point>) -> Polygon {
+        Polygon { points }
+    }
+}
+
+impl<'a> FromSql<'a> for Polygon {
+    fn from_sql(_: &Type, mut raw: &'a [u8]) -> Result<Polygon, Box<dyn Error + Sync + Send>> {
+        let points = read_points(&mut raw)?;
+        check_end(raw)?;
+        Ok(Polygon { points })
+    }
+
+    accepts!(POLYGON);
+}
+
+impl ToSql for Polygon {
+    fn to_sql(&self, _: &Type, w: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        write_points(&self.points, w)?;
+        Ok(IsNull::No)
+    }
+
+    accepts!(POLYGON);
+
+    to_sql_checked!();
+}
+
+fn read_f64(raw: &mut &[u8]) -> Result<f64, Box<dyn Error + Sync + Send>> {
+    if raw.len() < 8 {
+        return Err("invalid message length: geometric value length mismatch".into());
+    }
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&raw[..8]);
+    *raw = &raw[8..];
+    Ok(f64::from_be_bytes(bytes))
+}
+
+fn check_end(raw: &[u8]) -> Result<(), Box<dyn Error + Sync + Send>> {
+    if raw.is_empty() {
+        Ok(())
+    } else {
+        Err("invalid message length: geometric value length mismatch".into())
+    }
+}
+
+fn read_points(raw: &mut &[u8]) -> Result<Vec<Point>, Box<dyn Error + Sync + Send>> {
+    if raw.len() < 4 {
+        return Err("invalid message length: geometric value length mismatch".into());
+    }
+    let mut len = [0; 4];
+    len.copy_from_slice(&raw[..4]);
+    *raw = &raw[4..];
+    let len = i32::from_be_bytes(len);
+    // a corrupt count must not allocate more than the data can hold
+    if len < 0 || len as usize > raw.len() / 16 {
+        return Err("invalid point count".into());
+    }
+
+    let mut points = Vec::with_capacity(len as usize);
+    for _ in 0..len {
+        points.push(Point::read(raw)?);
+    }
+    Ok(points)
+}
+
+fn write_points(points: &[Point], w: &mut BytesMut) -> Result<(), Box<dyn Error + Sync + Send>> {
+    let len = i32::try_from(points.len()).map_err(|_| "too many points")?;
+    w.put_i32(len);
+    for point in points {
+        point.write(w);
+    }
+    Ok(())
+}
@@ -0,0 +1,73 @@
+use crate::types::{accepts, to_sql_checked, FromSql, IsNull, ToSql, Type};
+use bytes::BytesMut;
+use postgres_protocol::types;
+use std::error::Error;
+use std::fmt;
+use std::net::IpAddr;
+
+/// An IP address along with a network prefix length, as stored by `INET` and `CIDR` values.
+///
+/// `IpAddr` only reads the address of an `INET` value and drops the prefix length, while this type keeps both and
+/// accepts `CIDR` as well. The server rejects a `CIDR` value with bits set to the right of the prefix, such as
+/// `192.168.1.1/24`; `INET` allows them.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct IpCidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl IpCidr {
+    /// Creates a new value.
+    ///
+    /// Returns an error if `prefix` is longer than the address, i.e. more than 32 bits for IPv4 or 128 bits for
+    /// IPv6.
+    pub fn new(addr: IpAddr, prefix: u8) -> Result<IpCidr, Box<dyn Error + Sync + Send>> {
+        let max = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        if prefix > max {
+            return Err(format!("invalid prefix length {} for {}", prefix, addr).into());
+        }
+        Ok(IpCidr { addr, prefix })
+    }
+
+    /// Returns the address.
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    /// Returns the network prefix length in bits.
+    pub fn prefix(&self) -> u8 {
+        self.prefix
+    }
+}
+
+impl fmt::Display for IpCidr {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+impl<'a> FromSql<'a> for IpCidr {
+    fn from_sql(_: &Type, raw: &'a [u8]) -> Result<IpCidr, Box<dyn Error + Sync + Send>> {
+        let inet = types::inet_from_sql(raw)?;
+        Ok(IpCidr {
+            addr: inet.addr(),
+            prefix: inet.netmask(),
+        })
+    }
+
+    accepts!(INET, CIDR);
+}
+
+impl ToSql for IpCidr {
+    fn to_sql(&self, _: &Type, w: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        types::inet_to_sql(self.addr, self.prefix, w);
+        Ok(IsNull::No)
+    }
+
+    accepts!(INET, CIDR);
+
+    to_sql_checked!();
+}
@@ -0,0 +1,63 @@
+use crate::types::{accepts, to_sql_checked, FromSql, IsNull, ToSql, Type};
+use bytes::{BufMut, BytesMut};
+use std::error::Error;
+
+/// An `INTERVAL` value.
+///
+/// The server keeps the three parts apart, since the length of a month or a day depends on the date the interval is
+/// added to, so `'1 month'` and `'30 days'` are different values here. The parts may have different signs, like in
+/// `'1 month -1 day'`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Interval {
+    /// The number of months.
+    pub months: i32,
+    /// The number of days.
+    pub days: i32,
+    /// The number of microseconds.
+    pub microseconds: i64,
+}
+
+impl Interval {
+    /// Creates a new interval from its parts.
+    pub fn new(months: i32, days: i32, microseconds: i64) -> Interval {
+        Interval {
+            months,
+            days,
+            microseconds,
+        }
+    }
+}
+
+impl<'a> FromSql<'a> for Interval {
+    fn from_sql(_: &Type, raw: &'a [u8]) -> Result<Interval, Box<dyn Error + Sync + Send>> {
+        if raw.len() != 16 {
+            return Err("invalid message length: interval length mismatch".into());
+        }
+        let mut microseconds = [0; 8];
+        microseconds.copy_from_slice(&raw[..8]);
+        let mut days = [0; 4];
+        days.copy_from_slice(&raw[8..12]);
+        let mut months = [0; 4];
+        months.copy_from_slice(&raw[12..]);
+        Ok(Interval {
+            months: i32::from_be_bytes(months),
+            days: i32::from_be_bytes(days),
+            microseconds: i64::from_be_bytes(microseconds),
+        })
+    }
+
+    accepts!(INTERVAL);
+}
+
+impl ToSql for Interval {
+    fn to_sql(&self, _: &Type, w: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        w.put_i64(self.microseconds);
+        w.put_i32(self.days);
+        w.put_i32(self.months);
+        Ok(IsNull::No)
+    }
+
+    accepts!(INTERVAL);
+
+    to_sql_checked!();
+}
@@ -0,0 +1,117 @@
+use crate::types::{accepts, to_sql_checked, FromSql, IsNull, ToSql, Type};
+use bytes::{BufMut, BytesMut};
+use postgres_protocol::types;
+use std::error::Error;
+use std::fmt;
+
+/// A 6 byte MAC address, as stored by `MACADDR` values.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MacAddress([u8; 6]);
+
+impl MacAddress {
+    /// Creates a new address from its bytes.
+    pub fn new(bytes: [u8; 6]) -> MacAddress {
+        MacAddress(bytes)
+    }
+
+    /// Returns the bytes of the address.
+    pub fn as_bytes(&self) -> &[u8; 6] {
+        &self.0
+    }
+}
+
+impl From<[u8; 6]> for MacAddress {
+    fn from(bytes: [u8; 6]) -> MacAddress {
+        MacAddress(bytes)
+    }
+}
+
+impl fmt::Display for MacAddress {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_hex(&self.0, fmt)
+    }
+}
+
+impl<'a> FromSql<'a> for MacAddress {
+    fn from_sql(_: &Type, raw: &'a [u8]) -> Result<MacAddress, Box<dyn Error + Sync + Send>> {
+        types::macaddr_from_sql(raw).map(MacAddress)
+    }
+
+    accepts!(MACADDR);
+}
+
+impl ToSql for MacAddress {
+    fn to_sql(&self, _: &Type, w: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        types::macaddr_to_sql(self.0, w);
+        Ok(IsNull::No)
+    }
+
+    accepts!(MACADDR);
+
+    to_sql_checked!();
+}
+
+/// An 8 byte (EUI-64) MAC address, as stored by `MACADDR8` values.
+///
+/// The server stores a 6 byte address cast to `MACADDR8` with `FF:FE` inserted in the middle, so it reads back as 8
+/// bytes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MacAddress8([u8; 8]);
+
+impl MacAddress8 {
+    /// Creates a new address from its bytes.
+    pub fn new(bytes: [u8; 8]) -> MacAddress8 {
+        MacAddress8(bytes)
+    }
+
+    /// Returns the bytes of the address.
+    pub fn as_bytes(&self) -> &[u8; 8] {
+        &self.0
+    }
+}
+
+impl From<[u8; 8]> for MacAddress8 {
+    fn from(bytes: [u8; 8]) -> MacAddress8 {
+        MacAddress8(bytes)
+    }
+}
+
+impl fmt::Display for MacAddress8 {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_hex(&self.0, fmt)
+    }
+}
+
+impl<'a> FromSql<'a> for MacAddress8 {
+    fn from_sql(_: &Type, raw: &'a [u8]) -> Result<MacAddress8, Box<dyn Error + Sync + Send>> {
+        if raw.len() != 8 {
+            return Err("invalid message length: macaddr8 length mismatch".into());
+        }
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(raw);
+        Ok(MacAddress8(bytes))
+    }
+
+    accepts!(MACADDR8);
+}
+
+impl ToSql for MacAddress8 {
+    fn to_sql(&self, _: &Type, w: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        w.put_slice(&self.0);
+        Ok(IsNull::No)
+    }
+
+    accepts!(MACADDR8);
+
+    to_sql_checked!();
+}
+
+fn write_hex(bytes: &[u8], fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for (i, b) in bytes.iter().enumerate() {
+        if i > 0 {
+            fmt.write_str(":")?;
+        }
+        write!(fmt, "{:02x}", b)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,48 @@
+use crate::types::{accepts, to_sql_checked, FromSql, IsNull, ToSql, Type};
+use bytes::BytesMut;
+use postgres_protocol::types;
+use std::error::Error;
+
+/// A `MONEY` value, counted in the smallest unit of the currency.
+///
+/// The number of fractional digits depends on the server's `lc_monetary` setting, which is 2 for most currencies, so
+/// `Money::new(1050)` usually is `$10.50`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Money(i64);
+
+impl Money {
+    /// Creates a new value from a number of units.
+    pub fn new(units: i64) -> Money {
+        Money(units)
+    }
+
+    /// Returns the number of units.
+    pub fn units(&self) -> i64 {
+        self.0
+    }
+}
+
+impl From<i64> for Money {
+    fn from(units: i64) -> Money {
+        Money(units)
+    }
+}
+
+impl<'a> FromSql<'a> for Money {
+    fn from_sql(_: &Type, raw: &'a [u8]) -> Result<Money, Box<dyn Error + Sync + Send>> {
+        types::int8_from_sql(raw).map(Money)
+    }
+
+    accepts!(MONEY);
+}
+
+impl ToSql for Money {
+    fn to_sql(&self, _: &Type, w: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        types::int8_to_sql(self.0, w);
+        Ok(IsNull::No)
+    }
+
+    accepts!(MONEY);
+
+    to_sql_checked!();
+}
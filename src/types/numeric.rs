@@ -0,0 +1,294 @@
+use crate::types::{accepts, to_sql_checked, FromSql, IsNull, ToSql, Type};
+use bytes::{BufMut, BytesMut};
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+// the sign word of the binary format, which also marks the special values
+const POSITIVE: u16 = 0x0000;
+const NEGATIVE: u16 = 0x4000;
+const NAN: u16 = 0xC000;
+const POS_INFINITY: u16 = 0xD000;
+const NEG_INFINITY: u16 = 0xF000;
+
+// the largest display scale the server accepts
+const MAX_SCALE: u16 = 0x3FFF;
+
+/// A `NUMERIC` value.
+///
+/// It keeps the value exactly as the server stores it: the digits in base 10000, the weight of the first of them, and
+/// the display scale, the number of decimal digits after the point. Values are created from and turned into their
+/// decimal text with `FromStr` and `Display`, which accept and print the same forms as the server, including `NaN`,
+/// `Infinity` and `-Infinity`.
+///
+/// The scale is part of the value, so `1.5` and `1.50` are not equal, just like their text forms differ.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Numeric {
+    sign: u16,
+    weight: i16,
+    scale: u16,
+    digits: Vec<i16>,
+}
+
+impl Numeric {
+    /// Returns `NaN`.
+    pub fn nan() -> Numeric {
+        Numeric::special(NAN)
+    }
+
+    /// Returns `Infinity`, or `-Infinity` if `negative` is `true`.
+    ///
+    /// Infinite values need a server running Postgres 14 or later.
+    pub fn infinity(negative: bool) -> Numeric {
+        Numeric::special(if negative { NEG_INFINITY } else { POS_INFINITY })
+    }
+
+    fn special(sign: u16) -> Numeric {
+        Numeric {
+            sign,
+            weight: 0,
+            scale: 0,
+            digits: vec![],
+        }
+    }
+
+    /// Determines if the value is `NaN`.
+    pub fn is_nan(&self) -> bool {
+        self.sign == NAN
+    }
+
+    /// Determines if the value is `Infinity` or `-Infinity`.
+    pub fn is_infinite(&self) -> bool {
+        self.sign == POS_INFINITY || self.sign == NEG_INFINITY
+    }
+
+    /// Determines if the value is less than zero.
+    pub fn is_sign_negative(&self) -> bool {
+        self.sign == NEGATIVE || self.sign == NEG_INFINITY
+    }
+
+    /// Returns the number of decimal digits after the point.
+    pub fn scale(&self) -> u16 {
+        self.scale
+    }
+
+    // strips the zero digits at either end, which carry no value, as the server does
+    fn finite(
+        negative: bool,
+        mut weight: i64,
+        scale: u16,
+        mut digits: Vec<i16>,
+    ) -> Result<Numeric, Box<dyn Error + Sync + Send>> {
+        let leading = digits.iter().take_while(|d| **d == 0).count();
+        digits.drain(..leading);
+        weight -= leading as i64;
+        while digits.last() == Some(&0) {
+            digits.pop();
+        }
+        if digits.is_empty() {
+            return Ok(Numeric {
+                sign: POSITIVE,
+                weight: 0,
+                scale,
+                digits,
+            });
+        }
+
+        if weight < i64::from(i16::MIN)
+            || weight > i64::from(i16::MAX)
+            || digits.len() > i16::MAX as usize
+        {
+            return Err("numeric value out of range".into());
+        }
+        Ok(Numeric {
+            sign: if negative { NEGATIVE } else { POSITIVE },
+            weight: weight as i16,
+            scale,
+            digits,
+        })
+    }
+
+    // the base 10000 digit worth 10000^power
+    fn digit(&self, power: i32) -> i16 {
+        let i = i32::from(self.weight) - power;
+        if i < 0 {
+            return 0;
+        }
+        self.digits.get(i as usize).copied().unwrap_or(0)
+    }
+}
+
+impl From<i64> for Numeric {
+    fn from(value: i64) -> Numeric {
+        let mut abs = value.unsigned_abs();
+        let mut digits = vec![];
+        while abs > 0 {
+            digits.push((abs % 10_000) as i16);
+            abs /= 10_000;
+        }
+        digits.reverse();
+        let weight = digits.len() as i64 - 1;
+        // an i64 has at most 5 digits in base 10000
+        Numeric::finite(value < 0, weight, 0, digits).unwrap()
+    }
+}
+
+impl From<i32> for Numeric {
+    fn from(value: i32) -> Numeric {
+        Numeric::from(i64::from(value))
+    }
+}
+
+impl fmt::Display for Numeric {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.sign {
+            NAN => return fmt.write_str("NaN"),
+            POS_INFINITY => return fmt.write_str("Infinity"),
+            NEG_INFINITY => return fmt.write_str("-Infinity"),
+            _ => {}
+        }
+
+        let mut out = String::new();
+        if self.sign == NEGATIVE {
+            out.push('-');
+        }
+        if self.weight < 0 {
+            out.push('0');
+        } else {
+            out.push_str(&self.digit(i32::from(self.weight)).to_string());
+            for power in (0..i32::from(self.weight)).rev() {
+                out.push_str(&format!("{:04}", self.digit(power)));
+            }
+        }
+        if self.scale > 0 {
+            out.push('.');
+            let mut fraction = String::new();
+            let mut power = -1;
+            while fraction.len() < usize::from(self.scale) {
+                fraction.push_str(&format!("{:04}", self.digit(power)));
+                power -= 1;
+            }
+            out.push_str(&fraction[..usize::from(self.scale)]);
+        }
+        fmt.write_str(&out)
+    }
+}
+
+impl FromStr for Numeric {
+    type Err = Box<dyn Error + Sync + Send>;
+
+    /// Parses a decimal number like the server does, such as `-12.50`, `1e-3`, `NaN` or `Infinity`.
+    fn from_str(s: &str) -> Result<Numeric, Self::Err> {
+        let s = s.trim();
+        match s.to_ascii_lowercase().as_str() {
+            "nan" => return Ok(Numeric::nan()),
+            "infinity" | "+infinity" | "inf" | "+inf" => return Ok(Numeric::infinity(false)),
+            "-infinity" | "-inf" => return Ok(Numeric::infinity(true)),
+            _ => {}
+        }
+
+        let (negative, rest) = match s.as_bytes().first() {
+            Some(b'-') => (true, &s[1..]),
+            Some(b'+') => (false, &s[1..]),
+            _ => (false, s),
+        };
+        let (mantissa, exponent) = match rest.find(['e', 'E']) {
+            Some(i) => {
+                let exponent = rest[i + 1..]
+                    .parse::<i32>()
+                    .map_err(|_| format!("invalid numeric exponent: {}", s))?;
+                (&rest[..i], exponent)
+            }
+            None => (rest, 0),
+        };
+        let (integer, fraction) = match mantissa.find('.') {
+            Some(i) => (&mantissa[..i], &mantissa[i + 1..]),
+            None => (mantissa, ""),
+        };
+        if integer.is_empty() && fraction.is_empty()
+            || !integer
+                .bytes()
+                .chain(fraction.bytes())
+                .all(|b| b.is_ascii_digit())
+        {
+            return Err(format!("invalid numeric value: {}", s).into());
+        }
+
+        // the decimal digits, with the point after the first `point` of them
+        let decimal = integer.bytes().chain(fraction.bytes()).map(|b| b - b'0');
+        let point = integer.len() as i64 + i64::from(exponent);
+        let scale = (fraction.len() as i64 - i64::from(exponent)).max(0);
+        if scale > i64::from(MAX_SCALE) {
+            return Err(format!("numeric scale out of range: {}", s).into());
+        }
+
+        // pad with zeros on the left, so that the point falls between two groups of four digits
+        let lead = (4 - point.rem_euclid(4)) % 4;
+        let mut digits = vec![];
+        let mut group = 0;
+        let mut len = 0;
+        for d in std::iter::repeat_n(0, lead as usize).chain(decimal) {
+            group = group * 10 + i16::from(d);
+            len += 1;
+            if len == 4 {
+                digits.push(group);
+                group = 0;
+                len = 0;
+            }
+        }
+        if len > 0 {
+            digits.push(group * 10i16.pow(4 - len));
+        }
+
+        let weight = (point + lead) / 4 - 1;
+        Numeric::finite(negative, weight, scale as u16, digits)
+    }
+}
+
+impl<'a> FromSql<'a> for Numeric {
+    fn from_sql(_: &Type, raw: &'a [u8]) -> Result<Numeric, Box<dyn Error + Sync + Send>> {
+        if raw.len() < 8 {
+            return Err("invalid message length: numeric header mismatch".into());
+        }
+        let word = |i: usize| u16::from_be_bytes([raw[i], raw[i + 1]]);
+        let ndigits = usize::from(word(0));
+        let weight = word(2) as i16;
+        let sign = word(4);
+        let scale = word(6);
+        if raw.len() != 8 + 2 * ndigits {
+            return Err("invalid message length: numeric digits mismatch".into());
+        }
+
+        match sign {
+            NAN | POS_INFINITY | NEG_INFINITY => return Ok(Numeric::special(sign)),
+            POSITIVE | NEGATIVE => {}
+            _ => return Err(format!("invalid numeric sign: {:#06x}", sign).into()),
+        }
+        let digits = (0..ndigits)
+            .map(|i| word(8 + 2 * i) as i16)
+            .collect::<Vec<_>>();
+        if digits.iter().any(|d| !(0..10_000).contains(d)) {
+            return Err("invalid numeric digit".into());
+        }
+        Numeric::finite(sign == NEGATIVE, i64::from(weight), scale, digits)
+    }
+
+    accepts!(NUMERIC);
+}
+
+impl ToSql for Numeric {
+    fn to_sql(&self, _: &Type, w: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        w.put_u16(self.digits.len() as u16);
+        w.put_i16(self.weight);
+        w.put_u16(self.sign);
+        w.put_u16(self.scale);
+        for digit in &self.digits {
+            w.put_i16(*digit);
+        }
+        Ok(IsNull::No)
+    }
+
+    accepts!(NUMERIC);
+
+    to_sql_checked!();
+}
@@ -0,0 +1,135 @@
+use crate::types::{to_sql_checked, FromSql, IsNull, Kind, ToSql, Type};
+use bytes::BytesMut;
+use postgres_protocol::types::{self, RangeBound};
+use std::error::Error;
+use std::ops::Bound;
+
+/// A Postgres range, such as an `INT4RANGE` or `TSRANGE` value.
+///
+/// It maps to any range type whose element type maps to `T`. Postgres normalizes the ranges of discrete types, so
+/// `[1,10]` is read back from an `INT4RANGE` as `[1,11)`, and a range with no values, like `[5,5)`, is read back as
+/// `Range::Empty`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Range<T> {
+    /// An empty range.
+    Empty,
+    /// A nonempty range. Either bound may be `Bound::Unbounded`.
+    Nonempty(Bound<T>, Bound<T>),
+}
+
+impl<T> Range<T> {
+    /// Creates a range with the given bounds.
+    pub fn new(lower: Bound<T>, upper: Bound<T>) -> Range<T> {
+        Range::Nonempty(lower, upper)
+    }
+
+    /// Creates an empty range.
+    pub fn empty() -> Range<T> {
+        Range::Empty
+    }
+
+    /// Determines if the range is empty.
+    pub fn is_empty(&self) -> bool {
+        matches!(self, Range::Empty)
+    }
+
+    /// Returns the lower bound, or `None` if the range is empty.
+    pub fn lower(&self) -> Option<Bound<&T>> {
+        match self {
+            Range::Empty => None,
+            Range::Nonempty(lower, _) => Some(lower.as_ref()),
+        }
+    }
+
+    /// Returns the upper bound, or `None` if the range is empty.
+    pub fn upper(&self) -> Option<Bound<&T>> {
+        match self {
+            Range::Empty => None,
+            Range::Nonempty(_, upper) => Some(upper.as_ref()),
+        }
+    }
+}
+
+fn element(ty: &Type) -> &Type {
+    match *ty.kind() {
+        Kind::Range(ref inner) => inner,
+        _ => unreachable!(),
+    }
+}
+
+impl<'a, T: FromSql<'a>> FromSql<'a> for Range<T> {
+    fn from_sql(ty: &Type, raw: &'a [u8]) -> Result<Range<T>, Box<dyn Error + Sync + Send>> {
+        let member = element(ty);
+        match types::range_from_sql(raw)? {
+            types::Range::Empty => Ok(Range::Empty),
+            types::Range::Nonempty(lower, upper) => Ok(Range::Nonempty(
+                read_bound(member, lower)?,
+                read_bound(member, upper)?,
+            )),
+        }
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        match *ty.kind() {
+            Kind::Range(ref inner) => T::accepts(inner),
+            _ => false,
+        }
+    }
+}
+
+fn read_bound<'a, T: FromSql<'a>>(
+    member: &Type,
+    bound: RangeBound<Option<&'a [u8]>>,
+) -> Result<Bound<T>, Box<dyn Error + Sync + Send>> {
+    let value = |raw: Option<&'a [u8]>| match raw {
+        Some(raw) => T::from_sql(member, raw),
+        None => Err("unexpected NULL range bound".into()),
+    };
+    match bound {
+        RangeBound::Inclusive(raw) => value(raw).map(Bound::Included),
+        RangeBound::Exclusive(raw) => value(raw).map(Bound::Excluded),
+        RangeBound::Unbounded => Ok(Bound::Unbounded),
+    }
+}
+
+impl<T: ToSql> ToSql for Range<T> {
+    fn to_sql(&self, ty: &Type, w: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        match self {
+            Range::Empty => types::empty_range_to_sql(w),
+            Range::Nonempty(lower, upper) => {
+                let member = element(ty);
+                types::range_to_sql(
+                    |buf| write_bound(member, lower, buf),
+                    |buf| write_bound(member, upper, buf),
+                    w,
+                )?;
+            }
+        }
+        Ok(IsNull::No)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        match *ty.kind() {
+            Kind::Range(ref inner) => T::accepts(inner),
+            _ => false,
+        }
+    }
+
+    to_sql_checked!();
+}
+
+fn write_bound<T: ToSql>(
+    member: &Type,
+    bound: &Bound<T>,
+    buf: &mut BytesMut,
+) -> Result<RangeBound<postgres_protocol::IsNull>, Box<dyn Error + Sync + Send>> {
+    let value = |value: &T, buf: &mut BytesMut| match value.to_sql(member, buf)? {
+        IsNull::No => Ok(postgres_protocol::IsNull::No),
+        IsNull::Yes => Err("unexpected NULL range bound".into()),
+    };
+    match bound {
+        Bound::Included(v) => value(v, buf).map(RangeBound::Inclusive),
+        Bound::Excluded(v) => value(v, buf).map(RangeBound::Exclusive),
+        Bound::Unbounded => Ok(RangeBound::Unbounded),
+    }
+}
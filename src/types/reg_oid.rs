@@ -0,0 +1,78 @@
+use crate::types::{accepts, to_sql_checked, FromSql, IsNull, ToSql, Type};
+use bytes::BytesMut;
+use postgres_protocol::types;
+use std::error::Error;
+
+/// An OID, read from or written to the `oid` type or any of the `reg*` types aliasing it, like `regclass`.
+///
+/// `u32` maps to `oid` alone. A `reg*` value is transferred as the bare OID; cast it to `TEXT` to get the object's
+/// name instead.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RegOid(u32);
+
+impl RegOid {
+    /// Creates a new value from an OID.
+    pub fn new(oid: u32) -> RegOid {
+        RegOid(oid)
+    }
+
+    /// Returns the OID.
+    pub fn oid(&self) -> u32 {
+        self.0
+    }
+}
+
+impl From<u32> for RegOid {
+    fn from(oid: u32) -> RegOid {
+        RegOid(oid)
+    }
+}
+
+impl From<RegOid> for u32 {
+    fn from(oid: RegOid) -> u32 {
+        oid.0
+    }
+}
+
+impl<'a> FromSql<'a> for RegOid {
+    fn from_sql(_: &Type, raw: &'a [u8]) -> Result<RegOid, Box<dyn Error + Sync + Send>> {
+        types::oid_from_sql(raw).map(RegOid)
+    }
+
+    accepts!(
+        OID,
+        REGPROC,
+        REGPROCEDURE,
+        REGOPER,
+        REGOPERATOR,
+        REGCLASS,
+        REGTYPE,
+        REGCONFIG,
+        REGDICTIONARY,
+        REGNAMESPACE,
+        REGROLE
+    );
+}
+
+impl ToSql for RegOid {
+    fn to_sql(&self, _: &Type, w: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        types::oid_to_sql(self.0, w);
+        Ok(IsNull::No)
+    }
+
+    accepts!(
+        OID,
+        REGPROC,
+        REGPROCEDURE,
+        REGOPER,
+        REGOPERATOR,
+        REGCLASS,
+        REGTYPE,
+        REGCONFIG,
+        REGDICTIONARY,
+        REGNAMESPACE,
+        REGROLE
+    );
+
+    to_sql_checked!();
+}
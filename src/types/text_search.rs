@@ -0,0 +1,635 @@
+use crate::types::{accepts, to_sql_checked, FromSql, IsNull, ToSql, Type};
+use bytes::{BufMut, BytesMut};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::str::{self, FromStr};
+
+const MAX_POSITION: u16 = 0x3fff;
+
+const QI_VAL: u8 = 1;
+const QI_OPR: u8 = 2;
+
+const OP_NOT: u8 = 1;
+const OP_AND: u8 = 2;
+const OP_OR: u8 = 3;
+const OP_PHRASE: u8 = 4;
+
+// the most operators a `TsQuery` that is parsed or read can nest. Working with a query, like dropping or comparing
+// it, recurses once per level, which has to fit on a coroutine's stack.
+const MAX_DEPTH: usize = 32;
+
+/// The weight of a lexeme position, `A` being the highest.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub enum Weight {
+    /// The `A` weight.
+    A,
+    /// The `B` weight.
+    B,
+    /// The `C` weight.
+    C,
+    /// The `D` weight, which positions have unless given another one.
+    #[default]
+    D,
+}
+
+impl Weight {
+    fn from_bits(bits: u16) -> Weight {
+        match bits {
+            3 => Weight::A,
+            2 => Weight::B,
+            1 => Weight::C,
+            _ => Weight::D,
+        }
+    }
+
+    fn bits(self) -> u16 {
+        match self {
+            Weight::A => 3,
+            Weight::B => 2,
+            Weight::C => 1,
+            Weight::D => 0,
+        }
+    }
+
+    // the bit of the weight in the mask of a query lexeme
+    fn mask(self) -> u8 {
+        1 << self.bits()
+    }
+}
+
+/// A `TSVECTOR` value, the sorted list of the distinct lexemes of a document.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TsVector {
+    /// The lexemes.
+    pub lexemes: Vec<Lexeme>,
+}
+
+/// A lexeme of a `TsVector`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Lexeme {
+    /// The normalized word.
+    pub word: String,
+    /// The positions of the word in the document, in increasing order. A vector built without positions, like with
+    /// `strip`, has none.
+    pub positions: Vec<LexemePosition>,
+}
+
+/// A position of a `Lexeme`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct LexemePosition {
+    /// The position, from 1 to 16383. The server counts positions past the last as the last.
+    pub position: u16,
+    /// The weight.
+    pub weight: Weight,
+}
+
+impl LexemePosition {
+    /// Creates a new position.
+    pub fn new(position: u16, weight: Weight) -> LexemePosition {
+        LexemePosition { position, weight }
+    }
+}
+
+impl<'a> FromSql<'a> for TsVector {
+    fn from_sql(_: &Type, mut raw: &'a [u8]) -> Result<TsVector, Box<dyn Error + Sync + Send>> {
+        let len = read_i32(&mut raw)?;
+        if len < 0 {
+            return Err("invalid lexeme count".into());
+        }
+
+        // every lexeme takes at least 3 bytes, which bounds the allocation if the count is corrupt
+        let mut lexemes = Vec::with_capacity((len as usize).min(raw.len() / 3));
+        for _ in 0..len {
+            let word = read_cstr(&mut raw)?.to_string();
+            let count = read_u16(&mut raw)?;
+            let mut positions = Vec::with_capacity((count as usize).min(raw.len() / 2));
+            for _ in 0..count {
+                let bits = read_u16(&mut raw)?;
+                positions.push(LexemePosition {
+                    position: bits & MAX_POSITION,
+                    weight: Weight::from_bits(bits >> 14),
+                });
+            }
+            lexemes.push(Lexeme { word, positions });
+        }
+        check_end(raw)?;
+
+        Ok(TsVector { lexemes })
+    }
+
+    accepts!(TS_VECTOR);
+}
+
+impl ToSql for TsVector {
+    fn to_sql(&self, _: &Type, w: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        let len = i32::try_from(self.lexemes.len()).map_err(|_| "too many lexemes")?;
+        w.put_i32(len);
+        for lexeme in &self.lexemes {
+            write_cstr(&lexeme.word, w)?;
+            let count = u16::try_from(lexeme.positions.len()).map_err(|_| "too many positions")?;
+            w.put_u16(count);
+            for position in &lexeme.positions {
+                if position.position > MAX_POSITION {
+                    return Err("lexeme position out of range".into());
+                }
+                w.put_u16(position.weight.bits() << 14 | position.position);
+            }
+        }
+        Ok(IsNull::No)
+    }
+
+    accepts!(TS_VECTOR);
+
+    to_sql_checked!();
+}
+
+/// A `TSQUERY` value.
+///
+/// A query can be parsed from the syntax of a `TSQUERY` literal, like `"'fat' & !('cat' | rat:*B)"`. Like a cast to
+/// `TSQUERY`, which this mirrors, the words are taken as they are; use `to_tsquery` in the statement to normalize them.
+///
+/// Queries nesting more than 32 operators, counting each operator of a chain like `a & b & c` as one level, are
+/// rejected when parsed or read from the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TsQuery {
+    /// A query with no lexemes, as produced by `to_tsquery` for a text made of stop words only.
+    ///
+    /// The server can't read an empty query in the binary format, so it can only be read, not sent.
+    Empty,
+    /// Matches a lexeme.
+    Lexeme(QueryLexeme),
+    /// Matches if the query doesn't, `!`.
+    Not(Box<TsQuery>),
+    /// Matches if both queries do, `&`.
+    And(Box<TsQuery>, Box<TsQuery>),
+    /// Matches if either query does, `|`.
+    Or(Box<TsQuery>, Box<TsQuery>),
+    /// Matches if the second query matches the given distance after the first one, `<->` for a distance of 1 or
+    /// `<N>`.
+    Phrase(Box<TsQuery>, Box<TsQuery>, u16),
+}
+
+/// A lexeme of a `TsQuery`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct QueryLexeme {
+    /// The word.
+    pub word: String,
+    /// The weights the lexeme matches. It matches any weight if empty.
+    pub weights: Vec<Weight>,
+    /// Determines if the word matches as a prefix, `:*`.
+    pub prefix: bool,
+}
+
+impl QueryLexeme {
+    /// Creates a new lexeme matching a word with any weight.
+    pub fn new(word: &str) -> QueryLexeme {
+        QueryLexeme {
+            word: word.to_string(),
+            weights: vec![],
+            prefix: false,
+        }
+    }
+}
+
+impl FromStr for TsQuery {
+    type Err = Box<dyn Error + Sync + Send>;
+
+    fn from_str(s: &str) -> Result<TsQuery, Self::Err> {
+        let mut parser = Parser {
+            s,
+            pos: 0,
+            operands: vec![],
+            operators: vec![],
+        };
+        parser.skip_whitespace();
+        if parser.pos == s.len() {
+            return Ok(TsQuery::Empty);
+        }
+
+        let query = parser.query()?;
+        parser.skip_whitespace();
+        if parser.pos != s.len() {
+            return Err(format!("syntax error in tsquery at `{}`", &s[parser.pos..]).into());
+        }
+        Ok(query)
+    }
+}
+
+impl<'a> FromSql<'a> for TsQuery {
+    fn from_sql(_: &Type, mut raw: &'a [u8]) -> Result<TsQuery, Box<dyn Error + Sync + Send>> {
+        let len = read_i32(&mut raw)?;
+        if len < 0 {
+            return Err("invalid tsquery item count".into());
+        }
+        if len == 0 {
+            check_end(raw)?;
+            return Ok(TsQuery::Empty);
+        }
+
+        let query = read_query(&mut raw)?;
+        check_end(raw)?;
+        if query.len() != len as usize {
+            return Err("tsquery item count mismatch".into());
+        }
+        Ok(query)
+    }
+
+    accepts!(TSQUERY);
+}
+
+impl ToSql for TsQuery {
+    fn to_sql(&self, _: &Type, w: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        // the server fails to read back the empty query it sends
+        if *self == TsQuery::Empty {
+            return Err("an empty tsquery can't be sent".into());
+        }
+        let len = i32::try_from(self.len()).map_err(|_| "too many tsquery items")?;
+        w.put_i32(len);
+        write_item(self, w)?;
+        Ok(IsNull::No)
+    }
+
+    accepts!(TSQUERY);
+
+    to_sql_checked!();
+}
+
+impl TsQuery {
+    // the number of items the query is sent as
+    fn len(&self) -> usize {
+        match self {
+            TsQuery::Empty => 0,
+            TsQuery::Lexeme(_) => 1,
+            TsQuery::Not(query) => 1 + query.len(),
+            TsQuery::And(left, right)
+            | TsQuery::Or(left, right)
+            | TsQuery::Phrase(left, right, _) => 1 + left.len() + right.len(),
+        }
+    }
+}
+
+// the items of a query are sent in prefix order, with the right operand of an operator before its left. They are read
+// with a stack of the operators still missing operands rather than by recursing, so that deep nesting is an error
+// instead of a stack overflow.
+fn read_query(raw: &mut &[u8]) -> Result<TsQuery, Box<dyn Error + Sync + Send>> {
+    // each operator, with its right operand once it has been read
+    let mut pending: Vec<(u8, u16, Option<TsQuery>)> = vec![];
+    loop {
+        let mut query = match read_u8(raw)? {
+            QI_VAL => TsQuery::Lexeme(read_lexeme(raw)?),
+            QI_OPR => {
+                let oper = read_u8(raw)?;
+                if ![OP_NOT, OP_AND, OP_OR, OP_PHRASE].contains(&oper) {
+                    return Err(format!("unknown tsquery operator {}", oper).into());
+                }
+                let distance = if oper == OP_PHRASE { read_u16(raw)? } else { 0 };
+                if pending.len() == MAX_DEPTH {
+                    return Err("tsquery nested too deeply".into());
+                }
+                pending.push((oper, distance, None));
+                continue;
+            }
+            ty => return Err(format!("unknown tsquery item type {}", ty).into()),
+        };
+
+        // hand the item to the operators it completes
+        loop {
+            let (oper, distance, right) = match pending.pop() {
+                Some(operator) => operator,
+                None => return Ok(query),
+            };
+            query = match (oper, right) {
+                (OP_NOT, _) => TsQuery::Not(Box::new(query)),
+                (_, None) => {
+                    pending.push((oper, distance, Some(query)));
+                    break;
+                }
+                (OP_AND, Some(right)) => TsQuery::And(Box::new(query), Box::new(right)),
+                (OP_OR, Some(right)) => TsQuery::Or(Box::new(query), Box::new(right)),
+                (_, Some(right)) => TsQuery::Phrase(Box::new(query), Box::new(right), distance),
+            };
+        }
+    }
+}
+
+fn read_lexeme(raw: &mut &[u8]) -> Result<QueryLexeme, Box<dyn Error + Sync + Send>> {
+    let mask = read_u8(raw)?;
+    let prefix = read_u8(raw)? != 0;
+    let word = read_cstr(raw)?.to_string();
+    let weights = [Weight::A, Weight::B, Weight::C, Weight::D]
+        .iter()
+        .copied()
+        .filter(|weight| mask & weight.mask() != 0)
+        .collect();
+    Ok(QueryLexeme {
+        word,
+        weights,
+        prefix,
+    })
+}
+
+fn write_item(query: &TsQuery, w: &mut BytesMut) -> Result<(), Box<dyn Error + Sync + Send>> {
+    match query {
+        TsQuery::Empty => return Err("an empty query can't be part of another one".into()),
+        TsQuery::Lexeme(lexeme) => {
+            w.put_u8(QI_VAL);
+            w.put_u8(lexeme.weights.iter().fold(0, |mask, w| mask | w.mask()));
+            w.put_u8(lexeme.prefix as u8);
+            write_cstr(&lexeme.word, w)?;
+        }
+        TsQuery::Not(query) => {
+            w.put_u8(QI_OPR);
+            w.put_u8(OP_NOT);
+            write_item(query, w)?;
+        }
+        TsQuery::And(left, right) | TsQuery::Or(left, right) | TsQuery::Phrase(left, right, _) => {
+            w.put_u8(QI_OPR);
+            match query {
+                TsQuery::And(..) => w.put_u8(OP_AND),
+                TsQuery::Or(..) => w.put_u8(OP_OR),
+                TsQuery::Phrase(_, _, distance) => {
+                    w.put_u8(OP_PHRASE);
+                    w.put_u16(*distance);
+                }
+                _ => unreachable!(),
+            }
+            write_item(right, w)?;
+            write_item(left, w)?;
+        }
+    }
+    Ok(())
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Operator {
+    Or,
+    And,
+    Phrase(u16),
+    Not,
+    // an open parenthesis
+    Open,
+}
+
+impl Operator {
+    fn precedence(self) -> u8 {
+        match self {
+            Operator::Open => 0,
+            Operator::Or => 1,
+            Operator::And => 2,
+            Operator::Phrase(_) => 3,
+            Operator::Not => 4,
+        }
+    }
+}
+
+// parses the tsquery syntax, where `!` binds tighter than `<->`, which binds tighter than `&`, then `|`. It keeps the
+// operands and operators it has yet to combine on stacks of its own rather than recursing, so that deep nesting is an
+// error instead of a stack overflow.
+struct Parser<'a> {
+    s: &'a str,
+    pos: usize,
+    // each with the number of operators it nests
+    operands: Vec<(TsQuery, usize)>,
+    operators: Vec<Operator>,
+}
+
+impl<'a> Parser<'a> {
+    fn query(&mut self) -> Result<TsQuery, Box<dyn Error + Sync + Send>> {
+        loop {
+            loop {
+                if self.eat("!") {
+                    self.operators.push(Operator::Not);
+                } else if self.eat("(") {
+                    self.operators.push(Operator::Open);
+                } else {
+                    break;
+                }
+            }
+            let lexeme = self.lexeme()?;
+            self.operands.push((TsQuery::Lexeme(lexeme), 0));
+            self.reduce(Operator::Not.precedence())?;
+
+            // a `)` without its `(` is left for the caller to reject
+            while self.operators.contains(&Operator::Open) && self.eat(")") {
+                self.reduce(Operator::Or.precedence())?;
+                self.operators.pop();
+                self.reduce(Operator::Not.precedence())?;
+            }
+
+            let operator = if self.eat("|") {
+                Operator::Or
+            } else if self.eat("&") {
+                Operator::And
+            } else if let Some(distance) = self.distance()? {
+                Operator::Phrase(distance)
+            } else {
+                break;
+            };
+            // the operators are left associative
+            self.reduce(operator.precedence())?;
+            self.operators.push(operator);
+        }
+
+        self.reduce(Operator::Or.precedence())?;
+        if !self.operators.is_empty() {
+            return Err("syntax error in tsquery: missing `)`".into());
+        }
+        match self.operands.pop() {
+            Some((query, _)) => Ok(query),
+            None => Err("syntax error in tsquery".into()),
+        }
+    }
+
+    // combines the operands of the operators on top of the stack which bind at least as tightly as `precedence`
+    fn reduce(&mut self, precedence: u8) -> Result<(), Box<dyn Error + Sync + Send>> {
+        while let Some(&operator) = self.operators.last() {
+            if operator == Operator::Open || operator.precedence() < precedence {
+                break;
+            }
+            self.operators.pop();
+
+            let (right, right_depth) = self.operand()?;
+            let (query, depth) = if operator == Operator::Not {
+                (TsQuery::Not(Box::new(right)), right_depth + 1)
+            } else {
+                let (left, left_depth) = self.operand()?;
+                let (left, right) = (Box::new(left), Box::new(right));
+                let query = match operator {
+                    Operator::Or => TsQuery::Or(left, right),
+                    Operator::And => TsQuery::And(left, right),
+                    Operator::Phrase(distance) => TsQuery::Phrase(left, right, distance),
+                    Operator::Not | Operator::Open => unreachable!(),
+                };
+                (query, left_depth.max(right_depth) + 1)
+            };
+            if depth > MAX_DEPTH {
+                return Err("tsquery nested too deeply".into());
+            }
+            self.operands.push((query, depth));
+        }
+        Ok(())
+    }
+
+    fn operand(&mut self) -> Result<(TsQuery, usize), Box<dyn Error + Sync + Send>> {
+        self.operands
+            .pop()
+            .ok_or_else(|| "syntax error in tsquery".into())
+    }
+
+    // `<->` or `<N>`
+    fn distance(&mut self) -> Result<Option<u16>, Box<dyn Error + Sync + Send>> {
+        if !self.eat("<") {
+            return Ok(None);
+        }
+        let rest = &self.s[self.pos..];
+        let end = match rest.find('>') {
+            Some(end) => end,
+            None => return Err("syntax error in tsquery: missing `>`".into()),
+        };
+        let distance = match rest[..end].trim() {
+            "-" => 1,
+            n => n
+                .parse()
+                .map_err(|_| format!("invalid tsquery distance `{}`", n))?,
+        };
+        self.pos += end + 1;
+        Ok(Some(distance))
+    }
+
+    fn lexeme(&mut self) -> Result<QueryLexeme, Box<dyn Error + Sync + Send>> {
+        self.skip_whitespace();
+        let rest = &self.s[self.pos..];
+
+        let word = if let Some(quoted) = rest.strip_prefix('\'') {
+            let mut word = String::new();
+            let mut chars = quoted.char_indices();
+            loop {
+                match chars.next() {
+                    Some((_, '\\')) => match chars.next() {
+                        Some((_, c)) => word.push(c),
+                        None => return Err("syntax error in tsquery: unterminated quote".into()),
+                    },
+                    Some((i, '\'')) => {
+                        if quoted[i + 1..].starts_with('\'') {
+                            chars.next();
+                            word.push('\'');
+                        } else {
+                            self.pos += i + 2;
+                            break;
+                        }
+                    }
+                    Some((_, c)) => word.push(c),
+                    None => return Err("syntax error in tsquery: unterminated quote".into()),
+                }
+            }
+            word
+        } else {
+            let end = rest
+                .find(|c: char| c.is_whitespace() || "()!&|<:'".contains(c))
+                .unwrap_or(rest.len());
+            if end == 0 {
+                return Err(format!("syntax error in tsquery at `{}`", rest).into());
+            }
+            self.pos += end;
+            rest[..end].to_string()
+        };
+
+        let mut lexeme = QueryLexeme::new(&word);
+        if self.s[self.pos..].starts_with(':') {
+            self.pos += 1;
+            for c in self.s[self.pos..].chars() {
+                let weight = match c.to_ascii_uppercase() {
+                    '*' => {
+                        lexeme.prefix = true;
+                        self.pos += 1;
+                        continue;
+                    }
+                    'A' => Weight::A,
+                    'B' => Weight::B,
+                    'C' => Weight::C,
+                    'D' => Weight::D,
+                    _ => break,
+                };
+                if !lexeme.weights.contains(&weight) {
+                    lexeme.weights.push(weight);
+                }
+                self.pos += 1;
+            }
+        }
+        Ok(lexeme)
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        if self.s[self.pos..].starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = &self.s[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+}
+
+fn read_u8(raw: &mut &[u8]) -> Result<u8, Box<dyn Error + Sync + Send>> {
+    match raw.split_first() {
+        Some((&b, rest)) => {
+            *raw = rest;
+            Ok(b)
+        }
+        None => Err(length_mismatch()),
+    }
+}
+
+fn read_u16(raw: &mut &[u8]) -> Result<u16, Box<dyn Error + Sync + Send>> {
+    if raw.len() < 2 {
+        return Err(length_mismatch());
+    }
+    let value = u16::from_be_bytes([raw[0], raw[1]]);
+    *raw = &raw[2..];
+    Ok(value)
+}
+
+fn read_i32(raw: &mut &[u8]) -> Result<i32, Box<dyn Error + Sync + Send>> {
+    if raw.len() < 4 {
+        return Err(length_mismatch());
+    }
+    let value = i32::from_be_bytes([raw[0], raw[1], raw[2], raw[3]]);
+    *raw = &raw[4..];
+    Ok(value)
+}
+
+fn read_cstr<'a>(raw: &mut &'a [u8]) -> Result<&'a str, Box<dyn Error + Sync + Send>> {
+    let end = match raw.iter().position(|&b| b == 0) {
+        Some(end) => end,
+        None => return Err(length_mismatch()),
+    };
+    let s = str::from_utf8(&raw[..end])?;
+    *raw = &raw[end + 1..];
+    Ok(s)
+}
+
+fn write_cstr(s: &str, w: &mut BytesMut) -> Result<(), Box<dyn Error + Sync + Send>> {
+    if s.contains('\0') {
+        return Err("lexeme contains a nul byte".into());
+    }
+    w.put_slice(s.as_bytes());
+    w.put_u8(0);
+    Ok(())
+}
+
+fn check_end(raw: &[u8]) -> Result<(), Box<dyn Error + Sync + Send>> {
+    if raw.is_empty() {
+        Ok(())
+    } else {
+        Err(length_mismatch())
+    }
+}
+
+fn length_mismatch() -> Box<dyn Error + Sync + Send> {
+    "invalid message length: text search value length mismatch".into()
+}
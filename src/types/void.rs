@@ -0,0 +1,25 @@
+use crate::types::{accepts, FromSql, Type};
+use std::error::Error;
+
+/// The result of a function returning `void`.
+///
+/// `()` can't take this role, since `FromSql` can't be implemented for it outside of `postgres_types`. A `NULL`
+/// `void`, which a strict function returns for a `NULL` argument, is read as well.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Void;
+
+impl<'a> FromSql<'a> for Void {
+    fn from_sql(_: &Type, raw: &'a [u8]) -> Result<Void, Box<dyn Error + Sync + Send>> {
+        if raw.is_empty() {
+            Ok(Void)
+        } else {
+            Err("invalid message length: void isn't empty".into())
+        }
+    }
+
+    fn from_sql_null(_: &Type) -> Result<Void, Box<dyn Error + Sync + Send>> {
+        Ok(Void)
+    }
+
+    accepts!(VOID);
+}
@@ -1,27 +1,28 @@
 use bytes::Bytes;
 use std::io::{IoSlice, Write};
-use std::mem::MaybeUninit;
 
 const MAX_VEC_BUF: usize = 128;
 
 pub struct VecBufs<W> {
     block: usize,
     pos: usize,
+    // bytes buffered since the last flush, and the amount which triggers one (0 for no limit)
+    pending: usize,
+    max_pending: usize,
     bufs: Vec<Bytes>,
     io_slice: [IoSlice<'static>; MAX_VEC_BUF],
     writer: W,
 }
 
 impl<W: Write> VecBufs<W> {
-    pub fn new(writer: W) -> Self {
+    pub fn new(writer: W, max_pending: usize) -> Self {
         VecBufs {
             block: 0,
             pos: 0,
+            pending: 0,
+            max_pending,
             bufs: Vec::with_capacity(MAX_VEC_BUF),
-            io_slice: unsafe {
-                #[allow(clippy::uninit_assumed_init)]
-                MaybeUninit::uninit().assume_init()
-            },
+            io_slice: [IoSlice::new(&[]); MAX_VEC_BUF],
             writer,
         }
     }
@@ -33,11 +34,14 @@ impl<W: Write> VecBufs<W> {
     pub fn write_bytes(&mut self, buf: Bytes) -> std::io::Result<()> {
         let len = self.bufs.len();
         assert!(len < MAX_VEC_BUF);
+        self.pending += buf.len();
         self.bufs.push(buf);
-        let slice = IoSlice::new(unsafe { std::mem::transmute(&self.bufs[len][..]) });
+        let slice = IoSlice::new(unsafe {
+            std::mem::transmute::<&[u8], &'static [u8]>(&self.bufs[len][..])
+        });
         self.io_slice[len] = slice;
 
-        if len + 1 == MAX_VEC_BUF {
+        if len + 1 == MAX_VEC_BUF || (self.max_pending > 0 && self.pending >= self.max_pending) {
             self.flush()?;
         }
 
@@ -54,7 +58,9 @@ impl<W: Write> VecBufs<W> {
                 self.pos = 0;
             } else {
                 self.pos += left;
-                let slice = IoSlice::new(unsafe { std::mem::transmute(&buf[self.pos..]) });
+                let slice = IoSlice::new(unsafe {
+                    std::mem::transmute::<&[u8], &'static [u8]>(&buf[self.pos..])
+                });
                 self.io_slice[self.block] = slice;
                 break;
             }
@@ -77,6 +83,7 @@ impl<W: Write> VecBufs<W> {
         self.bufs.clear();
         self.block = 0;
         self.pos = 0;
+        self.pending = 0;
         Ok(())
     }
 }
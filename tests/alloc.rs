@@ -0,0 +1,90 @@
+//! Tests checking what reading results allocates.
+//!
+//! They live in their own test binary because the counting allocator sees the allocations of every thread, including
+//! the connection's coroutines, and would pick up those of unrelated tests running alongside them.
+
+use may_postgres::Client;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+// counts the bytes allocated and still live across all threads
+struct CountingAlloc;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        LIVE.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LIVE.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATED.fetch_add(new_size, Ordering::Relaxed);
+        LIVE.fetch_add(new_size, Ordering::Relaxed);
+        LIVE.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+// the tests of this binary take turns, so that each one only counts its own allocations
+static SERIAL: Mutex<()> = Mutex::new(());
+
+fn connect() -> Client {
+    may_postgres::connect("host=localhost port=5433 user=postgres").unwrap()
+}
+
+#[test]
+fn large_bytea_is_not_copied() {
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    let client = connect();
+    client
+        .batch_execute("CREATE TEMPORARY TABLE blobs (data BYTEA)")
+        .unwrap();
+    let blob = (0..4 * 1024 * 1024).map(|i| i as u8).collect::<Vec<u8>>();
+
+    // a borrowed slice is encoded straight into the request
+    client
+        .execute("INSERT INTO blobs (data) VALUES ($1)", &[&&blob[..]])
+        .unwrap();
+
+    // the value is read straight out of the receive buffer, nothing holds a second copy of it once the connection's
+    // buffer has grown to fit it
+    drop(client.query_one("SELECT data FROM blobs", &[]).unwrap());
+    let before = LIVE.load(Ordering::Relaxed);
+    let row = client.query_one("SELECT data FROM blobs", &[]).unwrap();
+    let bytes = row.get_bytes(0).unwrap();
+    let held = LIVE.load(Ordering::Relaxed).saturating_sub(before);
+    assert!(held < blob.len(), "{}", held);
+    assert_eq!(bytes, &blob[..]);
+}
+
+#[test]
+fn query_each_rows_are_not_allocated() {
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    let client = connect();
+    let stmt = client
+        .prepare("SELECT i, 'row ' || i, NULLIF(i % 2, 0) FROM generate_series(1, $1) i")
+        .unwrap();
+
+    // a row holds no allocation of its own, unlike collecting the rows, once a first run has grown the connection's
+    // buffers
+    client.query_each(&stmt, &[&10_000i32], |_| Ok(())).unwrap();
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    client.query_each(&stmt, &[&10_000i32], |_| Ok(())).unwrap();
+    let each = ALLOCATED.load(Ordering::Relaxed) - before;
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    client.query(&stmt, &[&10_000i32]).unwrap();
+    let collected = ALLOCATED.load(Ordering::Relaxed) - before;
+    assert!(each * 4 < collected, "{} vs {}", each, collected);
+}
@@ -1,6 +1,7 @@
 use crate::connect;
+use bytes::Bytes;
 use may_postgres::binary_copy::{BinaryCopyInWriter, BinaryCopyOutStream};
-use may_postgres::types::Type;
+use may_postgres::types::{Format, Type};
 
 #[test]
 fn write_basic() {
@@ -168,3 +169,182 @@ fn read_big_rows() {
         assert_eq!(row.get::<&[u8]>(1), &vec![i as u8; 128 * 1024][..]);
     }
 }
+
+#[test]
+fn write_typed_rows() {
+    let client = connect("user=postgres");
+
+    client
+        .batch_execute(
+            "
+            CREATE TEMPORARY TABLE binary_foo (id INT, big BIGINT, score FLOAT8, flag BOOL, name TEXT);
+            CREATE TEMPORARY TABLE text_foo (id INT, big BIGINT, score FLOAT8, flag BOOL, name TEXT);
+            ",
+        )
+        .unwrap();
+
+    let sink = client
+        .copy_in("COPY binary_foo (id, big, score, flag, name) FROM STDIN BINARY")
+        .unwrap();
+    let mut writer = BinaryCopyInWriter::new(
+        sink,
+        &[Type::INT4, Type::INT8, Type::FLOAT8, Type::BOOL, Type::TEXT],
+    );
+    for i in 0..5_000i32 {
+        let name = if i % 10 == 0 {
+            None
+        } else {
+            Some(format!("name {}", i))
+        };
+        writer
+            .write(&[
+                &i,
+                &(i64::from(i) << 32),
+                &(f64::from(i) / 4.0),
+                &(i % 2 == 0),
+                &name,
+            ])
+            .unwrap();
+    }
+    assert_eq!(writer.finish().unwrap(), 5_000);
+
+    let mut sink = client
+        .copy_in("COPY text_foo (id, big, score, flag, name) FROM STDIN")
+        .unwrap();
+    for i in 0..5_000i32 {
+        let name = if i % 10 == 0 {
+            "\\N".to_string()
+        } else {
+            format!("name {}", i)
+        };
+        let line = format!(
+            "{}\t{}\t{}\t{}\t{}\n",
+            i,
+            i64::from(i) << 32,
+            f64::from(i) / 4.0,
+            i % 2 == 0,
+            name,
+        );
+        sink.send(Bytes::from(line)).unwrap();
+    }
+    assert_eq!(sink.finish().unwrap(), 5_000);
+
+    let rows = client
+        .query("SELECT * FROM binary_foo ORDER BY id", &[])
+        .unwrap();
+    assert_eq!(rows.len(), 5_000);
+    for (i, row) in (0..).zip(&rows) {
+        assert_eq!(row.get::<_, i32>(0), i);
+        assert_eq!(row.get::<_, i64>(1), i64::from(i) << 32);
+        assert_eq!(row.get::<_, f64>(2), f64::from(i) / 4.0);
+        assert_eq!(row.get::<_, bool>(3), i % 2 == 0);
+        if i % 10 == 0 {
+            assert_eq!(row.get::<_, Option<&str>>(4), None);
+        } else {
+            assert_eq!(row.get::<_, Option<&str>>(4), Some(&*format!("name {}", i)));
+        }
+    }
+
+    let row = client
+        .query_one(
+            "SELECT COUNT(*) FROM (
+                (SELECT * FROM binary_foo EXCEPT SELECT * FROM text_foo)
+                UNION ALL
+                (SELECT * FROM text_foo EXCEPT SELECT * FROM binary_foo)
+            ) diff",
+            &[],
+        )
+        .unwrap();
+    assert_eq!(row.get::<_, i64>(0), 0);
+}
+
+#[test]
+fn read_typed_rows() {
+    let client = connect("user=postgres");
+
+    client
+        .batch_execute(
+            "
+            CREATE TEMPORARY TABLE foo (id INT, big BIGINT, score FLOAT8, ratio FLOAT4, name TEXT);
+            INSERT INTO foo
+                SELECT i, i::BIGINT << 32, i / 4.0, i / 2.0, CASE WHEN i % 10 = 0 THEN NULL ELSE 'name ' || i END
+                FROM generate_series(0, 999) i;
+            ",
+        )
+        .unwrap();
+
+    let stream = client
+        .copy_out("COPY foo (id, big, score, ratio, name) TO STDOUT BINARY")
+        .unwrap();
+    let mut stream = BinaryCopyOutStream::new(
+        stream,
+        &[
+            Type::INT4,
+            Type::INT8,
+            Type::FLOAT8,
+            Type::FLOAT4,
+            Type::TEXT,
+        ],
+    );
+    let rows = stream.by_ref().collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(rows.len(), 1_000);
+    assert!(stream.next().is_none());
+
+    for (i, row) in (0..).zip(&rows) {
+        assert_eq!(row.get::<i32>(0), i);
+        assert_eq!(row.get::<i64>(1), i64::from(i) << 32);
+        assert_eq!(row.get::<f64>(2), f64::from(i) / 4.0);
+        assert_eq!(row.get::<f32>(3), i as f32 / 2.0);
+        if i % 10 == 0 {
+            assert_eq!(row.get::<Option<&str>>(4), None);
+        } else {
+            assert_eq!(row.get::<Option<&str>>(4), Some(&*format!("name {}", i)));
+        }
+    }
+
+    let row = client.query_one("SELECT COUNT(*) FROM foo", &[]).unwrap();
+    assert_eq!(row.get::<_, i64>(0), 1_000);
+}
+
+#[test]
+fn write_without_signature() {
+    use std::io::Write as _;
+
+    let client = connect("user=postgres");
+
+    client
+        .batch_execute("CREATE TEMPORARY TABLE foo (id INT, bar TEXT)")
+        .unwrap();
+
+    let mut sink = client
+        .copy_in::<_, Bytes>("COPY foo (id, bar) FROM STDIN BINARY")
+        .unwrap();
+    assert_eq!(sink.format(), Format::Binary);
+    let err = sink
+        .send(Bytes::from_static(b"1\tfoobar\n2\tbaz\n"))
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("PGCOPY signature"), "{}", err);
+    assert!(sink.finish().is_err());
+
+    let mut writer = client
+        .copy_in_writer("COPY foo (id, bar) FROM STDIN BINARY")
+        .unwrap();
+    assert_eq!(writer.format(), Format::Binary);
+    writer.write_all(b"1\t").unwrap();
+    let err = writer.finish().unwrap_err();
+    assert!(err.to_string().contains("PGCOPY signature"), "{}", err);
+
+    let text = client
+        .copy_in_writer("COPY foo (id, bar) FROM STDIN")
+        .unwrap();
+    assert_eq!(text.format(), Format::Text);
+    drop(text);
+
+    let count: i64 = client
+        .query_one("SELECT COUNT(*) FROM foo", &[])
+        .unwrap()
+        .get(0);
+    assert_eq!(count, 0);
+    client.batch_execute("SELECT 1").unwrap();
+}
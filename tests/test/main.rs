@@ -1,15 +1,19 @@
+use std::error::Error as _;
 use std::fmt::Write;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use bytes::{Bytes, BytesMut};
+use may::join;
 use may::net::TcpStream;
-use may::{go, join};
-use may_postgres::error::SqlState;
-use may_postgres::types::{Kind, Type};
-use may_postgres::{Client, Config, Error, IsolationLevel, SimpleQueryMessage};
+use may_postgres::error::{Severity, SqlState};
+use may_postgres::types::{Format, Kind, ToSql, Type, WrongType};
+use may_postgres::{Client, Config, Error, IsolationLevel, SimpleQueryMessage, Transaction};
 
 mod binary_copy;
 mod parse;
+mod pool;
+mod replication;
 mod runtime;
 mod types;
 
@@ -23,6 +27,12 @@ fn connect(s: &str) -> Client {
     connect_raw(s).unwrap()
 }
 
+fn slice_iter<'a>(
+    s: &'a [&'a (dyn ToSql + Sync)],
+) -> impl ExactSizeIterator<Item = &'a dyn ToSql> + 'a {
+    s.iter().map(|s| *s as _)
+}
+
 #[test]
 fn plain_password_missing() {
     connect_raw("user=pass_user dbname=postgres").err().unwrap();
@@ -83,511 +93,2757 @@ fn scram_password_ok() {
 }
 
 #[test]
-fn pipelined_prepare() {
-    let client = connect("user=postgres");
+fn password_channel_binding_required() {
+    // channel binding needs SCRAM over TLS, so none of the methods qualify on a plain connection
+    for user in &["pass_user", "md5_user", "scram_user"] {
+        let err = connect_raw(&format!(
+            "user={} password=password dbname=postgres channel_binding=require",
+            user
+        ))
+        .err()
+        .unwrap();
+        assert!(err.to_string().contains("channel binding"), "{}", err);
+    }
+}
 
-    let statement1 = client.prepare("SELECT $1::HSTORE[]").unwrap();
-    let statement2 = client.prepare("SELECT $1::BIGINT").unwrap();
+#[test]
+fn startup_parameters() {
+    let client = connect(
+        "user=postgres application_name=may_postgres_test options='-c statement_timeout=5000'",
+    );
 
-    assert_eq!(statement1.params()[0].name(), "_hstore");
-    assert_eq!(statement1.columns()[0].type_().name(), "_hstore");
+    let row = client
+        .query_one(
+            "SELECT application_name FROM pg_stat_activity WHERE pid = pg_backend_pid()",
+            &[],
+        )
+        .unwrap();
+    assert_eq!(row.get::<_, &str>(0), "may_postgres_test");
+    assert_eq!(
+        client.parameter("application_name").as_deref(),
+        Some("may_postgres_test")
+    );
 
-    assert_eq!(statement2.params()[0], Type::INT8);
-    assert_eq!(statement2.columns()[0].type_(), &Type::INT8);
+    let row = client.query_one("SHOW statement_timeout", &[]).unwrap();
+    assert_eq!(row.get::<_, &str>(0), "5s");
 }
 
 #[test]
-fn insert_select() {
+fn prepare_typed() {
+    let client = connect("user=postgres");
+
+    let stmt = client.prepare_typed("SELECT $1", &[Type::INT8]).unwrap();
+    assert_eq!(stmt.params(), &[Type::INT8]);
+    assert_eq!(stmt.columns()[0].type_(), &Type::INT8);
+    let row = client.query_one(&stmt, &[&7i64]).unwrap();
+    assert_eq!(row.get::<_, i64>(0), 7);
+    let err = client.query_one(&stmt, &[&7i32]).err().unwrap();
+    assert!(
+        err.to_string().contains("error serializing parameter 0"),
+        "{}",
+        err
+    );
+
+    // the types of the remaining parameters are inferred
+    let stmt = client
+        .prepare_typed("SELECT $1 + $2", &[Type::NUMERIC])
+        .unwrap();
+    assert_eq!(stmt.params(), &[Type::NUMERIC, Type::NUMERIC]);
+}
+
+#[test]
+fn statement_metadata() {
     let client = connect("user=postgres");
 
+    let stmt = client
+        .prepare("SELECT $1::TEXT AS name, $2::INT8 * 2 AS doubled, now() AS at")
+        .unwrap();
+    assert_eq!(stmt.params(), &[Type::TEXT, Type::INT8]);
+    assert_eq!(
+        stmt.columns()
+            .iter()
+            .map(|c| (c.name(), c.type_()))
+            .collect::<Vec<_>>(),
+        [
+            ("name", &Type::TEXT),
+            ("doubled", &Type::INT8),
+            ("at", &Type::TIMESTAMPTZ)
+        ]
+    );
+
+    // statements which return no rows have no columns
     client
-        .batch_execute("CREATE TEMPORARY TABLE foo (id SERIAL, name TEXT)")
+        .batch_execute("CREATE TEMPORARY TABLE foo (id INT)")
         .unwrap();
+    let stmt = client.prepare("INSERT INTO foo VALUES ($1)").unwrap();
+    assert_eq!(stmt.params(), &[Type::INT4]);
+    assert!(stmt.columns().is_empty());
+}
 
-    let insert = client
-        .prepare("INSERT INTO foo (name) VALUES ($1), ($2)")
+#[test]
+fn statements_without_columns() {
+    let client = connect("user=postgres");
+
+    client
+        .batch_execute("CREATE TEMPORARY TABLE foo (id INT)")
         .unwrap();
-    let select = client
-        .prepare("SELECT id, name FROM foo ORDER BY id")
+
+    // the server describes each of these with `NoData`
+    for query in &[
+        "INSERT INTO foo VALUES ($1)",
+        "UPDATE foo SET id = $1",
+        "DELETE FROM foo WHERE id = $1",
+    ] {
+        let stmt = client.prepare(query).unwrap();
+        assert!(stmt.columns().is_empty(), "{}", query);
+        assert!(client.query(&stmt, &[&1i32]).unwrap().is_empty());
+        assert!(client
+            .query_unnamed(query, &[(&1i32, Type::INT4)])
+            .unwrap()
+            .is_empty());
+        assert!(client
+            .query_typed(query, &[(&1i32, Type::INT4)])
+            .unwrap()
+            .is_empty());
+    }
+    let stmt = client.prepare("CREATE INDEX ON foo (id)").unwrap();
+    assert!(stmt.columns().is_empty());
+    assert_eq!(client.execute(&stmt, &[]).unwrap(), 0);
+
+    let stmt = client
+        .prepare("INSERT INTO foo VALUES ($1) RETURNING id")
         .unwrap();
+    assert_eq!(stmt.columns().len(), 1);
+}
 
-    let _ = client.execute(&insert, &[&"alice", &"bob"]).unwrap();
-    let rows = client.query(&select, &[]).unwrap();
+#[test]
+fn query_each() {
+    let mut client = connect("user=postgres");
 
-    assert_eq!(rows.len(), 2);
+    let stmt = client
+        .prepare("SELECT i, 'row ' || i, NULLIF(i % 2, 0) FROM generate_series(1, $1) i")
+        .unwrap();
+    let mut seen = vec![];
+    let count = client
+        .query_each(&stmt, &[&1000i32], |row| {
+            assert_eq!(row.len(), 3);
+            assert_eq!(row.columns()[1].type_(), &Type::TEXT);
+            let id: i32 = row.get(0);
+            let name: &str = row.get(1);
+            assert_eq!(name, format!("row {}", id));
+            assert_eq!(
+                row.get::<_, Option<i32>>(2),
+                Some(id % 2).filter(|n| *n != 0)
+            );
+            seen.push(id);
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(count, 1000);
+    assert_eq!(seen, (1..=1000).collect::<Vec<_>>());
+
+    // an error from the callback stops the iteration
+    let mut calls = 0;
+    let err = client
+        .query_each(&stmt, &[&1000i32], |row| {
+            calls += 1;
+            row.try_get::<_, String>(0).map(|_| ())
+        })
+        .unwrap_err();
+    assert!(
+        err.to_string().contains("error deserializing column 0"),
+        "{}",
+        err
+    );
+    assert_eq!(calls, 1);
+
+    // the rest of the rows were discarded
+    let rows = client.query("SELECT 1::INT4", &[]).unwrap();
     assert_eq!(rows[0].get::<_, i32>(0), 1);
-    assert_eq!(rows[0].get::<_, &str>(1), "alice");
-    assert_eq!(rows[1].get::<_, i32>(0), 2);
-    assert_eq!(rows[1].get::<_, &str>(1), "bob");
+
+    assert_eq!(
+        client
+            .query_each("SELECT 1 WHERE false", &[], |_| unreachable!())
+            .unwrap(),
+        0
+    );
+
+    let transaction = client.transaction().unwrap();
+    let mut sum = 0;
+    transaction
+        .query_each("SELECT generate_series(1, 10)", &[], |row| {
+            sum += row.get::<_, i32>(0);
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(sum, 55);
+    transaction.commit().unwrap();
 }
 
 #[test]
-fn custom_enum() {
-    let client = connect("user=postgres");
+fn query_raw_txt() {
+    let mut client = connect("user=postgres");
 
     client
         .batch_execute(
-            "CREATE TYPE pg_temp.mood AS ENUM (
-                'sad',
-                'ok',
-                'happy'
-            )",
+            "CREATE TYPE pg_temp.mood AS ENUM ('sad', 'happy');
+             CREATE TYPE pg_temp.pair AS (a INT, b TEXT);
+             CREATE TEMPORARY TABLE foo (id INT, mood pg_temp.mood, pair pg_temp.pair, amount NUMERIC);
+             INSERT INTO foo VALUES (1, 'happy', (1, 'one'), 1.50), (2, NULL, NULL, NULL);",
         )
         .unwrap();
 
-    let select = client.prepare("SELECT $1::mood").unwrap();
-
-    let ty = &select.params()[0];
-    assert_eq!("mood", ty.name());
+    let rows = client
+        .query_raw_txt("SELECT * FROM foo WHERE id <= $1 ORDER BY id", &[&2i32])
+        .unwrap();
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0].columns(), ["id", "mood", "pair", "amount"]);
+    let values = |i: usize| {
+        (0..rows[i].len())
+            .map(|j| rows[i].get(j))
+            .collect::<Vec<_>>()
+    };
     assert_eq!(
-        &Kind::Enum(vec![
-            "sad".to_string(),
-            "ok".to_string(),
-            "happy".to_string(),
-        ]),
-        ty.kind(),
+        values(0),
+        [Some("1"), Some("happy"), Some("(1,one)"), Some("1.50")]
+    );
+    assert_eq!(values(1), [Some("2"), None, None, None]);
+    assert_eq!(rows[1].get("id"), Some("2"));
+
+    let stmt = client.prepare("SELECT now()::DATE - $1::DATE").unwrap();
+    let transaction = client.transaction().unwrap();
+    // parameters are still sent in the binary format, so a string isn't taken for a `DATE`
+    let err = transaction
+        .query_raw_txt(&stmt, &[&"2000-01-01"])
+        .err()
+        .unwrap();
+    assert!(
+        err.to_string().contains("error serializing parameter 0"),
+        "{}",
+        err
     );
+    let rows = transaction
+        .query_raw_txt("SELECT 'a' WHERE false", &[])
+        .unwrap();
+    assert!(rows.is_empty());
 }
 
 #[test]
-fn custom_domain() {
+fn pipelined_prepare() {
     let client = connect("user=postgres");
 
-    client
-        .batch_execute("CREATE DOMAIN pg_temp.session_id AS bytea CHECK(octet_length(VALUE) = 16)")
-        .unwrap();
+    let statement1 = client.prepare("SELECT $1::HSTORE[]").unwrap();
+    let statement2 = client.prepare("SELECT $1::BIGINT").unwrap();
 
-    let select = client.prepare("SELECT $1::session_id").unwrap();
+    assert_eq!(statement1.params()[0].name(), "_hstore");
+    assert_eq!(statement1.columns()[0].type_().name(), "_hstore");
 
-    let ty = &select.params()[0];
-    assert_eq!("session_id", ty.name());
-    assert_eq!(&Kind::Domain(Type::BYTEA), ty.kind());
+    assert_eq!(statement2.params()[0], Type::INT8);
+    assert_eq!(statement2.columns()[0].type_(), &Type::INT8);
+}
+
+fn prepared_count(client: &Client, query: &str) -> i64 {
+    client
+        .query_one(
+            "SELECT count(*) FROM pg_prepared_statements WHERE statement = $1",
+            &[&query],
+        )
+        .unwrap()
+        .get(0)
 }
 
 #[test]
-fn custom_array() {
+fn prepare_cached() {
     let client = connect("user=postgres");
 
-    let select = client.prepare("SELECT $1::HSTORE[]").unwrap();
+    let query = "SELECT $1::INT";
+    let a = client.prepare_cached(query).unwrap();
+    let b = client.prepare_cached(query).unwrap();
+    // a cache hit does not send another Parse, so the server only knows the statement once
+    assert_eq!(prepared_count(&client, query), 1);
+
+    let row = client.query_one(&b, &[&1i32]).unwrap();
+    assert_eq!(row.get::<_, i32>(0), 1);
+    // rows keep their statement alive as well
+    drop(row);
+    drop(a);
+    drop(b);
+
+    client.clear_statement_cache();
+    assert_eq!(prepared_count(&client, query), 0);
+}
 
-    let ty = &select.params()[0];
-    assert_eq!("_hstore", ty.name());
-    match ty.kind() {
-        Kind::Array(ty) => {
-            assert_eq!("hstore", ty.name());
-            assert_eq!(&Kind::Simple, ty.kind());
-        }
-        _ => panic!("unexpected kind"),
-    }
+#[test]
+fn prepare_cached_eviction() {
+    let socket = TcpStream::connect("127.0.0.1:5433").unwrap();
+    let client = "user=postgres"
+        .parse::<Config>()
+        .unwrap()
+        .statement_cache_size(1)
+        .connect_raw(socket)
+        .unwrap();
+
+    client.prepare_cached("SELECT 1").unwrap();
+    client.prepare_cached("SELECT 2").unwrap();
+    // the evicted statement is closed on the server
+    assert_eq!(prepared_count(&client, "SELECT 1"), 0);
+    assert_eq!(prepared_count(&client, "SELECT 2"), 1);
+
+    // the statement that stayed cached is still reused
+    client.prepare_cached("SELECT 2").unwrap();
+    assert_eq!(prepared_count(&client, "SELECT 2"), 1);
 }
 
 #[test]
-fn custom_composite() {
-    let client = connect("user=postgres");
+fn prepare_cached_evicts_least_recently_used() {
+    let socket = TcpStream::connect("127.0.0.1:5433").unwrap();
+    let client = "user=postgres"
+        .parse::<Config>()
+        .unwrap()
+        .statement_cache_size(2)
+        .connect_raw(socket)
+        .unwrap();
 
-    client
-        .batch_execute(
-            "CREATE TYPE pg_temp.inventory_item AS (
-                name TEXT,
-                supplier INTEGER,
-                price NUMERIC
-            )",
-        )
+    client.prepare_cached("SELECT 1").unwrap();
+    client.prepare_cached("SELECT 2").unwrap();
+    // using the oldest entry makes the other one the next to go
+    client.prepare_cached("SELECT 1").unwrap();
+    client.prepare_cached("SELECT 3").unwrap();
+    assert_eq!(prepared_count(&client, "SELECT 1"), 1);
+    assert_eq!(prepared_count(&client, "SELECT 2"), 0);
+    assert_eq!(prepared_count(&client, "SELECT 3"), 1);
+
+    client.prepare_cached("SELECT 4").unwrap();
+    assert_eq!(prepared_count(&client, "SELECT 1"), 0);
+    assert_eq!(prepared_count(&client, "SELECT 3"), 1);
+}
+
+#[test]
+fn prepare_cached_disabled() {
+    let socket = TcpStream::connect("127.0.0.1:5433").unwrap();
+    let client = "user=postgres"
+        .parse::<Config>()
+        .unwrap()
+        .statement_cache_size(0)
+        .connect_raw(socket)
         .unwrap();
 
-    let select = client.prepare("SELECT $1::inventory_item").unwrap();
+    let a = client.prepare_cached("SELECT 1").unwrap();
+    let _b = client.prepare_cached("SELECT 1").unwrap();
+    drop(a);
+    assert_eq!(prepared_count(&client, "SELECT 1"), 1);
+}
 
-    let ty = &select.params()[0];
-    assert_eq!(ty.name(), "inventory_item");
-    match ty.kind() {
-        Kind::Composite(fields) => {
-            assert_eq!(fields[0].name(), "name");
-            assert_eq!(fields[0].type_(), &Type::TEXT);
-            assert_eq!(fields[1].name(), "supplier");
-            assert_eq!(fields[1].type_(), &Type::INT4);
-            assert_eq!(fields[2].name(), "price");
-            assert_eq!(fields[2].type_(), &Type::NUMERIC);
-        }
-        _ => panic!("unexpected kind"),
+#[test]
+fn query_raw_chunked() {
+    let client = connect("user=postgres");
+
+    let stmt = client
+        .prepare("SELECT g FROM generate_series(1, 1000000) g")
+        .unwrap();
+    let mut count = 0i64;
+    let mut sum = 0i64;
+    for row in client
+        .query_raw_chunked(&stmt, slice_iter(&[]), 1000)
+        .unwrap()
+    {
+        count += 1;
+        sum += i64::from(row.unwrap().get::<_, i32>(0));
     }
+    assert_eq!(count, 1_000_000);
+    assert_eq!(sum, 500_000_500_000);
+
+    let row = client.query_one("SELECT 1", &[]).unwrap();
+    assert_eq!(row.get::<_, i32>(0), 1);
 }
 
 #[test]
-fn custom_range() {
+fn query_raw_chunked_dropped_early() {
     let client = connect("user=postgres");
 
-    client
-        .batch_execute(
-            "CREATE TYPE pg_temp.floatrange AS RANGE (
-                subtype = float8,
-                subtype_diff = float8mi
-            )",
+    let rows = client
+        .query_raw_chunked(
+            "SELECT g FROM generate_series(1, 1000) g",
+            slice_iter(&[]),
+            10,
         )
+        .unwrap()
+        .take(25)
+        .collect::<Result<Vec<_>, _>>()
         .unwrap();
+    assert_eq!(rows.len(), 25);
 
-    let select = client.prepare("SELECT $1::floatrange").unwrap();
-
-    let ty = &select.params()[0];
-    assert_eq!("floatrange", ty.name());
-    assert_eq!(&Kind::Range(Type::FLOAT8), ty.kind());
+    // the portal was closed and the connection is usable again
+    let row = client.query_one("SELECT 1", &[]).unwrap();
+    assert_eq!(row.get::<_, i32>(0), 1);
 }
 
 #[test]
-fn simple_query() {
+fn query_raw_chunked_is_exclusive() {
     let client = connect("user=postgres");
 
-    let messages = client
-        .simple_query(
-            "CREATE TEMPORARY TABLE foo (
-                id SERIAL,
-                name TEXT
-            );
-            INSERT INTO foo (name) VALUES ('steven'), ('joe');
-            SELECT * FROM foo ORDER BY id;",
+    let mut rows = client
+        .query_raw_chunked(
+            "SELECT g FROM generate_series(1, 1000) g",
+            slice_iter(&[]),
+            10,
         )
         .unwrap();
+    rows.next().unwrap().unwrap();
+
+    let done = Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let other = {
+        let client = client.clone();
+        let done = done.clone();
+        may::go!(move || {
+            let row = client.query_one("SELECT 1", &[]).unwrap();
+            assert_eq!(row.get::<_, i32>(0), 1);
+            done.store(true, std::sync::atomic::Ordering::SeqCst);
+        })
+    };
+
+    // the other request waits for the stream, which can still be read in the meantime
+    std::thread::sleep(Duration::from_millis(200));
+    assert_eq!(rows.by_ref().take(100).count(), 100);
+    assert!(!done.load(std::sync::atomic::Ordering::SeqCst));
+
+    drop(rows);
+    other.join().unwrap();
+    assert!(done.load(std::sync::atomic::Ordering::SeqCst));
+}
 
-    match messages[0] {
-        SimpleQueryMessage::CommandComplete(0) => {}
-        _ => panic!("unexpected message"),
-    }
-    match messages[1] {
-        SimpleQueryMessage::CommandComplete(2) => {}
-        _ => panic!("unexpected message"),
-    }
-    match &messages[2] {
-        SimpleQueryMessage::Row(row) => {
-            assert_eq!(row.get(0), Some("1"));
-            assert_eq!(row.get(1), Some("steven"));
-        }
-        _ => panic!("unexpected message"),
-    }
-    match &messages[3] {
-        SimpleQueryMessage::Row(row) => {
-            assert_eq!(row.get(0), Some("2"));
-            assert_eq!(row.get(1), Some("joe"));
-        }
-        _ => panic!("unexpected message"),
-    }
-    match messages[4] {
-        SimpleQueryMessage::CommandComplete(2) => {}
-        _ => panic!("unexpected message"),
-    }
-    assert_eq!(messages.len(), 5);
+#[test]
+fn query_raw_chunked_same_coroutine() {
+    let client = connect("user=postgres");
+
+    let mut rows = client
+        .query_raw_chunked(
+            "SELECT g FROM generate_series(1, 1000) g",
+            slice_iter(&[]),
+            10,
+        )
+        .unwrap();
+    rows.next().unwrap().unwrap();
+
+    // waiting for the stream from the task that reads it would never end
+    let err = match client.query_one("SELECT 1", &[]) {
+        Ok(_) => panic!("a request from the stream's own task succeeded"),
+        Err(e) => e,
+    };
+    assert!(err.to_string().contains("chunked row stream"), "{}", err);
+    assert_eq!(rows.count(), 999);
+
+    let row = client.query_one("SELECT 1", &[]).unwrap();
+    assert_eq!(row.get::<_, i32>(0), 1);
 }
 
 #[test]
-fn cancel_query_raw() {
+fn query_raw_chunked_error() {
     let client = connect("user=postgres");
 
-    join! {
-        {
-            let ret = client.batch_execute("SELECT pg_sleep(100)");
-            assert!(ret.is_err());
-            let err = ret.err().unwrap();
-            let err = err.code();
-            assert_eq!(err, Some(&SqlState::QUERY_CANCELED));
-        },
-        {
-            let socket = TcpStream::connect("127.0.0.1:5433").unwrap();
-            may::coroutine::sleep(Duration::from_millis(100));
-        let cancel_token = client.cancel_token();
-            let ret = cancel_token.cancel_query_raw(socket);
-            assert!(ret.is_ok());
+    let mut rows = client
+        .query_raw_chunked(
+            "SELECT 1 / (500 - g) FROM generate_series(1, 1000) g",
+            slice_iter(&[]),
+            100,
+        )
+        .unwrap();
+    let mut count = 0;
+    let err = loop {
+        match rows.next().unwrap() {
+            Ok(_) => count += 1,
+            Err(e) => break e,
         }
-    }
+    };
+    assert_eq!(count, 499);
+    assert_eq!(err.code(), Some(&SqlState::DIVISION_BY_ZERO));
+    drop(rows);
+
+    let row = client.query_one("SELECT 1", &[]).unwrap();
+    assert_eq!(row.get::<_, i32>(0), 1);
 }
 
 #[test]
-fn transaction_commit() {
-    let mut client = connect("user=postgres");
+fn query_raw_fallible_iterator() {
+    use may_postgres::fallible_iterator::FallibleIterator;
 
-    client
+    let client = connect("user=postgres");
+
+    let rows = client
+        .query_raw("SELECT g FROM generate_series(1, 10) g", slice_iter(&[]))
+        .unwrap();
+    // `Iterator` has a `map` too, so the first adapter is picked by its trait
+    let sum = FallibleIterator::map(rows, |row| row.try_get::<_, i32>(0))
+        .filter(|g| Ok(g % 2 == 0))
+        .fold(0, |sum, g| Ok(sum + g))
+        .unwrap();
+    assert_eq!(sum, 30);
+
+    // an error from the server ends the iteration with that error
+    let err = client
+        .query_raw(
+            "SELECT 1 / (5 - g) FROM generate_series(1, 10) g",
+            slice_iter(&[]),
+        )
+        .unwrap()
+        .map_err(|e| e.code().cloned())
+        .collect::<Vec<_>>()
+        .err()
+        .unwrap();
+    assert_eq!(err, Some(SqlState::DIVISION_BY_ZERO));
+
+    // as does an error from a closure
+    let rows = client
+        .query_raw("SELECT 'a'::TEXT", slice_iter(&[]))
+        .unwrap();
+    let err = FallibleIterator::map(rows, |row| row.try_get::<_, i32>(0))
+        .collect::<Vec<_>>()
+        .unwrap_err();
+    assert!(err.is_wrong_type());
+
+    let row = client.query_one("SELECT 1", &[]).unwrap();
+    assert_eq!(row.get::<_, i32>(0), 1);
+}
+
+#[test]
+fn pipeline() {
+    let client = connect("user=postgres");
+
+    client
+        .batch_execute("CREATE TEMPORARY TABLE foo (id INT PRIMARY KEY, name TEXT)")
+        .unwrap();
+    let insert = client
+        .prepare("INSERT INTO foo (id, name) VALUES ($1, $2)")
+        .unwrap();
+
+    let mut pipeline = client.pipeline();
+    for i in 0..3i32 {
+        pipeline
+            .push(&insert, &[&i, &format!("name {}", i)])
+            .unwrap();
+    }
+    pipeline
+        .push("UPDATE foo SET name = 'updated' WHERE id < $1", &[&2i32])
+        .unwrap();
+    assert_eq!(pipeline.len(), 4);
+    let counts = pipeline
+        .execute()
+        .unwrap()
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(counts, [1, 1, 1, 2]);
+
+    let select = client
+        .prepare("SELECT name FROM foo WHERE id = $1")
+        .unwrap();
+    let mut pipeline = client.pipeline();
+    for i in 0..3i32 {
+        pipeline.push(&select, &[&i]).unwrap();
+    }
+    let names = pipeline
+        .query()
+        .unwrap()
+        .into_iter()
+        .map(|rows| rows.unwrap()[0].get::<_, String>(0))
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["updated", "updated", "name 2"]);
+
+    assert!(client.pipeline().execute().unwrap().is_empty());
+}
+
+#[test]
+fn query_all() {
+    let client = connect("user=postgres");
+
+    let stmt = client.prepare("SELECT $1::INT").unwrap();
+    let values = (0..100).collect::<Vec<i32>>();
+    let params = values
+        .iter()
+        .map(|n| [n as &(dyn ToSql + Sync)])
+        .collect::<Vec<_>>();
+    let statements = params
+        .iter()
+        .map(|params| (&stmt, &params[..]))
+        .collect::<Vec<_>>();
+    let results = client.query_all(&statements);
+    assert_eq!(results.len(), 100);
+    for (n, rows) in results.into_iter().enumerate() {
+        let rows = rows.unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].get::<_, i32>(0), n as i32);
+    }
+
+    // a failing statement doesn't affect the others
+    let results = client.query_all::<str>(&[
+        ("SELECT 1", &[]),
+        ("SELECT 1 / 0", &[]),
+        ("SELECT 3", &[]),
+        ("SELECT * FROM nonexistent_table", &[]),
+        ("SELECT 5", &[]),
+    ]);
+    assert_eq!(results[0].as_ref().unwrap()[0].get::<_, i32>(0), 1);
+    assert_eq!(
+        results[1].as_ref().err().unwrap().code(),
+        Some(&SqlState::DIVISION_BY_ZERO)
+    );
+    assert_eq!(results[2].as_ref().unwrap()[0].get::<_, i32>(0), 3);
+    assert_eq!(
+        results[3].as_ref().err().unwrap().code(),
+        Some(&SqlState::UNDEFINED_TABLE)
+    );
+    assert_eq!(results[4].as_ref().unwrap()[0].get::<_, i32>(0), 5);
+
+    assert!(client.query_all::<str>(&[]).is_empty());
+}
+
+#[test]
+fn empty_query() {
+    let client = Arc::new(connect("user=postgres"));
+    let check = |client: &Client| {
+        let row = client.query_one("SELECT 1", &[]).unwrap();
+        assert_eq!(row.get::<_, i32>(0), 1);
+    };
+
+    // each kind of request completes with EmptyQueryResponse, and the next one still gets its own response
+    for query in ["", "  ", "-- comment"] {
+        client.batch_execute(query).unwrap();
+        check(&client);
+        let messages = client.simple_query(query).unwrap();
+        assert!(matches!(
+            messages[..],
+            [SimpleQueryMessage::CommandComplete(0)]
+        ));
+        check(&client);
+        assert_eq!(client.execute(query, &[]).unwrap(), 0);
+        check(&client);
+        assert!(client.query(query, &[]).unwrap().is_empty());
+        check(&client);
+        assert!(client.query_unnamed(query, &[]).unwrap().is_empty());
+        check(&client);
+
+        let mut pipeline = client.pipeline();
+        pipeline.push(query, &[]).unwrap();
+        pipeline.push("SELECT 1", &[]).unwrap();
+        let counts = pipeline.execute().unwrap();
+        assert_eq!(counts[0].as_ref().unwrap(), &0);
+        assert_eq!(counts[1].as_ref().unwrap(), &1);
+        check(&client);
+    }
+
+    // pipelined with other requests
+    let handles = (0..20)
+        .map(|i| {
+            let client = client.clone();
+            may::go!(move || {
+                if i % 2 == 0 {
+                    client.batch_execute("").unwrap();
+                } else {
+                    let row = client.query_one("SELECT $1::INT", &[&i]).unwrap();
+                    assert_eq!(row.get::<_, i32>(0), i);
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
+
+#[test]
+fn pipeline_error() {
+    let client = connect("user=postgres");
+
+    client
+        .batch_execute("CREATE TEMPORARY TABLE foo (id INT PRIMARY KEY)")
+        .unwrap();
+    let insert = client.prepare("INSERT INTO foo (id) VALUES ($1)").unwrap();
+
+    let mut pipeline = client.pipeline();
+    for i in &[1i32, 2, 1, 3] {
+        pipeline.push(&insert, &[i]).unwrap();
+    }
+    let results = pipeline.execute().unwrap();
+    assert_eq!(results.len(), 4);
+    assert_eq!(*results[0].as_ref().unwrap(), 1);
+    assert_eq!(*results[1].as_ref().unwrap(), 1);
+    let err = results[2].as_ref().unwrap_err();
+    assert_eq!(err.code(), Some(&SqlState::UNIQUE_VIOLATION));
+    assert!(results[3].as_ref().unwrap_err().is_skipped());
+
+    // the batch shares one implicit transaction, so the statements before the failure are rolled back too
+    let row = client.query_one("SELECT COUNT(*) FROM foo", &[]).unwrap();
+    assert_eq!(row.get::<_, i64>(0), 0);
+}
+
+#[test]
+fn insert_many() {
+    let client = connect("user=postgres");
+
+    client
+        .batch_execute(
+            "CREATE TEMPORARY TABLE foo (a INT, b INT, c INT, d INT, e INT, f INT, g INT, \"H\" TEXT)",
+        )
+        .unwrap();
+    let columns = ["a", "b", "c", "d", "e", "f", "g", r#""H""#];
+
+    // 10k rows of 8 values don't fit in the parameters of a single statement
+    let ids = (0..10_000i32).collect::<Vec<_>>();
+    let name = "name".to_string();
+    let values = ids
+        .iter()
+        .map(|id| {
+            let mut row = vec![id as &(dyn ToSql + Sync); 7];
+            row.push(&name);
+            row
+        })
+        .collect::<Vec<_>>();
+    let rows = values.iter().map(|row| &row[..]).collect::<Vec<_>>();
+
+    let before = client.stats();
+    assert_eq!(client.insert_many("foo", &columns, &rows).unwrap(), 10_000);
+    // a prepare for the full statements, one for the last, and the pipeline, then the closes of the two statements.
+    // The closes aren't waited for, but they are queued before the empty query, so they are written once it completes.
+    client.batch_execute("").unwrap();
+    assert_eq!(client.stats().requests() - before.requests(), 6);
+
+    let row = client
+        .query_one(
+            r#"SELECT COUNT(*), SUM(a), MIN("H") FROM foo WHERE a = g"#,
+            &[],
+        )
+        .unwrap();
+    assert_eq!(row.get::<_, i64>(0), 10_000);
+    assert_eq!(
+        row.get::<_, i64>(1),
+        ids.iter().map(|&i| i as i64).sum::<i64>()
+    );
+    assert_eq!(row.get::<_, &str>(2), "name");
+
+    assert_eq!(client.insert_many("foo", &columns, &[]).unwrap(), 0);
+
+    // not even one row fits in a statement
+    let too_many_columns = vec!["a"; 65536];
+    let err = client
+        .insert_many("foo", &too_many_columns, &[])
+        .unwrap_err();
+    assert!(err.is_too_many_params(), "{}", err);
+
+    // the statements share one implicit transaction, so a failure in the last one inserts nothing
+    client
+        .batch_execute("DELETE FROM foo; ALTER TABLE foo ADD CHECK (a < 9999)")
+        .unwrap();
+    let err = client.insert_many("foo", &columns, &rows).unwrap_err();
+    assert_eq!(err.code(), Some(&SqlState::CHECK_VIOLATION));
+    let row = client.query_one("SELECT COUNT(*) FROM foo", &[]).unwrap();
+    assert_eq!(row.get::<_, i64>(0), 0);
+}
+
+#[test]
+fn pipeline_single_request() {
+    let client = connect("user=postgres");
+
+    client
+        .batch_execute("CREATE TEMPORARY TABLE foo (id INT)")
+        .unwrap();
+    let insert = client
+        .prepare("INSERT INTO foo (id) VALUES ($1) RETURNING id")
+        .unwrap();
+
+    let mut pipeline = client.pipeline();
+    for i in 0..50i32 {
+        pipeline.push(&insert, &[&i]).unwrap();
+    }
+    let before = client.stats();
+    let results = pipeline.execute().unwrap();
+    // the whole batch goes out as one request, rather than a round trip per statement
+    assert_eq!(client.stats().requests() - before.requests(), 1);
+    assert_eq!(results.len(), 50);
+    for result in results {
+        assert_eq!(result.unwrap(), 1);
+    }
+
+    let ids = client
+        .query("SELECT id FROM foo", &[])
+        .unwrap()
+        .iter()
+        .map(|r| r.get(0))
+        .collect::<Vec<i32>>();
+    assert_eq!(ids, (0..50).collect::<Vec<_>>());
+}
+
+#[test]
+fn insert_select() {
+    let client = connect("user=postgres");
+
+    client
+        .batch_execute("CREATE TEMPORARY TABLE foo (id SERIAL, name TEXT)")
+        .unwrap();
+
+    let insert = client
+        .prepare("INSERT INTO foo (name) VALUES ($1), ($2)")
+        .unwrap();
+    let select = client
+        .prepare("SELECT id, name FROM foo ORDER BY id")
+        .unwrap();
+
+    let _ = client.execute(&insert, &[&"alice", &"bob"]).unwrap();
+    let rows = client.query(&select, &[]).unwrap();
+
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0].get::<_, i32>(0), 1);
+    assert_eq!(rows[0].get::<_, &str>(1), "alice");
+    assert_eq!(rows[1].get::<_, i32>(0), 2);
+    assert_eq!(rows[1].get::<_, &str>(1), "bob");
+}
+
+#[test]
+fn execute_rows_affected() {
+    let client = connect("user=postgres");
+
+    // commands without a count report 0
+    assert_eq!(
+        client
+            .execute("CREATE TEMPORARY TABLE foo (id INT, name TEXT)", &[])
+            .unwrap(),
+        0
+    );
+
+    // INSERT tags carry the legacy OID before the count, e.g. `INSERT 0 5`
+    let inserted = client
+        .execute(
+            "INSERT INTO foo SELECT g, 'name ' || g FROM generate_series(1, $1) g",
+            &[&5i32],
+        )
+        .unwrap();
+    assert_eq!(inserted, 5);
+    let messages = client
+        .simple_query("INSERT INTO foo VALUES (6, 'six')")
+        .unwrap();
+    match messages[0] {
+        SimpleQueryMessage::CommandComplete(1) => {}
+        _ => panic!("unexpected message"),
+    }
+
+    let updated = client
+        .execute("UPDATE foo SET name = 'updated' WHERE id <= $1", &[&3i32])
+        .unwrap();
+    assert_eq!(updated, 3);
+    let deleted = client
+        .execute("DELETE FROM foo WHERE id > $1", &[&4i32])
+        .unwrap();
+    assert_eq!(deleted, 2);
+    assert_eq!(
+        client
+            .execute("DELETE FROM foo WHERE id > 100", &[])
+            .unwrap(),
+        0
+    );
+    assert_eq!(client.execute("SELECT * FROM foo", &[]).unwrap(), 4);
+    assert_eq!(
+        client
+            .execute("CREATE TEMPORARY TABLE bar AS SELECT * FROM foo", &[])
+            .unwrap(),
+        4
+    );
+    assert_eq!(client.execute("", &[]).unwrap(), 0);
+}
+
+#[test]
+fn execute_with_tag() {
+    let client = connect("user=postgres");
+
+    assert_eq!(
+        client
+            .execute_with_tag("CREATE TEMPORARY TABLE foo (id INT, name TEXT)", &[])
+            .unwrap(),
+        "CREATE TABLE"
+    );
+    assert_eq!(
+        client
+            .execute_with_tag(
+                "INSERT INTO foo SELECT g, 'name' FROM generate_series(1, $1) g",
+                &[&3i32],
+            )
+            .unwrap(),
+        "INSERT 0 3"
+    );
+    assert_eq!(
+        client
+            .execute_with_tag("COPY foo TO '/dev/null'", &[])
+            .unwrap(),
+        "COPY 3"
+    );
+    assert_eq!(
+        client
+            .execute_with_tag(
+                "MERGE INTO foo USING (SELECT 2 AS id) s ON foo.id = s.id
+                 WHEN MATCHED THEN UPDATE SET name = 'merged'",
+                &[],
+            )
+            .unwrap(),
+        "MERGE 1"
+    );
+    assert_eq!(client.execute_with_tag("", &[]).unwrap(), "");
+
+    let mut client = client;
+    let transaction = client.transaction().unwrap();
+    assert_eq!(
+        transaction
+            .execute_with_tag("SELECT * FROM foo", &[])
+            .unwrap(),
+        "SELECT 3"
+    );
+}
+
+#[test]
+fn too_many_params() {
+    let client = connect("user=postgres");
+
+    let values = (0..70_000).collect::<Vec<i32>>();
+    let params = values
+        .iter()
+        .map(|v| v as &(dyn ToSql + Sync))
+        .collect::<Vec<_>>();
+
+    let stmt = client.prepare("SELECT 1").unwrap();
+    let err = client.query(&stmt, &params).err().unwrap();
+    assert!(err.is_too_many_params(), "{}", err);
+    assert_eq!(
+        err.to_string(),
+        "too many parameters: 70000 given but a statement takes at most 65535"
+    );
+    let err = client
+        .prepare_typed("SELECT 1", &vec![Type::INT4; 70_000])
+        .err()
+        .unwrap();
+    assert!(err.is_too_many_params(), "{}", err);
+
+    // counts above i16::MAX are still within the limit
+    let stmt = client
+        .prepare_typed("SELECT $40000", &vec![Type::INT4; 40_000])
+        .unwrap();
+    assert_eq!(stmt.params().len(), 40_000);
+    let row = client.query_one(&stmt, &params[..40_000]).unwrap();
+    assert_eq!(row.get::<_, i32>(0), 39_999);
+}
+
+#[test]
+fn params_macro() {
+    let client = connect("user=postgres");
+
+    let name = String::from("steven");
+    let row = client
+        .query_one(
+            "SELECT $1::INT, $2::TEXT, $3::FLOAT8, $4::BIGINT",
+            may_postgres::params![1i32, name, Some(2.5f64), None::<i64>],
+        )
+        .unwrap();
+    assert_eq!(row.get::<_, i32>(0), 1);
+    assert_eq!(row.get::<_, &str>(1), "steven");
+    assert_eq!(row.get::<_, Option<f64>>(2), Some(2.5));
+    assert_eq!(row.get::<_, Option<i64>>(3), None);
+
+    let rows = client.query("SELECT 1", may_postgres::params![]).unwrap();
+    assert_eq!(rows.len(), 1);
+}
+
+#[test]
+fn column_metadata() {
+    let client = connect("user=postgres");
+
+    client
+        .batch_execute(
+            "CREATE TEMPORARY TABLE foo (id INT, name VARCHAR(10));
+             INSERT INTO foo VALUES (1, 'alice');",
+        )
+        .unwrap();
+    let table_oid: u32 = client
+        .query_one("SELECT 'foo'::regclass::oid", &[])
+        .unwrap()
+        .get(0);
+
+    let rows = client
+        .query("SELECT *, id + 1 AS next FROM foo", &[])
+        .unwrap();
+    let columns = rows[0].columns();
+    assert_eq!(
+        columns.iter().map(|c| c.name()).collect::<Vec<_>>(),
+        ["id", "name", "next"]
+    );
+    assert_eq!(
+        columns.iter().map(|c| c.type_()).collect::<Vec<_>>(),
+        [&Type::INT4, &Type::VARCHAR, &Type::INT4]
+    );
+    assert_eq!(columns[0].table_oid(), Some(table_oid));
+    assert_eq!(columns[0].column_id(), Some(1));
+    assert_eq!(columns[0].type_modifier(), -1);
+    assert_eq!(columns[1].table_oid(), Some(table_oid));
+    assert_eq!(columns[1].column_id(), Some(2));
+    // the length plus the size of the varlena header
+    assert_eq!(columns[1].type_modifier(), 14);
+    assert_eq!(columns[2].table_oid(), None);
+    assert_eq!(columns[2].column_id(), None);
+}
+
+#[test]
+fn get_by_name() {
+    let client = connect("user=postgres");
+
+    let row = client
+        .query_one("SELECT 1::INT AS id, 'a@b.c'::TEXT AS email", &[])
+        .unwrap();
+    assert_eq!(row.get::<_, &str>("email"), "a@b.c");
+    assert_eq!(row.get::<_, i32>("id"), 1);
+
+    let err = row.try_get::<_, &str>("phone").err().unwrap();
+    assert!(err.is_missing_column());
+    assert!(!err.is_wrong_type());
+    assert_eq!(err.to_string(), "invalid column `phone`");
+    let err = row.try_get::<_, &str>(2).err().unwrap();
+    assert!(err.is_missing_column());
+
+    let err = row.try_get::<_, i32>("email").err().unwrap();
+    assert!(err.is_wrong_type());
+    assert!(!err.is_missing_column());
+    assert!(
+        err.to_string().contains("error deserializing column 1"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn get_null() {
+    let client = connect("user=postgres");
+
+    let row = client
+        .query_one("SELECT NULL::TEXT, 'foo'::TEXT, NULL::INT", &[])
+        .unwrap();
+    assert_eq!(row.get::<_, Option<String>>(0), None);
+    assert_eq!(row.get::<_, Option<&str>>(0), None);
+    assert_eq!(row.get::<_, Option<String>>(1), Some("foo".to_string()));
+    assert_eq!(row.get::<_, Option<i32>>(2), None);
+
+    // a NULL is never turned into an empty or default value
+    for err in [
+        row.try_get::<_, String>(0).err().unwrap(),
+        row.try_get::<_, &str>(0).err().unwrap(),
+        row.try_get::<_, i32>(2).err().unwrap(),
+    ] {
+        assert!(err.is_unexpected_null(), "{}", err);
+        assert!(!err.is_wrong_type());
+    }
+    assert!(!row.try_get::<_, i32>(1).err().unwrap().is_unexpected_null());
+}
+
+#[test]
+fn query_with_formats() {
+    let client = connect("user=postgres");
+
+    let query = "SELECT 42::INT, 'foo'::TEXT, '1 day'::INTERVAL, NULL::INT";
+    let rows = client
+        .query_with_formats(query, &[], &[Format::Text])
+        .unwrap();
+    let row = &rows[0];
+    assert_eq!(row.format(0), Format::Text);
+    assert_eq!(row.get_bytes(0), Some(&b"42"[..]));
+    assert_eq!(row.get_parsed::<_, i32>(0), 42);
+    assert_eq!(row.get::<_, String>(1), "foo");
+    assert_eq!(row.get::<_, &str>(2), "1 day");
+    assert_eq!(row.get::<_, Option<&str>>(3), None);
+    // the text bytes are never decoded as a binary value
+    let err = row.try_get::<_, i32>(0).err().unwrap();
+    assert!(err.is_wrong_type(), "{}", err);
+    let err = row.try_get_parsed::<_, i32>(1).err().unwrap();
+    assert!(err.to_string().contains("invalid digit"), "{}", err);
+    let err = row.try_get_parsed::<_, i32>(3).err().unwrap();
+    assert!(err.is_unexpected_null(), "{}", err);
+
+    let stmt = client.prepare("SELECT $1::INT, $1::INT + 1").unwrap();
+    let rows = client
+        .query_with_formats(&stmt, &[&1i32], &[Format::Text, Format::Binary])
+        .unwrap();
+    assert_eq!(rows[0].format(0), Format::Text);
+    assert_eq!(rows[0].format(1), Format::Binary);
+    assert_eq!(rows[0].get::<_, &str>(0), "1");
+    assert_eq!(rows[0].get::<_, i32>(1), 2);
+    // only a string column can be parsed from the binary format
+    let err = rows[0].try_get_parsed::<_, i32>(1).err().unwrap();
+    assert!(err.is_wrong_type(), "{}", err);
+
+    // the statement keeps using the binary format for other queries
+    let row = client.query_one(&stmt, &[&1i32]).unwrap();
+    assert_eq!(row.format(0), Format::Binary);
+    assert_eq!(row.get::<_, i32>(0), 1);
+}
+
+#[test]
+fn text_and_binary_results_agree() {
+    let client = connect("user=postgres");
+
+    let stmt = client
+        .prepare("SELECT i::INT8, i * 1.5::FLOAT8, i::NUMERIC::TEXT FROM generate_series(1, 100) i")
+        .unwrap();
+    let text = client
+        .query_with_formats(&stmt, &[], &[Format::Text])
+        .unwrap();
+    let binary = client
+        .query_with_formats(&stmt, &[], &[Format::Binary])
+        .unwrap();
+    assert_eq!(text.len(), binary.len());
+    for (text, binary) in text.iter().zip(&binary) {
+        assert_eq!(
+            text.get::<_, &str>(0).parse::<i64>().unwrap(),
+            binary.get::<_, i64>(0)
+        );
+        assert_eq!(
+            text.get::<_, &str>(1).parse::<f64>().unwrap(),
+            binary.get::<_, f64>(1)
+        );
+        // text columns read the same in either format
+        assert_eq!(text.get::<_, &str>(2), binary.get::<_, &str>(2));
+    }
+}
+
+#[test]
+fn custom_enum() {
+    let client = connect("user=postgres");
+
+    client
+        .batch_execute(
+            "CREATE TYPE pg_temp.mood AS ENUM (
+                'sad',
+                'ok',
+                'happy'
+            )",
+        )
+        .unwrap();
+
+    let select = client.prepare("SELECT $1::mood").unwrap();
+
+    let ty = &select.params()[0];
+    assert_eq!("mood", ty.name());
+    assert_eq!(
+        &Kind::Enum(vec![
+            "sad".to_string(),
+            "ok".to_string(),
+            "happy".to_string(),
+        ]),
+        ty.kind(),
+    );
+}
+
+may_postgres::pg_enum! {
+    #[derive(Debug, PartialEq)]
+    enum Mood = "mood" {
+        Sad = "sad",
+        Ok = "ok",
+        Happy = "happy",
+    }
+}
+
+#[test]
+fn custom_enum_mapping() {
+    let client = connect("user=postgres");
+
+    client
+        .batch_execute(
+            "CREATE TYPE pg_temp.mood AS ENUM ('sad', 'ok', 'happy');
+             CREATE TYPE pg_temp.weather AS ENUM ('sunny', 'rainy');
+             CREATE TYPE pg_temp.feeling AS ENUM ('sad', 'ok', 'happy', 'angry');
+             CREATE TEMPORARY TABLE moods (id SERIAL, mood mood);",
+        )
+        .unwrap();
+
+    for mood in &[Mood::Sad, Mood::Ok, Mood::Happy] {
+        let row = client.query_one("SELECT $1::mood", &[mood]).unwrap();
+        assert_eq!(mood, &row.get::<_, Mood>(0));
+    }
+
+    client
+        .execute(
+            "INSERT INTO moods (mood) VALUES ($1), ($2)",
+            &[&Mood::Happy, &None::<Mood>],
+        )
+        .unwrap();
+    let rows = client
+        .query("SELECT mood FROM moods ORDER BY id", &[])
+        .unwrap();
+    assert_eq!(Some(Mood::Happy), rows[0].get(0));
+    assert_eq!(None, rows[1].get::<_, Option<Mood>>(0));
+
+    // types with a different name or different labels are rejected
+    let err = client
+        .query_one("SELECT $1::weather", &[&Mood::Sad])
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("cannot convert"), "{}", err);
+    let row = client.query_one("SELECT 'angry'::feeling", &[]).unwrap();
+    assert!(row.try_get::<_, Mood>(0).is_err());
+
+    let err = <Mood as may_postgres::types::FromSql>::from_sql(&Type::TEXT, b"angry").unwrap_err();
+    assert_eq!("unknown label `angry` for enum `mood`", err.to_string());
+}
+
+may_postgres::pg_composite! {
+    #[derive(Debug, PartialEq)]
+    struct InventoryItem = "inventory_item" {
+        name: String,
+        supplier_id: i32,
+        price: Option<f64>,
+    }
+}
+
+#[test]
+fn custom_composite_mapping() {
+    let client = connect("user=postgres");
+
+    client
+        .batch_execute(
+            "CREATE TYPE pg_temp.inventory_item AS (name TEXT, supplier_id INT, price DOUBLE PRECISION);
+             CREATE TYPE pg_temp.reordered_item AS (supplier_id INT, name TEXT, price DOUBLE PRECISION);
+             CREATE FUNCTION pg_temp.cheapest() RETURNS inventory_item
+                 AS $$ SELECT ROW('fuzzy dice', 42, 1.99)::inventory_item $$ LANGUAGE SQL;",
+        )
+        .unwrap();
+
+    let row = client
+        .query_one("SELECT ROW('fuzzy dice', 42, 1.99)::inventory_item", &[])
+        .unwrap();
+    let expected = InventoryItem {
+        name: "fuzzy dice".to_string(),
+        supplier_id: 42,
+        price: Some(1.99),
+    };
+    assert_eq!(expected, row.get::<_, InventoryItem>(0));
+
+    let row = client.query_one("SELECT pg_temp.cheapest()", &[]).unwrap();
+    assert_eq!(expected, row.get::<_, InventoryItem>(0));
+
+    let item = InventoryItem {
+        name: "widget".to_string(),
+        supplier_id: 7,
+        price: None,
+    };
+    let row = client
+        .query_one("SELECT $1::inventory_item", &[&item])
+        .unwrap();
+    assert_eq!(item, row.get::<_, InventoryItem>(0));
+    let row = client
+        .query_one("SELECT ($1::inventory_item).price IS NULL", &[&item])
+        .unwrap();
+    assert!(row.get::<_, bool>(0));
+
+    // fields are matched in order
+    let row = client
+        .query_one("SELECT ROW(42, 'fuzzy dice', 1.99)::reordered_item", &[])
+        .unwrap();
+    let err = row.try_get::<_, InventoryItem>(0).err().unwrap();
+    assert!(err.is_wrong_type(), "{}", err);
+}
+
+#[test]
+fn custom_domain() {
+    let client = connect("user=postgres");
+
+    client
+        .batch_execute("CREATE DOMAIN pg_temp.session_id AS bytea CHECK(octet_length(VALUE) = 16)")
+        .unwrap();
+
+    let select = client.prepare("SELECT $1::session_id").unwrap();
+
+    let ty = &select.params()[0];
+    assert_eq!("session_id", ty.name());
+    assert_eq!(&Kind::Domain(Type::BYTEA), ty.kind());
+}
+
+#[test]
+fn custom_array() {
+    let client = connect("user=postgres");
+
+    let select = client.prepare("SELECT $1::HSTORE[]").unwrap();
+
+    let ty = &select.params()[0];
+    assert_eq!("_hstore", ty.name());
+    match ty.kind() {
+        Kind::Array(ty) => {
+            assert_eq!("hstore", ty.name());
+            assert_eq!(&Kind::Simple, ty.kind());
+        }
+        _ => panic!("unexpected kind"),
+    }
+}
+
+#[test]
+fn custom_composite() {
+    let client = connect("user=postgres");
+
+    client
+        .batch_execute(
+            "CREATE TYPE pg_temp.inventory_item AS (
+                name TEXT,
+                supplier INTEGER,
+                price NUMERIC
+            )",
+        )
+        .unwrap();
+
+    let select = client.prepare("SELECT $1::inventory_item").unwrap();
+
+    let ty = &select.params()[0];
+    assert_eq!(ty.name(), "inventory_item");
+    match ty.kind() {
+        Kind::Composite(fields) => {
+            assert_eq!(fields[0].name(), "name");
+            assert_eq!(fields[0].type_(), &Type::TEXT);
+            assert_eq!(fields[1].name(), "supplier");
+            assert_eq!(fields[1].type_(), &Type::INT4);
+            assert_eq!(fields[2].name(), "price");
+            assert_eq!(fields[2].type_(), &Type::NUMERIC);
+        }
+        _ => panic!("unexpected kind"),
+    }
+}
+
+#[test]
+fn custom_range() {
+    let client = connect("user=postgres");
+
+    client
+        .batch_execute(
+            "CREATE TYPE pg_temp.floatrange AS RANGE (
+                subtype = float8,
+                subtype_diff = float8mi
+            )",
+        )
+        .unwrap();
+
+    let select = client.prepare("SELECT $1::floatrange").unwrap();
+
+    let ty = &select.params()[0];
+    assert_eq!("floatrange", ty.name());
+    assert_eq!(&Kind::Range(Type::FLOAT8), ty.kind());
+}
+
+#[test]
+fn simple_query() {
+    let client = connect("user=postgres");
+
+    let messages = client
+        .simple_query(
+            "CREATE TEMPORARY TABLE foo (
+                id SERIAL,
+                name TEXT
+            );
+            INSERT INTO foo (name) VALUES ('steven'), ('joe');
+            SELECT * FROM foo ORDER BY id;",
+        )
+        .unwrap();
+
+    match messages[0] {
+        SimpleQueryMessage::CommandComplete(0) => {}
+        _ => panic!("unexpected message"),
+    }
+    match messages[1] {
+        SimpleQueryMessage::CommandComplete(2) => {}
+        _ => panic!("unexpected message"),
+    }
+    match &messages[2] {
+        SimpleQueryMessage::Row(row) => {
+            assert_eq!(row.get(0), Some("1"));
+            assert_eq!(row.get(1), Some("steven"));
+        }
+        _ => panic!("unexpected message"),
+    }
+    match &messages[3] {
+        SimpleQueryMessage::Row(row) => {
+            assert_eq!(row.get(0), Some("2"));
+            assert_eq!(row.get(1), Some("joe"));
+        }
+        _ => panic!("unexpected message"),
+    }
+    match messages[4] {
+        SimpleQueryMessage::CommandComplete(2) => {}
+        _ => panic!("unexpected message"),
+    }
+    assert_eq!(messages.len(), 5);
+}
+
+#[test]
+fn simple_query_multiple_statements() {
+    let client = connect("user=postgres");
+
+    let messages = client.simple_query("SELECT 1; SELECT 2").unwrap();
+    assert_eq!(messages.len(), 4);
+    for (i, expected) in ["1", "2"].iter().enumerate() {
+        match &messages[i * 2] {
+            SimpleQueryMessage::Row(row) => {
+                assert_eq!(row.columns(), ["?column?"]);
+                assert_eq!(row.get(0), Some(*expected));
+            }
+            _ => panic!("unexpected message"),
+        }
+        match messages[i * 2 + 1] {
+            SimpleQueryMessage::CommandComplete(1) => {}
+            _ => panic!("unexpected message"),
+        }
+    }
+
+    // each statement brings its own row description
+    let messages = client
+        .simple_query(
+            "SET application_name = 'simple';
+             SELECT current_setting('application_name') AS name, NULL AS nothing;
+             SELECT 1 AS a WHERE false;
+             SELECT g AS n FROM generate_series(1, 2) g;",
+        )
+        .unwrap();
+    match messages[0] {
+        SimpleQueryMessage::CommandComplete(0) => {}
+        _ => panic!("unexpected message"),
+    }
+    match &messages[1] {
+        SimpleQueryMessage::Row(row) => {
+            assert_eq!(row.columns(), ["name", "nothing"]);
+            assert_eq!(row.get("name"), Some("simple"));
+            assert_eq!(row.get("nothing"), None);
+        }
+        _ => panic!("unexpected message"),
+    }
+    match messages[2] {
+        SimpleQueryMessage::CommandComplete(1) => {}
+        _ => panic!("unexpected message"),
+    }
+    match messages[3] {
+        SimpleQueryMessage::CommandComplete(0) => {}
+        _ => panic!("unexpected message"),
+    }
+    let rows = messages[4..6]
+        .iter()
+        .map(|m| match m {
+            SimpleQueryMessage::Row(row) => {
+                assert_eq!(row.columns(), ["n"]);
+                row.get("n").unwrap().to_string()
+            }
+            _ => panic!("unexpected message"),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(rows, ["1", "2"]);
+    match messages[6] {
+        SimpleQueryMessage::CommandComplete(2) => {}
+        _ => panic!("unexpected message"),
+    }
+    assert_eq!(messages.len(), 7);
+
+    // an error stops the script and leaves the connection usable
+    let err = client
+        .simple_query("SELECT 1; SELECT 1 / 0; SELECT 3")
+        .err()
+        .unwrap();
+    assert_eq!(err.code(), Some(&SqlState::DIVISION_BY_ZERO));
+    let messages = client.simple_query("SELECT 4").unwrap();
+    match &messages[0] {
+        SimpleQueryMessage::Row(row) => assert_eq!(row.get(0), Some("4")),
+        _ => panic!("unexpected message"),
+    }
+}
+
+#[test]
+fn execute_script() {
+    let client = connect("user=postgres");
+
+    client
+        .batch_execute("CREATE TEMPORARY TABLE foo (id INT PRIMARY KEY)")
+        .unwrap();
+
+    let err = client
+        .execute_script(
+            "INSERT INTO foo VALUES (1);
+             INSERT INTO foo VALUES (1);
+             INSERT INTO foo VALUES (2);",
+        )
+        .unwrap_err();
+    assert_eq!(err.statement(), Some(1));
+    assert_eq!(err.error().code(), Some(&SqlState::UNIQUE_VIOLATION));
+    // the implicit transaction rolled back the first statement as well
+    let count: i64 = client
+        .query_one("SELECT COUNT(*) FROM foo", &[])
+        .unwrap()
+        .get(0);
+    assert_eq!(count, 0);
+
+    // empty statements are not counted, and rows are skipped
+    let err = client
+        .execute_script("SELECT 1;; SELECT * FROM foo; SELECT 1 / 0")
+        .unwrap_err();
+    assert_eq!(err.statement(), Some(2));
+    assert_eq!(err.error().code(), Some(&SqlState::DIVISION_BY_ZERO));
+
+    let n = client
+        .execute_script("INSERT INTO foo VALUES (1); SELECT * FROM foo; UPDATE foo SET id = 2")
+        .unwrap();
+    assert_eq!(n, 3);
+
+    // an empty script runs no statement
+    assert_eq!(client.execute_script("").unwrap(), 0);
+    assert_eq!(client.execute_script(" ;; ").unwrap(), 0);
+
+    // errors which aren't the server's have no statement
+    let err = client.execute_script("SELECT '\0'").unwrap_err();
+    assert_eq!(err.statement(), None);
+    client.clone().close().unwrap();
+    let err = client.execute_script("SELECT 1").unwrap_err();
+    assert!(err.error().is_closed());
+    assert_eq!(err.statement(), None);
+}
+
+#[test]
+fn cancel_query_raw() {
+    let client = connect("user=postgres");
+
+    join! {
+        {
+            let ret = client.batch_execute("SELECT pg_sleep(100)");
+            assert!(ret.is_err());
+            let err = ret.err().unwrap();
+            let err = err.code();
+            assert_eq!(err, Some(&SqlState::QUERY_CANCELED));
+        },
+        {
+            let socket = TcpStream::connect("127.0.0.1:5433").unwrap();
+            may::coroutine::sleep(Duration::from_millis(100));
+        let cancel_token = client.cancel_token();
+            let ret = cancel_token.cancel_query_raw(socket);
+            assert!(ret.is_ok());
+        }
+    }
+}
+
+#[test]
+fn transaction_commit() {
+    let mut client = connect("user=postgres");
+
+    client
+        .batch_execute(
+            "CREATE TEMPORARY TABLE foo(
+                id SERIAL,
+                name TEXT
+            )",
+        )
+        .unwrap();
+
+    let transaction = client.transaction().unwrap();
+    transaction
+        .batch_execute("INSERT INTO foo (name) VALUES ('steven')")
+        .unwrap();
+    transaction.commit().unwrap();
+
+    let stmt = client.prepare("SELECT name FROM foo").unwrap();
+    let rows = client.query(&stmt, &[]).unwrap();
+
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].get::<_, &str>(0), "steven");
+}
+
+#[test]
+fn transaction_rollback() {
+    let mut client = connect("user=postgres");
+
+    client
+        .batch_execute(
+            "CREATE TEMPORARY TABLE foo(
+                id SERIAL,
+                name TEXT
+            )",
+        )
+        .unwrap();
+
+    let transaction = client.transaction().unwrap();
+    transaction
+        .batch_execute("INSERT INTO foo (name) VALUES ('steven')")
+        .unwrap();
+    transaction.rollback().unwrap();
+
+    let stmt = client.prepare("SELECT name FROM foo").unwrap();
+    let rows = client.query(&stmt, &[]).unwrap();
+
+    assert_eq!(rows.len(), 0);
+}
+
+#[test]
+fn transaction_rollback_drop() {
+    let mut client = connect("user=postgres");
+
+    client
+        .batch_execute(
+            "CREATE TEMPORARY TABLE foo(
+                id SERIAL,
+                name TEXT
+            )",
+        )
+        .unwrap();
+
+    let transaction = client.transaction().unwrap();
+    transaction
+        .batch_execute("INSERT INTO foo (name) VALUES ('steven')")
+        .unwrap();
+    drop(transaction);
+
+    let stmt = client.prepare("SELECT name FROM foo").unwrap();
+    let rows = client.query(&stmt, &[]).unwrap();
+
+    assert_eq!(rows.len(), 0);
+}
+
+#[test]
+fn transaction_rollback_drop_other_connection() {
+    let mut client = connect("user=postgres");
+    let other = connect("user=postgres");
+
+    client
+        .batch_execute(
+            "DROP TABLE IF EXISTS transaction_drop_visibility;
+             CREATE TABLE transaction_drop_visibility (name TEXT)",
+        )
+        .unwrap();
+
+    let transaction = client.transaction().unwrap();
+    transaction
+        .execute(
+            "INSERT INTO transaction_drop_visibility (name) VALUES ('steven')",
+            &[],
+        )
+        .unwrap();
+    drop(transaction);
+
+    // wait for the rollback queued by drop on the original connection
+    client.batch_execute("SELECT 1").unwrap();
+
+    let rows = other
+        .query("SELECT name FROM transaction_drop_visibility", &[])
+        .unwrap();
+    assert_eq!(rows.len(), 0);
+
+    client
+        .batch_execute("DROP TABLE transaction_drop_visibility")
+        .unwrap();
+}
+
+#[test]
+fn savepoint() {
+    let mut client = connect("user=postgres");
+
+    client
+        .batch_execute(
+            "CREATE TEMPORARY TABLE foo(
+                id SERIAL,
+                name TEXT
+            )",
+        )
+        .unwrap();
+
+    let mut transaction = client.transaction().unwrap();
+    transaction
+        .batch_execute("INSERT INTO foo (name) VALUES ('steven')")
+        .unwrap();
+
+    let savepoint = transaction.savepoint("inner_write").unwrap();
+    savepoint
+        .batch_execute("INSERT INTO foo (name) VALUES ('joe')")
+        .unwrap();
+    savepoint.rollback().unwrap();
+
+    let savepoint = transaction.savepoint("inner_write").unwrap();
+    savepoint
+        .batch_execute("INSERT INTO foo (name) VALUES ('bob')")
+        .unwrap();
+    savepoint.commit().unwrap();
+
+    transaction.commit().unwrap();
+
+    let rows = client
+        .query("SELECT name FROM foo ORDER BY id", &[])
+        .unwrap();
+    let names = rows.iter().map(|r| r.get(0)).collect::<Vec<&str>>();
+    assert_eq!(names, ["steven", "bob"]);
+}
+
+#[test]
+fn savepoint_name_is_quoted() {
+    let mut client = connect("user=postgres");
+
+    client
+        .batch_execute("CREATE TEMPORARY TABLE foo (id INT)")
+        .unwrap();
+
+    let mut transaction = client.transaction().unwrap();
+    for name in ["My Savepoint", r#"x"; DROP TABLE foo; --"#] {
+        let savepoint = transaction.savepoint(name).unwrap();
+        savepoint
+            .batch_execute("INSERT INTO foo (id) VALUES (1)")
+            .unwrap();
+        savepoint.rollback().unwrap();
+    }
+
+    // an automatic name can't shadow a user savepoint of the same depth
+    let mut outer = transaction.savepoint("sp_2").unwrap();
+    outer
+        .batch_execute("INSERT INTO foo (id) VALUES (2)")
+        .unwrap();
+    let inner = outer.transaction().unwrap();
+    inner
+        .batch_execute("INSERT INTO foo (id) VALUES (3)")
+        .unwrap();
+    inner.commit().unwrap();
+    outer.rollback().unwrap();
+
+    let count: i64 = transaction
+        .query_one("SELECT count(*) FROM foo", &[])
+        .unwrap()
+        .get(0);
+    assert_eq!(count, 0);
+    transaction.commit().unwrap();
+}
+
+#[test]
+fn nested_transaction_rollback_drop() {
+    let mut client = connect("user=postgres");
+
+    client
+        .batch_execute(
+            "CREATE TEMPORARY TABLE foo(
+                id SERIAL,
+                name TEXT
+            )",
+        )
+        .unwrap();
+
+    let mut transaction = client.transaction().unwrap();
+    transaction
+        .batch_execute("INSERT INTO foo (name) VALUES ('steven')")
+        .unwrap();
+    {
+        let mut inner = transaction.transaction().unwrap();
+        inner
+            .batch_execute("INSERT INTO foo (name) VALUES ('joe')")
+            .unwrap();
+        {
+            let innermost = inner.transaction().unwrap();
+            innermost
+                .batch_execute("INSERT INTO foo (name) VALUES ('bob')")
+                .unwrap();
+            innermost.commit().unwrap();
+        }
+        // dropping the inner transaction also discards the released innermost one
+    }
+
+    let rows = transaction
+        .query("SELECT name FROM foo ORDER BY id", &[])
+        .unwrap();
+    let names = rows.iter().map(|r| r.get(0)).collect::<Vec<&str>>();
+    assert_eq!(names, ["steven"]);
+
+    drop(transaction);
+
+    let rows = client.query("SELECT name FROM foo", &[]).unwrap();
+    assert_eq!(rows.len(), 0);
+}
+
+#[test]
+fn transaction_builder() {
+    let mut client = connect("user=postgres");
+
+    client
+        .batch_execute(
+            "CREATE TEMPORARY TABLE foo(
+                id SERIAL,
+                name TEXT
+            )",
+        )
+        .unwrap();
+
+    let transaction = client
+        .build_transaction()
+        .isolation_level(IsolationLevel::Serializable)
+        .read_only(true)
+        .deferrable(true)
+        .start()
+        .unwrap();
+    transaction
+        .batch_execute("INSERT INTO foo (name) VALUES ('steven')")
+        .unwrap();
+    transaction.commit().unwrap();
+
+    let stmt = client.prepare("SELECT name FROM foo").unwrap();
+    let rows = client.query(&stmt, &[]).unwrap();
+
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].get::<_, &str>(0), "steven");
+}
+
+#[test]
+fn transaction_builder_settings() {
+    let mut client = connect("user=postgres");
+
+    let transaction = client
+        .build_transaction()
+        .isolation_level(IsolationLevel::RepeatableRead)
+        .read_only(true)
+        .start()
+        .unwrap();
+    let row = transaction
+        .query_one(
+            "SELECT current_setting('transaction_isolation'), current_setting('transaction_read_only')",
+            &[],
+        )
+        .unwrap();
+    assert_eq!(row.get::<_, &str>(0), "repeatable read");
+    assert_eq!(row.get::<_, &str>(1), "on");
+
+    let err = transaction
+        .batch_execute("CREATE TEMPORARY TABLE foo (id INT)")
+        .unwrap_err();
+    assert_eq!(err.code(), Some(&SqlState::READ_ONLY_SQL_TRANSACTION));
+}
+
+#[test]
+fn transaction_repeatable_read_snapshot() {
+    let mut client = connect("user=postgres");
+    let other = connect("user=postgres");
+
+    client
+        .batch_execute(
+            "DROP TABLE IF EXISTS transaction_snapshot;
+             CREATE TABLE transaction_snapshot (name TEXT)",
+        )
+        .unwrap();
+
+    let transaction = client
+        .build_transaction()
+        .isolation_level(IsolationLevel::RepeatableRead)
+        .start()
+        .unwrap();
+    let count = |transaction: &Transaction| {
+        transaction
+            .query_one("SELECT COUNT(*) FROM transaction_snapshot", &[])
+            .unwrap()
+            .get::<_, i64>(0)
+    };
+    // the snapshot is taken by the first query
+    assert_eq!(count(&transaction), 0);
+
+    other
+        .execute(
+            "INSERT INTO transaction_snapshot (name) VALUES ('steven')",
+            &[],
+        )
+        .unwrap();
+    assert_eq!(count(&transaction), 0);
+    transaction.commit().unwrap();
+
+    let rows = client
+        .query("SELECT name FROM transaction_snapshot", &[])
+        .unwrap();
+    assert_eq!(rows.len(), 1);
+
+    client
+        .batch_execute("DROP TABLE transaction_snapshot")
+        .unwrap();
+}
+
+#[test]
+fn serialization_failure() {
+    let client = connect("user=postgres");
+    let other = connect("user=postgres");
+
+    client
         .batch_execute(
-            "CREATE TEMPORARY TABLE foo(
-                id SERIAL,
+            "DROP TABLE IF EXISTS serialization_failure;
+             CREATE TABLE serialization_failure (id INT PRIMARY KEY, n INT);
+             INSERT INTO serialization_failure VALUES (1, 0)",
+        )
+        .unwrap();
+    client
+        .batch_execute(
+            "BEGIN ISOLATION LEVEL REPEATABLE READ;
+             SELECT * FROM serialization_failure",
+        )
+        .unwrap();
+    other
+        .execute("UPDATE serialization_failure SET n = n + 1", &[])
+        .unwrap();
+    let err = client
+        .execute("UPDATE serialization_failure SET n = n + 1", &[])
+        .unwrap_err();
+    assert_eq!(err.code(), Some(&SqlState::T_R_SERIALIZATION_FAILURE));
+    assert_eq!(err.code().unwrap().class(), "40");
+
+    client
+        .batch_execute("ROLLBACK; DROP TABLE serialization_failure")
+        .unwrap();
+}
+
+#[test]
+fn deadlock_detected() {
+    let client = connect("user=postgres");
+    let other = connect("user=postgres");
+
+    client
+        .batch_execute(
+            "DROP TABLE IF EXISTS deadlock_detected;
+             CREATE TABLE deadlock_detected (id INT PRIMARY KEY);
+             INSERT INTO deadlock_detected VALUES (1), (2)",
+        )
+        .unwrap();
+    for (client, id) in [(&client, 1), (&other, 2)] {
+        client
+            .batch_execute(&format!(
+                "SET deadlock_timeout = '100ms';
+                 BEGIN;
+                 UPDATE deadlock_detected SET id = id WHERE id = {}",
+                id
+            ))
+            .unwrap();
+    }
+
+    // each waits for the row the other one locked
+    let blocked = std::thread::spawn(move || {
+        let r = client.execute("UPDATE deadlock_detected SET id = id WHERE id = 2", &[]);
+        (client, r)
+    });
+    std::thread::sleep(Duration::from_millis(200));
+    let r = other.execute("UPDATE deadlock_detected SET id = id WHERE id = 1", &[]);
+    let (client, blocked_r) = blocked.join().unwrap();
+
+    // the server aborts one of the transactions, which lets the other one go on
+    let err = match (blocked_r, r) {
+        (Err(e), Ok(_)) | (Ok(_), Err(e)) => e,
+        _ => panic!("expected exactly one transaction to fail"),
+    };
+    assert_eq!(err.code(), Some(&SqlState::T_R_DEADLOCK_DETECTED));
+    assert_eq!(err.code().unwrap().class(), "40");
+
+    other.batch_execute("ROLLBACK").unwrap();
+    client
+        .batch_execute("ROLLBACK; DROP TABLE deadlock_detected")
+        .unwrap();
+}
+
+#[test]
+fn copy_in() {
+    let client = connect("user=postgres");
+
+    client
+        .batch_execute(
+            "CREATE TEMPORARY TABLE foo (
+                id INTEGER,
                 name TEXT
             )",
         )
         .unwrap();
 
-    let transaction = client.transaction().unwrap();
-    transaction
-        .batch_execute("INSERT INTO foo (name) VALUES ('steven')")
+    let mut stream = vec![
+        Bytes::from_static(b"1\tjim\n"),
+        Bytes::from_static(b"2\tjoe\n"),
+    ]
+    .into_iter()
+    .map(Ok::<_, Error>);
+
+    let mut sink = client.copy_in("COPY foo FROM STDIN").unwrap();
+    sink.send_all(&mut stream).unwrap();
+    let rows = sink.finish().unwrap();
+    assert_eq!(rows, 2);
+
+    let rows = client
+        .query("SELECT id, name FROM foo ORDER BY id", &[])
         .unwrap();
-    transaction.commit().unwrap();
 
-    let stmt = client.prepare("SELECT name FROM foo").unwrap();
-    let rows = client.query(&stmt, &[]).unwrap();
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0].get::<_, i32>(0), 1);
+    assert_eq!(rows[0].get::<_, &str>(1), "jim");
+    assert_eq!(rows[1].get::<_, i32>(0), 2);
+    assert_eq!(rows[1].get::<_, &str>(1), "joe");
+}
+
+#[test]
+fn copy_in_large() {
+    let client = connect("user=postgres");
+
+    client
+        .batch_execute(
+            "CREATE TEMPORARY TABLE foo (
+                id INTEGER,
+                name TEXT
+            )",
+        )
+        .unwrap();
+
+    let a = Bytes::from_static(b"0\tname0\n");
+    let mut b = BytesMut::new();
+    for i in 1..5_000 {
+        writeln!(b, "{0}\tname{0}", i).unwrap();
+    }
+    let mut c = BytesMut::new();
+    for i in 5_000..10_000 {
+        writeln!(c, "{0}\tname{0}", i).unwrap();
+    }
+    let mut stream = vec![a, b.freeze(), c.freeze()]
+        .into_iter()
+        .map(Ok::<_, Error>);
+    let mut sink = client.copy_in("COPY foo FROM STDIN").unwrap();
+    sink.send_all(&mut stream).unwrap();
+    let rows = sink.finish().unwrap();
+    assert_eq!(rows, 10_000);
+}
+
+#[test]
+fn copy_in_error() {
+    let client = connect("user=postgres");
+
+    client
+        .batch_execute(
+            "CREATE TEMPORARY TABLE foo (
+                id INTEGER,
+                name TEXT
+            )",
+        )
+        .unwrap();
+
+    {
+        let mut sink = client.copy_in("COPY foo FROM STDIN").unwrap();
+        sink.send(Bytes::from_static(b"1\tsteven")).unwrap();
+    }
+
+    let rows = client
+        .query("SELECT id, name FROM foo ORDER BY id", &[])
+        .unwrap();
+    assert_eq!(rows.len(), 0);
+}
+
+#[test]
+fn copy_in_writer() {
+    use std::io::Write as _;
+
+    let client = connect("user=postgres");
+
+    client
+        .batch_execute(
+            "CREATE TEMPORARY TABLE foo (
+                id INTEGER,
+                name TEXT
+            )",
+        )
+        .unwrap();
+
+    let mut writer = client
+        .copy_in_writer("COPY foo FROM STDIN WITH (FORMAT csv)")
+        .unwrap();
+    for i in 0..10_000 {
+        writeln!(writer, "{0},\"name, {0}\"", i).unwrap();
+    }
+    writer.flush().unwrap();
+    writeln!(writer, "10000,").unwrap();
+    assert_eq!(writer.finish().unwrap(), 10_001);
+
+    // the copy can't be used once it is finished
+    assert!(writer.write_all(b"1,steven\n").is_err());
+    assert!(writer.finish().is_err());
+    assert!(writer.abort("again").is_err());
+
+    let rows = client
+        .query("SELECT id, name FROM foo ORDER BY id", &[])
+        .unwrap();
+    assert_eq!(rows.len(), 10_001);
+    assert_eq!(rows[1].get::<_, &str>(1), "name, 1");
+    assert_eq!(rows[10_000].get::<_, Option<&str>>(1), None);
+}
+
+#[test]
+fn copy_in_writer_abort() {
+    use std::io::Write as _;
+
+    let client = connect("user=postgres");
+
+    client
+        .batch_execute(
+            "CREATE TEMPORARY TABLE foo (
+                id INTEGER,
+                name TEXT
+            )",
+        )
+        .unwrap();
+
+    let mut writer = client
+        .copy_in_writer("COPY foo FROM STDIN WITH (FORMAT csv)")
+        .unwrap();
+    writeln!(writer, "1,steven").unwrap();
+    writer.flush().unwrap();
+    // a message the protocol can't carry is rejected and leaves the copy running
+    let err = writer.abort("nul\0byte").err().unwrap();
+    assert!(
+        err.to_string().contains("error encoding message"),
+        "{}",
+        err
+    );
+    writer.abort("changed my mind").unwrap();
+    let err = writer.finish().err().unwrap();
+    assert_eq!(err.to_string(), "copy already finished or aborted");
+
+    let rows = client.query("SELECT id FROM foo", &[]).unwrap();
+    assert_eq!(rows.len(), 0);
+}
+
+#[test]
+fn copy_out() {
+    let client = connect("user=postgres");
+
+    client
+        .batch_execute(
+            "CREATE TEMPORARY TABLE foo (
+            id SERIAL,
+            name TEXT
+        );
+
+        INSERT INTO foo (name) VALUES ('jim'), ('joe');",
+        )
+        .unwrap();
+
+    let stmt = client.prepare("COPY foo TO STDOUT").unwrap();
+    let data = client
+        .copy_out(&stmt)
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap()
+        .concat();
+    assert_eq!(&data[..], b"1\tjim\n2\tjoe\n");
+}
+
+#[test]
+fn copy_out_round_trip() {
+    let client = connect("user=postgres");
+
+    client
+        .batch_execute(
+            "CREATE TEMPORARY TABLE foo (
+                id INT,
+                name TEXT,
+                score FLOAT8
+            );
 
+            INSERT INTO foo SELECT i, 'name ' || i, i / 4.0 FROM generate_series(1, 1000) i;",
+        )
+        .unwrap();
+
+    let data = client
+        .copy_out("COPY foo TO STDOUT")
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap()
+        .concat();
+    let text = std::str::from_utf8(&data).unwrap();
+
+    let mut lines = 0;
+    for (i, line) in (1..).zip(text.lines()) {
+        let fields = line.split('\t').collect::<Vec<_>>();
+        assert_eq!(fields.len(), 3);
+        assert_eq!(fields[0].parse::<i32>().unwrap(), i);
+        assert_eq!(fields[1], format!("name {}", i));
+        assert_eq!(fields[2].parse::<f64>().unwrap(), f64::from(i) / 4.0);
+        lines += 1;
+    }
+    assert_eq!(lines, 1000);
+
+    // the stream stops at the command completion, so the connection is ready for the next request
+    let row = client.query_one("SELECT COUNT(*) FROM foo", &[]).unwrap();
+    assert_eq!(row.get::<_, i64>(0), 1000);
+}
+
+#[test]
+fn copy_out_error() {
+    let client = connect("user=postgres");
+
+    let result = client
+        .copy_out("COPY (SELECT 1 / (3 - i) FROM generate_series(1, 5) i) TO STDOUT")
+        .and_then(|stream| stream.collect::<Result<Vec<_>, _>>());
+    let err = result.unwrap_err();
+    assert_eq!(err.code(), Some(&SqlState::DIVISION_BY_ZERO));
+
+    let row = client.query_one("SELECT 1", &[]).unwrap();
+    assert_eq!(row.get::<_, i32>(0), 1);
+}
+
+#[test]
+fn copy_progress() {
+    use std::io::Write as _;
+
+    let client = connect("user=postgres");
+
+    client
+        .batch_execute("CREATE TEMPORARY TABLE foo (id INT, name TEXT)")
+        .unwrap();
+
+    let path = std::env::temp_dir().join(format!("may_postgres_copy_{}.txt", std::process::id()));
+    let mut file = std::io::BufWriter::new(std::fs::File::create(&path).unwrap());
+    for i in 0..100_000 {
+        writeln!(file, "{}\tname {}", i, i).unwrap();
+    }
+    file.flush().unwrap();
+    drop(file);
+    let len = std::fs::metadata(&path).unwrap().len();
+
+    let check = |reports: &[may_postgres::CopyProgress], rows| {
+        assert!(reports.len() > 10, "{}", reports.len());
+        assert!(reports.windows(2).all(|w| w[0].bytes() < w[1].bytes()));
+        let last = reports.last().unwrap();
+        assert_eq!(last.bytes(), len);
+        assert_eq!(last.rows(), rows);
+    };
+
+    let reports = Arc::new(Mutex::new(vec![]));
+    let mut writer = client.copy_in_writer("COPY foo FROM STDIN").unwrap();
+    let sink = reports.clone();
+    writer.on_progress(64 * 1024, move |progress| {
+        sink.lock().unwrap().push(progress)
+    });
+    let mut file = std::fs::File::open(&path).unwrap();
+    std::io::copy(&mut file, &mut writer).unwrap();
+    assert_eq!(writer.finish().unwrap(), 100_000);
+    std::fs::remove_file(&path).unwrap();
+    check(&reports.lock().unwrap(), Some(100_000));
+
+    let reports = Arc::new(Mutex::new(vec![]));
+    let mut stream = client.copy_out("COPY foo TO STDOUT").unwrap();
+    let sink = reports.clone();
+    stream.on_progress(64 * 1024, move |progress| {
+        sink.lock().unwrap().push(progress)
+    });
+    assert_eq!(stream.map(Result::unwrap).count(), 100_000);
+    check(&reports.lock().unwrap(), Some(100_000));
+
+    // the rows of a binary copy aren't counted
+    let data = client
+        .copy_out("COPY foo TO STDOUT (FORMAT binary)")
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let len = data.iter().map(|data| data.len() as u64).sum::<u64>();
+    let reports = Arc::new(Mutex::new(vec![]));
+    let mut copy = client
+        .copy_in::<_, Bytes>("COPY foo FROM STDIN (FORMAT binary)")
+        .unwrap();
+    let sink = reports.clone();
+    copy.on_progress(64 * 1024, move |progress| {
+        sink.lock().unwrap().push(progress)
+    });
+    for data in data {
+        copy.send(data).unwrap();
+    }
+    assert_eq!(copy.finish().unwrap(), 100_000);
+    let reports = reports.lock().unwrap();
+    assert!(reports.len() > 10, "{}", reports.len());
+    assert_eq!(reports.last().unwrap().bytes(), len);
+    assert_eq!(reports.last().unwrap().rows(), None);
+}
+
+#[test]
+fn closed_after_backend_terminated() {
+    let client = connect("user=postgres");
+    let other = connect("user=postgres");
+    assert!(!client.is_closed());
+
+    let pid: i32 = client
+        .query_one("SELECT pg_backend_pid()", &[])
+        .unwrap()
+        .get(0);
+    other
+        .execute("SELECT pg_terminate_backend($1)", &[&pid])
+        .unwrap();
+
+    let err = client.query("SELECT 1", &[]).err().unwrap();
+    assert!(err.is_closed() || err.code() == Some(&SqlState::ADMIN_SHUTDOWN));
+
+    for _ in 0..100 {
+        if client.is_closed() {
+            break;
+        }
+        may::coroutine::sleep(Duration::from_millis(10));
+    }
+    assert!(client.is_closed());
+    let err = client.query("SELECT 1", &[]).err().unwrap();
+    assert!(err.is_closed());
+}
+
+#[test]
+fn query_unnamed() {
+    let client = connect("user=postgres");
+
+    let before = client.stats();
+    let rows = client
+        .query_unnamed(
+            "SELECT $1 + 1, $2",
+            &[(&41i32, Type::INT4), (&"hello", Type::TEXT)],
+        )
+        .unwrap();
     assert_eq!(rows.len(), 1);
-    assert_eq!(rows[0].get::<_, &str>(0), "steven");
+    assert_eq!(rows[0].get::<_, i32>(0), 42);
+    assert_eq!(rows[0].get::<_, &str>(1), "hello");
+    assert_eq!(rows[0].columns()[1].type_(), &Type::TEXT);
+    // a single request, with no Close following
+    assert_eq!(client.stats().requests() - before.requests(), 1);
+
+    let rows = client
+        .query_unnamed("SELECT generate_series(1, $1)", &[(&3i32, Type::INT4)])
+        .unwrap();
+    assert_eq!(rows.len(), 3);
+
+    let err = client
+        .query_unnamed("SELECT $1 / 0", &[(&1i32, Type::INT4)])
+        .err()
+        .unwrap();
+    assert_eq!(err.code(), Some(&SqlState::DIVISION_BY_ZERO));
+    let err = client
+        .query_unnamed("SELECT $1", &[(&"hello", Type::INT4)])
+        .err()
+        .unwrap();
+    assert!(err.source().unwrap().is::<WrongType>());
+    client.query_unnamed("SELECT 1", &[]).unwrap();
+
+    // column types outside the built in ones are looked up like for a prepared statement
+    client
+        .batch_execute("CREATE TYPE pg_temp.mood AS ENUM ('sad', 'ok', 'happy')")
+        .unwrap();
+    let rows = client
+        .query_unnamed("SELECT 'ok'::pg_temp.mood", &[])
+        .unwrap();
+    assert_eq!(rows[0].columns()[0].type_().name(), "mood");
 }
 
 #[test]
-fn transaction_rollback() {
-    let mut client = connect("user=postgres");
+fn query_typed() {
+    let client = connect("user=postgres");
 
-    client
-        .batch_execute(
-            "CREATE TEMPORARY TABLE foo(
-                id SERIAL,
-                name TEXT
-            )",
+    let rows = client
+        .query_typed(
+            "SELECT $1 + 1, $2",
+            &[(&41i32, Type::INT4), (&"hello", Type::TEXT)],
         )
         .unwrap();
+    assert_eq!(rows[0].get::<_, i32>(0), 42);
+    assert_eq!(rows[0].get::<_, &str>(1), "hello");
 
-    let transaction = client.transaction().unwrap();
-    transaction
-        .batch_execute("INSERT INTO foo (name) VALUES ('steven')")
+    // the mismatch is caught before anything is sent
+    let before = client.stats();
+    let err = client
+        .query_typed("SELECT $1", &[(&"hello", Type::INT4)])
+        .err()
         .unwrap();
-    transaction.rollback().unwrap();
+    assert_eq!(err.to_string(), "error serializing parameter 0: cannot convert between the Rust type `&str` and the Postgres type `int4`");
+    assert!(err.source().unwrap().is::<WrongType>());
+    assert_eq!(client.stats().requests(), before.requests());
+}
 
-    let stmt = client.prepare("SELECT name FROM foo").unwrap();
-    let rows = client.query(&stmt, &[]).unwrap();
+#[test]
+fn server_parameters() {
+    let client = connect("user=postgres");
 
-    assert_eq!(rows.len(), 0);
+    let version = client.parameter("server_version").unwrap();
+    let major = version.split('.').next().unwrap().parse::<u32>().unwrap();
+    assert!(major >= 9, "{}", version);
+    assert_eq!(client.parameter("integer_datetimes").unwrap(), "on");
+    assert_eq!(client.parameter("no_such_parameter"), None);
+
+    // changes are reported along with the response to the statement that made them
+    client.batch_execute("SET TimeZone = 'UTC'").unwrap();
+    assert_eq!(client.parameter("TimeZone").unwrap(), "UTC");
+    client
+        .batch_execute("SET TimeZone = 'America/New_York'")
+        .unwrap();
+    assert_eq!(client.parameter("TimeZone").unwrap(), "America/New_York");
 }
 
 #[test]
-fn transaction_rollback_drop() {
-    let mut client = connect("user=postgres");
+fn backend_pid() {
+    let client = connect("user=postgres");
 
-    client
-        .batch_execute(
-            "CREATE TEMPORARY TABLE foo(
-                id SERIAL,
-                name TEXT
-            )",
+    let row = client.query_one("SELECT pg_backend_pid()", &[]).unwrap();
+    assert_eq!(client.backend_pid(), row.get::<_, i32>(0));
+    assert_eq!(client.clone().backend_pid(), client.backend_pid());
+
+    let other = connect("user=postgres");
+    assert_ne!(other.backend_pid(), client.backend_pid());
+    let row = other
+        .query_one(
+            "SELECT usename FROM pg_stat_activity WHERE pid = $1",
+            &[&client.backend_pid()],
         )
         .unwrap();
+    assert_eq!(row.get::<_, &str>(0), "postgres");
+}
 
-    let transaction = client.transaction().unwrap();
-    transaction
-        .batch_execute("INSERT INTO foo (name) VALUES ('steven')")
-        .unwrap();
-    drop(transaction);
+#[test]
+fn close() {
+    let client = connect("user=postgres");
+    let stmt = client.prepare("SELECT $1::INT").unwrap();
+
+    // the responses to these are still outstanding when the client is closed
+    let streams = (0..10i32)
+        .map(|i| client.query_raw(&stmt, slice_iter(&[&i])).unwrap())
+        .collect::<Vec<_>>();
+    client.close().unwrap();
+
+    for (i, stream) in streams.into_iter().enumerate() {
+        let rows = stream.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].get::<_, i32>(0), i as i32);
+    }
+}
 
-    let stmt = client.prepare("SELECT name FROM foo").unwrap();
-    let rows = client.query(&stmt, &[]).unwrap();
+#[test]
+fn close_after_backend_terminated() {
+    let client = connect("user=postgres");
+    let admin = connect("user=postgres");
+    let pid = client
+        .query_one("SELECT pg_backend_pid()", &[])
+        .unwrap()
+        .get::<_, i32>(0);
+    admin
+        .execute("SELECT pg_terminate_backend($1)", &[&pid])
+        .unwrap();
 
-    assert_eq!(rows.len(), 0);
+    for _ in 0..100 {
+        if client.is_closed() {
+            break;
+        }
+        may::coroutine::sleep(Duration::from_millis(10));
+    }
+    assert!(client.close().unwrap_err().is_closed());
 }
 
 #[test]
-fn transaction_builder() {
-    let mut client = connect("user=postgres");
+fn db_error_fields() {
+    let client = connect("user=postgres");
 
     client
-        .batch_execute(
-            "CREATE TEMPORARY TABLE foo(
-                id SERIAL,
-                name TEXT
-            )",
-        )
+        .batch_execute("CREATE TEMPORARY TABLE foo (id INT CONSTRAINT foo_pk PRIMARY KEY)")
         .unwrap();
+    client.execute("INSERT INTO foo VALUES (1)", &[]).unwrap();
+    let err = client
+        .execute("INSERT INTO foo VALUES (1)", &[])
+        .unwrap_err();
+
+    assert_eq!(err.code(), Some(&SqlState::UNIQUE_VIOLATION));
+    assert_eq!(err.code().unwrap().code(), "23505");
+    let db = err.as_db_error().unwrap();
+    assert_eq!(db.code(), &SqlState::UNIQUE_VIOLATION);
+    assert_eq!(db.detail(), Some("Key (id)=(1) already exists."));
+    assert_eq!(db.table(), Some("foo"));
+    assert_eq!(db.constraint(), Some("foo_pk"));
+
+    let message = err.to_string();
+    assert!(
+        message.starts_with(
+            "db error: ERROR 23505: duplicate key value violates unique constraint \"foo_pk\""
+        ),
+        "{}",
+        message
+    );
+    assert!(
+        message.contains("\nDETAIL: Key (id)=(1) already exists."),
+        "{}",
+        message
+    );
+    assert!(message.contains("\nTABLE NAME: foo"), "{}", message);
+    assert!(message.contains("\nCONSTRAINT NAME: foo_pk"), "{}", message);
+    assert!(format!("{:?}", err).contains("foo_pk"));
 
-    let transaction = client
-        .build_transaction()
-        .isolation_level(IsolationLevel::Serializable)
-        .read_only(true)
-        .deferrable(true)
-        .start()
+    let err = client.execute("SELECT nonexistent", &[]).unwrap_err();
+    assert!(err.to_string().contains("\nPOSITION: 8"), "{}", err);
+
+    assert!(client
+        .query_one("SELECT 1 WHERE false", &[])
+        .err()
+        .unwrap()
+        .as_db_error()
+        .is_none());
+}
+
+#[test]
+fn fatal_error_closes_connection() {
+    let client = Arc::new(connect("user=postgres"));
+    let admin = connect("user=postgres");
+    let pid = client
+        .query_one("SELECT pg_backend_pid()", &[])
+        .unwrap()
+        .get::<_, i32>(0);
+
+    let pending = {
+        let client = client.clone();
+        may::go!(move || client.batch_execute("SELECT pg_sleep(10)"))
+    };
+    // let the sleep reach the server before terminating it
+    while admin
+        .query_one(
+            "SELECT count(*) FROM pg_stat_activity WHERE pid = $1 AND state = 'active'",
+            &[&pid],
+        )
+        .unwrap()
+        .get::<_, i64>(0)
+        == 0
+    {
+        may::coroutine::sleep(Duration::from_millis(10));
+    }
+    admin
+        .execute("SELECT pg_terminate_backend($1)", &[&pid])
         .unwrap();
-    transaction
-        .batch_execute("INSERT INTO foo (name) VALUES ('steven')")
+
+    let err = pending.join().unwrap().unwrap_err();
+    assert_eq!(err.code(), Some(&SqlState::ADMIN_SHUTDOWN));
+    // the connection is marked closed before the fatal error is handed over
+    assert!(client.is_closed());
+    assert!(client.batch_execute("SELECT 1").unwrap_err().is_closed());
+}
+
+#[test]
+fn is_valid() {
+    let client = connect("user=postgres");
+    let admin = connect("user=postgres");
+    assert!(client.is_valid(Duration::from_secs(5)));
+
+    let pid = client
+        .query_one("SELECT pg_backend_pid()", &[])
+        .unwrap()
+        .get::<_, i32>(0);
+    admin
+        .execute("SELECT pg_terminate_backend($1)", &[&pid])
         .unwrap();
-    transaction.commit().unwrap();
+    for _ in 0..100 {
+        if client.is_closed() {
+            break;
+        }
+        may::coroutine::sleep(Duration::from_millis(10));
+    }
 
-    let stmt = client.prepare("SELECT name FROM foo").unwrap();
-    let rows = client.query(&stmt, &[]).unwrap();
+    // a closed connection is reported without sending anything
+    let requests = client.stats().requests();
+    assert!(!client.is_valid(Duration::from_secs(5)));
+    assert_eq!(client.stats().requests(), requests);
+}
 
-    assert_eq!(rows.len(), 1);
-    assert_eq!(rows[0].get::<_, &str>(0), "steven");
+#[test]
+fn ordering_across_threads_and_coroutines() {
+    let client = Arc::new(connect("user=postgres"));
+    let (created_tx, created_rx) = may::sync::mpsc::channel();
+    let (inserted_tx, inserted_rx) = may::sync::mpsc::channel();
+
+    let creator = {
+        let client = client.clone();
+        may::go!(move || {
+            for i in 0..10 {
+                // the table only exists once the request is executed, which this doesn't wait for
+                let create = format!("CREATE TEMPORARY TABLE ordering_{} (id INT)", i);
+                let rows = client.query_raw(&*create, slice_iter(&[])).unwrap();
+                created_tx.send(i).unwrap();
+                inserted_rx.recv().unwrap();
+                assert_eq!(rows.count(), 0);
+            }
+        })
+    };
+    let inserter = {
+        let client = client.clone();
+        std::thread::spawn(move || {
+            for i in created_rx.iter() {
+                let insert = format!("INSERT INTO ordering_{} (id) VALUES ({})", i, i);
+                client.execute(&*insert, &[]).unwrap();
+                inserted_tx.send(()).unwrap();
+            }
+        })
+    };
+    creator.join().unwrap();
+    inserter.join().unwrap();
+
+    for i in 0..10 {
+        let select = format!("SELECT id FROM ordering_{}", i);
+        let rows = client.query(&*select, &[]).unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].get::<_, i32>(0), i);
+    }
 }
 
 #[test]
-fn copy_in() {
+fn connection_stats() {
     let client = connect("user=postgres");
+    let stmt = client.prepare("SELECT $1::INT").unwrap();
 
-    client
-        .batch_execute(
-            "CREATE TEMPORARY TABLE foo (
-                id INTEGER,
-                name TEXT
-            )",
-        )
-        .unwrap();
+    let before = client.stats();
+    assert_eq!(before.in_flight(), 0);
 
-    let mut stream = vec![
-        Bytes::from_static(b"1\tjim\n"),
-        Bytes::from_static(b"2\tjoe\n"),
-    ]
-    .into_iter()
-    .map(Ok::<_, Error>);
+    const N: u64 = 20;
+    for i in 0..N as i32 {
+        let row = client.query_one(&stmt, &[&i]).unwrap();
+        assert_eq!(row.get::<_, i32>(0), i);
+    }
 
-    let mut sink = client.copy_in("COPY foo FROM STDIN").unwrap();
-    sink.send_all(&mut stream).unwrap();
-    let rows = sink.finish().unwrap();
-    assert_eq!(rows, 2);
+    let after = client.stats();
+    assert_eq!(after.requests() - before.requests(), N);
+    assert_eq!(after.responses() - before.responses(), N);
+    assert_eq!(after.in_flight(), 0);
+    assert!(after.bytes_written() > before.bytes_written());
+}
 
-    let rows = client
-        .query("SELECT id, name FROM foo ORDER BY id", &[])
+#[test]
+fn max_coalesce_bytes() {
+    let socket = TcpStream::connect("127.0.0.1:5433").unwrap();
+    let client = "user=postgres"
+        .parse::<Config>()
+        .unwrap()
+        .max_coalesce_bytes(1)
+        .connect_raw(socket)
         .unwrap();
+    let stmt = client.prepare("SELECT $1::INT").unwrap();
+
+    let before = client.stats();
+    let streams = (0..20i32)
+        .map(|i| client.query_raw(&stmt, slice_iter(&[&i])).unwrap())
+        .collect::<Vec<_>>();
+    for (i, stream) in streams.into_iter().enumerate() {
+        let rows = stream.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(rows[0].get::<_, i32>(0), i as i32);
+    }
 
-    assert_eq!(rows.len(), 2);
-    assert_eq!(rows[0].get::<_, i32>(0), 1);
-    assert_eq!(rows[0].get::<_, &str>(1), "jim");
-    assert_eq!(rows[1].get::<_, i32>(0), 2);
-    assert_eq!(rows[1].get::<_, &str>(1), "joe");
+    // with a limit of a single byte every request is written on its own
+    let after = client.stats();
+    assert_eq!(after.requests() - before.requests(), 20);
+    assert!(after.writes() - before.writes() >= 20);
 }
 
 #[test]
-fn copy_in_large() {
+fn max_in_flight() {
+    let socket = TcpStream::connect("127.0.0.1:5433").unwrap();
+    let client = "user=postgres"
+        .parse::<Config>()
+        .unwrap()
+        .max_in_flight(4)
+        .connect_raw(socket)
+        .unwrap();
+    let client = Arc::new(client);
+    let stmt = client.prepare("SELECT $1::INT").unwrap();
+
+    let done = Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let monitor = {
+        let client = client.clone();
+        let done = done.clone();
+        may::go!(move || {
+            let mut max = 0;
+            while !done.load(std::sync::atomic::Ordering::Acquire) {
+                max = max.max(client.stats().in_flight());
+                may::coroutine::sleep(Duration::from_millis(1));
+            }
+            max
+        })
+    };
+
+    let handles = (0..50)
+        .map(|i| {
+            let client = client.clone();
+            let stmt = stmt.clone();
+            may::go!(move || {
+                for j in 0..20 {
+                    let row = client.query_one(&stmt, &[&(i * 100 + j)]).unwrap();
+                    assert_eq!(row.get::<_, i32>(0), i * 100 + j);
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    done.store(true, std::sync::atomic::Ordering::Release);
+
+    let max = monitor.join().unwrap();
+    assert!(max <= 4, "{}", max);
+    assert_eq!(client.stats().in_flight(), 0);
+}
+
+#[test]
+fn notices() {
     let client = connect("user=postgres");
 
+    let notices = Arc::new(Mutex::new(vec![]));
+    let notices_c = notices.clone();
+    client.set_notice_callback(move |notice| notices_c.lock().unwrap().push(notice));
+
     client
         .batch_execute(
-            "CREATE TEMPORARY TABLE foo (
-                id INTEGER,
-                name TEXT
-            )",
+            "SET client_min_messages = 'notice';
+             DO $$BEGIN RAISE NOTICE 'hello' USING DETAIL = 'some detail', HINT = 'some hint'; END$$;
+             DROP TABLE IF EXISTS noexisttable;",
         )
         .unwrap();
 
-    let a = Bytes::from_static(b"0\tname0\n");
-    let mut b = BytesMut::new();
-    for i in 1..5_000 {
-        writeln!(b, "{0}\tname{0}", i).unwrap();
-    }
-    let mut c = BytesMut::new();
-    for i in 5_000..10_000 {
-        writeln!(c, "{0}\tname{0}", i).unwrap();
-    }
-    let mut stream = vec![a, b.freeze(), c.freeze()]
-        .into_iter()
-        .map(Ok::<_, Error>);
-    let mut sink = client.copy_in("COPY foo FROM STDIN").unwrap();
-    sink.send_all(&mut stream).unwrap();
-    let rows = sink.finish().unwrap();
-    assert_eq!(rows, 10_000);
+    let notices = notices.lock().unwrap();
+    assert_eq!(notices.len(), 2);
+    assert_eq!(notices[0].severity(), "NOTICE");
+    assert_eq!(notices[0].parsed_severity(), Some(Severity::Notice));
+    assert_eq!(notices[0].code(), &SqlState::SUCCESSFUL_COMPLETION);
+    assert_eq!(notices[0].message(), "hello");
+    assert_eq!(notices[0].detail(), Some("some detail"));
+    assert_eq!(notices[0].hint(), Some("some hint"));
+    assert_eq!(
+        notices[1].message(),
+        "table \"noexisttable\" does not exist, skipping"
+    );
 }
 
 #[test]
-fn copy_in_error() {
+fn notifications() {
     let client = connect("user=postgres");
 
     client
         .batch_execute(
-            "CREATE TEMPORARY TABLE foo (
-                id INTEGER,
-                name TEXT
-            )",
+            "LISTEN test_notifications;
+             NOTIFY test_notifications, 'hello';
+             NOTIFY test_notifications, 'world';",
         )
         .unwrap();
 
-    {
-        let mut sink = client.copy_in("COPY foo FROM STDIN").unwrap();
-        sink.send(Bytes::from_static(b"1\tsteven")).unwrap();
-    }
-
-    let rows = client
-        .query("SELECT id, name FROM foo ORDER BY id", &[])
-        .unwrap();
-    assert_eq!(rows.len(), 0);
+    let notifications = client
+        .notifications()
+        .timeout_iter(Duration::from_secs(1))
+        .take(2)
+        .collect::<Vec<_>>();
+    assert_eq!(notifications.len(), 2);
+    assert_eq!(notifications[0].channel(), "test_notifications");
+    assert_eq!(notifications[0].payload(), "hello");
+    assert_eq!(notifications[1].channel(), "test_notifications");
+    assert_eq!(notifications[1].payload(), "world");
+
+    assert_eq!(client.notifications().iter().count(), 0);
 }
 
 #[test]
-fn copy_out() {
+fn notifications_from_other_client() {
     let client = connect("user=postgres");
+    let other = connect("user=postgres");
 
     client
-        .batch_execute(
-            "CREATE TEMPORARY TABLE foo (
-            id SERIAL,
-            name TEXT
-        );
-
-        INSERT INTO foo (name) VALUES ('jim'), ('joe');",
-        )
+        .batch_execute("LISTEN test_foo; LISTEN test_bar")
         .unwrap();
 
-    let stmt = client.prepare("COPY foo TO STDOUT").unwrap();
-    let data = client
-        .copy_out(&stmt)
-        .unwrap()
-        .collect::<Result<Vec<_>, _>>()
-        .unwrap()
-        .concat();
-    assert_eq!(&data[..], b"1\tjim\n2\tjoe\n");
+    other.batch_execute("NOTIFY test_foo, 'hi'").unwrap();
+    other.batch_execute("NOTIFY test_bar, 'there'").unwrap();
+
+    let mut notifications = client.notifications();
+    let mut it = notifications.timeout_iter(Duration::from_secs(1));
+    let notification = it.next().unwrap();
+    assert_eq!(notification.channel(), "test_foo");
+    assert_eq!(notification.payload(), "hi");
+    let notification = it.next().unwrap();
+    assert_eq!(notification.channel(), "test_bar");
+    assert_eq!(notification.payload(), "there");
+    assert!(it.next().is_none());
 }
 
-// #[test]
-// fn notices() {
-//     let long_name = "x".repeat(65);
-//     let client = connect_raw(&format!("user=postgres application_name={}", long_name,)).unwrap();
-
-//     let (tx, rx) = mpsc::unbounded();
-//     let stream = stream::poll_fn(move |cx| connection.poll_message(cx)).map_err(|e| panic!(e));
-//     let connection = stream.forward(tx).map(|r| r.unwrap());
-//     tokio::spawn(connection);
-
-//     client
-//         .batch_execute("DROP DATABASE IF EXISTS noexistdb")
-//         .unwrap();
-
-//     drop(client);
-
-//     let notices = rx
-//         .filter_map(|m| match m {
-//             AsyncMessage::Notice(n) => future::ready(Some(n)),
-//             _ => future::ready(None),
-//         })
-//         .collect::<Vec<_>>();
-//     assert_eq!(notices.len(), 2);
-//     assert_eq!(
-//         notices[0].message(),
-//         "identifier \"xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx\" \
-//          will be truncated to \"xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx\""
-//     );
-//     assert_eq!(
-//         notices[1].message(),
-//         "database \"noexistdb\" does not exist, skipping"
-//     );
-// }
-
-// #[test]
-// fn notifications() {
-//     let (mut client, mut connection) = connect_raw("user=postgres").unwrap();
-
-//     let (tx, rx) = mpsc::unbounded();
-//     let stream = stream::poll_fn(move |cx| connection.poll_message(cx)).map_err(|e| panic!(e));
-//     let connection = stream.forward(tx).map(|r| r.unwrap());
-//     tokio::spawn(connection);
-
-//     client
-//         .batch_execute(
-//             "LISTEN test_notifications;
-//              NOTIFY test_notifications, 'hello';
-//              NOTIFY test_notifications, 'world';",
-//         )
-//         .unwrap();
-
-//     drop(client);
-
-//     let notifications = rx
-//         .filter_map(|m| match m {
-//             AsyncMessage::Notification(n) => future::ready(Some(n)),
-//             _ => future::ready(None),
-//         })
-//         .collect::<Vec<_>>();
-//     assert_eq!(notifications.len(), 2);
-//     assert_eq!(notifications[0].channel(), "test_notifications");
-//     assert_eq!(notifications[0].payload(), "hello");
-//     assert_eq!(notifications[1].channel(), "test_notifications");
-//     assert_eq!(notifications[1].payload(), "world");
-// }
-
 #[test]
 fn query_portal() {
     let mut client = connect("user=postgres");
@@ -627,6 +2883,42 @@ fn query_portal() {
     assert_eq!(r3.len(), 0);
 }
 
+#[test]
+fn query_portal_pages() {
+    let mut client = connect("user=postgres");
+
+    let transaction = client.transaction().unwrap();
+    let portal = transaction
+        .bind("SELECT g FROM generate_series(1, $1) g", &[&35i32])
+        .unwrap();
+
+    let mut pages = vec![];
+    while !portal.is_exhausted() {
+        let rows = transaction.query_portal(&portal, 10).unwrap();
+        pages.push(rows.iter().map(|r| r.get::<_, i32>(0)).collect::<Vec<_>>());
+    }
+    assert_eq!(
+        pages.iter().map(|p| p.len()).collect::<Vec<_>>(),
+        [10, 10, 10, 5]
+    );
+    assert_eq!(pages.concat(), (1..=35).collect::<Vec<_>>());
+
+    // an exact multiple of the page size needs an empty page to find the end
+    let portal = transaction
+        .bind("SELECT g FROM generate_series(1, 20) g", &[])
+        .unwrap();
+    assert_eq!(transaction.query_portal(&portal, 10).unwrap().len(), 10);
+    assert_eq!(transaction.query_portal(&portal, 10).unwrap().len(), 10);
+    assert!(!portal.is_exhausted());
+    assert_eq!(transaction.query_portal(&portal, 10).unwrap().len(), 0);
+    assert!(portal.is_exhausted());
+
+    let other = portal.clone();
+    portal.close().unwrap();
+    let err = transaction.query_portal(&other, 10).err().unwrap();
+    assert_eq!(err.code(), Some(&SqlState::UNDEFINED_CURSOR));
+}
+
 #[test]
 fn check_send() {
     fn is_send<T: Send>(_: &T) {}
@@ -675,13 +2967,21 @@ fn query_one() {
         )
         .unwrap();
 
-    client
+    let err = client
         .query_one("SELECT * FROM foo WHERE name = 'dave'", &[])
         .err()
         .unwrap();
+    assert!(err.is_no_rows());
+    assert!(!err.is_too_many_rows());
     client
         .query_one("SELECT * FROM foo WHERE name = 'alice'", &[])
         .unwrap();
+    let err = client
+        .query_one("SELECT * FROM foo WHERE name IN ('alice', 'bob')", &[])
+        .err()
+        .unwrap();
+    assert!(err.is_too_many_rows());
+    assert!(!err.is_no_rows());
     client.query_one("SELECT * FROM foo", &[]).err().unwrap();
 }
 
@@ -708,5 +3008,143 @@ fn query_opt() {
         .query_opt("SELECT * FROM foo WHERE name = 'alice'", &[])
         .unwrap()
         .unwrap();
-    client.query_one("SELECT * FROM foo", &[]).err().unwrap();
+    let err = client
+        .query_opt("SELECT * FROM foo WHERE name IN ('alice', 'bob')", &[])
+        .err()
+        .unwrap();
+    assert!(err.is_too_many_rows());
+    client.query_opt("SELECT * FROM foo", &[]).err().unwrap();
+}
+
+#[test]
+fn escape() {
+    let client = connect("user=postgres");
+
+    assert_eq!(may_postgres::escape_identifier("foo"), r#""foo""#);
+    assert_eq!(
+        may_postgres::escape_identifier(r#"weird "name""#),
+        r#""weird ""name""""#
+    );
+    assert_eq!(may_postgres::escape_identifier("a\0b"), r#""a""#);
+    assert_eq!(may_postgres::escape_literal("it's"), "'it''s'");
+    assert_eq!(may_postgres::escape_literal(r"a\'b"), r" E'a\\''b'");
+    assert_eq!(may_postgres::escape_literal("a\0b"), "'a'");
+
+    let table = r#"weird "Table""#;
+    let column = r#"it's "id""#;
+    client
+        .batch_execute(&format!(
+            "CREATE TEMPORARY TABLE {} ({} TEXT)",
+            may_postgres::escape_identifier(table),
+            may_postgres::escape_identifier(column)
+        ))
+        .unwrap();
+
+    let values = ["plain", "it's", r"back\slash", r"both \' and '\"];
+    for value in &values {
+        client
+            .batch_execute(&format!(
+                "INSERT INTO {} VALUES ({})",
+                may_postgres::escape_identifier(table),
+                may_postgres::escape_literal(value)
+            ))
+            .unwrap();
+    }
+
+    let rows = client
+        .query("SELECT relname FROM pg_class WHERE relname = $1", &[&table])
+        .unwrap();
+    assert_eq!(rows.len(), 1);
+
+    let rows = client
+        .query(
+            &*format!(
+                "SELECT {} FROM {}",
+                may_postgres::escape_identifier(column),
+                may_postgres::escape_identifier(table)
+            ),
+            &[],
+        )
+        .unwrap();
+    let got = rows.iter().map(|r| r.get::<_, &str>(0)).collect::<Vec<_>>();
+    assert_eq!(got, values);
+
+    client
+        .batch_execute("SET standard_conforming_strings = off")
+        .unwrap();
+    let row = client
+        .query_one(
+            &*format!("SELECT {}", may_postgres::escape_literal(r"back\slash")),
+            &[],
+        )
+        .unwrap();
+    assert_eq!(row.get::<_, &str>(0), r"back\slash");
+}
+
+#[test]
+fn cloned_clients() {
+    let client = connect("user=postgres");
+    client
+        .batch_execute("CREATE TEMPORARY TABLE foo (id INT, worker INT)")
+        .unwrap();
+    let before = client.stats();
+
+    let handles = (0..8)
+        .map(|worker| {
+            let client = client.clone();
+            may::go!(move || {
+                let insert = client
+                    .prepare_cached("INSERT INTO foo (id, worker) VALUES ($1, $2)")
+                    .unwrap();
+                for id in 0..50 {
+                    client.execute(&insert, &[&id, &worker]).unwrap();
+                    let row = client.query_one("SELECT $1::INT + 1", &[&id]).unwrap();
+                    assert_eq!(row.get::<_, i32>(0), id + 1);
+                }
+                insert
+            })
+        })
+        .collect::<Vec<_>>();
+    let statements = handles
+        .into_iter()
+        .map(|handle| handle.join().unwrap())
+        .collect::<Vec<_>>();
+
+    // the clones share one connection and one statement cache
+    let prepared = client
+        .query_one(
+            "SELECT COUNT(*) FROM pg_prepared_statements WHERE statement LIKE 'INSERT INTO foo%'",
+            &[],
+        )
+        .unwrap();
+    assert_eq!(prepared.get::<_, i64>(0), 1);
+    drop(statements);
+    let counts = client
+        .query(
+            "SELECT worker, COUNT(*) FROM foo GROUP BY worker ORDER BY worker",
+            &[],
+        )
+        .unwrap();
+    assert_eq!(counts.len(), 8);
+    for row in &counts {
+        assert_eq!(row.get::<_, i64>(1), 50);
+    }
+    assert!(client.stats().requests() - before.requests() >= 8 * 100);
+
+    // the connection outlives every clone but the last
+    let clone = client.clone();
+    drop(client);
+    assert!(!clone.is_closed());
+    let rows = clone.query("SELECT COUNT(*) FROM foo", &[]).unwrap();
+    assert_eq!(rows[0].get::<_, i64>(0), 400);
+
+    // but closing any clone closes it for all of them, after answering what they sent before
+    let other = clone.clone();
+    let pending = other.query_raw("SELECT 1", slice_iter(&[])).unwrap();
+    clone.close().unwrap();
+    let rows = pending.collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(rows.len(), 1);
+    assert!(other.is_closed());
+    let err = other.query("SELECT 1", &[]).err().unwrap();
+    assert!(err.is_closed(), "{}", err);
 }
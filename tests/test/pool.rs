@@ -0,0 +1,173 @@
+use may_postgres::{Config, Pool};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+fn config() -> Config {
+    "host=localhost port=5433 user=postgres".parse().unwrap()
+}
+
+#[test]
+fn lazy_and_eager() {
+    let pool = Pool::builder().max_size(3).build(config()).unwrap();
+    assert_eq!(pool.size(), 0);
+
+    let conn = pool.get().unwrap();
+    let row = conn.query_one("SELECT 1", &[]).unwrap();
+    assert_eq!(row.get::<_, i32>(0), 1);
+    assert_eq!(pool.size(), 1);
+    assert_eq!(pool.idle(), 0);
+    drop(conn);
+    assert_eq!(pool.idle(), 1);
+
+    let pool = Pool::builder()
+        .max_size(3)
+        .eager(true)
+        .build(config())
+        .unwrap();
+    assert_eq!(pool.size(), 3);
+    assert_eq!(pool.idle(), 3);
+}
+
+#[test]
+fn reuse() {
+    let pool = Pool::builder().max_size(1).build(config()).unwrap();
+
+    let pid: i32 = pool
+        .get()
+        .unwrap()
+        .query_one("SELECT pg_backend_pid()", &[])
+        .unwrap()
+        .get(0);
+    let mut conn = pool.get().unwrap();
+    let transaction = conn.transaction().unwrap();
+    let row = transaction
+        .query_one("SELECT pg_backend_pid()", &[])
+        .unwrap();
+    assert_eq!(row.get::<_, i32>(0), pid);
+}
+
+#[test]
+fn concurrent_get() {
+    let pool = Pool::builder().max_size(3).build(config()).unwrap();
+    let in_use = Arc::new(AtomicUsize::new(0));
+    let max_in_use = Arc::new(AtomicUsize::new(0));
+
+    let handles = (0..20)
+        .map(|i| {
+            let pool = pool.clone();
+            let in_use = in_use.clone();
+            let max_in_use = max_in_use.clone();
+            may::go!(move || {
+                let conn = pool.get().unwrap();
+                let n = in_use.fetch_add(1, Ordering::SeqCst) + 1;
+                max_in_use.fetch_max(n, Ordering::SeqCst);
+
+                let row = conn
+                    .query_one("SELECT $1::INT, pg_sleep(0.01)::TEXT", &[&i])
+                    .unwrap();
+                assert_eq!(row.get::<_, i32>(0), i);
+
+                in_use.fetch_sub(1, Ordering::SeqCst);
+            })
+        })
+        .collect::<Vec<_>>();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    assert!(max_in_use.load(Ordering::SeqCst) <= 3);
+    assert_eq!(pool.size(), 3);
+    assert_eq!(pool.idle(), 3);
+}
+
+#[test]
+fn get_timeout() {
+    let pool = Pool::builder()
+        .max_size(1)
+        .connection_timeout(Duration::from_millis(50))
+        .build(config())
+        .unwrap();
+
+    let conn = pool.get().unwrap();
+    let err = pool.get().err().unwrap();
+    assert!(err.is_timeout(), "{}", err);
+
+    drop(conn);
+    pool.get().unwrap();
+}
+
+#[test]
+fn broken_connection_evicted() {
+    let pool = Pool::builder().max_size(1).build(config()).unwrap();
+    let other = config().connect().unwrap();
+
+    let conn = pool.get().unwrap();
+    let pid: i32 = conn
+        .query_one("SELECT pg_backend_pid()", &[])
+        .unwrap()
+        .get(0);
+    drop(conn);
+    assert_eq!(pool.idle(), 1);
+
+    other
+        .execute("SELECT pg_terminate_backend($1)", &[&pid])
+        .unwrap();
+
+    // the idle connection fails validation and is replaced
+    let conn = pool.get().unwrap();
+    let new_pid: i32 = conn
+        .query_one("SELECT pg_backend_pid()", &[])
+        .unwrap()
+        .get(0);
+    assert_ne!(pid, new_pid);
+    assert_eq!(pool.size(), 1);
+}
+
+#[test]
+fn returned_connection_is_reset() {
+    let pool = Pool::builder().max_size(1).build(config()).unwrap();
+
+    let notices = Arc::new(AtomicUsize::new(0));
+    let conn = pool.get().unwrap();
+    let notices_c = notices.clone();
+    conn.set_notice_callback(move |_| {
+        notices_c.fetch_add(1, Ordering::SeqCst);
+    });
+    conn.prepare_cached("SELECT 1").unwrap();
+    drop(conn);
+
+    // the next borrower doesn't get the callback, but keeps using the statements cached by the previous one
+    let conn = pool.get().unwrap();
+    conn.batch_execute("DO $$BEGIN RAISE NOTICE 'hello'; END$$")
+        .unwrap();
+    assert_eq!(notices.load(Ordering::SeqCst), 0);
+    let before = conn.stats();
+    conn.prepare_cached("SELECT 1").unwrap();
+    assert_eq!(conn.stats().requests() - before.requests(), 0);
+}
+
+#[test]
+fn shared_connection_not_returned() {
+    let pool = Pool::builder().max_size(1).build(config()).unwrap();
+
+    let conn = pool.get().unwrap();
+    let client = (*conn).clone();
+    let pid: i32 = conn
+        .query_one("SELECT pg_backend_pid()", &[])
+        .unwrap()
+        .get(0);
+    drop(conn);
+    assert_eq!(pool.size(), 0);
+    assert_eq!(pool.idle(), 0);
+
+    // the clone keeps the connection, and the next borrower gets another one
+    let conn = pool.get().unwrap();
+    let new_pid: i32 = conn
+        .query_one("SELECT pg_backend_pid()", &[])
+        .unwrap()
+        .get(0);
+    assert_ne!(pid, new_pid);
+    let row = client.query_one("SELECT pg_backend_pid()", &[]).unwrap();
+    assert_eq!(row.get::<_, i32>(0), pid);
+}
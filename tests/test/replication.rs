@@ -0,0 +1,206 @@
+use crate::connect;
+use bytes::Bytes;
+use may::net::TcpStream;
+use may_postgres::config::{Config, ReplicationMode};
+use may_postgres::replication::{
+    standby_status_update, ReplicationMessage, ReplicationStream, XLogData,
+};
+use may_postgres::SimpleQueryMessage;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+fn parse_lsn(lsn: &str) -> u64 {
+    let (hi, lo) = lsn.split_once('/').unwrap();
+    u64::from_str_radix(hi, 16).unwrap() << 32 | u64::from_str_radix(lo, 16).unwrap()
+}
+
+fn format_lsn(lsn: u64) -> String {
+    format!("{:X}/{:X}", lsn >> 32, lsn & 0xffff_ffff)
+}
+
+fn data(data: &XLogData) -> String {
+    String::from_utf8(data.data().to_vec()).unwrap()
+}
+
+#[test]
+fn logical_replication() {
+    let client = connect("user=postgres");
+    client
+        .batch_execute(
+            "DROP TABLE IF EXISTS replication_foo;
+             CREATE TABLE replication_foo (id INT, name TEXT)",
+        )
+        .unwrap();
+
+    // keepalives which ask for a reply come every half second, and a missing reply ends the connection after one
+    let repl = connect("user=postgres replication=database options='-c wal_sender_timeout=1000'");
+    let slot = "may_postgres_logical";
+    let messages = repl
+        .simple_query(&format!(
+            "CREATE_REPLICATION_SLOT {} TEMPORARY LOGICAL test_decoding",
+            slot
+        ))
+        .unwrap();
+    let start = match &messages[0] {
+        SimpleQueryMessage::Row(row) => parse_lsn(row.get("consistent_point").unwrap()),
+        _ => panic!("expected a row"),
+    };
+
+    client
+        .batch_execute(
+            "INSERT INTO replication_foo VALUES (1, 'steven');
+             INSERT INTO replication_foo VALUES (2, 'joe')",
+        )
+        .unwrap();
+
+    let (sink, stream) = repl
+        .copy_both(&format!(
+            "START_REPLICATION SLOT {} LOGICAL {}",
+            slot,
+            format_lsn(start)
+        ))
+        .unwrap();
+    let mut stream = ReplicationStream::new(sink, stream);
+
+    let mut changes = vec![];
+    while changes.len() < 2 {
+        let data = stream.next().unwrap().unwrap();
+        assert!(data.wal_start() >= start);
+        stream.set_flushed_lsn(data.wal_start());
+        let text = self::data(&data);
+        if text.starts_with("table ") {
+            changes.push(text);
+        }
+    }
+    assert_eq!(
+        changes,
+        [
+            "table public.replication_foo: INSERT: id[integer]:1 name[text]:'steven'",
+            "table public.replication_foo: INSERT: id[integer]:2 name[text]:'joe'",
+        ]
+    );
+
+    // the stream must answer the keepalives to outlive the sender timeout
+    let inserted = Instant::now();
+    let writer = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(2500));
+        client
+            .batch_execute("INSERT INTO replication_foo VALUES (3, 'bob')")
+            .unwrap();
+        client
+    });
+    loop {
+        let data = stream.next().unwrap().unwrap();
+        stream.set_flushed_lsn(data.wal_start());
+        if self::data(&data).starts_with("table ") {
+            assert!(self::data(&data).contains("'bob'"));
+            break;
+        }
+    }
+    assert!(inserted.elapsed() >= Duration::from_secs(2));
+    let client = writer.join().unwrap();
+
+    stream.send_status_update(false).unwrap();
+    stream.finish().unwrap();
+    for data in stream {
+        data.unwrap();
+    }
+
+    let confirmed = client
+        .query_one(
+            "SELECT confirmed_flush_lsn::TEXT FROM pg_replication_slots WHERE slot_name = $1",
+            &[&slot],
+        )
+        .unwrap();
+    assert!(parse_lsn(confirmed.get(0)) > start);
+
+    // the connection can be used again once the copy is over
+    repl.simple_query("IDENTIFY_SYSTEM").unwrap();
+    client.batch_execute("DROP TABLE replication_foo").unwrap();
+}
+
+#[test]
+fn copy_both_error() {
+    let repl = connect("user=postgres replication=database");
+
+    let err = repl
+        .copy_both("START_REPLICATION SLOT may_postgres_missing LOGICAL 0/0")
+        .err()
+        .unwrap();
+    assert!(err.as_db_error().is_some(), "{}", err);
+
+    let messages = repl.simple_query("SELECT 1").unwrap();
+    assert!(matches!(messages[0], SimpleQueryMessage::Row(_)));
+}
+
+#[test]
+fn replication_messages() {
+    let mut keepalive = vec![b'k'];
+    keepalive.extend_from_slice(&0x1_0000_0010u64.to_be_bytes());
+    keepalive.extend_from_slice(&1_000_000i64.to_be_bytes());
+    keepalive.push(1);
+    let keepalive = match ReplicationMessage::parse(&Bytes::from(keepalive)).unwrap() {
+        ReplicationMessage::PrimaryKeepAlive(keepalive) => keepalive,
+        message => panic!("unexpected message {:?}", message),
+    };
+    assert_eq!(keepalive.wal_end(), 0x1_0000_0010);
+    assert_eq!(
+        keepalive.timestamp(),
+        UNIX_EPOCH + Duration::from_secs(946_684_801)
+    );
+    assert!(keepalive.reply());
+
+    let mut xlog = vec![b'w'];
+    xlog.extend_from_slice(&5u64.to_be_bytes());
+    xlog.extend_from_slice(&9u64.to_be_bytes());
+    xlog.extend_from_slice(&(-1i64).to_be_bytes());
+    xlog.extend_from_slice(b"data");
+    match ReplicationMessage::parse(&Bytes::from(xlog)).unwrap() {
+        ReplicationMessage::XLogData(data) => {
+            assert_eq!(data.wal_start(), 5);
+            assert_eq!(data.wal_end(), 9);
+            assert_eq!(data.data(), "data");
+        }
+        message => panic!("unexpected message {:?}", message),
+    }
+
+    assert!(ReplicationMessage::parse(&Bytes::from_static(b"w\0\0")).is_err());
+    assert!(ReplicationMessage::parse(&Bytes::from_static(b"x")).is_err());
+
+    let time = UNIX_EPOCH + Duration::from_secs(946_684_802);
+    let update = standby_status_update(1, 2, 3, time, true);
+    assert_eq!(update.len(), 34);
+    assert_eq!(update[0], b'r');
+    assert_eq!(&update[1..9], &1u64.to_be_bytes());
+    assert_eq!(&update[17..25], &3u64.to_be_bytes());
+    assert_eq!(&update[25..33], &2_000_000i64.to_be_bytes());
+    assert_eq!(update[33], 1);
+}
+
+#[test]
+fn identify_system() {
+    let client = connect("user=postgres");
+    client.simple_query("IDENTIFY_SYSTEM").err().unwrap();
+
+    for mode in [ReplicationMode::Logical, ReplicationMode::Physical] {
+        let socket = TcpStream::connect("127.0.0.1:5433").unwrap();
+        let repl = Config::new()
+            .user("postgres")
+            .replication_mode(mode)
+            .connect_raw(socket)
+            .unwrap();
+
+        let messages = repl.simple_query("IDENTIFY_SYSTEM").unwrap();
+        let row = match &messages[0] {
+            SimpleQueryMessage::Row(row) => row,
+            _ => panic!("expected a row"),
+        };
+        assert!(!row.get("systemid").unwrap().is_empty());
+        assert_eq!(row.get("timeline"), Some("1"));
+        parse_lsn(row.get("xlogpos").unwrap());
+        // only a logical replication connection is tied to a database
+        match mode {
+            ReplicationMode::Logical => assert_eq!(row.get("dbname"), Some("postgres")),
+            _ => assert_eq!(row.get("dbname"), None),
+        }
+    }
+}
@@ -1,7 +1,11 @@
-use may::{go, join};
+use may::join;
+use may::net::{TcpListener, TcpStream};
 use may_postgres::error::SqlState;
-use may_postgres::Client;
-use std::time::Duration;
+use may_postgres::tls::{MakeTlsConnect, NoTlsError, NoTlsStream, TlsConnect};
+use may_postgres::{Client, Config, Pool, ReconnectingClient, RetryPolicy};
+use std::io::{Read, Write};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 fn connect(s: &str) -> Client {
     may_postgres::connect(s).unwrap()
@@ -9,7 +13,10 @@ fn connect(s: &str) -> Client {
 
 fn smoke_test(s: &str) {
     let client = connect(s);
+    smoke_test_client(&client);
+}
 
+fn smoke_test_client(client: &Client) {
     let stmt = client.prepare("SELECT $1::INT").unwrap();
     let rows = client.query(&stmt, &[&1i32]).unwrap();
     assert_eq!(rows[0].get::<_, i32>(0), 1i32);
@@ -20,6 +27,121 @@ fn tcp() {
     smoke_test("host=localhost port=5433 user=postgres")
 }
 
+#[test]
+fn builder() {
+    let client = Config::new()
+        .host("localhost")
+        .port(5433)
+        .user("postgres")
+        .dbname("postgres")
+        .application_name("builder")
+        .connect_timeout(Duration::from_secs(10))
+        .connect()
+        .unwrap();
+    smoke_test_client(&client);
+    assert_eq!(client.parameter("application_name").unwrap(), "builder");
+}
+
+// collects the protocol trace lines logged by any client
+struct TraceCapture(std::sync::Mutex<Vec<String>>);
+
+impl log::Log for TraceCapture {
+    fn enabled(&self, metadata: &log::Metadata<'_>) -> bool {
+        metadata.target().starts_with("may_postgres")
+    }
+
+    fn log(&self, record: &log::Record<'_>) {
+        let line = record.args().to_string();
+        if self.enabled(record.metadata())
+            && (line.starts_with("sent ") || line.starts_with("received "))
+        {
+            self.0.lock().unwrap().push(line);
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static TRACE: TraceCapture = TraceCapture(std::sync::Mutex::new(vec![]));
+
+// joins the tags of the captured lines of each direction, since a batch may be logged in pieces
+fn traced(direction: &str) -> String {
+    TRACE
+        .0
+        .lock()
+        .unwrap()
+        .iter()
+        .filter_map(|line| line.strip_prefix(direction))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[test]
+fn trace_protocol() {
+    log::set_logger(&TRACE).unwrap();
+    log::set_max_level(log::LevelFilter::Trace);
+
+    let client = "host=localhost port=5433 user=postgres"
+        .parse::<Config>()
+        .unwrap()
+        .trace_protocol(true)
+        .connect()
+        .unwrap();
+    // kept alive, so that no close is sent behind the test's back
+    let stmt = client.prepare("SELECT 1").unwrap();
+    client.query(&stmt, &[]).unwrap();
+    assert!(
+        traced("sent ").contains("P D S B E S"),
+        "{}",
+        traced("sent ")
+    );
+    assert!(
+        traced("received ").contains("1 t T Z 2 D C Z"),
+        "{}",
+        traced("received ")
+    );
+
+    client.set_trace_protocol(false);
+    TRACE.0.lock().unwrap().clear();
+    client.query(&stmt, &[]).unwrap();
+    assert_eq!(traced("sent "), "");
+    assert_eq!(traced("received "), "");
+
+    client.set_trace_protocol(true);
+    client.batch_execute("SELECT 1").unwrap();
+    assert_eq!(traced("sent "), "Q");
+    assert_eq!(traced("received "), "T D C Z");
+}
+
+#[test]
+#[cfg(unix)]
+fn unix_socket() {
+    smoke_test("host=/var/run/postgresql port=5433 user=postgres")
+}
+
+#[test]
+#[cfg(unix)]
+fn unix_socket_url() {
+    smoke_test("postgresql://postgres@%2Fvar%2Frun%2Fpostgresql:5433")
+}
+
+#[test]
+#[cfg(unix)]
+fn unix_socket_require_tls() {
+    may_postgres::connect_tls(
+        "host=/var/run/postgresql port=5433 user=postgres sslmode=require",
+        UnusedTls,
+    )
+    .err()
+    .unwrap();
+}
+
+#[test]
+#[cfg(unix)]
+fn multiple_hosts_unix_fallback() {
+    smoke_test("host=/nonexistent,/var/run/postgresql port=5433 user=postgres")
+}
+
 #[test]
 fn multiple_hosts_one_port() {
     smoke_test("host=foobar.invalid,localhost port=5433 user=postgres")
@@ -71,3 +193,1047 @@ fn cancel_query() {
         }
     }
 }
+
+#[test]
+fn cancel_token() {
+    let client = connect("host=localhost port=5433 user=postgres");
+    let cancel_token = client.cancel_token();
+
+    let h = may::go!(move || {
+        may::coroutine::sleep(Duration::from_millis(100));
+        cancel_token.cancel_query().unwrap();
+    });
+
+    let err = client.batch_execute("SELECT pg_sleep(100)").err().unwrap();
+    assert_eq!(err.code(), Some(&SqlState::QUERY_CANCELED));
+    h.join().unwrap();
+
+    // the connection is still usable after the cancellation
+    smoke_test_client(&client);
+}
+
+#[test]
+fn cancel_token_without_host() {
+    let socket = TcpStream::connect("127.0.0.1:5433").unwrap();
+    let config = "user=postgres".parse::<may_postgres::Config>().unwrap();
+    let client = config.connect_raw(socket).unwrap();
+
+    // a raw connection does not know how to reach the server again
+    client.cancel_token().cancel_query().err().unwrap();
+}
+
+#[test]
+fn query_timeout() {
+    let client = connect("host=localhost port=5433 user=postgres");
+
+    let start = Instant::now();
+    let err = client
+        .query_timeout("SELECT pg_sleep(10)", &[], Duration::from_millis(200))
+        .err()
+        .unwrap();
+    assert!(err.is_timeout());
+    assert!(start.elapsed() < Duration::from_secs(5));
+
+    // the rest of the cancelled query's response is discarded
+    smoke_test_client(&client);
+}
+
+#[test]
+fn query_timeout_without_cancel() {
+    let socket = TcpStream::connect("127.0.0.1:5433").unwrap();
+    let config = "user=postgres".parse::<may_postgres::Config>().unwrap();
+    let client = config.connect_raw(socket).unwrap();
+
+    // a raw connection can't cancel the query, which doesn't hold up the timeout
+    let stmt = client.prepare("SELECT pg_sleep(3)").unwrap();
+    let start = Instant::now();
+    let err = client
+        .execute_timeout(&stmt, &[], Duration::from_millis(200))
+        .err()
+        .unwrap();
+    assert!(err.is_timeout());
+    assert!(start.elapsed() < Duration::from_secs(2));
+
+    // the next request is answered once the query is done
+    smoke_test_client(&client);
+}
+
+#[test]
+fn execute_timeout_not_expired() {
+    let client = connect("host=localhost port=5433 user=postgres");
+
+    let n = client
+        .execute_timeout("SELECT 1", &[], Duration::from_secs(10))
+        .unwrap();
+    assert_eq!(n, 1);
+}
+
+#[test]
+fn request_timeout() {
+    let mut config = "host=localhost port=5433 user=postgres"
+        .parse::<Config>()
+        .unwrap();
+    config.request_timeout(Duration::from_millis(300));
+    let client = Arc::new(config.connect().unwrap());
+    let slow_stmt = client.prepare("SELECT pg_sleep(2), 1").unwrap();
+    let stmt = client.prepare("SELECT $1::INT").unwrap();
+
+    // both requests wait on coroutines, the queued one behind the slow query
+    let start = Instant::now();
+    let slow = {
+        let client = client.clone();
+        may::go!(move || client.query(&slow_stmt, &[]))
+    };
+    while client.stats().in_flight() == 0 {
+        may::coroutine::yield_now();
+    }
+    let queued = {
+        let client = client.clone();
+        let stmt = stmt.clone();
+        may::go!(move || client.query_one(&stmt, &[&2i32]))
+    };
+    let slow = slow.join().unwrap().err().unwrap();
+    assert!(slow.is_timeout(), "{}", slow);
+    let queued = queued.join().unwrap().err().unwrap();
+    assert!(queued.is_timeout(), "{}", queued);
+    // the slow query takes 2 seconds, so anything shorter means the waits gave up on their own
+    assert!(start.elapsed() < Duration::from_secs(2));
+
+    // the connection reads the late rows of the slow query once it completes and discards them, a request without
+    // the deadline that is queued behind it then gets its own row rather than a late one
+    let row = client
+        .query_timeout(&stmt, &[&3i32], Duration::from_secs(10))
+        .unwrap();
+    assert_eq!(row[0].get::<_, i32>(0), 3);
+}
+
+// a connector for a server without TLS support, the handshake must never be attempted
+struct UnusedTls;
+
+impl MakeTlsConnect<TcpStream> for UnusedTls {
+    type Stream = NoTlsStream;
+    type TlsConnect = UnusedTls;
+    type Error = NoTlsError;
+
+    fn make_tls_connect(&mut self, _: &str) -> Result<UnusedTls, NoTlsError> {
+        Ok(UnusedTls)
+    }
+}
+
+impl TlsConnect<TcpStream> for UnusedTls {
+    type Stream = NoTlsStream;
+    type Error = NoTlsError;
+
+    fn connect(self, _: TcpStream) -> Result<NoTlsStream, NoTlsError> {
+        panic!("the test server does not support TLS")
+    }
+}
+
+#[test]
+fn ssl_mode_prefer_falls_back() {
+    let client =
+        may_postgres::connect_tls("host=localhost port=5433 user=postgres", UnusedTls).unwrap();
+    smoke_test_client(&client);
+}
+
+#[test]
+fn ssl_mode_require_without_server_support() {
+    let err = may_postgres::connect_tls(
+        "host=localhost port=5433 user=postgres sslmode=require",
+        UnusedTls,
+    )
+    .err()
+    .unwrap();
+    assert!(err.to_string().contains("TLS"), "{}", err);
+}
+
+#[test]
+fn ssl_mode_require_no_tls() {
+    may_postgres::connect("host=localhost port=5433 user=postgres sslmode=require")
+        .err()
+        .unwrap();
+}
+
+#[test]
+fn ssl_mode_disable() {
+    smoke_test("host=localhost port=5433 user=postgres sslmode=disable");
+}
+
+#[test]
+fn channel_binding_require_without_tls() {
+    let err =
+        may_postgres::connect("host=localhost port=5433 user=postgres channel_binding=require")
+            .err()
+            .unwrap();
+    assert!(err.to_string().contains("channel binding"), "{}", err);
+}
+
+#[test]
+fn channel_binding_disable() {
+    smoke_test("host=localhost port=5433 user=postgres channel_binding=disable");
+}
+
+// a backend which closes every connection right after accepting it
+fn rejecting_server() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    may::go!(move || {
+        for stream in listener.incoming() {
+            drop(stream);
+        }
+    });
+    port
+}
+
+fn message(tag: u8, body: &[u8]) -> Vec<u8> {
+    let mut buf = vec![tag];
+    buf.extend_from_slice(&(body.len() as i32 + 4).to_be_bytes());
+    buf.extend_from_slice(body);
+    buf
+}
+
+// a backend which accepts any startup and reports itself as a read-only standby
+fn read_only_server() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    may::go!(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+
+        let mut len = [0; 4];
+        stream.read_exact(&mut len).unwrap();
+        let mut startup = vec![0; i32::from_be_bytes(len) as usize - 4];
+        stream.read_exact(&mut startup).unwrap();
+
+        let mut buf = message(b'R', &0i32.to_be_bytes());
+        buf.extend(message(b'K', &[0, 0, 0, 1, 0, 0, 0, 2]));
+        buf.extend(message(b'Z', b"I"));
+        stream.write_all(&buf).unwrap();
+
+        let mut header = [0; 5];
+        stream.read_exact(&mut header).unwrap();
+        assert_eq!(header[0], b'Q');
+        let len = i32::from_be_bytes([header[1], header[2], header[3], header[4]]);
+        let mut query = vec![0; len as usize - 4];
+        stream.read_exact(&mut query).unwrap();
+
+        let mut row_description = 1i16.to_be_bytes().to_vec();
+        row_description.extend_from_slice(b"transaction_read_only\0");
+        row_description.extend_from_slice(&0i32.to_be_bytes());
+        row_description.extend_from_slice(&0i16.to_be_bytes());
+        row_description.extend_from_slice(&25i32.to_be_bytes());
+        row_description.extend_from_slice(&(-1i16).to_be_bytes());
+        row_description.extend_from_slice(&(-1i32).to_be_bytes());
+        row_description.extend_from_slice(&0i16.to_be_bytes());
+        let mut data_row = 1i16.to_be_bytes().to_vec();
+        data_row.extend_from_slice(&2i32.to_be_bytes());
+        data_row.extend_from_slice(b"on");
+
+        let mut buf = message(b'T', &row_description);
+        buf.extend(message(b'D', &data_row));
+        buf.extend(message(b'C', b"SHOW\0"));
+        buf.extend(message(b'Z', b"I"));
+        stream.write_all(&buf).unwrap();
+
+        // wait for the client to hang up
+        let mut rest = vec![];
+        stream.read_to_end(&mut rest).ok();
+    });
+    port
+}
+
+// a backend built with floating point timestamps, which closes the connection once the startup is done
+fn float_timestamp_server() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    may::go!(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+
+        let mut len = [0; 4];
+        stream.read_exact(&mut len).unwrap();
+        let mut startup = vec![0; i32::from_be_bytes(len) as usize - 4];
+        stream.read_exact(&mut startup).unwrap();
+
+        let mut buf = message(b'R', &0i32.to_be_bytes());
+        buf.extend(message(b'S', b"integer_datetimes\0off\0"));
+        buf.extend(message(b'K', &[0, 0, 0, 1, 0, 0, 0, 2]));
+        buf.extend(message(b'Z', b"I"));
+        stream.write_all(&buf).unwrap();
+
+        let mut rest = vec![];
+        stream.read_to_end(&mut rest).ok();
+    });
+    port
+}
+
+#[test]
+fn float_timestamps_rejected() {
+    let port = float_timestamp_server();
+    let err = may_postgres::connect(&format!("host=127.0.0.1 port={} user=postgres", port))
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("integer_datetimes"), "{}", err);
+}
+
+// a backend which answers every execute with two `int4` rows and then suspends the portal, as if a row limit applied
+fn suspending_server() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    may::go!(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+
+        let mut len = [0; 4];
+        stream.read_exact(&mut len).unwrap();
+        let mut startup = vec![0; i32::from_be_bytes(len) as usize - 4];
+        stream.read_exact(&mut startup).unwrap();
+
+        let mut buf = message(b'R', &0i32.to_be_bytes());
+        buf.extend(message(b'K', &[0, 0, 0, 1, 0, 0, 0, 2]));
+        buf.extend(message(b'Z', b"I"));
+        stream.write_all(&buf).unwrap();
+
+        let mut row_description = 1i16.to_be_bytes().to_vec();
+        row_description.extend_from_slice(b"n\0");
+        row_description.extend_from_slice(&0i32.to_be_bytes());
+        row_description.extend_from_slice(&0i16.to_be_bytes());
+        row_description.extend_from_slice(&23i32.to_be_bytes());
+        row_description.extend_from_slice(&4i16.to_be_bytes());
+        row_description.extend_from_slice(&(-1i32).to_be_bytes());
+        row_description.extend_from_slice(&0i16.to_be_bytes());
+
+        loop {
+            let mut header = [0; 5];
+            if stream.read_exact(&mut header).is_err() {
+                return;
+            }
+            let len = i32::from_be_bytes([header[1], header[2], header[3], header[4]]);
+            let mut body = vec![0; len as usize - 4];
+            stream.read_exact(&mut body).unwrap();
+
+            let mut buf = vec![];
+            match header[0] {
+                b'P' => buf.extend(message(b'1', b"")),
+                b'D' => {
+                    buf.extend(message(b't', &0i16.to_be_bytes()));
+                    buf.extend(message(b'T', &row_description));
+                }
+                b'B' => buf.extend(message(b'2', b"")),
+                b'E' => {
+                    for n in 1i32..=2 {
+                        let mut data_row = 1i16.to_be_bytes().to_vec();
+                        data_row.extend_from_slice(&4i32.to_be_bytes());
+                        data_row.extend_from_slice(&n.to_be_bytes());
+                        buf.extend(message(b'D', &data_row));
+                    }
+                    buf.extend(message(b's', b""));
+                }
+                b'C' => buf.extend(message(b'3', b"")),
+                b'S' => buf.extend(message(b'Z', b"I")),
+                b'X' => return,
+                _ => {}
+            }
+            stream.write_all(&buf).unwrap();
+        }
+    });
+    port
+}
+
+#[test]
+fn portal_suspended() {
+    let port = suspending_server();
+    let client =
+        may_postgres::connect(&format!("host=127.0.0.1 port={} user=postgres", port)).unwrap();
+
+    let rows = client.query("SELECT n FROM foo", &[]).unwrap();
+    let values = rows.iter().map(|r| r.get::<_, i32>(0)).collect::<Vec<_>>();
+    assert_eq!(values, [1, 2]);
+
+    assert_eq!(client.execute("SELECT n FROM foo", &[]).unwrap(), 0);
+
+    let statement = client.prepare("SELECT n FROM foo").unwrap();
+    let results = client.query_all(&[(&statement, &[]), (&statement, &[])]);
+    for rows in results {
+        assert_eq!(rows.unwrap().len(), 2);
+    }
+
+    let mut pipeline = client.pipeline();
+    pipeline.push(&statement, &[]).unwrap();
+    let counts = pipeline.execute().unwrap();
+    assert_eq!(counts.len(), 1);
+    assert_eq!(*counts[0].as_ref().unwrap(), 2);
+
+    // the connection is still in step with the server
+    assert!(client
+        .query_one(&statement, &[])
+        .err()
+        .unwrap()
+        .is_too_many_rows());
+}
+
+// a backend answering `SELECT n FROM foo` with one row, whose first response is preceded by a malformed notice and
+// parameter status and carries a `DataRow` whose field runs past its end
+fn corrupting_server() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    may::go!(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+
+        let mut len = [0; 4];
+        stream.read_exact(&mut len).unwrap();
+        let mut startup = vec![0; i32::from_be_bytes(len) as usize - 4];
+        stream.read_exact(&mut startup).unwrap();
+
+        let mut buf = message(b'R', &0i32.to_be_bytes());
+        buf.extend(message(b'K', &[0, 0, 0, 1, 0, 0, 0, 2]));
+        buf.extend(message(b'Z', b"I"));
+        stream.write_all(&buf).unwrap();
+
+        let mut row_description = 1i16.to_be_bytes().to_vec();
+        row_description.extend_from_slice(b"n\0");
+        row_description.extend_from_slice(&0i32.to_be_bytes());
+        row_description.extend_from_slice(&0i16.to_be_bytes());
+        row_description.extend_from_slice(&23i32.to_be_bytes());
+        row_description.extend_from_slice(&4i16.to_be_bytes());
+        row_description.extend_from_slice(&(-1i32).to_be_bytes());
+        row_description.extend_from_slice(&0i16.to_be_bytes());
+
+        let mut corrupt = true;
+        loop {
+            let mut header = [0; 5];
+            if stream.read_exact(&mut header).is_err() {
+                return;
+            }
+            let len = i32::from_be_bytes([header[1], header[2], header[3], header[4]]);
+            let mut body = vec![0; len as usize - 4];
+            stream.read_exact(&mut body).unwrap();
+
+            let mut buf = vec![];
+            match header[0] {
+                b'P' => {
+                    if corrupt {
+                        // the fields of both lack their terminating nul
+                        buf.extend(message(b'N', b"Mbad"));
+                        buf.extend(message(b'S', b"name\0value"));
+                    }
+                    buf.extend(message(b'1', b""));
+                }
+                b'D' => {
+                    buf.extend(message(b't', &0i16.to_be_bytes()));
+                    buf.extend(message(b'T', &row_description));
+                }
+                b'B' => buf.extend(message(b'2', b"")),
+                b'E' => {
+                    let mut data_row = 1i16.to_be_bytes().to_vec();
+                    data_row.extend_from_slice(&if corrupt { 100i32 } else { 4 }.to_be_bytes());
+                    data_row.extend_from_slice(&7i32.to_be_bytes());
+                    buf.extend(message(b'D', &data_row));
+                    buf.extend(message(b'C', b"SELECT 1\0"));
+                    corrupt = false;
+                }
+                b'S' => buf.extend(message(b'Z', b"I")),
+                b'X' => return,
+                _ => {}
+            }
+            stream.write_all(&buf).unwrap();
+        }
+    });
+    port
+}
+
+#[test]
+fn malformed_message_fails_only_its_request() {
+    let port = corrupting_server();
+    let client =
+        may_postgres::connect(&format!("host=127.0.0.1 port={} user=postgres", port)).unwrap();
+
+    let err = client.query("SELECT n FROM foo", &[]).err().unwrap();
+    assert!(err.to_string().contains("parsing response"), "{}", err);
+    assert!(!client.is_closed());
+
+    // the malformed asynchronous messages were dropped, and the next response is read in step
+    assert_eq!(client.parameter("name"), None);
+    let row = client.query_one("SELECT n FROM foo", &[]).unwrap();
+    assert_eq!(row.get::<_, i32>(0), 7);
+    let rows = client.query("SELECT n FROM foo", &[]).unwrap();
+    assert_eq!(rows.len(), 1);
+}
+
+// a backend which starts a `COPY` in both directions for every execute, as a replication command would, and fails it
+// once the client gives up on it
+fn copy_both_server() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    may::go!(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+
+        let mut len = [0; 4];
+        stream.read_exact(&mut len).unwrap();
+        let mut startup = vec![0; i32::from_be_bytes(len) as usize - 4];
+        stream.read_exact(&mut startup).unwrap();
+
+        let mut buf = message(b'R', &0i32.to_be_bytes());
+        buf.extend(message(b'K', &[0, 0, 0, 1, 0, 0, 0, 2]));
+        buf.extend(message(b'Z', b"I"));
+        stream.write_all(&buf).unwrap();
+
+        // a sync is ignored while the copy runs, like the server does
+        let mut copying = false;
+        loop {
+            let mut header = [0; 5];
+            if stream.read_exact(&mut header).is_err() {
+                return;
+            }
+            let len = i32::from_be_bytes([header[1], header[2], header[3], header[4]]);
+            let mut body = vec![0; len as usize - 4];
+            stream.read_exact(&mut body).unwrap();
+
+            let mut buf = vec![];
+            match header[0] {
+                b'P' => buf.extend(message(b'1', b"")),
+                b'D' => {
+                    buf.extend(message(b't', &0i16.to_be_bytes()));
+                    buf.extend(message(b'n', b""));
+                }
+                b'B' => buf.extend(message(b'2', b"")),
+                b'E' => {
+                    buf.extend(message(b'W', &[0, 0, 0]));
+                    copying = true;
+                }
+                b'f' => {
+                    buf.extend(message(b'E', b"SERROR\0VERROR\0C57014\0Mcopy failed\0\0"));
+                    copying = false;
+                }
+                b'S' if !copying => buf.extend(message(b'Z', b"I")),
+                b'X' => return,
+                _ => {}
+            }
+            stream.write_all(&buf).unwrap();
+        }
+    });
+    port
+}
+
+#[test]
+fn copy_in_rejects_copy_both() {
+    let port = copy_both_server();
+    let client =
+        may_postgres::connect(&format!("host=127.0.0.1 port={} user=postgres", port)).unwrap();
+
+    let stmt = client.prepare("START_REPLICATION 0/0").unwrap();
+    let err = client.copy_in::<_, bytes::Bytes>(&stmt).err().unwrap();
+    assert!(err.to_string().contains("parsing response"), "{}", err);
+
+    // the copy was failed, and the connection is still in step with the server
+    assert!(!client.is_closed());
+    client.prepare("SELECT 1").unwrap();
+}
+
+// a backend which accepts any startup and then reads everything without ever answering, like a dead peer
+fn unresponsive_server() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    may::go!(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+
+        let mut len = [0; 4];
+        stream.read_exact(&mut len).unwrap();
+        let mut startup = vec![0; i32::from_be_bytes(len) as usize - 4];
+        stream.read_exact(&mut startup).unwrap();
+
+        let mut buf = message(b'R', &0i32.to_be_bytes());
+        buf.extend(message(b'K', &[0, 0, 0, 1, 0, 0, 0, 2]));
+        buf.extend(message(b'Z', b"I"));
+        stream.write_all(&buf).unwrap();
+
+        let mut rest = vec![];
+        stream.read_to_end(&mut rest).ok();
+    });
+    port
+}
+
+// the `TCP_NODELAY` setting of each socket of this process connected to `port`
+#[cfg(target_os = "linux")]
+fn nodelay_of_sockets_to(port: u16) -> Vec<bool> {
+    use std::os::fd::BorrowedFd;
+
+    let mut found = vec![];
+    for entry in std::fs::read_dir("/proc/self/fd").unwrap() {
+        let fd = match entry.unwrap().file_name().to_str().unwrap().parse() {
+            Ok(fd) => fd,
+            Err(_) => continue,
+        };
+        // the descriptor is only borrowed for the calls below, and one closed meanwhile just fails them
+        let fd = unsafe { BorrowedFd::borrow_raw(fd) };
+        let socket = socket2::SockRef::from(&fd);
+        let peer = socket.peer_addr().ok().and_then(|addr| addr.as_socket());
+        if peer.map(|addr| addr.port()) == Some(port) {
+            found.push(socket.nodelay().unwrap());
+        }
+    }
+    found
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn nodelay() {
+    let port = unresponsive_server();
+    let _client =
+        may_postgres::connect(&format!("host=127.0.0.1 port={} user=postgres", port)).unwrap();
+    // the connection holds clones of its socket, which share the setting
+    let nodelay = nodelay_of_sockets_to(port);
+    assert!(!nodelay.is_empty());
+    assert!(nodelay.iter().all(|nodelay| *nodelay));
+
+    let socket = TcpStream::connect("127.0.0.1:5433").unwrap();
+    let probe = socket.try_clone().unwrap();
+    assert!(!probe.inner().nodelay().unwrap());
+    let client = "user=postgres"
+        .parse::<Config>()
+        .unwrap()
+        .connect_raw(socket)
+        .unwrap();
+    assert!(probe.inner().nodelay().unwrap());
+    smoke_test_client(&client);
+}
+
+// polls with thread sleeps, which don't depend on the coroutine timers under test
+fn wait_until(mut f: impl FnMut() -> bool) -> bool {
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(5) {
+        if f() {
+            return true;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    false
+}
+
+#[test]
+fn keepalive_query() {
+    let mut config = "host=localhost port=5433 user=postgres"
+        .parse::<Config>()
+        .unwrap();
+    config.keepalive_query(Some(Duration::from_millis(100)));
+    let client = config.connect().unwrap();
+
+    let requests = client.stats().requests();
+    assert!(wait_until(|| client.stats().requests() >= requests + 2));
+    assert!(!client.is_closed());
+    smoke_test_client(&client);
+
+    // a connection answering its requests stays open while it is kept busy
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_millis(500) {
+        client.batch_execute("SELECT pg_sleep(0.01)").unwrap();
+    }
+    assert!(!client.is_closed());
+}
+
+#[test]
+fn keepalive_query_closes_dead_connection() {
+    let port = unresponsive_server();
+    let mut config = format!("host=127.0.0.1 port={} user=postgres", port)
+        .parse::<Config>()
+        .unwrap();
+    config.keepalive_query(Some(Duration::from_millis(100)));
+    let client = config.connect().unwrap();
+
+    assert!(wait_until(|| client.is_closed()));
+    let err = client.batch_execute("SELECT 1").err().unwrap();
+    assert!(err.is_closed(), "{}", err);
+}
+
+#[test]
+fn keepalive_query_closes_dead_connection_with_request_in_flight() {
+    let port = unresponsive_server();
+    let mut config = format!("host=127.0.0.1 port={} user=postgres", port)
+        .parse::<Config>()
+        .unwrap();
+    config.keepalive_query(Some(Duration::from_millis(100)));
+    let client = config.connect().unwrap();
+
+    // the request hangs on the connection, which is closed all the same
+    let request = {
+        let client = client.clone();
+        may::go!(move || client.batch_execute("SELECT 1"))
+    };
+    assert!(wait_until(|| client.is_closed()));
+    let err = request.join().unwrap().err().unwrap();
+    assert!(err.is_closed(), "{}", err);
+}
+
+#[test]
+fn pool_discards_unresponsive_connection() {
+    let port = unresponsive_server();
+    let mut config = format!("host=127.0.0.1 port={} user=postgres", port)
+        .parse::<Config>()
+        .unwrap();
+    config.connect_timeout(Duration::from_millis(200));
+    let pool = Pool::builder()
+        .max_size(1)
+        .validation_timeout(Duration::from_millis(100))
+        .build(config)
+        .unwrap();
+    drop(pool.get().unwrap());
+    assert_eq!(pool.idle(), 1);
+
+    // the idle connection never answers its check, so it is dropped and a new one is opened, which times out too
+    let start = Instant::now();
+    assert!(pool.get().is_err());
+    assert!(start.elapsed() < Duration::from_secs(2));
+    assert_eq!(pool.size(), 0);
+}
+
+// a backend which asks for GSSAPI authentication, as configured with `gss` in pg_hba.conf
+fn gss_server() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    may::go!(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+
+        let mut len = [0; 4];
+        stream.read_exact(&mut len).unwrap();
+        let mut startup = vec![0; i32::from_be_bytes(len) as usize - 4];
+        stream.read_exact(&mut startup).unwrap();
+
+        stream
+            .write_all(&message(b'R', &7i32.to_be_bytes()))
+            .unwrap();
+
+        let mut rest = vec![];
+        stream.read_to_end(&mut rest).ok();
+    });
+    port
+}
+
+#[test]
+#[cfg(not(feature = "with-gssapi"))]
+fn gss_unsupported() {
+    let port = gss_server();
+    let err = may_postgres::connect(&format!("host=127.0.0.1 port={} user=postgres", port))
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("with-gssapi"), "{}", err);
+}
+
+#[test]
+#[cfg(feature = "with-gssapi")]
+fn gss_without_credentials() {
+    let port = gss_server();
+    // an empty credential cache, so that the library fails the same way wherever the test runs
+    std::env::set_var("KRB5CCNAME", "FILE:/nonexistent/may_postgres_test_ccache");
+    let err = may_postgres::connect(&format!("host=127.0.0.1 port={} user=postgres", port))
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("GSSAPI error"), "{}", err);
+}
+
+// needs a server with a `gss` entry in pg_hba.conf, a KDC, and a ticket for the user from `kinit`, with the server
+// and user given as `GSS_TEST_HOST` and `GSS_TEST_USER`
+#[test]
+#[ignore]
+#[cfg(feature = "with-gssapi")]
+fn gss_kdc() {
+    let host = std::env::var("GSS_TEST_HOST").unwrap();
+    let user = std::env::var("GSS_TEST_USER").unwrap();
+    let client = connect(&format!("host={} user={} krbsrvname=postgres", host, user));
+    let row = client.query_one("SELECT current_user", &[]).unwrap();
+    assert_eq!(row.get::<_, &str>(0), user);
+}
+
+// a backend which completes the startup, then drops the connection as soon as the first request arrives
+fn dropping_server() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    may::go!(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+
+        let mut len = [0; 4];
+        stream.read_exact(&mut len).unwrap();
+        let mut startup = vec![0; i32::from_be_bytes(len) as usize - 4];
+        stream.read_exact(&mut startup).unwrap();
+
+        let mut buf = message(b'R', &0i32.to_be_bytes());
+        buf.extend(message(b'K', &[0, 0, 0, 1, 0, 0, 0, 2]));
+        buf.extend(message(b'Z', b"I"));
+        stream.write_all(&buf).unwrap();
+
+        let mut byte = [0; 1];
+        stream.read_exact(&mut byte).ok();
+    });
+    port
+}
+
+#[test]
+fn connection_loss_fails_every_request() {
+    let port = dropping_server();
+    let client = Arc::new(connect(&format!(
+        "host=127.0.0.1 port={} user=postgres",
+        port
+    )));
+
+    let handles = (0..20)
+        .map(|_| {
+            let client = client.clone();
+            may::go!(move || client.batch_execute("SELECT 1"))
+        })
+        .collect::<Vec<_>>();
+    for handle in handles {
+        let err = handle.join().unwrap().unwrap_err();
+        assert!(err.is_closed(), "{}", err);
+    }
+    assert!(client.is_closed());
+}
+
+#[test]
+fn client_dropped_mid_query() {
+    let client = connect("host=127.0.0.1 port=5433 user=postgres");
+    let mut rows = client
+        .query_raw(
+            "SELECT pg_sleep(0.5)",
+            std::iter::empty::<&dyn may_postgres::types::ToSql>(),
+        )
+        .unwrap();
+    drop(client);
+
+    // the connection goes away with the last client, and the pending response with it
+    let err = rows.next().unwrap().err().unwrap();
+    assert!(err.is_closed(), "{}", err);
+
+    // the same holds while waiting with a deadline
+    let socket = TcpStream::connect("127.0.0.1:5433").unwrap();
+    let client = "user=postgres"
+        .parse::<Config>()
+        .unwrap()
+        .request_timeout(Duration::from_secs(5))
+        .connect_raw(socket)
+        .unwrap();
+    let mut rows = client
+        .query_raw(
+            "SELECT pg_sleep(0.5)",
+            std::iter::empty::<&dyn may_postgres::types::ToSql>(),
+        )
+        .unwrap();
+    drop(client);
+    let err = rows.next().unwrap().err().unwrap();
+    assert!(err.is_closed(), "{}", err);
+}
+
+#[test]
+fn failover_to_second_host() {
+    let port = rejecting_server();
+    smoke_test(&format!(
+        "host=127.0.0.1,localhost port={},5433 user=postgres",
+        port
+    ));
+}
+
+#[test]
+fn failover_skips_read_only_host() {
+    let port = read_only_server();
+    smoke_test(&format!(
+        "host=127.0.0.1,localhost port={},5433 user=postgres target_session_attrs=read-write",
+        port
+    ));
+}
+
+#[test]
+fn read_only_host_accepted_without_target_session_attrs() {
+    let port = read_only_server();
+    let config = format!("host=127.0.0.1,localhost port={},5433 user=postgres", port);
+    let client = connect(&config);
+    assert!(!client.is_closed());
+}
+
+#[test]
+fn failover_reports_every_host() {
+    let first = rejecting_server();
+    let second = rejecting_server();
+    let err = may_postgres::connect(&format!(
+        "host=127.0.0.1,127.0.0.1 port={},{} user=postgres",
+        first, second
+    ))
+    .err()
+    .unwrap();
+
+    let err = err.to_string();
+    assert!(err.contains("all 2 hosts failed"), "{}", err);
+    assert!(err.contains(&format!("127.0.0.1:{}", first)), "{}", err);
+    assert!(err.contains(&format!("127.0.0.1:{}", second)), "{}", err);
+}
+
+#[test]
+fn failover_keeps_every_error() {
+    let first = rejecting_server();
+    let err = may_postgres::connect(&format!(
+        "host=127.0.0.1,localhost port={},5433 user=postgres dbname=no_such_database",
+        first
+    ))
+    .err()
+    .unwrap();
+
+    let errors = err.as_connect_errors().unwrap().errors();
+    assert_eq!(errors.len(), 2);
+    assert_eq!(errors[0].0, format!("127.0.0.1:{}", first));
+    assert!(errors[0].1.code().is_none());
+    assert_eq!(errors[1].0, "localhost:5433");
+    assert_eq!(errors[1].1.code(), Some(&SqlState::INVALID_CATALOG_NAME));
+}
+
+#[test]
+fn connect_timeout_blackholed() {
+    let mut config = "host=10.255.255.1 port=5433 user=postgres"
+        .parse::<Config>()
+        .unwrap();
+    config.connect_timeout(Duration::from_millis(100));
+
+    // some networks reset the connection instead of dropping it, all that matters is that it doesn't hang
+    let start = Instant::now();
+    assert!(config.connect().is_err());
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+// a backend which accepts connections but never answers the startup
+fn silent_server() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    may::go!(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        // returns once the client shuts the socket down
+        let mut rest = vec![];
+        stream.read_to_end(&mut rest).ok();
+    });
+    port
+}
+
+#[test]
+fn connect_timeout_handshake() {
+    let port = silent_server();
+
+    let start = Instant::now();
+    let err = may_postgres::connect(&format!(
+        "host=127.0.0.1 port={} user=postgres connect_timeout=1",
+        port
+    ))
+    .err()
+    .unwrap();
+    assert!(err.is_timeout(), "{}", err);
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+// the kernel's keepalive timer for the client's end of the connection, in hundredths of a second
+#[cfg(target_os = "linux")]
+fn keepalive_timer(client: &Client) -> Option<u64> {
+    let port = client
+        .query_one(
+            "SELECT client_port FROM pg_stat_activity WHERE pid = pg_backend_pid()",
+            &[],
+        )
+        .unwrap()
+        .get::<_, i32>(0);
+    let local = format!(":{:04X}", port);
+
+    // while data is unacknowledged the retransmit timer is shown instead, so wait for the socket to go idle
+    for _ in 0..50 {
+        let table = std::fs::read_to_string("/proc/net/tcp").unwrap();
+        let line = table
+            .lines()
+            .skip(1)
+            .find(|line| {
+                let fields = line.split_whitespace().collect::<Vec<_>>();
+                fields[1].ends_with(&local) && fields[2].ends_with(":1539")
+            })
+            .unwrap()
+            .to_string();
+
+        // the `tr:tm->when` column, timer 2 is the keepalive timer
+        let timer = line.split_whitespace().nth(5).unwrap();
+        match timer.split_at(2) {
+            ("01", _) => may::coroutine::sleep(Duration::from_millis(20)),
+            ("02", when) => return Some(u64::from_str_radix(&when[1..], 16).unwrap()),
+            _ => return None,
+        }
+    }
+    panic!("connection never went idle");
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn keepalives() {
+    let client = connect("host=127.0.0.1 port=5433 user=postgres keepalives_idle=30");
+    let when = keepalive_timer(&client).unwrap();
+    assert!(when > 2000 && when <= 3000, "{}", when);
+
+    let client = connect("host=127.0.0.1 port=5433 user=postgres keepalives=0");
+    assert_eq!(keepalive_timer(&client), None);
+}
+
+#[test]
+fn reconnect_after_backend_terminated() {
+    let config = "host=localhost port=5433 user=postgres"
+        .parse::<Config>()
+        .unwrap();
+    let client =
+        ReconnectingClient::new(config, RetryPolicy::new(3, Duration::from_millis(10))).unwrap();
+    let other = connect("host=localhost port=5433 user=postgres");
+
+    let pid: i32 = client
+        .query_one("SELECT pg_backend_pid()", &[])
+        .unwrap()
+        .get(0);
+    other
+        .execute("SELECT pg_terminate_backend($1)", &[&pid])
+        .unwrap();
+
+    // the request that runs into the closed connection fails, a later one dials again
+    let mut new_pid = None;
+    for _ in 0..100 {
+        match client.query_one("SELECT pg_backend_pid()", &[]) {
+            Ok(row) => {
+                new_pid = Some(row.get::<_, i32>(0));
+                break;
+            }
+            Err(e) => {
+                assert!(e.is_closed() || e.code() == Some(&SqlState::ADMIN_SHUTDOWN));
+                may::coroutine::sleep(Duration::from_millis(10));
+            }
+        }
+    }
+    let new_pid = new_pid.unwrap();
+    assert_ne!(pid, new_pid);
+
+    let row = client.query_one("SELECT $1::INT", &[&1i32]).unwrap();
+    assert_eq!(row.get::<_, i32>(0), 1);
+}
+
+#[test]
+fn reconnect_gives_up() {
+    let mut config = Config::new();
+    config.host("localhost").port(1).user("postgres");
+
+    let start = Instant::now();
+    let err = ReconnectingClient::new(config, RetryPolicy::new(3, Duration::from_millis(20)))
+        .err()
+        .unwrap();
+    // waits of 20ms and 40ms between the three attempts
+    assert!(start.elapsed() >= Duration::from_millis(60), "{}", err);
+}
+
+#[test]
+fn pool_get_timeout_limits_validation_and_connect() {
+    let port = unresponsive_server();
+    let config = format!("host=127.0.0.1 port={} user=postgres", port)
+        .parse::<Config>()
+        .unwrap();
+    let pool = Pool::builder()
+        .max_size(1)
+        .connection_timeout(Duration::from_millis(200))
+        .build(config)
+        .unwrap();
+    drop(pool.get().unwrap());
+
+    // the idle connection never answers its check, and no other server ever answers the handshake
+    let start = Instant::now();
+    assert!(pool.get().is_err());
+    assert!(start.elapsed() < Duration::from_secs(2));
+    assert_eq!(pool.size(), 0);
+}
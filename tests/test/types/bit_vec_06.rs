@@ -27,3 +27,26 @@ fn test_varbit_params() {
         ],
     )
 }
+
+#[test]
+fn bits_not_filling_a_byte() {
+    let bv = BitVec::from_fn(3, |i| i != 1);
+    test_type("BIT(3)", &[(Some(bv.clone()), "B'101'")]);
+    test_type("VARBIT", &[(Some(bv), "B'101'")]);
+}
+
+#[test]
+fn bits_of_a_u64() {
+    let value = 0x8000_0000_0000_0001u64;
+    let bv = BitVec::from_bytes(&value.to_be_bytes());
+    test_type(
+        "BIT(64)",
+        &[(Some(bv.clone()), format!("B'{:064b}'", value))],
+    );
+
+    let client = crate::connect("user=postgres");
+    let row = client
+        .query_one("SELECT $1::BIT(64)::INT8", &[&bv])
+        .unwrap();
+    assert_eq!(row.get::<_, i64>(0) as u64, value);
+}
@@ -1,6 +1,7 @@
-use chrono_04::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use chrono_04::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Utc};
 use may_postgres::types::{Date, Timestamp};
 
+use crate::connect;
 use crate::types::test_type;
 
 #[test]
@@ -49,8 +50,9 @@ fn test_date_time_params() {
     fn make_check(time: &str) -> (Option<DateTime<Utc>>, &str) {
         (
             Some(
-                Utc.datetime_from_str(time, "'%Y-%m-%d %H:%M:%S.%f'")
-                    .unwrap(),
+                NaiveDateTime::parse_from_str(time, "'%Y-%m-%d %H:%M:%S.%f'")
+                    .unwrap()
+                    .and_utc(),
             ),
             time,
         )
@@ -71,8 +73,9 @@ fn test_with_special_date_time_params() {
     fn make_check(time: &str) -> (Timestamp<DateTime<Utc>>, &str) {
         (
             Timestamp::Value(
-                Utc.datetime_from_str(time, "'%Y-%m-%d %H:%M:%S.%f'")
-                    .unwrap(),
+                NaiveDateTime::parse_from_str(time, "'%Y-%m-%d %H:%M:%S.%f'")
+                    .unwrap()
+                    .and_utc(),
             ),
             time,
         )
@@ -146,3 +149,47 @@ fn test_time_params() {
         ],
     );
 }
+
+#[test]
+fn test_fixed_offset_date_time_params() {
+    fn make_check(time: &str) -> (Option<DateTime<FixedOffset>>, &str) {
+        (
+            Some(DateTime::parse_from_str(time, "'%Y-%m-%d %H:%M:%S.%f %z'").unwrap()),
+            time,
+        )
+    }
+    test_type(
+        "TIMESTAMP WITH TIME ZONE",
+        &[
+            make_check("'1970-01-01 00:00:00.010000000 +0000'"),
+            make_check("'1965-09-25 11:19:33.100314000 +0530'"),
+            make_check("'2010-02-09 23:11:45.120200000 -0800'"),
+            (None, "NULL"),
+        ],
+    );
+}
+
+#[test]
+fn test_special_values_require_wrapper() {
+    let client = connect("user=postgres");
+
+    let row = client
+        .query_one(
+            "SELECT 'infinity'::TIMESTAMP, '-infinity'::TIMESTAMPTZ, 'infinity'::DATE",
+            &[],
+        )
+        .unwrap();
+    assert!(row.try_get::<_, NaiveDateTime>(0).is_err());
+    assert!(row.try_get::<_, DateTime<Utc>>(1).is_err());
+    assert!(row.try_get::<_, NaiveDate>(2).is_err());
+
+    assert_eq!(
+        row.get::<_, Timestamp<NaiveDateTime>>(0),
+        Timestamp::PosInfinity
+    );
+    assert_eq!(
+        row.get::<_, Timestamp<DateTime<Utc>>>(1),
+        Timestamp::NegInfinity
+    );
+    assert_eq!(row.get::<_, Date<NaiveDate>>(2), Date::PosInfinity);
+}
@@ -1,4 +1,4 @@
-use geo_010::{Coordinate, LineString, Point, Rect};
+use geo_types_04::{Coordinate, LineString, Point, Rect};
 
 use crate::types::test_type;
 
@@ -8,7 +8,7 @@ fn test_point_params() {
         "POINT",
         &[
             (Some(Point::new(0.0, 0.0)), "POINT(0, 0)"),
-            (Some(Point::new(-3.14, 1.618)), "POINT(-3.14, 1.618)"),
+            (Some(Point::new(-2.5, 1.618)), "POINT(-2.5, 1.618)"),
             (None, "NULL"),
         ],
     );
@@ -21,13 +21,13 @@ fn test_box_params() {
         &[
             (
                 Some(Rect {
-                    min: Coordinate { x: -3.14, y: 1.618 },
+                    min: Coordinate { x: -2.5, y: 1.618 },
                     max: Coordinate {
                         x: 160.0,
                         y: 69701.5615,
                     },
                 }),
-                "BOX(POINT(160.0, 69701.5615), POINT(-3.14, 1.618))",
+                "BOX(POINT(160.0, 69701.5615), POINT(-2.5, 1.618))",
             ),
             (None, "NULL"),
         ],
@@ -38,7 +38,7 @@ fn test_box_params() {
 fn test_path_params() {
     let points = vec![
         Coordinate { x: 0., y: 0. },
-        Coordinate { x: -3.14, y: 1.618 },
+        Coordinate { x: -2.5, y: 1.618 },
         Coordinate {
             x: 160.0,
             y: 69701.5615,
@@ -49,7 +49,7 @@ fn test_path_params() {
         &[
             (
                 Some(LineString(points)),
-                "path '((0, 0), (-3.14, 1.618), (160.0, 69701.5615))'",
+                "path '((0, 0), (-2.5, 1.618), (160.0, 69701.5615))'",
             ),
             (None, "NULL"),
         ],
@@ -5,13 +5,19 @@ use std::f32;
 use std::f64;
 use std::fmt;
 use std::net::IpAddr;
+use std::ops::Bound;
 use std::result;
 use std::time::{Duration, UNIX_EPOCH};
 
-use may_postgres::types::{FromSql, FromSqlOwned, IsNull, Kind, ToSql, Type, WrongType};
+use may_postgres::types::{
+    Circle, FromSql, FromSqlOwned, Interval, IpCidr, IsNull, Kind, Lexeme, LexemePosition, Line,
+    LineSegment, MacAddress, MacAddress8, Money, Numeric, Path, Point, Polygon, QueryLexeme, Range,
+    Rect, RegOid, ToSql, TsQuery, TsVector, Type, Void, Weight, WrongType,
+};
 
 use crate::connect;
 use bytes::BytesMut;
+use may_postgres::escape_literal;
 
 #[cfg(feature = "with-bit-vec-0_6")]
 mod bit_vec_06;
@@ -19,7 +25,7 @@ mod bit_vec_06;
 mod chrono_04;
 #[cfg(feature = "with-eui48-0_4")]
 mod eui48_04;
-#[cfg(feature = "with-geo-0_10")]
+#[cfg(feature = "with-geo-types-0_4")]
 mod geo_010;
 #[cfg(feature = "with-serde_json-1")]
 mod serde_json_1;
@@ -61,6 +67,89 @@ fn test_i8_params() {
     test_type("\"char\"", &[(Some('a' as i8), "'a'"), (None, "NULL")]);
 }
 
+#[test]
+fn internal_char() {
+    let client = connect("user=postgres");
+
+    let row = client
+        .query_one(
+            "SELECT relkind, 'x'::CHAR(1), $1::\"char\" FROM pg_class WHERE relname = 'pg_class'",
+            &[&(b'z' as i8)],
+        )
+        .unwrap();
+    assert_eq!(row.columns()[0].type_(), &Type::CHAR);
+    assert_eq!(row.get::<_, i8>(0), b'r' as i8);
+    assert_eq!(row.get::<_, i8>(2), b'z' as i8);
+
+    // `CHAR(n)` is a character type, not `"char"`
+    assert_eq!(row.columns()[1].type_(), &Type::BPCHAR);
+    assert_eq!(row.get::<_, &str>(1), "x");
+    assert!(row.try_get::<_, i8>(1).is_err());
+    assert!(row.try_get::<_, &str>(0).is_err());
+}
+
+#[test]
+fn oid() {
+    let client = connect("user=postgres");
+
+    let row = client
+        .query_one(
+            "SELECT 'pg_class'::regclass::oid, 'pg_class'::regclass, oid, $1::regclass::text \
+             FROM pg_class WHERE relname = 'pg_class'",
+            &[&RegOid::new(1259)],
+        )
+        .unwrap();
+    // pg_class has a fixed OID
+    assert_eq!(row.columns()[0].type_(), &Type::OID);
+    assert_eq!(row.get::<_, u32>(0), 1259);
+    assert_eq!(row.get::<_, RegOid>(0), RegOid::new(1259));
+    assert_eq!(row.get::<_, u32>(2), 1259);
+    assert_eq!(row.get::<_, &str>(3), "pg_class");
+
+    // `u32` is limited to `oid`
+    assert_eq!(row.columns()[1].type_(), &Type::REGCLASS);
+    assert!(row.try_get::<_, u32>(1).is_err());
+    assert_eq!(row.get::<_, RegOid>(1).oid(), 1259);
+
+    let row = client
+        .query_one(
+            "SELECT 'int4'::regtype, 'pg_catalog'::regnamespace, $1::oid",
+            &[&23u32],
+        )
+        .unwrap();
+    assert_eq!(u32::from(row.get::<_, RegOid>(0)), Type::INT4.oid());
+    assert_eq!(row.get::<_, RegOid>(1).oid(), 11);
+    assert_eq!(row.get::<_, u32>(2), 23);
+    assert!(row.try_get::<_, RegOid>(2).is_ok());
+}
+
+#[test]
+fn void() {
+    let client = connect("user=postgres");
+
+    client
+        .batch_execute(
+            "CREATE FUNCTION pg_temp.nothing(x INT) RETURNS void \
+             LANGUAGE plpgsql STRICT AS $$ BEGIN END $$",
+        )
+        .unwrap();
+
+    let row = client.query_one("SELECT pg_temp.nothing(1)", &[]).unwrap();
+    assert_eq!(row.columns()[0].type_(), &Type::VOID);
+    assert_eq!(row.get::<_, Void>(0), Void);
+    assert!(row.try_get::<_, i32>(0).is_err());
+
+    // a strict function returns NULL for a NULL argument
+    let row = client
+        .query_one("SELECT pg_temp.nothing(NULL)", &[])
+        .unwrap();
+    assert_eq!(row.get::<_, Void>(0), Void);
+    assert_eq!(row.get::<_, Option<Void>>(0), None);
+
+    let row = client.query_one("SELECT pg_sleep(0)", &[]).unwrap();
+    assert_eq!(row.get::<_, Void>(0), Void);
+}
+
 #[test]
 fn test_name_params() {
     test_type(
@@ -277,6 +366,29 @@ fn test_borrowed_bytea() {
     assert_eq!(s, b"foo");
 }
 
+#[test]
+fn large_bytea() {
+    let client = connect("user=postgres");
+    client
+        .batch_execute("CREATE TEMPORARY TABLE blobs (data BYTEA)")
+        .unwrap();
+    let blob = (0..4 * 1024 * 1024).map(|i| i as u8).collect::<Vec<u8>>();
+
+    // a borrowed slice is encoded straight into the request
+    client
+        .execute("INSERT INTO blobs (data) VALUES ($1)", &[&&blob[..]])
+        .unwrap();
+
+    // the value is borrowed straight out of the receive buffer
+    let row = client.query_one("SELECT data FROM blobs", &[]).unwrap();
+    let bytes = row.get_bytes(0).unwrap();
+    assert_eq!(bytes, &blob[..]);
+    assert_eq!(bytes.as_ptr(), row.get::<_, &[u8]>(0).as_ptr());
+
+    let row = client.query_one("SELECT NULL::BYTEA", &[]).unwrap();
+    assert_eq!(row.get_bytes(0), None);
+}
+
 macro_rules! make_map {
     ($($k:expr => $v:expr),+) => ({
         let mut map = HashMap::new();
@@ -305,6 +417,55 @@ fn test_hstore_params() {
     );
 }
 
+#[test]
+fn hstore_column() {
+    let client = connect("user=postgres");
+
+    client
+        .batch_execute("CREATE TEMPORARY TABLE foo (id INT, map HSTORE)")
+        .unwrap();
+    let insert = client
+        .prepare("INSERT INTO foo (id, map) VALUES ($1, $2)")
+        .unwrap();
+    // the extension's OID is looked up in the catalog, and the type matched by name
+    let hstore = insert.params()[1].clone();
+    assert_eq!(hstore.name(), "hstore");
+    assert_ne!(hstore.oid(), 0);
+
+    let map = make_map!("key".to_owned() => Some("value".to_owned()),
+                        "null".to_owned() => None,
+                        "".to_owned() => Some("".to_owned()));
+    client.execute(&insert, &[&1i32, &map]).unwrap();
+    client
+        .execute(&insert, &[&2i32, &HashMap::<String, Option<String>>::new()])
+        .unwrap();
+
+    let rows = client
+        .query("SELECT map FROM foo ORDER BY id", &[])
+        .unwrap();
+    assert_eq!(rows[0].columns()[0].type_(), &hstore);
+    assert_eq!(rows[0].get::<_, HashMap<String, Option<String>>>(0), map);
+    assert!(rows[1]
+        .get::<_, HashMap<String, Option<String>>>(0)
+        .is_empty());
+
+    let row = client
+        .query_one(
+            "SELECT map -> 'null', map ? 'null' FROM foo WHERE id = 1",
+            &[],
+        )
+        .unwrap();
+    assert_eq!(row.get::<_, Option<&str>>(0), None);
+    assert!(row.get::<_, bool>(1));
+
+    // the type can also be given when preparing
+    let stmt = client
+        .prepare_typed("SELECT $1 -> 'key'", &[hstore])
+        .unwrap();
+    let row = client.query_one(&stmt, &[&map]).unwrap();
+    assert_eq!(row.get::<_, &str>(0), "value");
+}
+
 #[test]
 fn test_array_params() {
     test_type(
@@ -318,6 +479,64 @@ fn test_array_params() {
     );
 }
 
+#[test]
+fn test_text_array_params() {
+    test_type(
+        "text[]",
+        &[
+            (
+                Some(vec![
+                    "foo".to_owned(),
+                    "".to_owned(),
+                    "with, comma".to_owned(),
+                ]),
+                "ARRAY['foo', '', 'with, comma']",
+            ),
+            (Some(vec![]), "ARRAY[]::text[]"),
+            (None, "NULL"),
+        ],
+    );
+}
+
+#[test]
+fn test_array_null_elements() {
+    test_type(
+        "integer[]",
+        &[
+            (Some(vec![Some(1i32), None, Some(3)]), "ARRAY[1,NULL,3]"),
+            (Some(vec![None]), "ARRAY[NULL]::integer[]"),
+        ],
+    );
+    test_type(
+        "text[]",
+        &[(
+            Some(vec![None, Some("bar".to_owned())]),
+            "ARRAY[NULL,'bar']",
+        )],
+    );
+
+    let client = connect("user=postgres");
+    let row = client
+        .query_one("SELECT ARRAY[1,NULL]::integer[]", &[])
+        .unwrap();
+    assert!(row.try_get::<_, Vec<i32>>(0).is_err());
+}
+
+#[test]
+fn test_multi_dimensional_array() {
+    let client = connect("user=postgres");
+
+    let row = client
+        .query_one(
+            "SELECT '{{1,2},{3,4}}'::integer[], '[0:1]={5,6}'::integer[]",
+            &[],
+        )
+        .unwrap();
+    // only one dimension maps to a Vec, deeper arrays are reported instead of being misread
+    assert!(row.try_get::<_, Vec<i32>>(0).is_err());
+    assert_eq!(row.get::<_, Vec<i32>>(1), vec![5, 6]);
+}
+
 #[allow(clippy::eq_op)]
 fn test_nan_param<T>(sql_type: &str)
 where
@@ -378,6 +597,59 @@ fn test_slice() {
     assert_eq!(vec!["a".to_owned(), "c".to_owned(), "d".to_owned()], rows);
 }
 
+#[test]
+fn test_vec_any() {
+    let client = connect("user=postgres");
+
+    client
+        .batch_execute(
+            "CREATE TEMPORARY TABLE foo (
+                id SERIAL PRIMARY KEY,
+                f TEXT
+            );
+            INSERT INTO foo (f) VALUES ('a'), ('b'), ('c'), ('d');",
+        )
+        .unwrap();
+
+    let ids = vec![1i32, 3, 4];
+    let query = "SELECT f FROM foo WHERE id = ANY($1) ORDER BY id";
+    let expected = vec!["a".to_owned(), "c".to_owned(), "d".to_owned()];
+    let texts =
+        |rows: Vec<may_postgres::Row>| rows.iter().map(|r| r.get(0)).collect::<Vec<String>>();
+
+    // the server infers the array type, or it is given as a hint in the Parse message
+    assert_eq!(texts(client.query(query, &[&ids]).unwrap()), expected);
+    assert_eq!(
+        texts(
+            client
+                .query_unnamed(query, &[(&ids, Type::INT4_ARRAY)])
+                .unwrap()
+        ),
+        expected
+    );
+    assert_eq!(
+        texts(
+            client
+                .query_typed(query, &[(&ids, Type::INT4_ARRAY)])
+                .unwrap()
+        ),
+        expected
+    );
+
+    let none = Vec::<i32>::new();
+    assert!(client.query(query, &[&none]).unwrap().is_empty());
+
+    // the element type is checked against the hint before anything is sent
+    let err = client
+        .query_typed(query, &[(&ids, Type::INT8_ARRAY)])
+        .err()
+        .unwrap();
+    match err.source() {
+        Some(e) if e.is::<WrongType>() => {}
+        _ => panic!("Unexpected error {:?}", err),
+    };
+}
+
 #[test]
 fn test_slice_wrong_type() {
     let client = connect("user=postgres");
@@ -431,11 +703,7 @@ fn domain() {
         }
 
         fn accepts(ty: &Type) -> bool {
-            ty.name() == "session_id"
-                && match *ty.kind() {
-                    Kind::Domain(_) => true,
-                    _ => false,
-                }
+            ty.name() == "session_id" && matches!(*ty.kind(), Kind::Domain(_))
         }
 
         to_sql_checked!();
@@ -578,3 +846,705 @@ fn inet() {
         ],
     );
 }
+
+#[test]
+fn inet_prefix() {
+    let cidr = |addr: &str, prefix| Some(IpCidr::new(addr.parse().unwrap(), prefix).unwrap());
+
+    test_type(
+        "INET",
+        &[
+            (cidr("192.168.1.1", 24), "'192.168.1.1/24'"),
+            (cidr("192.168.1.1", 32), "'192.168.1.1'"),
+            (cidr("2001:db8::1", 64), "'2001:db8::1/64'"),
+            (None, "NULL"),
+        ],
+    );
+    test_type(
+        "CIDR",
+        &[
+            (cidr("10.0.0.0", 8), "'10.0.0.0/8'"),
+            (cidr("192.168.1.1", 32), "'192.168.1.1'"),
+            (cidr("2001:db8::", 32), "'2001:db8::/32'"),
+            (None, "NULL"),
+        ],
+    );
+}
+
+#[test]
+fn cidr_host_bits() {
+    let client = connect("user=postgres");
+
+    let value = IpCidr::new("192.168.1.1".parse().unwrap(), 24).unwrap();
+    assert_eq!(value.to_string(), "192.168.1.1/24");
+    assert!(client.query_one("SELECT $1::CIDR", &[&value]).is_err());
+
+    assert!(IpCidr::new("10.0.0.0".parse().unwrap(), 33).is_err());
+    assert!(IpCidr::new("::".parse().unwrap(), 129).is_err());
+}
+
+#[test]
+fn macaddr() {
+    test_type(
+        "MACADDR",
+        &[
+            (
+                Some(MacAddress::new([0x08, 0x00, 0x2b, 0x01, 0x02, 0x03])),
+                "'08:00:2b:01:02:03'",
+            ),
+            (None, "NULL"),
+        ],
+    );
+    test_type(
+        "MACADDR8",
+        &[
+            (
+                Some(MacAddress8::new([
+                    0x08, 0x00, 0x2b, 0x01, 0x02, 0x03, 0x04, 0x05,
+                ])),
+                "'08:00:2b:01:02:03:04:05'",
+            ),
+            (
+                Some(MacAddress8::new([
+                    0x08, 0x00, 0x2b, 0xff, 0xfe, 0x01, 0x02, 0x03,
+                ])),
+                "'08:00:2b:01:02:03'",
+            ),
+            (None, "NULL"),
+        ],
+    );
+
+    let mac = MacAddress::new([0x08, 0x00, 0x2b, 0x01, 0x02, 0x03]);
+    assert_eq!(mac.to_string(), "08:00:2b:01:02:03");
+}
+
+#[test]
+fn macaddr_type_mismatch() {
+    let client = connect("user=postgres");
+
+    let row = client
+        .query_one("SELECT '08:00:2b:01:02:03'::MACADDR", &[])
+        .unwrap();
+    let err = row.try_get::<_, MacAddress8>(0).err().unwrap();
+    assert!(err.is_wrong_type(), "{}", err);
+
+    let row = client
+        .query_one("SELECT '08:00:2b:01:02:03:04:05'::MACADDR8", &[])
+        .unwrap();
+    let err = row.try_get::<_, MacAddress>(0).err().unwrap();
+    assert!(err.is_wrong_type(), "{}", err);
+
+    let mac = MacAddress::new([0x08, 0x00, 0x2b, 0x01, 0x02, 0x03]);
+    let err = client
+        .query_one("SELECT $1::MACADDR8", &[&mac])
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("macaddr8"), "{}", err);
+}
+
+#[test]
+fn money() {
+    let client = connect("user=postgres");
+    client.batch_execute("SET lc_monetary = 'C'").unwrap();
+
+    for (val, repr) in [
+        (Some(Money::new(1050)), "'10.50'"),
+        (Some(Money::new(-1)), "'-0.01'"),
+        (Some(Money::new(i64::MAX)), "'92233720368547758.07'"),
+        (None, "NULL"),
+    ] {
+        let row = client
+            .query_one(&*format!("SELECT {}::MONEY", repr), &[])
+            .unwrap();
+        assert_eq!(val, row.get(0));
+
+        let row = client.query_one("SELECT $1::MONEY", &[&val]).unwrap();
+        assert_eq!(val, row.get(0));
+    }
+
+    let row = client.query_one("SELECT '10.50'::MONEY", &[]).unwrap();
+    let err = row.try_get::<_, f64>(0).err().unwrap();
+    assert!(err.is_wrong_type(), "{}", err);
+    let err = row.try_get::<_, i64>(0).err().unwrap();
+    assert!(err.is_wrong_type(), "{}", err);
+}
+
+#[test]
+fn point() {
+    test_type(
+        "POINT",
+        &[
+            (Some(Point::new(0.0, 0.0)), "'(0, 0)'"),
+            (Some(Point::new(-2.5, 1.618)), "'(-2.5, 1.618)'"),
+            (None, "NULL"),
+        ],
+    );
+}
+
+#[test]
+fn rect() {
+    test_type(
+        "BOX",
+        &[
+            (
+                Some(Rect::new(
+                    Point::new(160.0, 69701.5615),
+                    Point::new(-2.5, 1.618),
+                )),
+                "'((160.0, 69701.5615), (-2.5, 1.618))'",
+            ),
+            (None, "NULL"),
+        ],
+    );
+
+    // the server orders the corners
+    let client = connect("user=postgres");
+    let row = client
+        .query_one(
+            "SELECT $1::BOX",
+            &[&Rect::new(Point::new(0.0, 2.0), Point::new(1.0, -1.0))],
+        )
+        .unwrap();
+    assert_eq!(
+        row.get::<_, Rect>(0),
+        Rect::new(Point::new(1.0, 2.0), Point::new(0.0, -1.0))
+    );
+}
+
+#[test]
+fn geometric_types() {
+    test_type(
+        "CIRCLE",
+        &[
+            (
+                Some(Circle::new(Point::new(1.0, -2.0), 3.5)),
+                "'<(1, -2), 3.5>'",
+            ),
+            (None, "NULL"),
+        ],
+    );
+    test_type(
+        "LINE",
+        &[
+            (Some(Line::new(1.0, -1.0, 0.5)), "'{1, -1, 0.5}'"),
+            (None, "NULL"),
+        ],
+    );
+    test_type(
+        "LSEG",
+        &[
+            (
+                Some(LineSegment::new(
+                    Point::new(1.0, 2.0),
+                    Point::new(-3.0, 4.0),
+                )),
+                "'[(1, 2), (-3, 4)]'",
+            ),
+            (None, "NULL"),
+        ],
+    );
+    let points = vec![
+        Point::new(0.0, 0.0),
+        Point::new(1.0, 1.0),
+        Point::new(2.0, 0.0),
+    ];
+    test_type(
+        "PATH",
+        &[
+            (
+                Some(Path::new(false, points.clone())),
+                "'[(0,0),(1,1),(2,0)]'",
+            ),
+            (
+                Some(Path::new(true, points.clone())),
+                "'((0,0),(1,1),(2,0))'",
+            ),
+            (None, "NULL"),
+        ],
+    );
+    test_type(
+        "POLYGON",
+        &[
+            (Some(Polygon::new(points)), "'((0,0),(1,1),(2,0))'"),
+            (None, "NULL"),
+        ],
+    );
+
+    let client = connect("user=postgres");
+    let row = client.query_one("SELECT '(1, 2)'::POINT", &[]).unwrap();
+    let err = row.try_get::<_, Rect>(0).err().unwrap();
+    assert!(err.is_wrong_type(), "{}", err);
+    let err = client
+        .query_one(
+            "SELECT $1::CIRCLE",
+            &[&Circle::new(Point::new(0.0, 0.0), -1.0)],
+        )
+        .err()
+        .unwrap();
+    assert!(err.code().is_some(), "{}", err);
+}
+
+#[test]
+fn tsvector() {
+    let client = connect("user=postgres");
+
+    client
+        .batch_execute(
+            "CREATE TEMPORARY TABLE foo (id INT, body TEXT, tsv TSVECTOR);
+             INSERT INTO foo VALUES (1, 'The quick brown fox jumps over the lazy dog', NULL);
+             UPDATE foo SET tsv = setweight(to_tsvector('english', 'Fox'), 'A')
+                 || to_tsvector('english', body)",
+        )
+        .unwrap();
+
+    let lexeme = |word: &str, positions: &[(u16, Weight)]| Lexeme {
+        word: word.to_string(),
+        positions: positions
+            .iter()
+            .map(|&(position, weight)| LexemePosition::new(position, weight))
+            .collect(),
+    };
+    let expected = TsVector {
+        lexemes: vec![
+            lexeme("brown", &[(4, Weight::D)]),
+            lexeme("dog", &[(10, Weight::D)]),
+            lexeme("fox", &[(1, Weight::A), (5, Weight::D)]),
+            lexeme("jump", &[(6, Weight::D)]),
+            lexeme("lazi", &[(9, Weight::D)]),
+            lexeme("quick", &[(3, Weight::D)]),
+        ],
+    };
+
+    let row = client.query_one("SELECT tsv FROM foo", &[]).unwrap();
+    let tsv = row.get::<_, TsVector>(0);
+    assert_eq!(tsv, expected);
+
+    let row = client
+        .query_one("SELECT $1::TSVECTOR::TEXT, $1 = tsv FROM foo", &[&tsv])
+        .unwrap();
+    assert_eq!(
+        row.get::<_, &str>(0),
+        "'brown':4 'dog':10 'fox':1A,5 'jump':6 'lazi':9 'quick':3"
+    );
+    assert!(row.get::<_, bool>(1));
+
+    let row = client.query_one("SELECT strip(tsv) FROM foo", &[]).unwrap();
+    let stripped = row.get::<_, TsVector>(0);
+    assert_eq!(stripped.lexemes.len(), 6);
+    assert!(stripped.lexemes.iter().all(|l| l.positions.is_empty()));
+    test_type(
+        "TSVECTOR",
+        &[
+            (Some(stripped), "'brown dog fox jump lazi quick'"),
+            (Some(TsVector::default()), "''"),
+            (None, "NULL"),
+        ],
+    );
+}
+
+#[test]
+fn tsquery() {
+    let client = connect("user=postgres");
+
+    for text in &[
+        "'fox'",
+        "'fox' & !( 'cat' | 'jump':*B )",
+        "'quick' <-> 'brown' <3> 'jump':AC",
+        "'it''s' | !!'a b'",
+    ] {
+        let query = text.parse::<TsQuery>().unwrap();
+        let row = client
+            .query_one(
+                &*format!(
+                    "SELECT $1::TSQUERY::TEXT, {}::TSQUERY",
+                    escape_literal(text)
+                ),
+                &[&query],
+            )
+            .unwrap();
+        let expected = client
+            .query_one(
+                &*format!("SELECT {}::TSQUERY::TEXT", escape_literal(text)),
+                &[],
+            )
+            .unwrap();
+        assert_eq!(row.get::<_, &str>(0), expected.get::<_, &str>(0));
+        assert_eq!(row.get::<_, TsQuery>(1), query);
+    }
+
+    assert_eq!(
+        "fox:*AB".parse::<TsQuery>().unwrap(),
+        TsQuery::Lexeme(QueryLexeme {
+            word: "fox".to_string(),
+            weights: vec![Weight::A, Weight::B],
+            prefix: true,
+        })
+    );
+    assert!("fox &".parse::<TsQuery>().is_err());
+    assert!("(fox".parse::<TsQuery>().is_err());
+
+    let query = "fox & (dog | cat)".parse::<TsQuery>().unwrap();
+    let row = client
+        .query_one(
+            "SELECT to_tsvector('english', 'The quick brown fox jumps over the lazy dog') @@ $1",
+            &[&query],
+        )
+        .unwrap();
+    assert!(row.get::<_, bool>(0));
+
+    let row = client.query_one("SELECT ''::TSQUERY", &[]).unwrap();
+    assert_eq!(row.get::<_, TsQuery>(0), TsQuery::Empty);
+    let err = client
+        .query_one("SELECT $1::TSQUERY", &[&TsQuery::Empty])
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("empty tsquery"), "{}", err);
+}
+
+#[test]
+fn tsquery_nesting() {
+    let client = connect("user=postgres");
+
+    let nots = |n| format!("{}fox", "!".repeat(n));
+    let parens = |n| format!("{}fox{}", "(fox & ".repeat(n), ")".repeat(n));
+    assert!(nots(32).parse::<TsQuery>().is_ok());
+    assert!(parens(32).parse::<TsQuery>().is_ok());
+    assert!(vec!["fox"; 33].join(" | ").parse::<TsQuery>().is_ok());
+    for text in [nots(33), parens(33), vec!["fox"; 34].join(" | ")] {
+        let err = text.parse::<TsQuery>().unwrap_err();
+        assert!(err.to_string().contains("nested too deeply"), "{}", err);
+    }
+
+    // deep input is parsed or rejected without running out of a coroutine's stack
+    let text = format!("{}fox{}", "(".repeat(100_000), ")".repeat(100_000));
+    assert!(may::go!(move || text.parse::<TsQuery>().is_ok())
+        .join()
+        .unwrap());
+    let text = nots(100_000);
+    assert!(may::go!(move || text.parse::<TsQuery>().is_err())
+        .join()
+        .unwrap());
+
+    // the server's parser has the same limit, but its operators don't
+    let row = client
+        .query_one(
+            "WITH RECURSIVE q (n, query) AS (
+                SELECT 0, 'fox'::TSQUERY UNION ALL SELECT n + 1, !! query FROM q WHERE n < 33
+            )
+            SELECT (SELECT query FROM q WHERE n = 32), (SELECT query FROM q WHERE n = 33)",
+            &[],
+        )
+        .unwrap();
+    assert_eq!(row.get::<_, TsQuery>(0), nots(32).parse().unwrap());
+    let err = row.try_get::<_, TsQuery>(1).unwrap_err();
+    assert!(err.to_string().contains("nested too deeply"), "{}", err);
+}
+
+#[test]
+fn interval() {
+    test_type(
+        "INTERVAL",
+        &[
+            (
+                Some(Interval::new(14, 3, 4 * 3_600_000_000 + 5_000_000)),
+                "'1 year 2 months 3 days 04:00:05'",
+            ),
+            (Some(Interval::new(0, 0, 1)), "'1 microsecond'"),
+            (
+                Some(Interval::new(-1, -2, -3_000_000)),
+                "'-1 month -2 days -00:00:03'",
+            ),
+            (Some(Interval::new(1, -1, 0)), "'1 month -1 day'"),
+            (Some(Interval::new(0, 30, 0)), "'30 days'"),
+            (None, "NULL"),
+        ],
+    );
+}
+
+#[test]
+fn int_ranges() {
+    test_type(
+        "INT4RANGE",
+        &[
+            (
+                Some(Range::new(Bound::Included(1i32), Bound::Excluded(10))),
+                "'[1,10)'",
+            ),
+            (
+                Some(Range::new(Bound::Included(1i32), Bound::Excluded(11))),
+                "'[1,10]'",
+            ),
+            (Some(Range::empty()), "'empty'"),
+            (Some(Range::empty()), "'[5,5)'"),
+            (
+                Some(Range::new(Bound::Unbounded, Bound::Excluded(6i32))),
+                "'(,5]'",
+            ),
+            (
+                Some(Range::new(Bound::Included(3i32), Bound::Unbounded)),
+                "'[3,)'",
+            ),
+            (None, "NULL"),
+        ],
+    );
+    test_type(
+        "INT8RANGE",
+        &[
+            (
+                Some(Range::new(
+                    Bound::Included(-9_000_000_000i64),
+                    Bound::Excluded(9_000_000_000),
+                )),
+                "'[-9000000000,9000000000)'",
+            ),
+            (
+                Some(Range::new(Bound::Unbounded, Bound::Unbounded)),
+                "'(,)'",
+            ),
+            (None, "NULL"),
+        ],
+    );
+}
+
+#[test]
+fn tsrange() {
+    let time = |secs| UNIX_EPOCH + Duration::from_secs(secs);
+
+    test_type(
+        "TSRANGE",
+        &[
+            (
+                Some(Range::new(
+                    Bound::Included(time(1_000_000_000)),
+                    Bound::Included(time(1_500_000_000)),
+                )),
+                "'[2001-09-09 01:46:40,2017-07-14 02:40:00]'",
+            ),
+            (
+                Some(Range::new(
+                    Bound::Excluded(time(1_000_000_000)),
+                    Bound::Unbounded,
+                )),
+                "'(2001-09-09 01:46:40,)'",
+            ),
+            (Some(Range::empty()), "'empty'"),
+        ],
+    );
+}
+
+#[test]
+fn numrange() {
+    let client = connect("user=postgres");
+
+    let numeric = |s: &str| s.parse::<Numeric>().unwrap();
+    test_type(
+        "NUMRANGE",
+        &[
+            (
+                Some(Range::new(
+                    Bound::Included(numeric("1.5")),
+                    Bound::Excluded(numeric("10.25")),
+                )),
+                "'[1.5,10.25)'",
+            ),
+            (
+                Some(Range::new(
+                    Bound::Unbounded,
+                    Bound::Included(numeric("-5.5")),
+                )),
+                "'(,-5.5]'",
+            ),
+            (Some(Range::empty()), "'empty'"),
+            (None, "NULL"),
+        ],
+    );
+
+    let row = client.query_one("SELECT '[1,2)'::NUMRANGE", &[]).unwrap();
+    let err = row.try_get::<_, Range<i32>>(0).err().unwrap();
+    assert!(err.is_wrong_type(), "{}", err);
+}
+
+#[test]
+fn numeric() {
+    let client = connect("user=postgres");
+
+    for value in &[
+        "0",
+        "0.000",
+        "-1",
+        "12345.6789",
+        "-0.001",
+        "0.00000000000000000001",
+        "123456789012345678901234567890",
+        "10000000000000000000000000000000000000000.5",
+        "-99990000.00010000",
+        "1.000",
+        "NaN",
+        "Infinity",
+        "-Infinity",
+    ] {
+        let numeric = value.parse::<Numeric>().unwrap();
+        assert_eq!(numeric.to_string(), *value);
+
+        // the value the server reads from the text is the one it sends back
+        let row = client
+            .query_one(&*format!("SELECT '{}'::NUMERIC", value), &[])
+            .unwrap();
+        assert_eq!(row.get::<_, Numeric>(0), numeric);
+
+        let row = client
+            .query_one("SELECT $1::NUMERIC, $1::NUMERIC::TEXT", &[&numeric])
+            .unwrap();
+        assert_eq!(row.get::<_, Numeric>(0), numeric);
+        assert_eq!(row.get::<_, &str>(1), *value);
+    }
+
+    // the server's other spellings are read like it reads them
+    for (value, text) in &[
+        ("-0", "0"),
+        ("+1.50", "1.50"),
+        ("1e3", "1000"),
+        ("1.5E-3", "0.0015"),
+        ("1.5e+1", "15"),
+        (".5", "0.5"),
+        ("5.", "5"),
+        ("nan", "NaN"),
+        ("-inf", "-Infinity"),
+    ] {
+        let numeric = value.parse::<Numeric>().unwrap();
+        assert_eq!(numeric.to_string(), *text);
+        let row = client
+            .query_one(&*format!("SELECT '{}'::NUMERIC::TEXT", value), &[])
+            .unwrap();
+        assert_eq!(row.get::<_, &str>(0), *text);
+    }
+    for value in &["", "-", ".", "1.2.3", "1e", "abc", "1e131072"] {
+        assert!(value.parse::<Numeric>().is_err(), "{}", value);
+    }
+
+    assert_eq!(
+        Numeric::from(-1_234_567_890_123i64).to_string(),
+        "-1234567890123"
+    );
+    assert_eq!(Numeric::from(i64::MIN).to_string(), i64::MIN.to_string());
+    assert_eq!(Numeric::from(0).to_string(), "0");
+    assert!(Numeric::nan().is_nan());
+    assert!(Numeric::infinity(true).is_sign_negative());
+    assert_eq!("1.50".parse::<Numeric>().unwrap().scale(), 2);
+    assert_ne!("1.5".parse::<Numeric>().unwrap(), "1.50".parse().unwrap());
+
+    let row = client.query_one("SELECT 1.5::NUMERIC", &[]).unwrap();
+    let err = row.try_get::<_, f64>(0).err().unwrap();
+    assert!(err.is_wrong_type(), "{}", err);
+}
+
+#[test]
+fn type_catalog() {
+    let client = connect("user=postgres");
+
+    let builtin = [
+        Type::BOOL,
+        Type::INT2,
+        Type::INT4,
+        Type::INT8,
+        Type::FLOAT8,
+        Type::TEXT,
+        Type::BYTEA,
+        Type::TIMESTAMPTZ,
+        Type::JSONB,
+        Type::UUID,
+        Type::INT4_ARRAY,
+        Type::INT4_RANGE,
+    ];
+    for type_ in &builtin {
+        let row = client
+            .query_one(
+                "SELECT oid, typname FROM pg_type WHERE oid = $1",
+                &[&type_.oid()],
+            )
+            .unwrap();
+        assert_eq!(row.get::<_, u32>(0), type_.oid());
+        assert_eq!(row.get::<_, &str>(1), type_.name());
+        assert_eq!(Type::from_oid(type_.oid()).as_ref(), Some(type_));
+        assert_eq!(type_.schema(), "pg_catalog");
+    }
+    assert_eq!(Type::INT4.oid(), 23);
+    assert_eq!(Type::TEXT.oid(), 25);
+    assert_eq!(Type::TIMESTAMPTZ.oid(), 1184);
+    assert_eq!(Type::INT4.kind(), &Kind::Simple);
+    assert_eq!(Type::INT4_ARRAY.kind(), &Kind::Array(Type::INT4));
+    assert_eq!(Type::INT4_RANGE.kind(), &Kind::Range(Type::INT4));
+    assert!(Type::from_oid(0).is_none());
+
+    // user types have database specific OIDs, and are described by the server when a statement uses them
+    client
+        .batch_execute("CREATE TYPE pg_temp.mood AS ENUM ('sad', 'happy')")
+        .unwrap();
+    let oid = client
+        .query_one("SELECT 'pg_temp.mood'::regtype::oid", &[])
+        .unwrap()
+        .get::<_, u32>(0);
+    let statement = client.prepare("SELECT 'sad'::pg_temp.mood").unwrap();
+    let mood = statement.columns()[0].type_();
+    assert_eq!(mood.oid(), oid);
+    assert_eq!(mood.name(), "mood");
+    assert_eq!(
+        mood.kind(),
+        &Kind::Enum(vec!["sad".to_string(), "happy".to_string()])
+    );
+
+    let made = Type::new(
+        "mood".to_string(),
+        oid,
+        Kind::Enum(vec!["sad".to_string(), "happy".to_string()]),
+        mood.schema().to_string(),
+    );
+    assert_eq!(&made, mood);
+    assert!(Type::from_oid(oid).is_none());
+}
+
+#[test]
+fn domain_as_base_type() {
+    let client = connect("user=postgres");
+
+    client
+        .batch_execute(
+            "CREATE DOMAIN pg_temp.positive AS INT4 CHECK (VALUE > 0);
+             CREATE DOMAIN pg_temp.small_positive AS pg_temp.positive CHECK (VALUE < 100);
+             CREATE TEMPORARY TABLE foo (a pg_temp.positive, b pg_temp.small_positive, c TEXT)",
+        )
+        .unwrap();
+
+    let before = client.stats().requests();
+    let insert = client
+        .prepare("INSERT INTO foo (a, b, c) VALUES ($1, $2, 'x')")
+        .unwrap();
+    let looked_up = client.stats().requests() - before;
+    // the domains were looked up in the catalog, along with their base types
+    assert_eq!(insert.params()[0].name(), "positive");
+    assert_eq!(
+        insert.params()[1].kind(),
+        &Kind::Domain(insert.params()[0].clone())
+    );
+    assert_eq!(insert.params()[0].kind(), &Kind::Domain(Type::INT4));
+
+    client.execute(&insert, &[&7i32, &42i32]).unwrap();
+    let err = client.execute(&insert, &[&7i32, &"42"]).unwrap_err();
+    assert!(err.source().unwrap().is::<WrongType>(), "{}", err);
+    assert!(err.to_string().contains("small_positive"), "{}", err);
+
+    // the types are cached, so preparing another statement using them doesn't look them up again
+    let before = client.stats().requests();
+    let update = client
+        .prepare("UPDATE foo SET b = $2 WHERE a = $1")
+        .unwrap();
+    assert!(client.stats().requests() - before < looked_up);
+    client.execute(&update, &[&7i32, &43i32]).unwrap();
+
+    // result columns of a domain are described by the server as its base type
+    let row = client.query_one("SELECT a, b, c FROM foo", &[]).unwrap();
+    assert_eq!(row.columns()[1].type_(), &Type::INT4);
+    assert_eq!(row.get::<_, i32>(0), 7);
+    assert_eq!(row.get::<_, i32>(1), 43);
+    assert_eq!(row.get::<_, &str>(2), "x");
+}
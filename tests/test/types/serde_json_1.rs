@@ -1,5 +1,8 @@
+use bytes::BytesMut;
 use serde_json_1::Value;
 
+use may_postgres::types::{FromSql, ToSql, Type};
+
 use crate::types::test_type;
 
 #[test]
@@ -37,3 +40,35 @@ fn test_jsonb_params() {
         ],
     )
 }
+
+#[test]
+fn test_nested_json_params() {
+    let value = serde_json_1::json!({
+        "name": "foo",
+        "tags": ["a", "b", null],
+        "nested": {"count": 3, "ratio": 0.5, "items": [{"id": 1}, {"id": 2}], "empty": {}},
+        "flag": true
+    });
+    let repr = format!("'{}'", value);
+
+    test_type("JSON", &[(Some(value.clone()), &*repr)]);
+    test_type("JSONB", &[(Some(value), &*repr)]);
+}
+
+#[test]
+fn test_jsonb_version() {
+    let value = serde_json_1::json!({"f": [1, 2]});
+
+    let mut buf = BytesMut::new();
+    value.to_sql(&Type::JSONB, &mut buf).unwrap();
+    assert_eq!(buf[0], 1);
+    assert_eq!(Value::from_sql(&Type::JSONB, &buf).unwrap(), value);
+
+    let mut buf = BytesMut::new();
+    value.to_sql(&Type::JSON, &mut buf).unwrap();
+    assert_eq!(&buf[..], br#"{"f":[1,2]}"#);
+    assert_eq!(Value::from_sql(&Type::JSON, &buf).unwrap(), value);
+
+    assert!(Value::from_sql(&Type::JSONB, b"\x02{\"f\":[1,2]}").is_err());
+    assert!(Value::from_sql(&Type::JSONB, b"").is_err());
+}
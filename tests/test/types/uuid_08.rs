@@ -1,5 +1,9 @@
+use std::error::Error;
 use uuid_08::Uuid;
 
+use may_postgres::types::WrongType;
+
+use crate::connect;
 use crate::types::test_type;
 
 #[test]
@@ -15,3 +19,47 @@ fn test_uuid_params() {
         ],
     )
 }
+
+#[test]
+fn test_uuid_primary_key() {
+    let client = connect("user=postgres");
+
+    client
+        .batch_execute("CREATE TEMPORARY TABLE foo (id UUID PRIMARY KEY, name TEXT)")
+        .unwrap();
+
+    let id = Uuid::parse_str("a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11").unwrap();
+    client
+        .execute("INSERT INTO foo (id, name) VALUES ($1, 'a')", &[&id])
+        .unwrap();
+
+    let row = client
+        .query_one("SELECT id FROM foo WHERE id = $1", &[&id])
+        .unwrap();
+    assert_eq!(row.get::<_, Uuid>(0), id);
+}
+
+#[test]
+fn test_uuid_wrong_type() {
+    let client = connect("user=postgres");
+
+    client
+        .batch_execute("CREATE TEMPORARY TABLE foo (name TEXT)")
+        .unwrap();
+
+    let id = Uuid::parse_str("a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11").unwrap();
+    let err = client
+        .execute("INSERT INTO foo (name) VALUES ($1)", &[&id])
+        .err()
+        .unwrap();
+    match err.source() {
+        Some(e) if e.is::<WrongType>() => {}
+        _ => panic!("Unexpected error {:?}", err),
+    };
+
+    client
+        .execute("INSERT INTO foo (name) VALUES ($1)", &[&id.to_string()])
+        .unwrap();
+    let row = client.query_one("SELECT name FROM foo", &[]).unwrap();
+    assert!(row.try_get::<_, Uuid>(0).is_err());
+}